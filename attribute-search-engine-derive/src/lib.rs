@@ -0,0 +1,269 @@
+//! Derive macro for the `Searchable` trait of the
+//! [attribute-search-engine](https://docs.rs/attribute-search-engine) crate.
+//!
+//! This crate is not meant to be used directly. Enable the `derive` feature of
+//! `attribute-search-engine` and use the re-exported `Searchable` derive macro.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Field, Fields, GenericArgument, PathArguments,
+    Result, Type,
+};
+
+/// Implements `Searchable` for a struct with named fields and adds a function
+/// for every field that returns a typed attribute.
+///
+/// Fields can be configured with `#[searchable(skip)]`, `#[searchable(prefix)]`
+/// or `#[searchable(range)]`. The mapping from field types to indices is
+/// documented on the `Searchable` trait.
+#[proc_macro_derive(Searchable, attributes(searchable))]
+pub fn derive_searchable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The index that is used for a field.
+enum IndexKind {
+    /// A SearchIndexHashMap with an ExactAttribute.
+    Exact,
+    /// A SearchIndexPrefixTree with a PrefixAttribute.
+    Prefix,
+    /// A SearchIndexBTreeRange with a RangeAttribute.
+    Range,
+}
+
+/// How the values of a field are stored in the struct.
+enum Multiplicity {
+    /// A single value of type `T`.
+    Single,
+    /// An `Option<T>` with zero or one value.
+    Optional,
+    /// A `Vec<T>` with any number of values.
+    Multiple,
+}
+
+/// A field that gets an index.
+struct SearchableField<'a> {
+    field: &'a Field,
+    value_type: &'a Type,
+    multiplicity: Multiplicity,
+    kind: IndexKind,
+}
+
+/// Integer types that get a range index by default.
+const RANGE_TYPES: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Searchable can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Searchable can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut searchable_fields = vec![];
+    for field in fields.named.iter() {
+        if let Some(searchable_field) = parse_field(field)? {
+            searchable_fields.push(searchable_field);
+        }
+    }
+
+    let krate = quote!(::attribute_search_engine);
+    let mut accessors = vec![];
+    let mut indices = vec![];
+    let mut inserts = vec![];
+    for SearchableField {
+        field,
+        value_type,
+        multiplicity,
+        kind,
+    } in searchable_fields.iter()
+    {
+        let ident = field.ident.as_ref().expect("named fields have an ident");
+        let vis = &field.vis;
+        let name = ident.to_string();
+        let (attribute_type, index) = match kind {
+            IndexKind::Exact => (
+                quote!(#krate::ExactAttribute<#value_type>),
+                quote!(#krate::SearchIndexHashMap::<P, #value_type>::new()),
+            ),
+            IndexKind::Prefix => (
+                quote!(#krate::PrefixAttribute),
+                quote!(#krate::SearchIndexPrefixTree::<P>::new()),
+            ),
+            IndexKind::Range => (
+                quote!(#krate::RangeAttribute<#value_type>),
+                quote!(#krate::SearchIndexBTreeRange::<P, #value_type>::new()),
+            ),
+        };
+        accessors.push(quote! {
+            #[doc = concat!("Returns the typed attribute of the field `", #name, "`.")]
+            #vis fn #ident() -> #attribute_type {
+                <#attribute_type>::new(#name)
+            }
+        });
+        indices.push(quote! {
+            engine.add_index(#name, #index);
+        });
+        let insert = quote! {
+            engine.insert(#name, primary_id.clone(), &::std::string::ToString::to_string(value))?;
+        };
+        inserts.push(match multiplicity {
+            Multiplicity::Single => quote! {
+                let value = &self.#ident;
+                #insert
+            },
+            Multiplicity::Optional => quote! {
+                if let ::std::option::Option::Some(value) = &self.#ident {
+                    #insert
+                }
+            },
+            Multiplicity::Multiple => quote! {
+                for value in self.#ident.iter() {
+                    #insert
+                }
+            },
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #ident #type_generics #where_clause {
+            #(#accessors)*
+        }
+
+        impl #impl_generics #krate::Searchable for #ident #type_generics #where_clause {
+            fn search_engine<P>() -> #krate::SearchEngine<P>
+            where
+                P: ::std::cmp::Eq + ::std::hash::Hash + ::std::clone::Clone
+                    + ::std::marker::Send
+                    + ::std::marker::Sync
+                    + 'static,
+            {
+                let mut engine = #krate::SearchEngine::<P>::new();
+                #(#indices)*
+                engine
+            }
+
+            fn insert_into<P>(
+                &self,
+                engine: &mut #krate::SearchEngine<P>,
+                primary_id: P,
+            ) -> #krate::Result<()>
+            where
+                P: ::std::cmp::Eq + ::std::hash::Hash + ::std::clone::Clone
+                    + ::std::marker::Send
+                    + ::std::marker::Sync
+                    + 'static,
+            {
+                #(#inserts)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// Reads the type and the `#[searchable(...)]` options of a field.
+/// Returns `None` if the field is skipped.
+fn parse_field(field: &Field) -> Result<Option<SearchableField<'_>>> {
+    let mut kind = None;
+    for attr in field.attrs.iter() {
+        if !attr.path().is_ident("searchable") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let option = if meta.path.is_ident("skip") {
+                None
+            } else if meta.path.is_ident("prefix") {
+                Some(IndexKind::Prefix)
+            } else if meta.path.is_ident("range") {
+                Some(IndexKind::Range)
+            } else {
+                return Err(meta.error("expected `skip`, `prefix` or `range`"));
+            };
+            if kind.is_some() {
+                return Err(meta.error("only one searchable option is allowed per field"));
+            }
+            kind = Some(option);
+            Ok(())
+        })?;
+    }
+    let kind = match kind {
+        Some(None) => return Ok(None),
+        Some(Some(kind)) => Some(kind),
+        None => None,
+    };
+
+    let (multiplicity, value_type) = match generic_wrapper(&field.ty) {
+        Some(("Option", inner)) => (Multiplicity::Optional, inner),
+        Some(("Vec", inner)) => (Multiplicity::Multiple, inner),
+        _ => (Multiplicity::Single, &field.ty),
+    };
+    let kind = kind.unwrap_or_else(|| {
+        let is_integer = type_name(value_type).is_some_and(|name| RANGE_TYPES.contains(&name));
+        if is_integer {
+            IndexKind::Range
+        } else {
+            IndexKind::Exact
+        }
+    });
+    if matches!(kind, IndexKind::Prefix) && type_name(value_type) != Some("String") {
+        return Err(Error::new_spanned(
+            value_type,
+            "`#[searchable(prefix)]` requires a String field",
+        ));
+    }
+
+    Ok(Some(SearchableField {
+        field,
+        value_type,
+        multiplicity,
+        kind,
+    }))
+}
+
+/// Returns the last path segment and the only type argument of
+/// types like `Option<T>` or `Vec<T>`.
+fn generic_wrapper(ty: &Type) -> Option<(&'static str, &Type)> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let wrapper = match segment.ident.to_string().as_str() {
+        "Option" => "Option",
+        "Vec" => "Vec",
+        _ => return None,
+    };
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.iter().collect::<Vec<_>>().as_slice() {
+        [GenericArgument::Type(inner)] => Some((wrapper, inner)),
+        _ => None,
+    }
+}
+
+/// Returns the name of a type that is a single identifier, like `u8` or `String`.
+fn type_name(ty: &Type) -> Option<&'static str> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let ident = path.path.get_ident()?.to_string();
+    RANGE_TYPES
+        .iter()
+        .chain(&["String"])
+        .find(|name| **name == ident)
+        .copied()
+}
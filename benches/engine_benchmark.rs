@@ -0,0 +1,235 @@
+use attribute_search_engine::{Query, SearchEngine, SearchIndexBTreeRange, SearchIndexHashMap};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::{collections::HashSet, hint::black_box, time::Duration};
+
+fn create_engine(size: usize) -> SearchEngine<usize> {
+    let mut index_group = SearchIndexHashMap::<_, usize>::new();
+    let mut index_value = SearchIndexBTreeRange::<_, usize>::new();
+    for i in 0..size {
+        index_group.insert(i, i % 10);
+        index_value.insert(i, i % 1000);
+    }
+
+    let mut engine = SearchEngine::new();
+    engine.add_index("group", index_group);
+    engine.add_index("value", index_value);
+    engine
+}
+
+fn compiled_query_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine-compiled");
+    group.measurement_time(Duration::from_secs(10));
+
+    let engine = create_engine(100000);
+    let query = Query::And(vec![
+        Query::Exact("group".into(), "3".into()),
+        Query::InRange("value".into(), "100".into(), "200".into()),
+    ]);
+    let compiled = engine.compile(&query).expect("valid query");
+
+    group.bench_function("search", |b| {
+        b.iter(|| engine.search(black_box(&query)).expect("no error"));
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| black_box(&compiled).execute());
+    });
+    group.finish();
+}
+
+fn search_leaf_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine-leaf");
+    group.measurement_time(Duration::from_secs(10));
+
+    let engine = create_engine(100000);
+    let query = Query::Exact("group".into(), "3".into());
+
+    group.bench_function("search", |b| {
+        b.iter(|| engine.search(black_box(&query)).expect("no error"));
+    });
+    group.bench_function("search_leaf", |b| {
+        b.iter(|| engine.search_leaf(black_box(&query)).expect("no error"));
+    });
+    group.finish();
+}
+
+fn count_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine-count");
+    group.measurement_time(Duration::from_secs(10));
+
+    let engine = create_engine(1000000);
+    // The large range is listed first, so a naive count starts with it.
+    let query = Query::And(vec![
+        Query::Minimum("value".into(), "100".into()),
+        Query::Exact("group".into(), "3".into()),
+        Query::Exact("value".into(), "503".into()),
+    ]);
+    // No row has the value 1000, so the count doesn't need to search anything.
+    let query_empty = Query::And(vec![
+        Query::Minimum("value".into(), "100".into()),
+        Query::Exact("value".into(), "1000".into()),
+    ]);
+
+    group.bench_function("count", |b| {
+        b.iter(|| engine.count(black_box(&query)).expect("no error"));
+    });
+    group.bench_function("search-len", |b| {
+        b.iter(|| engine.search(black_box(&query)).expect("no error").len());
+    });
+    group.bench_function("count-empty", |b| {
+        b.iter(|| engine.count(black_box(&query_empty)).expect("no error"));
+    });
+    group.bench_function("search-len-empty", |b| {
+        b.iter(|| {
+            engine
+                .search(black_box(&query_empty))
+                .expect("no error")
+                .len()
+        });
+    });
+    group.finish();
+}
+
+fn and_order_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine-and-order");
+    group.measurement_time(Duration::from_secs(10));
+
+    let engine = create_engine(1000000);
+    // The first subquery matches 900k rows, the last only 1000.
+    let subqueries = vec![
+        Query::Minimum("value".into(), "100".into()),
+        Query::Exact("group".into(), "3".into()),
+        Query::Exact("value".into(), "503".into()),
+    ];
+    let query = Query::And(subqueries.clone());
+
+    group.bench_function("smallest-first", |b| {
+        b.iter(|| engine.search(black_box(&query)).expect("no error"));
+    });
+    // Baseline that intersects in the order of the subqueries.
+    group.bench_function("ordered", |b| {
+        b.iter(|| {
+            let mut result_set = engine.search(black_box(&subqueries[0])).expect("no error");
+            for pred in subqueries[1..].iter() {
+                let attribute_set = engine.search(pred).expect("no error");
+                result_set = result_set
+                    .intersection(&attribute_set)
+                    .cloned()
+                    .collect::<HashSet<_>>();
+            }
+            result_set
+        });
+    });
+    group.finish();
+}
+
+fn exclude_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine-exclude");
+    group.measurement_time(Duration::from_secs(10));
+
+    let engine = create_engine(1000000);
+    let base = Query::Minimum("value".into(), "0".into());
+    let exclude: Vec<_> = (0..10)
+        .map(|i| Query::Exact("value".into(), (i * 100).to_string()))
+        .collect();
+    let query = Query::Exclude(base.clone().into(), exclude.clone());
+
+    group.bench_function("search", |b| {
+        b.iter(|| engine.search(black_box(&query)).expect("no error"));
+    });
+    // Baseline that creates a new set for every exclusion.
+    group.bench_function("difference", |b| {
+        b.iter(|| {
+            let mut result_set = engine.search(black_box(&base)).expect("no error");
+            for pred in exclude.iter() {
+                let attribute_set = engine.search(pred).expect("no error");
+                result_set = result_set
+                    .difference(&attribute_set)
+                    .cloned()
+                    .collect::<HashSet<_>>();
+            }
+            result_set
+        });
+    });
+    group.finish();
+}
+
+fn ordered_fetch_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine-ordered-fetch");
+    group.measurement_time(Duration::from_secs(10));
+
+    let size = 1000000;
+    let engine = create_engine(size);
+    // Simulates a backing store with one record per row id.
+    let records: Vec<[u64; 8]> = (0..size as u64).map(|i| [i; 8]).collect();
+    let query = Query::InRange("value".into(), "0".into(), "499".into());
+    let fetch = |ids: &mut dyn Iterator<Item = &usize>| -> u64 {
+        ids.map(|id| records[*id].iter().sum::<u64>()).sum()
+    };
+
+    group.bench_function("search", |b| {
+        b.iter(|| {
+            let result = engine.search(black_box(&query)).expect("no error");
+            fetch(&mut result.iter())
+        });
+    });
+    group.bench_function("search_ordered", |b| {
+        b.iter(|| {
+            let result = engine.search_ordered(black_box(&query)).expect("no error");
+            fetch(&mut result.iter())
+        });
+    });
+    group.finish();
+}
+
+#[cfg(feature = "roaring")]
+fn bitmap_bench(c: &mut Criterion) {
+    use attribute_search_engine::SearchIndexBitmap;
+
+    let mut group = c.benchmark_group("engine-bitmap");
+    group.measurement_time(Duration::from_secs(10));
+
+    let size = 1000000;
+    let mut index_group = SearchIndexBitmap::<u32>::new();
+    let mut index_bucket = SearchIndexBitmap::<u32>::new();
+    for i in 0..size {
+        index_group.insert(i, i % 10);
+        index_bucket.insert(i, i % 3);
+    }
+    let mut engine = SearchEngine::<u32>::new();
+    engine.add_index("group", index_group);
+    engine.add_index("bucket", index_bucket);
+
+    let groups = (0..5)
+        .map(|i| Query::Exact("group".into(), i.to_string()))
+        .collect();
+    let query = Query::Exclude(
+        Box::new(Query::And(vec![
+            Query::Or(groups),
+            Query::Exact("bucket".into(), "1".into()),
+        ])),
+        vec![Query::Exact("group".into(), "3".into())],
+    );
+
+    group.bench_function("search", |b| {
+        b.iter(|| engine.search(black_box(&query)).expect("no error"));
+    });
+    group.bench_function("search_bitmap", |b| {
+        b.iter(|| engine.search_bitmap(black_box(&query)).expect("no error"));
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "roaring"))]
+fn bitmap_bench(_c: &mut Criterion) {}
+
+criterion_group!(
+    benches,
+    compiled_query_bench,
+    search_leaf_bench,
+    count_bench,
+    and_order_bench,
+    exclude_bench,
+    ordered_fetch_bench,
+    bitmap_bench
+);
+criterion_main!(benches);
@@ -1,7 +1,8 @@
+use attribute_search_engine::{SearchIndexBTreeRange, SearchIndexHashMap};
 use criterion::{
     criterion_group, criterion_main, BenchmarkId, Criterion, PlotConfiguration, Throughput,
 };
-use std::{hint::black_box, time::Duration};
+use std::{borrow::Cow, hint::black_box, time::Duration};
 
 mod indices;
 use indices::*;
@@ -44,5 +45,71 @@ fn insert_bench(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, insert_bench);
+fn insert_skewed_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert-skewed");
+    group.measurement_time(Duration::from_secs(10));
+
+    // One value is shared by most rows, like the root user of all hosts.
+    let size = 100000;
+    let input: Vec<u32> = (0..size as u32)
+        .map(|i| if i % 10 == 0 { i } else { 0 })
+        .collect();
+
+    group.throughput(Throughput::Elements(size as u64));
+    group.bench_function("insert", |b| {
+        b.iter(|| {
+            let mut index = SearchIndexBTreeRange::<usize, u32>::new();
+            for (id, value) in black_box(&input).iter().enumerate() {
+                index.insert(id, *value);
+            }
+            index
+        });
+    });
+    group.bench_function("insert_with_hint", |b| {
+        b.iter(|| {
+            let mut index = SearchIndexBTreeRange::<usize, u32>::new();
+            for (id, value) in black_box(&input).iter().enumerate() {
+                let hint = if *value == 0 { size } else { 1 };
+                index.insert_with_hint(id, *value, hint);
+            }
+            index
+        });
+    });
+    group.finish();
+}
+
+fn insert_cow_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert-cow");
+    group.measurement_time(Duration::from_secs(10));
+
+    // The values are borrowed from one line, like when parsing a CSV file.
+    let size = 100000;
+    let line = (0..size)
+        .map(|i| format!("{:06}", i % (size / 50)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    group.throughput(Throughput::Elements(size as u64));
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let mut index = SearchIndexHashMap::<usize, String>::new();
+            for (id, value) in black_box(&line).split(',').enumerate() {
+                index.insert(id, value.to_owned());
+            }
+            index
+        });
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let mut index = SearchIndexHashMap::<usize, String>::new();
+            for (id, value) in black_box(&line).split(',').enumerate() {
+                index.insert_cow(id, Cow::Borrowed(value));
+            }
+            index
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, insert_bench, insert_skewed_bench, insert_cow_bench);
 criterion_main!(benches);
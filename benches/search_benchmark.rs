@@ -1,4 +1,6 @@
-use attribute_search_engine::{Query, SearchIndex};
+use attribute_search_engine::{
+    Query, SearchIndex, SearchIndexBTreeRange, SearchIndexHashMap, SearchIndexSortedVec,
+};
 use criterion::{
     criterion_group, criterion_main, BenchmarkId, Criterion, PlotConfiguration, Throughput,
 };
@@ -68,5 +70,123 @@ fn search_exact_bench(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, search_exact_bench);
+fn search_outrange_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search-outrange");
+    group.measurement_time(Duration::from_secs(10));
+    group
+        .plot_config(PlotConfiguration::default().summary_scale(criterion::AxisScale::Logarithmic));
+
+    let index_size = 1000000;
+    let mut index_btree_range = SearchIndexBTreeRange::<usize, usize>::new();
+    for i in 0..index_size {
+        index_btree_range.insert(i, i);
+    }
+
+    for &size in [1000, 10000, 100000].iter() {
+        let start = (index_size - size) / 2;
+        let query = Query::OutRange("".into(), start.to_string(), (start + size).to_string());
+
+        group.throughput(Throughput::Elements((index_size - size) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("SearchIndexBTreeRange", size),
+            &query,
+            |b, query| {
+                b.iter(|| {
+                    index_btree_range
+                        .search(black_box(query))
+                        .expect("no error")
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn search_range_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search-range");
+    group.measurement_time(Duration::from_secs(10));
+    group
+        .plot_config(PlotConfiguration::default().summary_scale(criterion::AxisScale::Logarithmic));
+
+    let index_size = 1000000;
+    let mut index_btree_range = SearchIndexBTreeRange::<usize, usize>::new();
+    let mut index_sorted_vec = SearchIndexSortedVec::<usize, usize>::new();
+    for i in 0..index_size {
+        index_btree_range.insert(i, i);
+        index_sorted_vec.insert(i, i);
+    }
+    index_sorted_vec.build();
+
+    for &size in [10, 1000, 100000].iter() {
+        let start = (index_size - size) / 2;
+        let query = Query::InRange("".into(), start.to_string(), (start + size - 1).to_string());
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("SearchIndexBTreeRange", size),
+            &query,
+            |b, query| {
+                b.iter(|| {
+                    index_btree_range
+                        .search(black_box(query))
+                        .expect("no error")
+                });
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("SearchIndexSortedVec", size),
+            &query,
+            |b, query| {
+                b.iter(|| index_sorted_vec.search(black_box(query)).expect("no error"));
+            },
+        );
+    }
+    group.finish();
+}
+
+fn count_exact_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count-exact");
+    group.measurement_time(Duration::from_secs(10));
+    group
+        .plot_config(PlotConfiguration::default().summary_scale(criterion::AxisScale::Logarithmic));
+
+    for &size in [1000, 10000, 100000].iter() {
+        // All rows share one value, so the query matches a single large bucket.
+        let mut index_hashmap = SearchIndexHashMap::<usize, usize>::new();
+        for i in 0..size {
+            index_hashmap.insert(i, 0);
+        }
+        let query = Query::Exact("".into(), "0".into());
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Materialized", size),
+            &query,
+            |b, query| {
+                b.iter(|| {
+                    index_hashmap
+                        .search(black_box(query))
+                        .expect("no error")
+                        .len()
+                });
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("Direct", size), &query, |b, query| {
+            b.iter(|| {
+                index_hashmap
+                    .count_query(black_box(query))
+                    .expect("no error")
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    search_exact_bench,
+    search_outrange_bench,
+    search_range_bench,
+    count_exact_bench
+);
 criterion_main!(benches);
@@ -0,0 +1,139 @@
+use crate::engine::{with_query_attribute, SearchEngine};
+use crate::error::*;
+use crate::query::Query;
+use roaring::RoaringBitmap;
+use std::collections::HashMap;
+
+impl SearchEngine<u32> {
+    /// Run a query on the search engine and return the matching row ids
+    /// as a [RoaringBitmap].
+    ///
+    /// The result is the same as the result of [search](Self::search), but
+    /// [Or](Query::Or), [And](Query::And), [Not](Query::Not) and
+    /// [Exclude](Query::Exclude) are computed with bitmap operations instead of
+    /// HashSet operations. Indices that implement
+    /// [BitmapSearchIndex](crate::BitmapSearchIndex), like
+    /// [SearchIndexBitmap](crate::SearchIndexBitmap), return their results as
+    /// bitmaps directly, the results of all other indices are converted. This
+    /// is much faster for large results of dense ids. Requires the `roaring` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexBitmap, Query};
+    ///
+    /// let mut index_pet = SearchIndexBitmap::<String>::new();
+    /// let mut index_city = SearchIndexBitmap::<String>::new();
+    /// for id in 0..100 {
+    ///     index_pet.insert(id, if id % 2 == 0 { "Cat" } else { "Dog" }.into());
+    ///     index_city.insert(id, if id < 10 { "Berlin" } else { "Bonn" }.into());
+    /// }
+    ///
+    /// let mut engine = SearchEngine::<u32>::new();
+    /// engine.add_index("pet", index_pet);
+    /// engine.add_index("city", index_city);
+    ///
+    /// let q = Query::And(vec![
+    ///     Query::Exact("pet".into(), "Cat".into()),
+    ///     Query::Exact("city".into(), "Berlin".into()),
+    /// ]);
+    /// let result = engine.search_bitmap(&q).expect("valid query");
+    /// assert_eq!(result.iter().collect::<Vec<_>>(), vec![0, 2, 4, 6, 8]);
+    /// ```
+    pub fn search_bitmap(&self, query: &Query) -> Result<RoaringBitmap> {
+        self.record(query);
+        self.check_attributes(query)?;
+        self.search_query_bitmap(query)
+    }
+
+    /// This internal function runs a query like [search_bitmap](Self::search_bitmap)
+    /// without recording it.
+    fn search_query_bitmap(&self, query: &Query) -> Result<RoaringBitmap> {
+        match query {
+            Query::Or(vec) => {
+                let mut result = RoaringBitmap::new();
+                for pred in vec.iter() {
+                    result |= self.search_query_bitmap(pred)?;
+                }
+                Ok(result)
+            }
+            Query::All => Ok(self.universe_bitmap()),
+            Query::Not(inner) => Ok(self.universe_bitmap() - self.search_query_bitmap(inner)?),
+            Query::And(vec) => {
+                // All is the identity of And, so it is only searched
+                // if there are no other subqueries.
+                let constraints: Vec<&Query> = vec
+                    .iter()
+                    .filter(|pred| !matches!(pred, Query::All))
+                    .collect();
+                if constraints.is_empty() && !vec.is_empty() {
+                    return Ok(self.universe_bitmap());
+                }
+                let mut bitmaps = Vec::with_capacity(constraints.len());
+                for pred in constraints {
+                    let bitmap = self.search_query_bitmap(pred)?;
+                    if bitmap.is_empty() {
+                        return Ok(bitmap);
+                    }
+                    bitmaps.push(bitmap);
+                }
+                bitmaps.sort_unstable_by_key(RoaringBitmap::len);
+                let mut bitmaps = bitmaps.into_iter();
+                let Some(mut result) = bitmaps.next() else {
+                    return Ok(RoaringBitmap::new());
+                };
+                for bitmap in bitmaps {
+                    result &= bitmap;
+                }
+                Ok(result)
+            }
+            Query::AtLeast(min_matches, vec) => {
+                let mut match_counts = HashMap::<u32, usize>::new();
+                for pred in vec.iter() {
+                    for id in self.search_query_bitmap(pred)? {
+                        *match_counts.entry(id).or_default() += 1;
+                    }
+                }
+                Ok(match_counts
+                    .into_iter()
+                    .filter(|(_, count)| count >= min_matches)
+                    .map(|(id, _)| id)
+                    .collect())
+            }
+            Query::Exclude(base, exclude) => {
+                let mut result = self.search_query_bitmap(base)?;
+                for pred in exclude.iter() {
+                    if result.is_empty() {
+                        break;
+                    }
+                    result -= self.search_query_bitmap(pred)?;
+                }
+                Ok(result)
+            }
+            leaf => self.search_leaf_bitmap(leaf),
+        }
+    }
+
+    /// This internal function runs a leaf query like
+    /// [search_leaf](Self::search_leaf) and returns its rows as a bitmap.
+    fn search_leaf_bitmap(&self, query: &Query) -> Result<RoaringBitmap> {
+        let index = self.leaf_index(query)?;
+        let Some(bitmap_index) = index.as_bitmap_index() else {
+            return Ok(self.search_index(index, query)?.into_iter().collect());
+        };
+        let result = bitmap_index
+            .search_bitmap(&self.normalize(query))
+            .map_err(|error| with_query_attribute(error, query))?;
+        if self.matches_exactly(query) {
+            return Ok(result
+                .into_iter()
+                .filter(|id| index.values_of(id).len() == 1)
+                .collect());
+        }
+        Ok(result)
+    }
+
+    /// This internal function returns the [universe](Self::universe) as a bitmap.
+    fn universe_bitmap(&self) -> RoaringBitmap {
+        self.universe().iter().copied().collect()
+    }
+}
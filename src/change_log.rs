@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// A marker of the state of a [SearchEngine](crate::SearchEngine), created by
+/// [SearchEngine::checkpoint](crate::SearchEngine::checkpoint).
+///
+/// Pass it to [SearchEngine::changes_since](crate::SearchEngine::changes_since)
+/// to get the rows that were changed after it was created. Checkpoints are
+/// ordered, a later checkpoint is larger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checkpoint {
+    version: u64,
+}
+
+/// The rows / primary ids that were changed after a [Checkpoint], as returned by
+/// [SearchEngine::changes_since](crate::SearchEngine::changes_since).
+///
+/// Every changed row is in exactly one of the sets, the one of its last change.
+/// A row that got a value and was removed afterwards is only in `removed`.
+#[derive(Debug, Clone)]
+pub struct ChangeSet<P> {
+    /// Rows that got a new value with [insert](crate::SearchEngine::insert).
+    pub added: HashSet<P>,
+
+    /// Rows that were removed with [remove_row](crate::SearchEngine::remove_row).
+    pub removed: HashSet<P>,
+}
+
+impl<P> Default for ChangeSet<P> {
+    fn default() -> Self {
+        Self {
+            added: HashSet::new(),
+            removed: HashSet::new(),
+        }
+    }
+}
+
+impl<P> ChangeSet<P> {
+    /// Returns true if no row was changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The last change of every row, used by a [SearchEngine](crate::SearchEngine)
+/// to answer [changes_since](crate::SearchEngine::changes_since).
+///
+/// Only one entry per row is kept, so the log grows with the number of
+/// changed rows and not with the number of changes.
+#[derive(Clone)]
+pub(crate) struct ChangeLog<P> {
+    /// Version of the last change. Every change increments it.
+    version: u64,
+
+    /// Version and kind of the last change of every row. The kind is true
+    /// for added rows and false for removed rows.
+    last_changes: HashMap<P, (u64, bool)>,
+}
+
+impl<P: Eq + Hash + Clone> ChangeLog<P> {
+    /// Creates an empty log.
+    pub fn new() -> Self {
+        Self {
+            version: 0,
+            last_changes: HashMap::new(),
+        }
+    }
+
+    /// Returns a checkpoint after all changes so far.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            version: self.version,
+        }
+    }
+
+    /// Records that a row was added or removed.
+    pub fn record(&mut self, primary_id: &P, added: bool) {
+        self.version += 1;
+        self.last_changes
+            .insert(primary_id.clone(), (self.version, added));
+    }
+
+    /// Returns all rows with their last change after the checkpoint.
+    pub fn changes_since(&self, checkpoint: &Checkpoint) -> ChangeSet<P> {
+        let mut changes = ChangeSet::default();
+        for (primary_id, &(version, added)) in self.last_changes.iter() {
+            if version <= checkpoint.version {
+                continue;
+            }
+            match added {
+                true => changes.added.insert(primary_id.clone()),
+                false => changes.removed.insert(primary_id.clone()),
+            };
+        }
+        changes
+    }
+
+    /// Drops the changes up to the checkpoint.
+    pub fn compact(&mut self, checkpoint: &Checkpoint) {
+        self.last_changes
+            .retain(|_, (version, _)| *version > checkpoint.version);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{error::Result, index::CompiledSearch};
+use std::collections::HashSet;
+
+/// A CompiledQuery is a [Query](crate::query::Query) that was prepared by
+/// [SearchEngine::compile](crate::engine::SearchEngine::compile) for repeated execution.
+///
+/// All attributes are resolved to their indices and all values are parsed
+/// when the query is compiled, so executing it skips this work.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{SearchEngine, SearchIndexBTreeRange, Query};
+/// use std::collections::HashSet;
+///
+/// let mut index = SearchIndexBTreeRange::<_, u32>::new();
+/// index.insert(0, 17);
+/// index.insert(1, 42);
+///
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.add_index("age", index);
+///
+/// let compiled = engine
+///     .compile(&Query::Minimum("age".into(), "18".into()))
+///     .expect("valid query");
+/// assert_eq!(compiled.execute(), Ok(HashSet::from_iter(vec![1])));
+/// ```
+pub struct CompiledQuery<'a, P> {
+    search: CompiledSearch<'a, P>,
+}
+
+impl<'a, P> CompiledQuery<'a, P> {
+    /// Creates a new `CompiledQuery` from a compiled search function.
+    pub(crate) fn new(search: CompiledSearch<'a, P>) -> Self {
+        Self { search }
+    }
+
+    /// Execute the query.
+    ///
+    /// The result is a HashSet of all row ids / primary ids
+    /// with rows that matched the query. The query was already validated
+    /// when it was compiled, so an error is only returned if an index
+    /// fails while searching.
+    pub fn execute(&self) -> Result<HashSet<P>> {
+        (self.search)()
+    }
+}
@@ -1,11 +1,22 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hash;
+use std::io::BufRead;
+use std::sync::{Arc, OnceLock};
 
+use crate::change_log::*;
+use crate::compiled_query::*;
+use crate::engine_snapshot::*;
 use crate::error::*;
 use crate::index::*;
 use crate::query::*;
 use crate::query_lexer::*;
+use crate::query_optimizer::*;
+use crate::query_parser::*;
+use crate::query_recorder::*;
+use crate::refinable_search::*;
+use crate::result_snapshot::*;
 
 /// A SearchEngine is a wrapper around a collection of [search indices](SearchIndex)
 /// that can process complex [queries](Query) involving multiple indices.
@@ -16,7 +27,86 @@ use crate::query_lexer::*;
 /// # Example
 /// A complete example can be found on the [front page of this crate](crate).
 pub struct SearchEngine<P> {
-    indices: HashMap<String, Box<dyn SearchIndex<P>>>,
+    indices: HashMap<String, Arc<Box<dyn SearchIndex<P>>>>,
+    parser_options: QueryParserOptions,
+    default_attribute: Option<String>,
+    normalizer: Option<Arc<Normalizer>>,
+    multi_value_match: HashMap<String, MultiValueMatch>,
+    score_mode: ScoreMode,
+    metadata: HashMap<String, IndexMetadata>,
+    recorder: Option<QueryRecorder>,
+    universe: OnceLock<HashSet<P>>,
+    change_log: Option<ChangeLog<P>>,
+}
+
+/// Function that normalizes inserted values and query values.
+type Normalizer = dyn Fn(&str) -> String + Send + Sync;
+
+/// Describes how [Exact](Query::Exact) queries match rows that have
+/// multiple values for the same attribute.
+///
+/// It is set per attribute with
+/// [SearchEngine::set_multi_value_match](SearchEngine::set_multi_value_match).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiValueMatch {
+    /// A row matches if any of its values equals the queried value.
+    /// This is the default.
+    #[default]
+    Any,
+    /// A row matches only if the queried value is its only value
+    /// for the attribute.
+    Exactly,
+}
+
+/// Describes how [SearchEngine::search_scored](SearchEngine::search_scored) scores
+/// rows that match multiple alternatives of an [Or](Query::Or) or
+/// [AtLeast](Query::AtLeast) query.
+///
+/// It is set with [SearchEngine::set_score_mode](SearchEngine::set_score_mode).
+/// For example, the selector `+pet:Cat,Dog` is an Or of two Exact queries. A row
+/// with both pets matches both of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreMode {
+    /// An Or scores like its best matching alternative, so the row with both
+    /// pets counts once. This matches the result sets, where a row is only
+    /// returned once. This is the default.
+    #[default]
+    Dedup,
+    /// An Or scores the sum of all matching alternatives, so the row with
+    /// both pets counts twice.
+    CountEach,
+}
+
+/// Informational metadata of an index, for example to show it in an admin UI.
+///
+/// It is set per attribute with
+/// [SearchEngine::set_index_metadata](SearchEngine::set_index_metadata)
+/// and doesn't affect searching.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexMetadata {
+    /// A human readable name of the attribute.
+    pub label: String,
+
+    /// A longer description of the attribute.
+    pub description: String,
+
+    /// True if the attribute should be shown to users, false if it is internal.
+    pub user_facing: bool,
+}
+
+/// Describes the index of an attribute, as returned by
+/// [SearchEngine::schema_info](SearchEngine::schema_info).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeInfo {
+    /// Name of the attribute.
+    pub name: String,
+
+    /// Queries that the index of the attribute supports, see
+    /// [SearchIndex::supported_queries].
+    pub supported_queries: SupportedQueries,
+
+    /// Name of the value type of the index, see [SearchIndex::value_type_name].
+    pub value_type_name: &'static str,
 }
 
 impl<P: Eq + Hash + Clone> Default for SearchEngine<P> {
@@ -25,6 +115,26 @@ impl<P: Eq + Hash + Clone> Default for SearchEngine<P> {
     }
 }
 
+/// Cloning an engine is cheap, because the clone shares the indices with
+/// the original. An index is copied with [SearchIndex::clone_box] the first
+/// time either engine changes it, so changes never affect the other engine.
+impl<P: Clone> Clone for SearchEngine<P> {
+    fn clone(&self) -> Self {
+        Self {
+            indices: self.indices.clone(),
+            parser_options: self.parser_options.clone(),
+            default_attribute: self.default_attribute.clone(),
+            normalizer: self.normalizer.clone(),
+            multi_value_match: self.multi_value_match.clone(),
+            score_mode: self.score_mode,
+            metadata: self.metadata.clone(),
+            recorder: self.recorder.clone(),
+            universe: self.universe.clone(),
+            change_log: self.change_log.clone(),
+        }
+    }
+}
+
 impl<P: Eq + Hash + Clone> SearchEngine<P> {
     /// Creates a new `SearchEngine`.
     ///
@@ -37,265 +147,2481 @@ impl<P: Eq + Hash + Clone> SearchEngine<P> {
     pub fn new() -> Self {
         Self {
             indices: HashMap::new(),
+            parser_options: QueryParserOptions::default(),
+            default_attribute: None,
+            normalizer: None,
+            multi_value_match: HashMap::new(),
+            score_mode: ScoreMode::default(),
+            metadata: HashMap::new(),
+            recorder: None,
+            universe: OnceLock::new(),
+            change_log: None,
         }
     }
 
-    /// Add a new index to this search engine.
+    /// Set the options that are used by [query_from_str](Self::query_from_str).
+    pub fn set_parser_options(&mut self, options: QueryParserOptions) {
+        self.parser_options = options;
+    }
+
+    /// Returns the options that are used by [query_from_str](Self::query_from_str).
+    pub fn parser_options(&self) -> &QueryParserOptions {
+        &self.parser_options
+    }
+
+    /// Set the attribute that is searched for freetext by
+    /// [query_from_str_default](Self::query_from_str_default).
+    ///
+    /// If no index with this name was added,
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned
+    /// and the previous default attribute is kept.
+    pub fn set_default_attribute(&mut self, attribute: &str) -> Result<()> {
+        if !self.indices.contains_key(attribute) {
+            return Err(SearchEngineError::UnknownAttribute);
+        }
+        self.default_attribute = Some(attribute.to_owned());
+        Ok(())
+    }
+
+    /// Set how [Exact](Query::Exact) queries match rows with multiple values
+    /// for an attribute.
+    ///
+    /// With [MultiValueMatch::Exactly] a row only matches if its set of values
+    /// for the attribute is exactly the queried value. This needs a reverse lookup
+    /// with [values_of](SearchIndex::values_of) for every matching row, so it is
+    /// slower than the default [MultiValueMatch::Any]. Other queries are not affected.
+    /// If no index with this name was added,
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned.
     ///
     /// # Example
     /// ```rust
-    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, MultiValueMatch, Query};
+    /// use std::collections::HashSet;
     ///
-    /// let mut index = SearchIndexHashMap::<_, String>::new();
-    /// // Fill index here...
+    /// let mut index_pet = SearchIndexHashMap::<_, String>::new();
+    /// index_pet.insert(0, "Dog".into());
+    /// index_pet.insert(1, "Dog".into());
+    /// index_pet.insert(1, "Cat".into());
     ///
     /// let mut engine = SearchEngine::<usize>::new();
-    /// engine.add_index("attribute", index);
+    /// engine.add_index("pet", index_pet);
+    ///
+    /// let q = Query::Exact("pet".into(), "Dog".into());
+    /// assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+    ///
+    /// engine.set_multi_value_match("pet", MultiValueMatch::Exactly).expect("index exists");
+    /// assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
     /// ```
-    pub fn add_index<T: SearchIndex<P> + 'static>(&mut self, name: &str, index: T) {
-        self.indices.insert(name.into(), Box::new(index));
+    pub fn set_multi_value_match(&mut self, attribute: &str, mode: MultiValueMatch) -> Result<()> {
+        if !self.indices.contains_key(attribute) {
+            return Err(SearchEngineError::UnknownAttribute);
+        }
+        self.multi_value_match.insert(attribute.to_owned(), mode);
+        Ok(())
     }
 
-    /// Run a query on the search engine.
+    /// Set how [search_scored](Self::search_scored) scores rows that match
+    /// multiple alternatives of an Or query, see [ScoreMode].
+    pub fn set_score_mode(&mut self, mode: ScoreMode) {
+        self.score_mode = mode;
+    }
+
+    /// Returns the [ScoreMode] that is used by [search_scored](Self::search_scored).
+    pub fn score_mode(&self) -> ScoreMode {
+        self.score_mode
+    }
+
+    /// Set the [IndexMetadata] of an attribute, replacing previous metadata.
     ///
-    /// The result is a HashSet of all row ids / primary ids
-    /// with rows that matched the query.
-    pub fn search(&self, query: &Query) -> Result<HashSet<P>> {
-        match query {
-            Query::Exact(attr, _)
-            | Query::Prefix(attr, _)
-            | Query::InRange(attr, _, _)
-            | Query::OutRange(attr, _, _)
-            | Query::Minimum(attr, _)
-            | Query::Maximum(attr, _) => {
-                let index = self
-                    .indices
-                    .get(attr)
-                    .ok_or(SearchEngineError::UnknownAttribute)?;
-                index.search(query)
-            }
-            Query::Or(vec) => {
-                let mut result_set = HashSet::<P>::new();
-                for pred in vec.iter() {
-                    let attribute_set = self.search(pred)?;
-                    result_set = result_set.union(&attribute_set).cloned().collect();
-                }
-                Ok(result_set)
-            }
-            Query::And(vec) => {
-                let mut result_set = HashSet::<P>::new();
-                for (i, pred) in vec.iter().enumerate() {
-                    let attribute_set = self.search(pred)?;
-                    if i == 0 {
-                        result_set = attribute_set;
-                    } else {
-                        result_set = result_set.intersection(&attribute_set).cloned().collect();
-                    }
-                    if result_set.is_empty() {
-                        return Ok(result_set);
-                    }
-                }
-                Ok(result_set)
-            }
-            Query::Exclude(base, exclude) => {
-                let mut result_set = self.search(base)?;
-                for pred in exclude.iter() {
-                    let attribute_set = self.search(pred)?;
-                    result_set = result_set.difference(&attribute_set).cloned().collect();
-                    if result_set.is_empty() {
-                        return Ok(result_set);
-                    }
-                }
-                Ok(result_set)
-            }
+    /// The metadata is purely informational and doesn't affect searching.
+    /// If no index with this name was added,
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{IndexMetadata, SearchEngine, SearchIndexHashMap};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("pet", SearchIndexHashMap::<_, String>::new());
+    ///
+    /// let meta = IndexMetadata {
+    ///     label: "Pet".into(),
+    ///     user_facing: true,
+    ///     ..Default::default()
+    /// };
+    /// engine.set_index_metadata("pet", meta.clone()).expect("index exists");
+    /// assert_eq!(engine.index_metadata("pet"), Some(&meta));
+    /// ```
+    pub fn set_index_metadata(&mut self, attribute: &str, metadata: IndexMetadata) -> Result<()> {
+        if !self.indices.contains_key(attribute) {
+            return Err(SearchEngineError::UnknownAttribute);
         }
+        self.metadata.insert(attribute.to_owned(), metadata);
+        Ok(())
     }
 
-    /// Build a [Query] from a string slice.
+    /// Returns the [IndexMetadata] of an attribute or `None`
+    /// if no metadata was set for it.
+    pub fn index_metadata(&self, attribute: &str) -> Option<&IndexMetadata> {
+        self.metadata.get(attribute)
+    }
+
+    /// Returns an [AttributeInfo] for every attribute, sorted by name.
     ///
-    /// This function can return an error if an unknown index is referenced.
-    /// On success this function returns a fully owned Query and a vector of string
-    /// slices into the input query, that contains all parts of the query, that are not
-    /// following the query syntax. They are called "Freetext". They will never contain
-    /// whitespace.
+    /// This can be used by tools to describe the search capabilities
+    /// of an engine, for example to generate API documentation.
     ///
-    /// # Basic Syntax
-    /// The following text is an example for a query:
-    /// ```text
-    /// +attr1:foo,bar +attr2:=baz +attr3:<42,>84 -attr4:69-121
-    /// ```
-    /// As a boolean expression it will mean something like this:
-    /// ```text
-    ///    (attr1==foo || attr1==bar)
-    /// && (attr2==baz)
-    /// && (attr3 <= 42 || attr3 >= 84)
-    /// && !(69 <= attr4 <= 121)
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, SUPPORTS_EXACT};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    ///
+    /// let schema = engine.schema_info();
+    /// assert_eq!(schema[0].name, "name");
+    /// assert_ne!(schema[0].supported_queries & SUPPORTS_EXACT, 0);
+    /// assert_eq!(schema[0].value_type_name, "alloc::string::String");
     /// ```
+    pub fn schema_info(&self) -> Vec<AttributeInfo> {
+        let mut schema: Vec<AttributeInfo> = self
+            .indices
+            .iter()
+            .map(|(name, index)| AttributeInfo {
+                name: name.clone(),
+                supported_queries: index.supported_queries(),
+                value_type_name: index.value_type_name(),
+            })
+            .collect();
+        schema.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        schema
+    }
+
+    /// Attach a [QueryRecorder] that records every query passed to [search](Self::search).
     ///
-    /// # In-depth Syntax description
-    /// A string query consists of multiple whitespace seperated attribute selectors.
-    /// Each of them starts with a `+` or `-` sign, indicating if the rows matching this
-    /// selector should be included or excluded from the result. This is followed by the
-    /// name of the attribute/index and a single `:` char. Next comes a list of comma seperated
-    /// values that describe the basic queries that are used to select rows. There are
-    /// special operator symbols that can change the meaning of a value if the index
-    /// supports the matching query type. For example, if the Index supports Maximum queries,
-    /// the following value will return a Maximum query instead of an Exact query: `<123`.
+    /// Methods that are built on `search`, like [search_ordered](Self::search_ordered)
+    /// or [refinable](Self::refinable), are recorded as well, and so are
+    /// [search_acl](Self::search_acl) and [search_leaf](Self::search_leaf). Only the queries passed
+    /// by the caller are recorded, not their subqueries. A previous recorder is replaced.
+    /// The recorded queries can be replayed with [replay] to benchmark the engine.
     ///
-    /// The following operator symbols are currently used **if the index supports it**:
-    /// - `>val` - forces a Minimum query
-    /// - `<val` - forces a Maximum query
-    /// - `=val` - forces a Exact query
-    /// - `minval-maxval` - forces a InRange query
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, QueryRecorder, Query};
     ///
-    /// If no operator symbol is found, a Prefix query will be used if it is supported by the index.
-    /// Otherwise a Exact query is used, even if the index may not support it (all official indices
-    /// currently implement them).
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
     ///
-    /// All non-whitespace substrings in the query, that are not valid attribute selectors are
-    /// considered "Freetext". All of these are returned on success and can be used or
-    /// ignored by the caller. For example they can be used to filter the results further.
+    /// let recorder = QueryRecorder::new();
+    /// engine.set_recorder(recorder.clone());
     ///
-    /// # Limits
-    /// - OutRange queries don't have an operator symbol and are currently not supported.
-    ///   But it is possible to build a functionally equivalent query if the index supports
-    ///   Minimum and Maximum queries: `+attr:<10,>20`
-    /// - InRange does not support negative values because only one `-` char is allowed.
-    /// - There is no way to force a Prefix query. It will be automatically used if no
-    ///   operator symbol is found and the index supports them.
+    /// let q = Query::Exact("name".into(), "Alice".into());
+    /// engine.search(&q).expect("valid query");
+    /// assert_eq!(recorder.queries(), vec![q]);
+    /// ```
+    pub fn set_recorder(&mut self, recorder: QueryRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Set a function that normalizes all values, for example by trimming
+    /// or lowercasing them.
+    ///
+    /// The normalizer is applied to the values of all leaf queries before they
+    /// are passed to an index and to all values inserted with [insert](Self::insert).
+    /// Values that were inserted directly into an index or before the normalizer
+    /// was set are not normalized retroactively.
     ///
     /// # Example
     /// ```rust
     /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
-    ///
-    /// let mut index = SearchIndexHashMap::<_, String>::new();
-    /// // Fill index here...
+    /// use std::collections::HashSet;
     ///
     /// let mut engine = SearchEngine::<usize>::new();
-    /// engine.add_index("attribute", index);
-    /// let (q, freetext) = engine.query_from_str("+attribute:foo bar").expect("no error");
-    /// assert_eq!(q, Query::And(vec![Query::Exact("attribute".into(), "foo".into())]));
-    /// assert_eq!(freetext, vec!["bar"]);
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    /// engine.set_normalizer(|value| value.trim().to_lowercase());
+    /// engine.insert("name", 0, " Alice").expect("index exists");
+    ///
+    /// let q = Query::Exact("name".into(), "ALICE".into());
+    /// assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
     /// ```
-    pub fn query_from_str<'a>(&self, query_str: &'a str) -> Result<(Query, Vec<&'a str>)> {
-        let mut include = vec![];
-        let mut exclude = vec![];
-        let mut freetexts = vec![];
-
-        let lexer = QueryLexer::new(query_str);
-        for subquery in lexer {
-            match subquery {
-                QueryToken::Attribute(is_include, attribute, values) => {
-                    let index = self
-                        .indices
-                        .get(attribute)
-                        .ok_or(SearchEngineError::UnknownAttribute)?;
-                    let supported = index.supported_queries();
-
-                    let mut qs: Vec<_> = values
-                        .iter()
-                        .map(|&v| {
-                            let attr = attribute.to_owned();
-                            if (supported & SUPPORTS_MINIMUM) != 0 && v.starts_with('>') {
-                                return Query::Minimum(attr, v[1..].to_owned());
-                            }
-                            if (supported & SUPPORTS_MAXIMUM) != 0 && v.starts_with('<') {
-                                return Query::Maximum(attr, v[1..].to_owned());
-                            }
-                            if (supported & SUPPORTS_EXACT) != 0 && v.starts_with('=') {
-                                return Query::Exact(attr, v[1..].to_owned());
-                            }
-                            if (supported & SUPPORTS_INRANGE) != 0 && v.contains('-') {
-                                let parts = v.split('-').collect::<Vec<_>>();
-                                if parts.len() == 2 {
-                                    return Query::InRange(
-                                        attr,
-                                        parts[0].to_owned(),
-                                        parts[1].to_owned(),
-                                    );
-                                }
-                            }
-
-                            // Fallback, if nothing is found we use prefix if we can
-                            // and exact otherwise.
-                            if (supported & SUPPORTS_PREFIX) != 0 {
-                                return Query::Prefix(attr, v.to_owned());
-                            }
-                            Query::Exact(attr, v.to_owned())
-                        })
-                        .collect();
-                    let q = match qs.len().cmp(&1) {
-                        Ordering::Equal => qs.swap_remove(0),
-                        Ordering::Greater => Query::Or(qs),
-                        Ordering::Less => continue,
-                    };
-                    if is_include {
-                        include.push(q);
-                    } else {
-                        exclude.push(q);
-                    }
-                }
-                QueryToken::Freetext(text) => {
-                    freetexts.push(text);
-                }
-            }
-        }
-
-        let base_query = Query::And(include);
-        if !exclude.is_empty() {
-            Ok((Query::Exclude(base_query.into(), exclude), freetexts))
-        } else {
-            Ok((base_query, freetexts))
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    struct DummyIndex {
-        fixed_values: HashSet<usize>,
-        supported_queries: SupportedQueries,
+    pub fn set_normalizer(&mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) {
+        self.normalizer = Some(Arc::new(f));
     }
 
-    impl DummyIndex {
-        fn new(vals: Vec<usize>) -> Self {
-            Self {
-                fixed_values: HashSet::from_iter(vals),
-                supported_queries: SUPPORTS_EXACT,
+    /// Insert a new entry into the index of an attribute.
+    ///
+    /// The value is normalized by the normalizer that was set with
+    /// [set_normalizer](Self::set_normalizer) and then parsed by the index.
+    /// Returns [UnknownAttribute](SearchEngineError::UnknownAttribute) if there is
+    /// no index with this name,
+    /// [MismatchedQueryType](SearchEngineError::MismatchedQueryType) if the value
+    /// can't be parsed and [UnsupportedOperation](SearchEngineError::UnsupportedOperation)
+    /// if the index doesn't support inserting values by string.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexBTreeRange};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("age", SearchIndexBTreeRange::<_, u8>::new());
+    /// engine.insert("age", 0, "27").expect("valid value");
+    /// assert!(engine.insert("age", 1, "unknown").is_err());
+    /// ```
+    pub fn insert(&mut self, attribute: &str, primary_id: P, value: &str) -> Result<()> {
+        let value = self.normalize_value(value);
+        let index = self
+            .indices
+            .get_mut(attribute)
+            .ok_or(SearchEngineError::UnknownAttribute)?;
+        let index = Arc::make_mut(index);
+        self.universe.take();
+        match self.change_log.as_mut() {
+            Some(change_log) => {
+                index
+                    .insert_str(primary_id.clone(), &value)
+                    .map_err(|error| error.with_attribute(attribute))?;
+                change_log.record(&primary_id, true);
+                Ok(())
             }
+            None => index
+                .insert_str(primary_id, &value)
+                .map_err(|error| error.with_attribute(attribute)),
         }
+    }
 
-        fn supports(sup: SupportedQueries) -> Self {
-            Self {
-                fixed_values: HashSet::new(),
-                supported_queries: sup,
+    /// Insert the values of a line-delimited source, like a log file.
+    ///
+    /// Every line is passed to `parse` without its line ending, which returns
+    /// the `(primary_id, attribute, value)` triples of the line. The triples are
+    /// inserted with [insert](Self::insert). Returns the number of inserted triples.
+    ///
+    /// Ingestion stops at the first line that can't be read, which returns an
+    /// [Io](SearchEngineError::Io) error, or the first triple that can't be
+    /// inserted. All triples before it stay in the engine.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("level", SearchIndexHashMap::<_, String>::new());
+    ///
+    /// let log = "0 INFO\n1 WARN\n2 INFO\n";
+    /// let count = engine.ingest_lines(log.as_bytes(), |line| {
+    ///     let (id, level) = line.split_once(' ').unwrap();
+    ///     vec![(id.parse().unwrap(), "level".into(), level.into())]
+    /// });
+    /// assert_eq!(count, Ok(3));
+    ///
+    /// let result = engine.search(&Query::Exact("level".into(), "INFO".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+    /// ```
+    pub fn ingest_lines<R: BufRead>(
+        &mut self,
+        reader: R,
+        mut parse: impl FnMut(&str) -> Vec<(P, String, String)>,
+    ) -> Result<usize> {
+        let mut count = 0;
+        for line in reader.lines() {
+            for (primary_id, attribute, value) in parse(&line?) {
+                self.insert(&attribute, primary_id, &value)?;
+                count += 1;
             }
         }
+        Ok(count)
     }
 
-    impl SearchIndex<usize> for DummyIndex {
-        fn search(&self, _query: &Query) -> Result<HashSet<usize>> {
-            Ok(self.fixed_values.clone())
+    /// Applies the normalizer to a single value.
+    fn normalize_value<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        match &self.normalizer {
+            Some(normalizer) => Cow::Owned(normalizer(value)),
+            None => Cow::Borrowed(value),
         }
+    }
 
-        fn supported_queries(&self) -> SupportedQueries {
-            self.supported_queries
+    /// Applies the normalizer to the values of a leaf query.
+    pub(crate) fn normalize<'a>(&self, query: &'a Query) -> Cow<'a, Query> {
+        match &self.normalizer {
+            Some(normalizer) => Cow::Owned(query.map_values(normalizer.as_ref())),
+            None => Cow::Borrowed(query),
         }
     }
 
-    #[test]
-    fn search_or() {
-        let mut engine = SearchEngine::<usize>::new();
-        engine.add_index("a", DummyIndex::new(vec![1, 2]));
-        engine.add_index("b", DummyIndex::new(vec![3, 4]));
-        engine.add_index("c", DummyIndex::new(vec![2, 5, 6]));
-        let result = engine.search(&Query::Or(vec![
-            Query::Exact("a".into(), "DUMMY".into()),
-            Query::Exact("c".into(), "DUMMY".into()),
-        ]));
-        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 2, 5, 6])));
+    /// Add a new index to this search engine.
+    ///
+    /// If there already is an index with this name, it is replaced and dropped.
+    /// Use [remove_index](Self::remove_index) first to keep the old index.
+    /// Settings of the attribute, like its [metadata](Self::set_index_metadata),
+    /// are kept for the new index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// // Fill index here...
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    /// ```
+    pub fn add_index<T: SearchIndex<P> + 'static>(&mut self, name: &str, index: T) {
+        self.indices.insert(name.into(), Arc::new(Box::new(index)));
+        self.universe.take();
+    }
+
+    /// Remove the index with this name and return it, or `None` if there is none.
+    ///
+    /// Queries on the attribute return
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) until an index
+    /// with the same name is added again. Settings of the attribute, like its
+    /// [metadata](Self::set_index_metadata), are kept for that index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::*;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("city", SearchIndexHashMap::<_, String>::new());
+    /// engine.insert("city", 0, "Berlin").unwrap();
+    ///
+    /// let index = engine.remove_index("city").expect("index exists");
+    /// assert_eq!(index.entries(), vec![(0, "Berlin".into())]);
+    /// assert!(!engine.has_index("city"));
+    /// assert!(engine.remove_index("city").is_none());
+    /// ```
+    pub fn remove_index(&mut self, name: &str) -> Option<Box<dyn SearchIndex<P>>> {
+        self.universe.take();
+        let index = self.indices.remove(name)?;
+        Some(Arc::try_unwrap(index).unwrap_or_else(|index| (*index).clone()))
+    }
+
+    /// Returns true if an index with this name was added.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("city", SearchIndexHashMap::<_, String>::new());
+    /// assert!(engine.has_index("city"));
+    /// assert!(!engine.has_index("name"));
+    /// ```
+    pub fn has_index(&self, name: &str) -> bool {
+        self.indices.contains_key(name)
+    }
+
+    /// Remove a row / primary id from all indices.
+    ///
+    /// Calls [remove_all](SearchIndex::remove_all) on every index and returns
+    /// the number of indices that had anything stored for the row.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::*;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    /// engine.add_index("age", SearchIndexBTreeRange::<_, u8>::new());
+    /// engine.insert("name", 0, "Alice").unwrap();
+    /// engine.insert("age", 0, "27").unwrap();
+    /// engine.insert("name", 1, "Bob").unwrap();
+    ///
+    /// assert_eq!(engine.remove_row(&0), 2);
+    /// assert_eq!(engine.all_ids(), HashSet::from_iter(vec![1]));
+    /// ```
+    pub fn remove_row(&mut self, primary_id: &P) -> usize {
+        self.universe.take();
+        let count = self
+            .indices
+            .values_mut()
+            .filter_map(|index| Arc::make_mut(index).remove_all(primary_id).then_some(()))
+            .count();
+        if let Some(change_log) = self.change_log.as_mut().filter(|_| count > 0) {
+            change_log.record(primary_id, false);
+        }
+        count
+    }
+
+    /// Create a [Checkpoint] of the current state of the engine.
+    ///
+    /// Rows that are changed after the checkpoint are returned by
+    /// [changes_since](Self::changes_since). Changes are only recorded after the
+    /// first checkpoint was created, so engines that don't use checkpoints
+    /// don't pay for them.
+    ///
+    /// # Memory growth
+    /// The engine keeps the last change of every row that was changed after the
+    /// first checkpoint, so the change history grows with the number of distinct
+    /// changed rows, not with the number of changes. Use
+    /// [compact_changes](Self::compact_changes) to drop the history up to a
+    /// checkpoint that is no longer needed.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("status", SearchIndexHashMap::<_, String>::new());
+    /// engine.insert("status", 0, "online").unwrap();
+    ///
+    /// let checkpoint = engine.checkpoint();
+    /// engine.insert("status", 1, "online").unwrap();
+    /// engine.remove_row(&0);
+    ///
+    /// let changes = engine.changes_since(&checkpoint);
+    /// assert_eq!(changes.added, HashSet::from_iter(vec![1]));
+    /// assert_eq!(changes.removed, HashSet::from_iter(vec![0]));
+    /// ```
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.change_log
+            .get_or_insert_with(ChangeLog::new)
+            .checkpoint()
+    }
+
+    /// Returns the rows / primary ids that were added with [insert](Self::insert)
+    /// or removed with [remove_row](Self::remove_row) after a [Checkpoint].
+    ///
+    /// Each row is returned once, in the set of its last change. Changes that
+    /// were dropped with [compact_changes](Self::compact_changes) are not
+    /// returned, so the result for a checkpoint before the compacted one can
+    /// be incomplete. Adding, replacing or removing whole indices is not
+    /// recorded.
+    pub fn changes_since(&self, checkpoint: &Checkpoint) -> ChangeSet<P> {
+        self.change_log
+            .as_ref()
+            .map(|change_log| change_log.changes_since(checkpoint))
+            .unwrap_or_default()
+    }
+
+    /// Drop the change history up to and including a [Checkpoint].
+    ///
+    /// Afterwards [changes_since](Self::changes_since) still returns the correct
+    /// rows for this and all later checkpoints, but not for earlier ones.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("status", SearchIndexHashMap::<_, String>::new());
+    ///
+    /// let first = engine.checkpoint();
+    /// engine.insert("status", 0, "online").unwrap();
+    /// let second = engine.checkpoint();
+    /// engine.insert("status", 1, "online").unwrap();
+    ///
+    /// engine.compact_changes(&second);
+    /// assert_eq!(engine.changes_since(&second).added, HashSet::from_iter(vec![1]));
+    /// // The insert of row 0 was dropped from the history.
+    /// assert_eq!(engine.changes_since(&first).added, HashSet::from_iter(vec![1]));
+    /// ```
+    pub fn compact_changes(&mut self, checkpoint: &Checkpoint) {
+        if let Some(change_log) = self.change_log.as_mut() {
+            change_log.compact(checkpoint);
+        }
+    }
+
+    /// Replace the index of an attribute with a new index of type `T`
+    /// that contains the same values.
+    ///
+    /// The values are read from the old index with [entries](SearchIndex::entries)
+    /// and inserted into a default `T` with [insert_str](SearchIndex::insert_str),
+    /// so other attributes don't have to be ingested again. Rows that were only
+    /// recorded as null aren't part of the entries and are not carried over.
+    ///
+    /// Returns [UnknownAttribute](SearchEngineError::UnknownAttribute) if there
+    /// is no index with this name and
+    /// [UnsupportedOperation](SearchEngineError::UnsupportedOperation) if the
+    /// old index doesn't [support entries](SearchIndex::supports_entries), like
+    /// [SearchIndexFunction]. If a
+    /// value can't be inserted into `T`, its error is returned. In all these
+    /// cases the old index is kept.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::*;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("city", SearchIndexHashMap::<_, String>::new());
+    /// engine.insert("city", 0, "Berlin").unwrap();
+    /// engine.insert("city", 1, "Bern").unwrap();
+    ///
+    /// engine.reindex_attribute::<SearchIndexPrefixTree<_>>("city").unwrap();
+    /// let result = engine.search(&Query::Prefix("city".into(), "Ber".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+    /// ```
+    pub fn reindex_attribute<T: SearchIndex<P> + Default + 'static>(
+        &mut self,
+        name: &str,
+    ) -> Result<()> {
+        let old_index = self
+            .indices
+            .get(name)
+            .ok_or(SearchEngineError::UnknownAttribute)?;
+        if !old_index.supports_entries() {
+            return Err(SearchEngineError::UnsupportedOperation);
+        }
+
+        let mut index = T::default();
+        for (primary_id, value) in old_index.entries() {
+            index
+                .insert_str(primary_id, &value)
+                .map_err(|error| error.with_attribute(name))?;
+        }
+        self.add_index(name, index);
+        Ok(())
+    }
+
+    /// Run a query on the search engine.
+    ///
+    /// The result is a HashSet of all row ids / primary ids
+    /// with rows that matched the query.
+    ///
+    /// The subqueries of an [And](Query::And) are searched first and then
+    /// intersected starting with the smallest result, so their order
+    /// doesn't matter. If one of them matches no rows, the rest is skipped.
+    pub fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        let mut result_set = HashSet::new();
+        self.search_into(query, &mut result_set)?;
+        Ok(result_set)
+    }
+
+    /// Run a query on the search engine and add the matching row ids to `out`.
+    ///
+    /// Row ids that are already part of `out` are kept, so multiple results can
+    /// be collected in one set. The subqueries of an [Or](Query::Or) are added
+    /// directly, and if `out` is empty, [Exclude](Query::Exclude) queries narrow
+    /// it down in place. This avoids a new set for every subquery. If an error is returned, `out` may contain
+    /// a part of the result.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(0, "foo".into());
+    /// index.insert(1, "bar".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let mut result_set = HashSet::from_iter(vec![5]);
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// engine.search_into(&q, &mut result_set).expect("valid query");
+    /// assert_eq!(result_set, HashSet::from_iter(vec![0, 5]));
+    /// ```
+    pub fn search_into(&self, query: &Query, out: &mut HashSet<P>) -> Result<()> {
+        self.record(query);
+        self.check_attributes(query)?;
+        self.search_query_into(query, out)
+    }
+
+    /// Run a query on the search engine and return the row ids sorted
+    /// by a comparator.
+    ///
+    /// The result is the same as the result of [search](Self::search),
+    /// collected into a Vec and sorted by `cmp`. The sort is not stable,
+    /// so rows that compare equal may be returned in any order.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(1, "foo".into());
+    /// index.insert(3, "foo".into());
+    /// index.insert(2, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// assert_eq!(engine.search_sorted(&q, |a, b| b.cmp(a)), Ok(vec![3, 2, 1]));
+    /// ```
+    pub fn search_sorted<F>(&self, query: &Query, cmp: F) -> Result<Vec<P>>
+    where
+        F: FnMut(&P, &P) -> Ordering,
+    {
+        let mut result: Vec<P> = self.search(query)?.into_iter().collect();
+        result.sort_unstable_by(cmp);
+        Ok(result)
+    }
+
+    /// Run a query on the search engine and return the number of matching rows.
+    ///
+    /// The result is the length of the result of [search](Self::search). Leaf
+    /// queries, like [Exact](Query::Exact), are passed to
+    /// [SearchIndex::count_query], so indices like [SearchIndexHashMap] and
+    /// [SearchIndexBTreeRange] don't have to copy the matching rows.
+    ///
+    /// The [Exact](Query::Exact) subqueries of an [And](Query::And) are counted
+    /// first, so the smallest one is searched and intersected with the others.
+    /// If one of them matches no rows, nothing is searched at all. Other queries
+    /// are searched and the size of their result is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexBTreeRange, Query};
+    ///
+    /// let mut index = SearchIndexBTreeRange::<_, u8>::new();
+    /// index.insert(0, 17);
+    /// index.insert(1, 42);
+    /// index.insert(2, 42);
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("age", index);
+    ///
+    /// assert_eq!(engine.count(&Query::Exact("age".into(), "42".into())), Ok(2));
+    /// assert_eq!(engine.count(&Query::Maximum("age".into(), "20".into())), Ok(1));
+    /// ```
+    pub fn count(&self, query: &Query) -> Result<usize> {
+        self.record(query);
+        self.check_attributes(query)?;
+        self.count_query(query)
+    }
+
+    /// This internal function counts a query without recording it,
+    /// like [search_query](Self::search_query).
+    fn count_query(&self, query: &Query) -> Result<usize> {
+        match query {
+            Query::And(vec) => {
+                let constraints: Vec<&Query> = vec
+                    .iter()
+                    .filter(|pred| !matches!(pred, Query::All))
+                    .collect();
+                match constraints[..] {
+                    [] if vec.is_empty() => return Ok(0),
+                    [] => return Ok(self.universe().len()),
+                    [pred] => return self.count_query(pred),
+                    _ => {}
+                }
+
+                // Only Exact queries are counted in advance, because counting other
+                // queries may cost as much as searching them. They are sorted last,
+                // in their original order.
+                let mut sized = Vec::with_capacity(constraints.len());
+                for pred in constraints {
+                    let size = match pred {
+                        Query::Exact(_, _) if !self.matches_exactly(pred) => {
+                            self.count_query(pred)?
+                        }
+                        _ => usize::MAX,
+                    };
+                    if size == 0 {
+                        return Ok(0);
+                    }
+                    sized.push((size, pred));
+                }
+                sized.sort_by_key(|(size, _)| *size);
+
+                let (smallest, rest) = sized.split_first().expect("at least two constraints");
+                let mut result_set = self.search_query(smallest.1)?;
+                for (_, pred) in rest {
+                    if result_set.is_empty() {
+                        break;
+                    }
+                    result_set = intersect(result_set, &self.search_query(pred)?);
+                }
+                Ok(result_set.len())
+            }
+            query => match query.attribute() {
+                Some(attr) if !self.matches_exactly(query) => self
+                    .indices
+                    .get(attr)
+                    .ok_or(SearchEngineError::UnknownAttribute)?
+                    .count_query(&self.normalize(query))
+                    .map_err(|error| error.with_attribute(attr)),
+                _ => self.search_query(query).map(|result_set| result_set.len()),
+            },
+        }
+    }
+
+    /// This internal function passes a query to the recorder, if there is one.
+    pub(crate) fn record(&self, query: &Query) {
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.record(query);
+        }
+    }
+
+    /// This internal function runs a query without recording it,
+    /// so subqueries are not passed to the recorder.
+    fn search_query(&self, query: &Query) -> Result<HashSet<P>> {
+        let mut result_set = HashSet::new();
+        self.search_query_into(query, &mut result_set)?;
+        Ok(result_set)
+    }
+
+    /// This internal function adds the result of a query to `out`
+    /// without recording it, like [search_query](Self::search_query).
+    fn search_query_into(&self, query: &Query, out: &mut HashSet<P>) -> Result<()> {
+        match query {
+            Query::Exact(attr, _)
+            | Query::Prefix(attr, _)
+            | Query::Suffix(attr, _)
+            | Query::Contains(attr, _)
+            | Query::AllTokens(attr, _)
+            | Query::AnyTokens(attr, _)
+            | Query::Regex(attr, _)
+            | Query::Fuzzy(attr, _, _)
+            | Query::Pattern(attr, _)
+            | Query::InRange(attr, _, _)
+            | Query::InRangeBounds(attr, _, _)
+            | Query::OutRange(attr, _, _)
+            | Query::OutRangeBounds(attr, _, _)
+            | Query::Minimum(attr, _)
+            | Query::Maximum(attr, _)
+            | Query::LengthInRange(attr, _, _)
+            | Query::IsNull(attr)
+            | Query::IsNotNull(attr) => {
+                let index = self
+                    .indices
+                    .get(attr)
+                    .ok_or(SearchEngineError::UnknownAttribute)?;
+                let result_set = self.search_index(index.as_ref().as_ref(), query)?;
+                if out.is_empty() {
+                    *out = result_set;
+                } else {
+                    out.extend(result_set);
+                }
+            }
+            Query::Or(vec) => {
+                for pred in vec.iter() {
+                    self.search_query_into(pred, out)?;
+                }
+            }
+            Query::All => out.extend(self.universe().iter().cloned()),
+            Query::Not(inner) => {
+                let inner_set = self.search_query(inner)?;
+                out.extend(
+                    self.universe()
+                        .iter()
+                        .filter(|id| !inner_set.contains(*id))
+                        .cloned(),
+                );
+            }
+            Query::And(vec) => {
+                // All is the identity of And, so it is only searched
+                // if there are no other subqueries.
+                let constraints: Vec<&Query> = vec
+                    .iter()
+                    .filter(|pred| !matches!(pred, Query::All))
+                    .collect();
+                if constraints.is_empty() && !vec.is_empty() {
+                    out.extend(self.universe().iter().cloned());
+                    return Ok(());
+                }
+                // All subqueries are searched first, so the intersection can start
+                // with the smallest result and never copies a larger one.
+                let mut attribute_sets = Vec::with_capacity(constraints.len());
+                for pred in constraints {
+                    let attribute_set = self.search_query(pred)?;
+                    if attribute_set.is_empty() {
+                        return Ok(());
+                    }
+                    attribute_sets.push(attribute_set);
+                }
+                attribute_sets.sort_unstable_by_key(HashSet::len);
+                let mut attribute_sets = attribute_sets.into_iter();
+                let Some(mut result_set) = attribute_sets.next() else {
+                    return Ok(());
+                };
+                for attribute_set in attribute_sets {
+                    if result_set.is_empty() {
+                        break;
+                    }
+                    result_set.retain(|id| attribute_set.contains(id));
+                }
+                if out.is_empty() {
+                    *out = result_set;
+                } else {
+                    out.extend(result_set);
+                }
+            }
+            Query::AtLeast(min_matches, vec) => {
+                let mut match_counts = HashMap::<P, usize>::new();
+                for pred in vec.iter() {
+                    for id in self.search_query(pred)? {
+                        *match_counts.entry(id).or_default() += 1;
+                    }
+                }
+                out.extend(
+                    match_counts
+                        .into_iter()
+                        .filter(|(_, count)| count >= min_matches)
+                        .map(|(id, _)| id),
+                );
+            }
+            Query::Exclude(base, exclude) => {
+                // An empty output is narrowed down in place, otherwise
+                // the result is built separately and added.
+                let mut result_set = HashSet::new();
+                let target = if out.is_empty() {
+                    &mut *out
+                } else {
+                    &mut result_set
+                };
+                self.search_query_into(base, target)?;
+                for pred in exclude.iter() {
+                    if target.is_empty() {
+                        break;
+                    }
+                    remove_all(target, &self.search_query(pred)?);
+                }
+                out.extend(result_set);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a query and only return rows that are part of an access control list.
+    ///
+    /// The result is the same as intersecting the result of [search](Self::search)
+    /// with `acl`, but the ACL is already applied to intermediate results where this
+    /// is cheap. The first subquery of an [And](Query::And) is restricted to the ACL
+    /// immediately, so the query can stop early if no allowed row is left. Every
+    /// intersection iterates the smaller of both sets.
+    ///
+    /// This is meant for row-level security, where every result must be restricted
+    /// to the rows a caller is allowed to see.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(0, "foo".into());
+    /// index.insert(1, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// let acl = HashSet::from_iter(vec![1, 2]);
+    /// assert_eq!(engine.search_acl(&q, &acl), Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn search_acl(&self, query: &Query, acl: &HashSet<P>) -> Result<HashSet<P>> {
+        self.record(query);
+        self.check_attributes(query)?;
+        self.search_acl_query(query, acl)
+    }
+
+    /// This internal function runs a query restricted to an acl without recording it.
+    fn search_acl_query(&self, query: &Query, acl: &HashSet<P>) -> Result<HashSet<P>> {
+        match query {
+            Query::Or(vec) => {
+                let mut result_set = HashSet::<P>::new();
+                for pred in vec.iter() {
+                    result_set.extend(self.search_acl_query(pred, acl)?);
+                }
+                Ok(result_set)
+            }
+            Query::And(vec) => {
+                let Some((first, rest)) = vec.split_first() else {
+                    return Ok(HashSet::new());
+                };
+                let mut result_set = self.search_acl_query(first, acl)?;
+                for pred in rest.iter() {
+                    if result_set.is_empty() {
+                        break;
+                    }
+                    let attribute_set = self.search_query(pred)?;
+                    result_set = intersect(result_set, &attribute_set);
+                }
+                Ok(result_set)
+            }
+            Query::Exclude(base, exclude) => {
+                let mut result_set = self.search_acl_query(base, acl)?;
+                for pred in exclude.iter() {
+                    if result_set.is_empty() {
+                        break;
+                    }
+                    remove_all(&mut result_set, &self.search_query(pred)?);
+                }
+                Ok(result_set)
+            }
+            query => Ok(intersect(self.search_query(query)?, acl)),
+        }
+    }
+
+    /// Run a single leaf query on the search engine.
+    ///
+    /// This is a fast-path for queries like [Exact](Query::Exact) that only reference
+    /// a single attribute. The query is passed directly to the index and its result
+    /// is returned without going through the machinery for combined queries.
+    ///
+    /// The query must not be a combination of other queries, like [Or](Query::Or),
+    /// [And](Query::And), [AtLeast](Query::AtLeast) or [Exclude](Query::Exclude).
+    /// If it is, [UnsupportedQuery](SearchEngineError::UnsupportedQuery) is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(0, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// assert_eq!(engine.search_leaf(&q), Ok(HashSet::from_iter(vec![0])));
+    /// ```
+    pub fn search_leaf(&self, query: &Query) -> Result<HashSet<P>> {
+        self.record(query);
+        self.search_index(self.leaf_index(query)?, query)
+    }
+
+    /// This internal function returns the index of the attribute of a leaf query.
+    pub(crate) fn leaf_index(&self, query: &Query) -> Result<&dyn SearchIndex<P>> {
+        let attr = query
+            .attribute()
+            .ok_or(SearchEngineError::UnsupportedQuery)?;
+        let index = self
+            .indices
+            .get(attr)
+            .ok_or(SearchEngineError::UnknownAttribute)?;
+        Ok(index.as_ref().as_ref())
+    }
+
+    /// Returns true if the engine can answer the query as it is.
+    ///
+    /// Every attribute of the query must have an index, which directly supports
+    /// the leaf queries of the attribute. This is cheaper than running the query
+    /// and can be used to enable or disable search controls in a user interface.
+    /// The values of the query are not validated, so searching may still return a
+    /// [MismatchedQueryType](SearchEngineError::MismatchedQueryType) error.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    ///
+    /// assert!(engine.supports(&Query::Exact("name".into(), "Alice".into())));
+    /// assert!(!engine.supports(&Query::Prefix("name".into(), "Al".into())));
+    /// assert!(!engine.supports(&Query::Exact("city".into(), "Berlin".into())));
+    /// ```
+    pub fn supports(&self, query: &Query) -> bool {
+        match query {
+            Query::Or(vec) | Query::And(vec) | Query::AtLeast(_, vec) => {
+                vec.iter().all(|pred| self.supports(pred))
+            }
+            Query::Exclude(base, exclude) => {
+                self.supports(base) && exclude.iter().all(|pred| self.supports(pred))
+            }
+            Query::All => true,
+            Query::Not(inner) => self.supports(inner),
+            leaf => leaf
+                .attribute()
+                .and_then(|attr| self.indices.get(attr))
+                .is_some_and(|index| leaf.supported_flag() & index.supported_queries() != 0),
+        }
+    }
+
+    /// This internal function returns [UnknownAttribute](SearchEngineError::UnknownAttribute)
+    /// if a leaf of the query has no index.
+    ///
+    /// Combinations of queries stop early once their result is known to be
+    /// empty, so all attributes are checked before a query is searched or
+    /// counted. This reports the same error independent of the order of
+    /// the subqueries.
+    pub(crate) fn check_attributes(&self, query: &Query) -> Result<()> {
+        match query {
+            Query::Or(vec) | Query::And(vec) | Query::AtLeast(_, vec) => {
+                vec.iter().try_for_each(|pred| self.check_attributes(pred))
+            }
+            Query::Exclude(base, exclude) => {
+                self.check_attributes(base)?;
+                exclude
+                    .iter()
+                    .try_for_each(|pred| self.check_attributes(pred))
+            }
+            Query::All => Ok(()),
+            Query::Not(inner) => self.check_attributes(inner),
+            leaf => self.leaf_index(leaf).map(|_| ()),
+        }
+    }
+
+    /// This internal function runs a leaf query on its index after normalizing
+    /// it and applies the [MultiValueMatch] mode of the attribute.
+    pub(crate) fn search_index(
+        &self,
+        index: &dyn SearchIndex<P>,
+        query: &Query,
+    ) -> Result<HashSet<P>> {
+        let mut result_set = index
+            .search(&self.normalize(query))
+            .map_err(|error| with_query_attribute(error, query))?;
+        if self.matches_exactly(query) {
+            result_set.retain(|id| index.values_of(id).len() == 1);
+        }
+        Ok(result_set)
+    }
+
+    /// Returns true if the query is an [Exact](Query::Exact) query on an
+    /// attribute with the mode [MultiValueMatch::Exactly].
+    pub(crate) fn matches_exactly(&self, query: &Query) -> bool {
+        match query {
+            Query::Exact(attr, _) => {
+                self.multi_value_match.get(attr) == Some(&MultiValueMatch::Exactly)
+            }
+            _ => false,
+        }
+    }
+
+    /// Find all rows that have a value for at least one of the attributes.
+    ///
+    /// This is the union of the [all_ids](SearchIndex::all_ids) of the indices,
+    /// for example to find all rows that have an email or a phone number.
+    /// Returns [UnknownAttribute](SearchEngineError::UnknownAttribute) if one of
+    /// the attributes has no index. Indices that can't enumerate their rows, like
+    /// [SearchIndexFunction], don't contribute any rows.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index_email = SearchIndexHashMap::<_, String>::new();
+    /// index_email.insert(0, "alice@example.com".into());
+    /// let mut index_phone = SearchIndexHashMap::<_, String>::new();
+    /// index_phone.insert(1, "555-1234".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("email", index_email);
+    /// engine.add_index("phone", index_phone);
+    ///
+    /// let result = engine.any_present(&["email", "phone"]);
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+    /// ```
+    pub fn any_present(&self, attributes: &[&str]) -> Result<HashSet<P>> {
+        let mut result_set = HashSet::new();
+        for attribute in attributes {
+            let index = self
+                .indices
+                .get(*attribute)
+                .ok_or(SearchEngineError::UnknownAttribute)?;
+            result_set.extend(index.all_ids());
+        }
+        Ok(result_set)
+    }
+
+    /// Find all rows that have no value for any of the required attributes.
+    ///
+    /// This is [all_ids](Self::all_ids) without the rows of
+    /// [any_present](Self::any_present), so only rows that have a value in at
+    /// least one other index are found, for example records that slipped through
+    /// ingestion with only some optional attributes. Returns
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) if one of the
+    /// required attributes has no index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    /// engine.add_index("email", SearchIndexHashMap::<_, String>::new());
+    /// engine.add_index("tag", SearchIndexHashMap::<_, String>::new());
+    /// engine.insert("name", 0, "Alice").unwrap();
+    /// engine.insert("email", 1, "bob@example.com").unwrap();
+    /// engine.insert("tag", 1, "vip").unwrap();
+    /// engine.insert("tag", 2, "vip").unwrap();
+    ///
+    /// let result = engine.orphans(&["name", "email"]);
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+    /// ```
+    pub fn orphans(&self, required: &[&str]) -> Result<HashSet<P>> {
+        let present = self.any_present(required)?;
+        let mut result_set = self.universe().clone();
+        remove_all(&mut result_set, &present);
+        Ok(result_set)
+    }
+
+    /// Check a batch of string input before it is inserted.
+    ///
+    /// The `(primary_id, attribute, value)` triples are checked like
+    /// [insert](Self::insert) would insert them: the value is normalized and
+    /// passed to [validate_value](SearchIndex::validate_value) of the index of the
+    /// attribute. Triples whose attribute has no index or whose value can't be
+    /// parsed into the value type of the index, like `abc` for a numeric
+    /// attribute, are returned unchanged in the order of the input. The engine
+    /// isn't changed and no query is run.
+    ///
+    /// This is meant for string-sourced ingestion, like
+    /// [ingest_lines](Self::ingest_lines), which stops at the first invalid
+    /// triple. Validating the whole batch first reports all of them at once.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexBTreeRange};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("age", SearchIndexBTreeRange::<_, u8>::new());
+    ///
+    /// let rows = vec![
+    ///     (0, "age".to_owned(), "27".to_owned()),
+    ///     (1, "age".to_owned(), "abc".to_owned()),
+    /// ];
+    /// assert_eq!(
+    ///     engine.validate_data(rows),
+    ///     Err(vec![(1, "age".to_owned(), "abc".to_owned())])
+    /// );
+    /// ```
+    pub fn validate_data(
+        &self,
+        rows: impl IntoIterator<Item = (P, String, String)>,
+    ) -> std::result::Result<(), Vec<(P, String, String)>> {
+        let invalid: Vec<(P, String, String)> = rows
+            .into_iter()
+            .filter(|(_, attribute, value)| {
+                self.indices
+                    .get(attribute)
+                    .is_none_or(|index| index.validate_value(&self.normalize_value(value)).is_err())
+            })
+            .collect();
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+
+    /// Returns all rows that have a value in at least one index.
+    ///
+    /// This is the union of the [all_ids](SearchIndex::all_ids) of all indices
+    /// and the set of rows that [All](Query::All) matches and that [Not](Query::Not)
+    /// negates against. Indices that can't enumerate their rows, like
+    /// [SearchIndexFunction], don't contribute any rows.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index_email = SearchIndexHashMap::<_, String>::new();
+    /// index_email.insert(0, "alice@example.com".into());
+    /// let mut index_phone = SearchIndexHashMap::<_, String>::new();
+    /// index_phone.insert(1, "555-1234".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("email", index_email);
+    /// engine.add_index("phone", index_phone);
+    ///
+    /// assert_eq!(engine.all_ids(), HashSet::from_iter(vec![0, 1]));
+    /// ```
+    pub fn all_ids(&self) -> HashSet<P> {
+        self.indices
+            .values()
+            .flat_map(|index| index.all_ids())
+            .collect()
+    }
+
+    /// Returns all rows that have a value in at least one index, like
+    /// [all_ids](Self::all_ids), but only computes the union once.
+    ///
+    /// The set is built on the first call and cached. Later calls return
+    /// the cached set until the engine is changed by [insert](Self::insert),
+    /// [ingest_lines](Self::ingest_lines), [add_index](Self::add_index),
+    /// [remove_index](Self::remove_index) or [remove_row](Self::remove_row),
+    /// which drop the cache so that the next call builds it again.
+    /// [All](Query::All), [Not](Query::Not) and [orphans](Self::orphans)
+    /// use this set.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    /// engine.insert("name", 0, "Alice").unwrap();
+    /// assert_eq!(engine.universe(), &HashSet::from_iter(vec![0]));
+    ///
+    /// engine.insert("name", 1, "Bob").unwrap();
+    /// assert_eq!(engine.universe(), &HashSet::from_iter(vec![0, 1]));
+    /// ```
+    pub fn universe(&self) -> &HashSet<P> {
+        self.universe.get_or_init(|| self.all_ids())
+    }
+
+    /// Find the values of an attribute that all given rows share.
+    ///
+    /// The values of every row are looked up with [values_of](SearchIndex::values_of),
+    /// so this is meant for small selections, for example to show the shared
+    /// attributes in a bulk edit dialog. The values are returned sorted. If `ids`
+    /// is empty, no values are returned. Returns
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) if the attribute has no index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index_pet = SearchIndexHashMap::<_, String>::new();
+    /// index_pet.insert(0, "Dog".into());
+    /// index_pet.insert(0, "Cat".into());
+    /// index_pet.insert(1, "Cat".into());
+    /// index_pet.insert(1, "Dog".into());
+    /// index_pet.insert(1, "Bees".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("pet", index_pet);
+    ///
+    /// let ids = HashSet::from_iter(vec![0, 1]);
+    /// assert_eq!(engine.common_values(&ids, "pet"), Ok(vec!["Cat".into(), "Dog".into()]));
+    /// ```
+    pub fn common_values(&self, ids: &HashSet<P>, attribute: &str) -> Result<Vec<String>> {
+        let index = self
+            .indices
+            .get(attribute)
+            .ok_or(SearchEngineError::UnknownAttribute)?;
+        let mut ids = ids.iter();
+        let mut common: HashSet<String> = match ids.next() {
+            Some(id) => index.values_of(id).into_iter().collect(),
+            None => return Ok(vec![]),
+        };
+        for id in ids {
+            if common.is_empty() {
+                break;
+            }
+            let values: HashSet<String> = index.values_of(id).into_iter().collect();
+            common.retain(|value| values.contains(value));
+        }
+        let mut common: Vec<String> = common.into_iter().collect();
+        common.sort_unstable();
+        Ok(common)
+    }
+
+    /// Count the values of a numeric attribute of all rows that match a query
+    /// in buckets of `bucket_width`.
+    ///
+    /// Every bucket is keyed by its lower bound as an `i128`, which can hold
+    /// the buckets of all `i64` and `u64` values. For a width of `10` the
+    /// value `27` is counted in bucket `20` and `-3` in bucket `-10`. Float
+    /// values, like the ones of a [SearchIndexF64Range](crate::SearchIndexF64Range),
+    /// are counted in the bucket of their floor, so `27.5` is in bucket `20`
+    /// and `-0.5` in bucket `-10`. Buckets
+    /// without values are not included. The values of every matching row are
+    /// looked up with [values_of](SearchIndex::values_of). If a row has
+    /// multiple values for the attribute, each of them is counted, so the sum
+    /// of all counts can be higher than the number of matching rows. Rows
+    /// without a value are not counted.
+    ///
+    /// Returns [UnknownAttribute](SearchEngineError::UnknownAttribute) if the
+    /// attribute has no index, [UnsupportedOperation](SearchEngineError::UnsupportedOperation)
+    /// if `bucket_width` is zero and [MismatchedQueryType](SearchEngineError::MismatchedQueryType)
+    /// if a value can't be parsed as an `i64`, a `u64` or a finite `f64`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexBTreeRange, Query};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut index_age = SearchIndexBTreeRange::<_, i64>::new();
+    /// index_age.insert(0, 17);
+    /// index_age.insert(1, 23);
+    /// index_age.insert(2, 27);
+    /// index_age.insert(2, 31);
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("age", index_age);
+    ///
+    /// let q = Query::Minimum("age".into(), "20".into());
+    /// assert_eq!(
+    ///     engine.histogram(&q, "age", 10),
+    ///     Ok(BTreeMap::from([(20, 2), (30, 1)]))
+    /// );
+    /// ```
+    pub fn histogram(
+        &self,
+        query: &Query,
+        attribute: &str,
+        bucket_width: u64,
+    ) -> Result<BTreeMap<i128, usize>> {
+        let index = self
+            .indices
+            .get(attribute)
+            .ok_or(SearchEngineError::UnknownAttribute)?;
+        if bucket_width == 0 {
+            return Err(SearchEngineError::UnsupportedOperation);
+        }
+        let mut histogram = BTreeMap::new();
+        for id in self.search(query)? {
+            for value in index.values_of(&id) {
+                let bucket = histogram_bucket(&value, bucket_width)
+                    .map_err(|error| error.with_attribute(attribute))?;
+                *histogram.entry(bucket).or_default() += 1;
+            }
+        }
+        Ok(histogram)
+    }
+
+    /// This internal function scores all `rows` for a query. Every matching
+    /// leaf query scores 1, an And scores the sum of its subqueries and an
+    /// Or or AtLeast is combined depending on the [ScoreMode]. Exclusions
+    /// don't change the score. Rows with a score of 0 are left out.
+    fn score_rows(&self, query: &Query, rows: &HashSet<P>) -> Result<HashMap<P, usize>> {
+        let combine_all = |vec: &[Query], sum: bool| -> Result<HashMap<P, usize>> {
+            let mut scores = HashMap::<P, usize>::new();
+            for pred in vec {
+                for (id, score) in self.score_rows(pred, rows)? {
+                    let total = scores.entry(id).or_default();
+                    *total = if sum {
+                        *total + score
+                    } else {
+                        score.max(*total)
+                    };
+                }
+            }
+            Ok(scores)
+        };
+        match query {
+            Query::And(vec) => combine_all(vec, true),
+            Query::Or(vec) | Query::AtLeast(_, vec) => {
+                combine_all(vec, self.score_mode == ScoreMode::CountEach)
+            }
+            Query::Exclude(base, _) => self.score_rows(base, rows),
+            leaf => Ok(self
+                .search_query(leaf)?
+                .into_iter()
+                .filter(|id| rows.contains(id))
+                .map(|id| (id, 1))
+                .collect()),
+        }
+    }
+
+    /// Rewrite a query into an equivalent query that is usually faster to search.
+    ///
+    /// Nested [And](Query::And), [Or](Query::Or) and [Exclude](Query::Exclude)
+    /// queries are flattened, exclusions are pushed into the most selective subquery
+    /// of an And and the subqueries of an And are reordered, so that the most
+    /// selective ones are searched first. The selectivity is only estimated by the
+    /// kind of the query, for example an Exact query is assumed to match fewer rows
+    /// than a Minimum query.
+    ///
+    /// Searching the optimized query returns the same rows as the original one.
+    /// Both return [UnknownAttribute](SearchEngineError::UnknownAttribute) if an
+    /// attribute has no index, because all attributes are checked before a search
+    /// stops early. Values are only parsed by the index when a subquery is searched,
+    /// and an And stops searching once its result is empty. So if a value has the
+    /// wrong type, one form may return
+    /// [MismatchedQueryType](SearchEngineError::MismatchedQueryType) while the
+    /// other returns an empty result.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, Query};
+    ///
+    /// let engine = SearchEngine::<usize>::new();
+    /// let q = Query::Exclude(
+    ///     Box::new(Query::And(vec![
+    ///         Query::Minimum("age".into(), "18".into()),
+    ///         Query::Exact("city".into(), "Berlin".into()),
+    ///     ])),
+    ///     vec![Query::Exact("pet".into(), "Dog".into())],
+    /// );
+    /// assert_eq!(
+    ///     engine.optimize(&q),
+    ///     Query::And(vec![
+    ///         Query::Exclude(
+    ///             Box::new(Query::Exact("city".into(), "Berlin".into())),
+    ///             vec![Query::Exact("pet".into(), "Dog".into())],
+    ///         ),
+    ///         Query::Minimum("age".into(), "18".into()),
+    ///     ])
+    /// );
+    /// ```
+    pub fn optimize(&self, query: &Query) -> Query {
+        optimize(query)
+    }
+
+    /// Compile a query for repeated execution.
+    ///
+    /// All attributes of the query are resolved to their indices and all values
+    /// are validated and parsed once. Executing the resulting [CompiledQuery]
+    /// returns the same result as [search](Self::search) without this overhead.
+    /// Compiling returns the same errors as [search](Self::search). Indices that
+    /// can only fail while searching, like a custom index, return their errors
+    /// from [execute](CompiledQuery::execute).
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(0, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// let compiled = engine.compile(&q).expect("valid query");
+    /// for _ in 0..10 {
+    ///     assert_eq!(compiled.execute(), Ok(HashSet::from_iter(vec![0])));
+    /// }
+    /// ```
+    pub fn compile(&self, query: &Query) -> Result<CompiledQuery<'_, P>> {
+        Ok(CompiledQuery::new(self.compile_search(query)?))
+    }
+
+    /// Start a search that can be refined or broadened step by step.
+    ///
+    /// The query is executed once and the result is stored in a [RefinableSearch].
+    /// Further queries only have to be combined with that result, which avoids
+    /// running the earlier queries again. See [RefinableSearch] for an example.
+    pub fn refinable(&self, query: &Query) -> Result<RefinableSearch<'_, P>> {
+        Ok(RefinableSearch::new(self, self.search(query)?))
+    }
+
+    /// Run a query and store the result in a [ResultSnapshot].
+    ///
+    /// The snapshot can later be compared with a newer snapshot of the same
+    /// query to find the rows that were added or removed in between.
+    /// See [ResultSnapshot] for an example.
+    pub fn snapshot(&self, query: &Query) -> Result<ResultSnapshot<P>> {
+        Ok(ResultSnapshot::new(self.search(query)?))
+    }
+
+    /// Capture the current state of the engine in an [EngineSnapshot].
+    ///
+    /// The snapshot can be searched like the engine, but later changes to
+    /// the engine don't affect it. It shares the indices with the engine and
+    /// is cheap to create and to clone. See [EngineSnapshot] for the memory
+    /// cost of changing an index while a snapshot exists.
+    pub fn snapshot_handle(&self) -> EngineSnapshot<P> {
+        EngineSnapshot::new(self.clone())
+    }
+
+    /// Recursively compiles a query into a search function.
+    fn compile_search(&self, query: &Query) -> Result<CompiledSearch<'_, P>> {
+        let compile_all = |vec: &[Query]| -> Result<Vec<_>> {
+            vec.iter().map(|pred| self.compile_search(pred)).collect()
+        };
+        match query {
+            Query::Or(vec) => {
+                let preds = compile_all(vec)?;
+                Ok(Box::new(move || {
+                    let mut result_set = HashSet::<P>::new();
+                    for pred in preds.iter() {
+                        result_set.extend(pred()?);
+                    }
+                    Ok(result_set)
+                }))
+            }
+            Query::And(vec) => {
+                let preds = compile_all(vec)?;
+                Ok(Box::new(move || {
+                    let mut preds = preds.iter();
+                    let mut result_set = match preds.next() {
+                        Some(pred) => pred()?,
+                        None => return Ok(HashSet::new()),
+                    };
+                    for pred in preds {
+                        if result_set.is_empty() {
+                            break;
+                        }
+                        let attribute_set = pred()?;
+                        result_set.retain(|id| attribute_set.contains(id));
+                    }
+                    Ok(result_set)
+                }))
+            }
+            Query::AtLeast(min_matches, vec) => {
+                let min_matches = *min_matches;
+                let preds = compile_all(vec)?;
+                Ok(Box::new(move || {
+                    let mut match_counts = HashMap::<P, usize>::new();
+                    for pred in preds.iter() {
+                        for id in pred()? {
+                            *match_counts.entry(id).or_default() += 1;
+                        }
+                    }
+                    Ok(match_counts
+                        .into_iter()
+                        .filter(|(_, count)| *count >= min_matches)
+                        .map(|(id, _)| id)
+                        .collect())
+                }))
+            }
+            Query::Exclude(base, exclude) => {
+                let base = self.compile_search(base)?;
+                let preds = compile_all(exclude)?;
+                Ok(Box::new(move || {
+                    let mut result_set = base()?;
+                    for pred in preds.iter() {
+                        if result_set.is_empty() {
+                            break;
+                        }
+                        remove_all(&mut result_set, &pred()?);
+                    }
+                    Ok(result_set)
+                }))
+            }
+            Query::All => Ok(Box::new(move || Ok(self.universe().clone()))),
+            Query::Not(inner) => {
+                let inner = self.compile_search(inner)?;
+                Ok(Box::new(move || {
+                    let mut result_set = self.universe().clone();
+                    remove_all(&mut result_set, &inner()?);
+                    Ok(result_set)
+                }))
+            }
+            leaf => {
+                let attr = leaf
+                    .attribute()
+                    .ok_or(SearchEngineError::UnsupportedQuery)?;
+                let index = self
+                    .indices
+                    .get(attr)
+                    .ok_or(SearchEngineError::UnknownAttribute)?;
+                let compiled = index
+                    .compile(&self.normalize(leaf))
+                    .map_err(|error| error.with_attribute(attr))?;
+                let matches_exactly = self.matches_exactly(leaf);
+                let attr = attr.to_owned();
+                Ok(Box::new(move || {
+                    let mut result_set = compiled().map_err(|error| error.with_attribute(&attr))?;
+                    if matches_exactly {
+                        result_set.retain(|id| index.values_of(id).len() == 1);
+                    }
+                    Ok(result_set)
+                }))
+            }
+        }
+    }
+
+    /// Build a [Query] from a string slice.
+    ///
+    /// This function can return an error if an unknown index is referenced.
+    /// On success this function returns a fully owned Query and a vector of string
+    /// slices into the input query, that contains all parts of the query, that are not
+    /// following the query syntax. They are called "Freetext". They will never contain
+    /// whitespace.
+    ///
+    /// # Basic Syntax
+    /// The following text is an example for a query:
+    /// ```text
+    /// +attr1:foo,bar +attr2:=baz +attr3:<42,>84 -attr4:69-121
+    /// ```
+    /// As a boolean expression it will mean something like this:
+    /// ```text
+    ///    (attr1==foo || attr1==bar)
+    /// && (attr2==baz)
+    /// && (attr3 <= 42 || attr3 >= 84)
+    /// && !(69 <= attr4 <= 121)
+    /// ```
+    ///
+    /// # In-depth Syntax description
+    /// A string query consists of multiple whitespace seperated attribute selectors.
+    /// Each of them starts with a `+` or `-` sign, indicating if the rows matching this
+    /// selector should be included or excluded from the result. This is followed by the
+    /// name of the attribute/index and a single `:` char. Names consist of alphanumeric
+    /// characters and can be dotted paths like `address.city`, for example for flattened
+    /// JSON keys. Next comes a list of comma seperated
+    /// values that describe the basic queries that are used to select rows. There are
+    /// special operator symbols that can change the meaning of a value if the index
+    /// supports the matching query type. For example, if the Index supports Maximum queries,
+    /// the following value will return a Maximum query instead of an Exact query: `<123`.
+    ///
+    /// The following operator symbols are currently used **if the index supports it**:
+    /// - `>val` - forces a Minimum query
+    /// - `<val` - forces a Maximum query
+    /// - `=val` - forces a Exact query
+    /// - `minval-maxval` - forces a InRange query. Indices with String values can use
+    ///   another delimiter, like `minval..maxval`, see
+    ///   [string_range_delimiter](QueryParserOptions::string_range_delimiter)
+    /// - `foo~2` - forces a Fuzzy query with the number after the last `~` as the
+    ///   maximum edit distance
+    /// - `*foo` - forces a Suffix query, if there is no other `*` in the value
+    /// - `foo*bar` - forces a Pattern query, each `*` matches any sequence of characters
+    /// - `[min,max)` - forces a InRangeBounds query, `[`/`]` mark an inclusive and `(`/`)`
+    ///   an exclusive bound. If the index only supports InRange queries, `[min,max]` is
+    ///   used as a InRange query. An empty side like in `[18,)` or `(,65]` is unbounded.
+    ///   For indices that support ranges, the comma in brackets doesn't separate values.
+    ///
+    /// The value list can be prefixed with a count modifier `N#`, where `N` is a positive
+    /// number. Instead of matching any of the values, at least `N` of them must match.
+    /// For example `+tags:2#a,b,c` builds an [AtLeast](Query::AtLeast) query that matches
+    /// all rows tagged with at least two of `a`, `b` and `c`. A value that is listed
+    /// more than once only counts once. The modifier is only used if the selector lists
+    /// more than one value and `N` isn't larger than the number of values, so a single
+    /// value like `+issue:42#fix` is searched as written. To search a literal value
+    /// like `1#a` in a list, list it after another value, like `+tags:b,1#a`.
+    ///
+    /// Repeated `+` selectors of the same attribute are combined with And, unless the
+    /// attribute is listed in [or_attributes](QueryParserOptions::or_attributes) of the
+    /// parser options. Then they are combined with Or, like the values of a single selector.
+    ///
+    /// An index can define its own value syntax with [parse_value](SearchIndex::parse_value).
+    /// If it parses a value, its query is used and the operator symbols above are ignored.
+    ///
+    /// If no operator symbol is found, a Prefix query will be used if it is supported by the index.
+    /// Otherwise a Exact query is used, even if the index may not support it (all official indices
+    /// currently implement them).
+    ///
+    /// All non-whitespace substrings in the query, that are not valid attribute selectors are
+    /// considered "Freetext". All of these are returned on success and can be used or
+    /// ignored by the caller. For example they can be used to filter the results further.
+    ///
+    /// # Limits
+    /// - OutRange queries don't have an operator symbol and are currently not supported.
+    ///   But it is possible to build a functionally equivalent query if the index supports
+    ///   Minimum and Maximum queries: `+attr:<10,>20`
+    /// - InRange does not support negative values because only one `-` char is allowed,
+    ///   unless the index uses another [range delimiter](SearchIndex::range_delimiter).
+    /// - There is no way to force a Prefix query. It will be automatically used if no
+    ///   operator symbol is found and the index supports them.
+    /// - A selector without values, like `+attr:`, is ignored, unless the index
+    ///   [rejects empty prefixes](SearchIndex::rejects_empty_prefix). Then an
+    ///   [EmptyPrefix](SearchEngineError::EmptyPrefix) error is returned.
+    ///
+    /// # Boolean Keywords
+    /// If [boolean_keywords](QueryParserOptions::boolean_keywords) is enabled in the
+    /// [QueryParserOptions], selectors can be written without a sign and combined with
+    /// the uppercase keywords `AND`, `OR` and `NOT`, like `pet:Dog OR NOT name:Hans`.
+    /// `NOT` binds stronger than `AND`, which binds stronger than `OR`. Selectors
+    /// without a keyword between them are combined with `AND`. A query string without
+    /// `OR` builds the same query as it would without keywords.
+    ///
+    /// # Strict Mode
+    /// If [strict](QueryParserOptions::strict) is enabled in the [QueryParserOptions],
+    /// a selector that includes a single value which is also excluded by another selector,
+    /// like `+pet:Dog -pet:Dog`, results in a
+    /// [ContradictoryQuery](SearchEngineError::ContradictoryQuery) error because such
+    /// a query can never match anything.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// // Fill index here...
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    /// let (q, freetext) = engine.query_from_str("+attribute:foo bar").expect("no error");
+    /// assert_eq!(q, Query::And(vec![Query::Exact("attribute".into(), "foo".into())]));
+    /// assert_eq!(freetext, vec!["bar"]);
+    /// ```
+    pub fn query_from_str<'a>(&self, query_str: &'a str) -> Result<(Query, Vec<&'a str>)> {
+        Ok(self.build_query(query_str, None, None)?.into_query())
+    }
+
+    /// Build a [Query] from a string slice and explain how each value was parsed.
+    ///
+    /// This function works like [query_from_str](Self::query_from_str), but instead of the
+    /// freetext it returns a [ParseNote] for each value of all attribute selectors. The notes
+    /// record which query was chosen for a value, why, and which type the index expects,
+    /// which helps with debugging query strings.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{ParseReason, SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// // Fill index here...
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    /// let (q, notes) = engine.query_from_str_explained("+attribute:foo").expect("no error");
+    /// assert_eq!(notes[0].token, "foo");
+    /// assert_eq!(notes[0].query, Query::Exact("attribute".into(), "foo".into()));
+    /// assert_eq!(notes[0].reason, ParseReason::ExactFallback);
+    /// assert!(notes[0].value_type.ends_with("String"));
+    /// ```
+    pub fn query_from_str_explained<'a>(
+        &self,
+        query_str: &'a str,
+    ) -> Result<(Query, Vec<ParseNote<'a>>)> {
+        let mut notes = vec![];
+        let (query, _) = self
+            .build_query(query_str, Some(&mut notes), None)?
+            .into_query();
+        Ok((query, notes))
+    }
+
+    /// Build a [Query] from a string slice and report non-fatal issues.
+    ///
+    /// This function works like [query_from_str](Self::query_from_str), but it also
+    /// returns a [ParseWarning] for every part of the query string that was dropped
+    /// or may not be parsed like the user intended. For example, a selector without
+    /// values is dropped and `>42` is searched as a literal value if the index
+    /// doesn't support Minimum queries. Errors are still returned as usual.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{ParseWarningReason, SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// // Fill index here...
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    /// let (q, freetext, warnings) = engine
+    ///     .query_from_str_with_warnings("+attribute:>42 bar")
+    ///     .expect("no error");
+    /// assert_eq!(q, Query::And(vec![Query::Exact("attribute".into(), ">42".into())]));
+    /// assert_eq!(freetext, vec!["bar"]);
+    /// assert_eq!(warnings[0].token, ">42");
+    /// assert_eq!(warnings[0].reason, ParseWarningReason::UnsupportedOperator('>'));
+    /// ```
+    pub fn query_from_str_with_warnings<'a>(
+        &self,
+        query_str: &'a str,
+    ) -> Result<(Query, Vec<&'a str>, Vec<ParseWarning<'a>>)> {
+        let mut warnings = vec![];
+        let (query, freetexts) = self
+            .build_query(query_str, None, Some(&mut warnings))?
+            .into_query();
+        Ok((query, freetexts, warnings))
+    }
+
+    /// Build a [Query] from a string slice and search the freetext in the default attribute.
+    ///
+    /// This function works like [query_from_str](Self::query_from_str), but instead of
+    /// returning the freetext, every freetext token must match the attribute that was set
+    /// with [set_default_attribute](Self::set_default_attribute). A Prefix query is used
+    /// if the index of the default attribute supports it, otherwise an Exact query.
+    /// If no default attribute is set,
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexPrefixTree, Query};
+    ///
+    /// let mut index = SearchIndexPrefixTree::<_>::new();
+    /// // Fill index here...
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", index);
+    /// engine.set_default_attribute("name").expect("index exists");
+    /// let q = engine.query_from_str_default("ali").expect("no error");
+    /// assert_eq!(q, Query::And(vec![Query::Prefix("name".into(), "ali".into())]));
+    /// ```
+    pub fn query_from_str_default(&self, query_str: &str) -> Result<Query> {
+        let attribute = self
+            .default_attribute
+            .as_deref()
+            .ok_or(SearchEngineError::UnknownAttribute)?;
+        let supported = self
+            .indices
+            .get(attribute)
+            .ok_or(SearchEngineError::UnknownAttribute)?
+            .supported_queries();
+
+        let mut parsed = self.build_query(query_str, None, None)?;
+        for text in parsed.freetexts.drain(..) {
+            parsed
+                .include
+                .push(freetext_query(attribute, supported, text));
+        }
+        Ok(parsed.into_query().0)
+    }
+
+    /// Build a [Query] from a string slice and search the freetext in several attributes.
+    ///
+    /// This function works like [query_from_str_default](Self::query_from_str_default),
+    /// but every freetext token becomes an [Or](Query::Or) over the `defaults`, so a
+    /// token must match at least one of them. For each attribute a Prefix query is
+    /// used if its index supports it, otherwise an Exact query. The default attribute
+    /// set with [set_default_attribute](Self::set_default_attribute) is not used.
+    /// If `defaults` is empty or one of them has no index,
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, SearchIndexPrefixTree, Query};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexPrefixTree::<_>::new());
+    /// engine.add_index("city", SearchIndexHashMap::<_, String>::new());
+    /// let q = engine
+    ///     .query_from_str_multi_default("berlin", &["name", "city"])
+    ///     .expect("no error");
+    /// assert_eq!(
+    ///     q,
+    ///     Query::And(vec![Query::Or(vec![
+    ///         Query::Prefix("name".into(), "berlin".into()),
+    ///         Query::Exact("city".into(), "berlin".into()),
+    ///     ])])
+    /// );
+    /// ```
+    pub fn query_from_str_multi_default(
+        &self,
+        query_str: &str,
+        defaults: &[&str],
+    ) -> Result<Query> {
+        if defaults.is_empty() {
+            return Err(SearchEngineError::UnknownAttribute);
+        }
+        let mut attributes = Vec::with_capacity(defaults.len());
+        for attribute in defaults {
+            let supported = self
+                .indices
+                .get(*attribute)
+                .ok_or(SearchEngineError::UnknownAttribute)?
+                .supported_queries();
+            attributes.push((*attribute, supported));
+        }
+
+        let mut parsed = self.build_query(query_str, None, None)?;
+        for text in parsed.freetexts.drain(..) {
+            parsed.include.push(Query::Or(
+                attributes
+                    .iter()
+                    .map(|&(attribute, supported)| freetext_query(attribute, supported, text))
+                    .collect(),
+            ));
+        }
+        Ok(parsed.into_query().0)
+    }
+
+    /// Parse a query string and search its freetext in a full-text index.
+    ///
+    /// The attribute selectors are parsed like with [query_from_str](Self::query_from_str)
+    /// and the freetext must match the index `fulltext_index` as well. If the index
+    /// supports [AllTokens](Query::AllTokens) queries, like [SearchIndexText], the
+    /// whole freetext becomes one AllTokens query, so every token must match.
+    /// Otherwise every freetext token becomes a [Contains](Query::Contains) query if
+    /// the index supports it, like [SearchIndexNGram], or a Prefix or Exact query
+    /// like in [query_from_str_default](Self::query_from_str_default).
+    ///
+    /// If there is no freetext, only the attribute selectors are searched. If
+    /// there is no index named `fulltext_index`,
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, SearchIndexText};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index_city = SearchIndexHashMap::<_, String>::new();
+    /// let mut index_description = SearchIndexText::<_>::new();
+    /// index_city.insert(0, "Berlin".into());
+    /// index_description.insert(0, "Red bike, barely used".into());
+    /// index_city.insert(1, "Berlin".into());
+    /// index_description.insert(1, "Blue bike".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("city", index_city);
+    /// engine.add_index("description", index_description);
+    ///
+    /// let result = engine.search_str("+city:Berlin red bike", "description");
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+    /// let result = engine.search_str("+city:Berlin", "description");
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+    /// ```
+    pub fn search_str(&self, query_str: &str, fulltext_index: &str) -> Result<HashSet<P>> {
+        let supported = self
+            .indices
+            .get(fulltext_index)
+            .ok_or(SearchEngineError::UnknownAttribute)?
+            .supported_queries();
+
+        let mut parsed = self.build_query(query_str, None, None)?;
+        if supported & SUPPORTS_ALL_TOKENS != 0 && !parsed.freetexts.is_empty() {
+            let text = parsed.freetexts.join(" ");
+            parsed
+                .include
+                .push(Query::AllTokens(fulltext_index.to_owned(), text));
+        } else {
+            for text in parsed.freetexts.drain(..) {
+                let query = match supported & SUPPORTS_CONTAINS != 0 {
+                    true => Query::Contains(fulltext_index.to_owned(), text.to_owned()),
+                    false => freetext_query(fulltext_index, supported, text),
+                };
+                parsed.include.push(query);
+            }
+        }
+        self.search(&parsed.into_query().0)
+    }
+
+    /// Returns the delimiter of InRange values for an index,
+    /// see [SearchIndex::range_delimiter].
+    fn range_delimiter<'a>(&'a self, index: &'a dyn SearchIndex<P>) -> &'a str {
+        if let Some(delimiter) = index.range_delimiter() {
+            return delimiter;
+        }
+        match &self.parser_options.string_range_delimiter {
+            Some(delimiter) if index.has_string_values() => delimiter,
+            _ => "-",
+        }
+    }
+
+    /// Internal implementation of the query string parser. If `notes` is
+    /// set, a [ParseNote] is recorded for every parsed value. If `warnings`
+    /// is set, a [ParseWarning] is recorded for every non-fatal issue.
+    fn build_query<'a>(
+        &self,
+        query_str: &'a str,
+        mut notes: Option<&mut Vec<ParseNote<'a>>>,
+        mut warnings: Option<&mut Vec<ParseWarning<'a>>>,
+    ) -> Result<ParsedQuery<'a>> {
+        let mut include = vec![];
+        let mut exclude = vec![];
+        let mut freetexts = vec![];
+        let mut single_includes = vec![];
+        let mut or_positions = HashMap::<&str, usize>::new();
+        let mut keyword_groups = self
+            .parser_options
+            .boolean_keywords
+            .then(KeywordGroups::new);
+
+        let lexer = if keyword_groups.is_some() {
+            QueryLexer::new_with_bare_attributes(query_str)
+        } else {
+            QueryLexer::new(query_str)
+        };
+        for subquery in lexer {
+            match subquery {
+                QueryToken::Attribute(is_include, attribute, values) => {
+                    let index = self
+                        .indices
+                        .get(attribute)
+                        .ok_or(SearchEngineError::UnknownAttribute)?;
+                    let supported = index.supported_queries();
+                    let mut values =
+                        if supported & (SUPPORTS_INRANGE | SUPPORTS_INRANGE_BOUNDS) != 0 {
+                            values
+                        } else {
+                            split_bracket_commas(values)
+                        };
+                    let min_matches = split_min_matches(&mut values);
+                    if values.is_empty()
+                        && supported & SUPPORTS_PREFIX != 0
+                        && index.rejects_empty_prefix()
+                    {
+                        return Err(SearchEngineError::EmptyPrefix);
+                    }
+
+                    let mut qs = Vec::with_capacity(values.len());
+                    for &value in values.iter() {
+                        let (q, reason) = match index.parse_value(attribute, value) {
+                            Some(q) => (q, ParseReason::IndexSyntax),
+                            None => parse_value(
+                                attribute,
+                                value,
+                                supported,
+                                self.range_delimiter(index.as_ref().as_ref()),
+                            ),
+                        };
+                        let (q, reason) = if reason == ParseReason::PrefixFallback
+                            && self.parser_options.exact_if_present
+                            && supported & SUPPORTS_EXACT != 0
+                            && index.contains_value(&self.normalize_value(value))
+                        {
+                            let q = Query::Exact(attribute.to_owned(), value.to_owned());
+                            (q, ParseReason::ExactPresent)
+                        } else {
+                            (q, reason)
+                        };
+                        if matches!(&q, Query::Prefix(_, prefix) if prefix.is_empty())
+                            && index.rejects_empty_prefix()
+                        {
+                            return Err(SearchEngineError::EmptyPrefix);
+                        }
+                        if let (
+                            Some(warnings),
+                            ParseReason::PrefixFallback
+                            | ParseReason::ExactFallback
+                            | ParseReason::ExactPresent,
+                        ) = (warnings.as_deref_mut(), reason)
+                        {
+                            if let Some(operator) = implied_operator(value) {
+                                warnings.push(ParseWarning {
+                                    token: value,
+                                    reason: ParseWarningReason::UnsupportedOperator(operator),
+                                });
+                            }
+                        }
+                        if let Some(notes) = notes.as_deref_mut() {
+                            notes.push(ParseNote {
+                                token: value,
+                                query: q.clone(),
+                                reason,
+                                value_type: index.value_type_name(),
+                            });
+                        }
+                        qs.push(q);
+                    }
+                    let q = match (min_matches, qs.len().cmp(&1)) {
+                        (_, Ordering::Less) => {
+                            if let Some(warnings) = warnings.as_deref_mut() {
+                                warnings.push(ParseWarning {
+                                    token: attribute,
+                                    reason: ParseWarningReason::EmptyValueList,
+                                });
+                            }
+                            continue;
+                        }
+                        (Some(n), _) => {
+                            // A value that is listed twice only counts once.
+                            let mut distinct = Vec::with_capacity(qs.len());
+                            for q in qs {
+                                if !distinct.contains(&q) {
+                                    distinct.push(q);
+                                }
+                            }
+                            Query::AtLeast(n, distinct)
+                        }
+                        (None, Ordering::Equal) => qs.swap_remove(0),
+                        (None, Ordering::Greater)
+                            if self.parser_options.merge_out_range
+                                && supported & SUPPORTS_OUTRANGE_BOUNDS != 0 =>
+                        {
+                            merge_out_range(&qs).unwrap_or(Query::Or(qs))
+                        }
+                        (None, Ordering::Greater) => Query::Or(qs),
+                    };
+                    if let Some(keyword_groups) = keyword_groups.as_mut() {
+                        let single_value = (values.len() == 1).then(|| values[0]);
+                        freetexts.extend(keyword_groups.selector(q, is_include, single_value));
+                        continue;
+                    }
+                    let is_or_attribute = is_include
+                        && self
+                            .parser_options
+                            .or_attributes
+                            .iter()
+                            .any(|attr| attr == attribute);
+                    if self.parser_options.strict
+                        && is_include
+                        && !is_or_attribute
+                        && values.len() == 1
+                    {
+                        single_includes.push((q.clone(), values[0]));
+                    }
+                    if is_or_attribute {
+                        match or_positions.get(attribute) {
+                            Some(&pos) => {
+                                let mut alternatives = match std::mem::replace(
+                                    &mut include[pos],
+                                    Query::And(vec![]),
+                                ) {
+                                    Query::Or(vec) => vec,
+                                    previous => vec![previous],
+                                };
+                                match q {
+                                    Query::Or(vec) => alternatives.extend(vec),
+                                    q => alternatives.push(q),
+                                }
+                                include[pos] = Query::Or(alternatives);
+                            }
+                            None => {
+                                or_positions.insert(attribute, include.len());
+                                include.push(q);
+                            }
+                        }
+                    } else if is_include {
+                        include.push(q);
+                    } else {
+                        exclude.push(q);
+                    }
+                }
+                QueryToken::Freetext(text) => {
+                    let is_keyword = keyword_groups
+                        .as_mut()
+                        .is_some_and(|keyword_groups| keyword_groups.keyword(text));
+                    if !is_keyword {
+                        freetexts.push(text);
+                    }
+                }
+            }
+        }
+
+        if let Some(keyword_groups) = keyword_groups {
+            let leftover;
+            (include, exclude, leftover) = keyword_groups.finish(self.parser_options.strict)?;
+            freetexts.extend(leftover);
+        } else if self.parser_options.strict {
+            check_contradictions(&single_includes, &exclude)?;
+        }
+
+        Ok(ParsedQuery {
+            include,
+            exclude,
+            freetexts,
+        })
+    }
+}
+
+impl<P: Eq + Hash + Clone + Ord> SearchEngine<P> {
+    /// Export the contents of all indices as a flat table of rows.
+    ///
+    /// Each row contains the row id / primary id and a map from attribute names
+    /// to all values the row has for this attribute. The rows are sorted by their id
+    /// and the values are sorted as strings, so the result is deterministic and can
+    /// be used for debugging or snapshot tests. Indices that can't enumerate their
+    /// [entries](SearchIndex::entries) don't contribute to the table.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(0, "foo".into());
+    /// index.insert(0, "bar".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let rows = engine.export_rows();
+    /// assert_eq!(
+    ///     rows,
+    ///     vec![(0, BTreeMap::from([("attribute".into(), vec!["bar".into(), "foo".into()])]))]
+    /// );
+    /// ```
+    pub fn export_rows(&self) -> Vec<(P, BTreeMap<String, Vec<String>>)> {
+        let mut rows = BTreeMap::<P, BTreeMap<String, Vec<String>>>::new();
+        for (attribute, index) in self.indices.iter() {
+            for (id, value) in index.entries() {
+                rows.entry(id)
+                    .or_default()
+                    .entry(attribute.clone())
+                    .or_default()
+                    .push(value);
+            }
+        }
+
+        rows.into_iter()
+            .map(|(id, mut attributes)| {
+                attributes.values_mut().for_each(|values| values.sort());
+                (id, attributes)
+            })
+            .collect()
+    }
+
+    /// Run a query on the search engine and return the results in a stable order.
+    ///
+    /// The results are ranked by the ordering of the row ids / primary ids, so the
+    /// position of a row does not depend on the internal state of the indices.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(3, "foo".into());
+    /// index.insert(1, "foo".into());
+    /// index.insert(2, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// assert_eq!(engine.search_ranked_stable(&q), Ok(vec![1, 2, 3]));
+    /// ```
+    pub fn search_ranked_stable(&self, query: &Query) -> Result<Vec<P>> {
+        self.search_ordered(query)
+    }
+
+    /// Run a query on the search engine and return the results with a score,
+    /// ranked by the score and then by their row ids / primary ids.
+    ///
+    /// The score counts how many leaf queries a row matches, so rows that match
+    /// more selectors are ranked first. The subqueries of an And are summed.
+    /// If a row matches multiple alternatives of an Or, like both values of
+    /// `+pet:Cat,Dog`, the [ScoreMode] decides if it counts once (the default)
+    /// or for every alternative. Exclusions don't change the score. The rows
+    /// are the same as returned by [search](Self::search), only leaf queries
+    /// are searched again to score them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, ScoreMode};
+    ///
+    /// let mut index_pet = SearchIndexHashMap::<_, String>::new();
+    /// index_pet.insert(0, "Cat".into());
+    /// index_pet.insert(1, "Cat".into());
+    /// index_pet.insert(1, "Dog".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("pet", index_pet);
+    ///
+    /// let (q, _) = engine.query_from_str("+pet:Cat,Dog").expect("valid query");
+    /// assert_eq!(engine.search_scored(&q), Ok(vec![(0, 1), (1, 1)]));
+    ///
+    /// engine.set_score_mode(ScoreMode::CountEach);
+    /// assert_eq!(engine.search_scored(&q), Ok(vec![(1, 2), (0, 1)]));
+    /// ```
+    pub fn search_scored(&self, query: &Query) -> Result<Vec<(P, usize)>> {
+        let rows = self.search(query)?;
+        let scores = self.score_rows(query, &rows)?;
+        let mut result: Vec<(P, usize)> = rows
+            .into_iter()
+            .map(|id| {
+                let score = scores.get(&id).copied().unwrap_or_default();
+                (id, score)
+            })
+            .collect();
+        result.sort_unstable_by(|(id_a, score_a), (id_b, score_b)| {
+            score_b.cmp(score_a).then_with(|| id_a.cmp(id_b))
+        });
+        Ok(result)
+    }
+
+    /// Search a single term in several attributes and rank the rows by the
+    /// weights of the attributes they match.
+    ///
+    /// The term is searched as a [Prefix](Query::Prefix) query in attributes
+    /// whose index supports it and as an [Exact](Query::Exact) query otherwise,
+    /// without operator symbols. Attributes whose index can't parse the term, like
+    /// a number index for a name, don't match. The score of a row is the sum of
+    /// the weights of all matching attributes. The result is sorted by the score
+    /// from high to low and then by the row id. Returns
+    /// [UnknownAttribute](SearchEngineError::UnknownAttribute) if one of the
+    /// attributes has no index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap};
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    /// engine.add_index("city", SearchIndexHashMap::<_, String>::new());
+    /// engine.insert("name", 0, "Paris").unwrap();
+    /// engine.insert("city", 1, "Paris").unwrap();
+    ///
+    /// let result = engine.search_weighted("Paris", &[("name", 2.0), ("city", 1.0)]);
+    /// assert_eq!(result, Ok(vec![(0, 2.0), (1, 1.0)]));
+    /// ```
+    pub fn search_weighted(&self, term: &str, weights: &[(&str, f32)]) -> Result<Vec<(P, f32)>> {
+        let mut scores = HashMap::<P, f32>::new();
+        for &(attribute, weight) in weights {
+            let index = self
+                .indices
+                .get(attribute)
+                .ok_or(SearchEngineError::UnknownAttribute)?;
+            let query = if index.supported_queries() & SUPPORTS_PREFIX != 0 {
+                Query::Prefix(attribute.to_owned(), term.to_owned())
+            } else {
+                Query::Exact(attribute.to_owned(), term.to_owned())
+            };
+            let rows = match self.search_index(index.as_ref().as_ref(), &query) {
+                Err(SearchEngineError::MismatchedQueryType { .. }) => continue,
+                rows => rows?,
+            };
+            for id in rows {
+                *scores.entry(id).or_default() += weight;
+            }
+        }
+        let mut result: Vec<(P, f32)> = scores.into_iter().collect();
+        result.sort_unstable_by(|(id_a, score_a), (id_b, score_b)| {
+            score_b.total_cmp(score_a).then_with(|| id_a.cmp(id_b))
+        });
+        Ok(result)
+    }
+
+    /// Run a query on the search engine and return the results sorted
+    /// by their row ids / primary ids.
+    ///
+    /// This is useful to fetch the full records of a large result from a
+    /// backing store that is laid out by row id, because the records are then
+    /// visited in storage order. The result is sorted once without keeping the
+    /// intermediate HashSet around.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexBTreeRange, Query};
+    ///
+    /// let mut index = SearchIndexBTreeRange::<_, u32>::new();
+    /// index.insert(7, 30);
+    /// index.insert(2, 40);
+    /// index.insert(5, 10);
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("age", index);
+    ///
+    /// let q = Query::Minimum("age".into(), "20".into());
+    /// assert_eq!(engine.search_ordered(&q), Ok(vec![2, 7]));
+    /// ```
+    pub fn search_ordered(&self, query: &Query) -> Result<Vec<P>> {
+        let mut result: Vec<P> = self.search(query)?.into_iter().collect();
+        result.sort_unstable();
+        Ok(result)
+    }
+
+    /// Run a query on the search engine and return a page of at most `limit`
+    /// results that are ranked after the row id `after`.
+    ///
+    /// The results are ranked in the same order as by
+    /// [search_ranked_stable](Self::search_ranked_stable). Pass `None` as `after`
+    /// to fetch the first page and the last row id of the current page to fetch the
+    /// next one. Rows that are inserted or removed between requests don't shift
+    /// already fetched pages.
+    ///
+    /// Every page runs the query again, but only the results of the page are
+    /// sorted. The others are partitioned in linear time.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(3, "foo".into());
+    /// index.insert(1, "foo".into());
+    /// index.insert(2, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// assert_eq!(engine.search_after(&q, None, 2), Ok(vec![1, 2]));
+    /// assert_eq!(engine.search_after(&q, Some(&2), 2), Ok(vec![3]));
+    /// ```
+    pub fn search_after(&self, query: &Query, after: Option<&P>, limit: usize) -> Result<Vec<P>> {
+        let mut result: Vec<P> = self
+            .search(query)?
+            .into_iter()
+            .filter(|id| after.is_none_or(|after| id > after))
+            .collect();
+        // Only the page itself is sorted, the remaining results are just
+        // partitioned, so paging through all results doesn't sort them again.
+        if limit < result.len() {
+            result.select_nth_unstable(limit);
+            result.truncate(limit);
+        }
+        result.sort_unstable();
+        Ok(result)
+    }
+}
+
+/// Returns the intersection of two sets, consuming the first one.
+///
+/// Only the smaller of both sets is iterated.
+fn intersect<P: Eq + Hash + Clone>(mut result_set: HashSet<P>, other: &HashSet<P>) -> HashSet<P> {
+    if result_set.len() <= other.len() {
+        result_set.retain(|id| other.contains(id));
+        result_set
+    } else {
+        other
+            .iter()
+            .filter(|id| result_set.contains(*id))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Removes all ids of `exclude` from `result_set` in place.
+///
+/// Only the smaller of both sets is iterated, so excluding a few ids
+/// from a large result doesn't touch every id of the result.
+fn remove_all<P: Eq + Hash>(result_set: &mut HashSet<P>, exclude: &HashSet<P>) {
+    if exclude.len() < result_set.len() {
+        for id in exclude.iter() {
+            result_set.remove(id);
+        }
+    } else {
+        result_set.retain(|id| !exclude.contains(id));
+    }
+}
+
+/// Sets the attribute of a [MismatchedQueryType](SearchEngineError::MismatchedQueryType)
+/// error to the attribute of the query that caused it.
+pub(crate) fn with_query_attribute(error: SearchEngineError, query: &Query) -> SearchEngineError {
+    match query.attribute() {
+        Some(attribute) => error.with_attribute(attribute),
+        None => error,
+    }
+}
+
+/// Returns the lower bound of the histogram bucket of a value, which is
+/// parsed as an `i64` or `u64` or, if that fails, as a finite `f64`.
+fn histogram_bucket(value: &str, bucket_width: u64) -> Result<i128> {
+    let integer = match value.parse::<i64>() {
+        Ok(value) => Some(i128::from(value)),
+        Err(_) => value.parse::<u64>().ok().map(i128::from),
+    };
+    if let Some(value) = integer {
+        // The bucket of an i64 or u64 always fits into an i128.
+        return Ok(value - value.rem_euclid(i128::from(bucket_width)));
+    }
+    let value: f64 = value
+        .parse()
+        .map_err(|_| SearchEngineError::mismatched_type::<f64>())?;
+    if !value.is_finite() {
+        return Err(SearchEngineError::mismatched_type::<f64>());
+    }
+    let width = bucket_width as f64;
+    // Casting saturates at the bounds of i128.
+    Ok(((value / width).floor() * width) as i128)
+}
+
+/// Creates the query for a freetext token that is searched in a default
+/// attribute: a Prefix query if the index supports it, otherwise an Exact query.
+fn freetext_query(attribute: &str, supported: SupportedQueries, text: &str) -> Query {
+    if supported & SUPPORTS_PREFIX != 0 {
+        Query::Prefix(attribute.to_owned(), text.to_owned())
+    } else {
+        Query::Exact(attribute.to_owned(), text.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Bound;
+
+    #[derive(Clone)]
+    struct DummyIndex {
+        fixed_values: HashSet<usize>,
+        supported_queries: SupportedQueries,
+    }
+
+    impl DummyIndex {
+        fn new(vals: Vec<usize>) -> Self {
+            Self {
+                fixed_values: HashSet::from_iter(vals),
+                supported_queries: SUPPORTS_EXACT,
+            }
+        }
+
+        fn supports(sup: SupportedQueries) -> Self {
+            Self {
+                fixed_values: HashSet::new(),
+                supported_queries: sup,
+            }
+        }
+    }
+
+    impl SearchIndex<usize> for DummyIndex {
+        fn search(&self, _query: &Query) -> Result<HashSet<usize>> {
+            Ok(self.fixed_values.clone())
+        }
+
+        fn supported_queries(&self) -> SupportedQueries {
+            self.supported_queries
+        }
+
+        fn clone_box(&self) -> Box<dyn SearchIndex<usize>> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn search_or() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![1, 2]));
+        engine.add_index("b", DummyIndex::new(vec![3, 4]));
+        engine.add_index("c", DummyIndex::new(vec![2, 5, 6]));
+        let result = engine.search(&Query::Or(vec![
+            Query::Exact("a".into(), "DUMMY".into()),
+            Query::Exact("c".into(), "DUMMY".into()),
+        ]));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 2, 5, 6])));
     }
 
     #[test]
@@ -312,107 +2638,1181 @@ mod tests {
     }
 
     #[test]
-    fn search_exclude() {
-        let mut engine = SearchEngine::<usize>::new();
-        engine.add_index("a", DummyIndex::new(vec![1, 2]));
-        engine.add_index("b", DummyIndex::new(vec![3, 4]));
-        engine.add_index("c", DummyIndex::new(vec![2, 5, 6]));
-        let result = engine.search(&Query::Exclude(
-            Box::new(Query::Exact("c".into(), "DUMMY".into())),
-            vec![Query::Exact("a".into(), "DUMMY".into())],
-        ));
-        assert_eq!(result, Ok(HashSet::from_iter(vec![5, 6])));
-    }
-
-    fn create_parser_engine() -> SearchEngine<usize> {
-        let mut engine = SearchEngine::<usize>::new();
+    fn search_exclude() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![1, 2]));
+        engine.add_index("b", DummyIndex::new(vec![3, 4]));
+        engine.add_index("c", DummyIndex::new(vec![2, 5, 6]));
+        let result = engine.search(&Query::Exclude(
+            Box::new(Query::Exact("c".into(), "DUMMY".into())),
+            vec![Query::Exact("a".into(), "DUMMY".into())],
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![5, 6])));
+    }
+
+    #[test]
+    fn search_ranked_stable() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![9, 4, 7, 1, 3]));
+        let query = Query::Exact("a".into(), "DUMMY".into());
+        assert_eq!(engine.search_ranked_stable(&query), Ok(vec![1, 3, 4, 7, 9]));
+    }
+
+    #[test]
+    fn search_ordered_deterministic() {
+        let query = Query::And(vec![
+            Query::Exact("a".into(), "DUMMY".into()),
+            Query::Exact("b".into(), "DUMMY".into()),
+        ]);
+        let a: Vec<usize> = (0..1000).collect();
+        let b: Vec<usize> = (0..1000).rev().filter(|x| x % 3 == 0).collect();
+        let expected: Vec<usize> = (0..1000).filter(|x| x % 3 == 0).collect();
+        for _ in 0..10 {
+            let mut engine = SearchEngine::<usize>::new();
+            engine.add_index("a", DummyIndex::new(a.clone()));
+            engine.add_index("b", DummyIndex::new(b.clone()));
+            assert_eq!(engine.search_ordered(&query), Ok(expected.clone()));
+        }
+    }
+
+    #[test]
+    fn search_after_paging() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new((0..25).map(|x| x * 3).collect()));
+        let query = Query::Exact("a".into(), "DUMMY".into());
+
+        let mut pages = vec![engine.search_after(&query, None, 10).unwrap()];
+        while let Some(&last) = pages.last().unwrap().last() {
+            pages.push(engine.search_after(&query, Some(&last), 10).unwrap());
+        }
+
+        assert_eq!(
+            pages.iter().map(|page| page.len()).collect::<Vec<_>>(),
+            vec![10, 10, 5, 0]
+        );
+        let all_results: Vec<usize> = pages.into_iter().flatten().collect();
+        assert_eq!(all_results, (0..25).map(|x| x * 3).collect::<Vec<_>>());
+
+        assert_eq!(engine.search_after(&query, None, 0), Ok(vec![]));
+        assert_eq!(engine.search_after(&query, Some(&3), 1), Ok(vec![6]));
+        assert_eq!(
+            engine.search_after(&query, Some(&60), 5),
+            Ok(vec![63, 66, 69, 72])
+        );
+    }
+
+    #[test]
+    fn search_leaf() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![1, 2]));
+        let result = engine.search_leaf(&Query::Exact("a".into(), "DUMMY".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 2])));
+        let result = engine.search_leaf(&Query::Exact("b".into(), "DUMMY".into()));
+        assert_eq!(result, Err(SearchEngineError::UnknownAttribute));
+        let result = engine.search_leaf(&Query::Or(vec![]));
+        assert_eq!(result, Err(SearchEngineError::UnsupportedQuery));
+    }
+
+    #[test]
+    fn compile_matches_search() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![1, 2]));
+        engine.add_index("b", DummyIndex::new(vec![3, 4]));
+        engine.add_index("c", DummyIndex::new(vec![2, 3, 5, 6]));
+        let a = || Query::Exact("a".into(), "DUMMY".into());
+        let b = || Query::Exact("b".into(), "DUMMY".into());
+        let c = || Query::Exact("c".into(), "DUMMY".into());
+        let queries = [
+            a(),
+            Query::Or(vec![a(), b()]),
+            Query::And(vec![]),
+            Query::And(vec![c(), Query::Or(vec![a(), b()])]),
+            Query::AtLeast(2, vec![a(), b(), c()]),
+            Query::Exclude(Box::new(c()), vec![a(), b()]),
+        ];
+        for query in queries.iter() {
+            let compiled = engine.compile(query).expect("valid query");
+            assert_eq!(compiled.execute(), engine.search(query));
+        }
+
+        assert!(matches!(
+            engine.compile(&Query::Or(vec![a(), Query::Exact("x".into(), "".into())])),
+            Err(SearchEngineError::UnknownAttribute)
+        ));
+
+        /// An index that fails every search after the first one.
+        struct FailingIndex(std::sync::atomic::AtomicBool);
+        impl SearchIndex<usize> for FailingIndex {
+            fn search(&self, _query: &Query) -> Result<HashSet<usize>> {
+                match self.0.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                    false => Ok(HashSet::new()),
+                    true => Err(SearchEngineError::UnsupportedOperation),
+                }
+            }
+            fn supported_queries(&self) -> SupportedQueries {
+                SUPPORTS_EXACT
+            }
+            fn clone_box(&self) -> Box<dyn SearchIndex<usize>> {
+                let searched = self.0.load(std::sync::atomic::Ordering::Relaxed);
+                Box::new(FailingIndex(searched.into()))
+            }
+        }
+        engine.add_index("d", FailingIndex(Default::default()));
+        let compiled = engine
+            .compile(&Query::Or(vec![a(), Query::Exact("d".into(), "".into())]))
+            .expect("valid query");
+        assert_eq!(
+            compiled.execute(),
+            Err(SearchEngineError::UnsupportedOperation)
+        );
+    }
+
+    #[test]
+    fn refinable_search_and_filters() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![0, 1, 2, 3, 4, 5]));
+        engine.add_index("b", DummyIndex::new(vec![1, 2, 3, 4]));
+        engine.add_index("c", DummyIndex::new(vec![0, 2, 3, 4]));
+        engine.add_index("d", DummyIndex::new(vec![3, 4, 6]));
+
+        let first = Query::Exact("a".into(), "".into());
+        let mut search = engine.refinable(&first).unwrap();
+        let mut filters = vec![first];
+        for attr in ["b", "c", "d"] {
+            let filter = Query::Exact(attr.into(), "".into());
+            filters.push(filter.clone());
+            let expected = engine.search(&Query::And(filters.clone())).unwrap();
+            assert_eq!(search.refine(&filter), Ok(&expected));
+        }
+        assert_eq!(search.results(), &HashSet::from_iter(vec![3, 4]));
+
+        assert_eq!(
+            search.refine(&Query::Exact("unknown".into(), "".into())),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+        assert_eq!(
+            search.broaden(&Query::Exact("d".into(), "".into())),
+            Ok(&HashSet::from_iter(vec![3, 4, 6]))
+        );
+        assert_eq!(search.into_results(), HashSet::from_iter(vec![3, 4, 6]));
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let query = Query::Exact("a".into(), "".into());
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![0, 1, 2]));
+        let first = engine.snapshot(&query).unwrap();
+
+        engine.add_index("a", DummyIndex::new(vec![1, 2, 3, 4]));
+        let second = engine.snapshot(&query).unwrap();
+        assert_eq!(
+            first.diff(&second),
+            (HashSet::from_iter(vec![3, 4]), HashSet::from_iter(vec![0]))
+        );
+        assert_eq!(
+            second.diff(&first),
+            (HashSet::from_iter(vec![0]), HashSet::from_iter(vec![3, 4]))
+        );
+        assert_eq!(second.diff(&second), (HashSet::new(), HashSet::new()));
+        assert_eq!(second.results(), &HashSet::from_iter(vec![1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn normalizer_case_insensitive() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+        engine.add_index("tag", SearchIndexPrefixTree::new());
+        engine.add_index("function", DummyIndex::new(vec![0]));
+        engine.set_normalizer(|value| value.to_lowercase());
+
+        engine.insert("name", 0, "Alice").unwrap();
+        engine.insert("name", 1, "ALICE").unwrap();
+        engine.insert("name", 2, "Bob").unwrap();
+        engine.insert("tag", 2, "Admin").unwrap();
+        assert_eq!(
+            engine.insert("unknown", 3, "Eve"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+        assert_eq!(
+            engine.insert("function", 3, "Eve"),
+            Err(SearchEngineError::UnsupportedOperation)
+        );
+
+        for value in ["alice", "Alice", "aLiCe"] {
+            let q = Query::Exact("name".into(), value.into());
+            assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+            assert_eq!(engine.search_leaf(&q), Ok(HashSet::from_iter(vec![0, 1])));
+            assert_eq!(
+                engine.compile(&q).unwrap().execute(),
+                Ok(HashSet::from_iter(vec![0, 1]))
+            );
+        }
+        let q = Query::And(vec![
+            Query::Exact("name".into(), "BOB".into()),
+            Query::Prefix("tag".into(), "ADM".into()),
+        ]);
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2])));
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![0, 1, 2]));
+        engine.add_index("b", DummyIndex::new(vec![1, 2, 3]));
+        let recorder = QueryRecorder::new();
+        engine.set_recorder(recorder.clone());
+
+        let a = Query::Exact("a".into(), "".into());
+        let b = Query::Exact("b".into(), "".into());
+        let queries = [
+            Query::And(vec![a.clone(), b.clone()]),
+            Query::Exclude(Box::new(a.clone()), vec![b.clone()]),
+            Query::Exact("unknown".into(), "".into()),
+            b.clone(),
+        ];
+        for q in queries.iter() {
+            let _ = engine.search(q);
+        }
+        engine.search_ordered(&a).unwrap();
+        let and = Query::And(vec![a.clone(), b.clone()]);
+        engine.search_acl(&and, &HashSet::from([1])).unwrap();
+        engine.search_leaf(&b).unwrap();
+        assert_eq!(recorder.queries().len(), 7);
+        assert_eq!(&recorder.queries()[..4], &queries[..]);
+        assert_eq!(&recorder.queries()[4..], &[a.clone(), and, b.clone()]);
+
+        let recorded = recorder.take();
+        assert!(recorder.queries().is_empty());
+        let report = replay(&engine, &recorded);
+        assert_eq!(report.durations.len(), 7);
+        assert_eq!(report.errors, 1);
+        assert!(report.percentile(50.0).unwrap() <= report.total());
+        assert_eq!(recorder.queries(), recorded);
+
+        let sunk = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = sunk.clone();
+        engine.set_recorder(QueryRecorder::with_sink(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }));
+        engine.search(&a).unwrap();
+        assert_eq!(sunk.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn search_acl() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![0, 1, 2, 3, 4]));
+        engine.add_index("b", DummyIndex::new(vec![1, 2, 3]));
+        engine.add_index("c", DummyIndex::new(vec![3, 5]));
+        let a = Query::Exact("a".into(), "".into());
+        let b = Query::Exact("b".into(), "".into());
+        let c = Query::Exact("c".into(), "".into());
+
+        let acl = HashSet::from_iter(vec![1, 3, 5, 7]);
+        let queries = [
+            a.clone(),
+            Query::And(vec![a.clone(), b.clone()]),
+            Query::And(vec![]),
+            Query::Or(vec![b.clone(), c.clone()]),
+            Query::Exclude(Box::new(a.clone()), vec![c.clone()]),
+            Query::AtLeast(2, vec![a.clone(), b.clone(), c.clone()]),
+        ];
+        for q in queries.iter() {
+            let expected: HashSet<_> = engine
+                .search(q)
+                .unwrap()
+                .intersection(&acl)
+                .cloned()
+                .collect();
+            assert_eq!(engine.search_acl(q, &acl), Ok(expected), "{q:?}");
+        }
+
+        assert_eq!(
+            engine.search_acl(&a, &acl),
+            Ok(HashSet::from_iter(vec![1, 3]))
+        );
+        assert_eq!(engine.search_acl(&a, &HashSet::new()), Ok(HashSet::new()));
+        assert_eq!(
+            engine.search_acl(&Query::Exact("unknown".into(), "".into()), &acl),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+    }
+
+    fn create_parser_engine() -> SearchEngine<usize> {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index(
+            "zipcode",
+            DummyIndex::supports(
+                SUPPORTS_EXACT | SUPPORTS_MINIMUM | SUPPORTS_MAXIMUM | SUPPORTS_INRANGE,
+            ),
+        );
+        engine.add_index("pet", DummyIndex::supports(SUPPORTS_EXACT));
+        engine.add_index(
+            "name",
+            DummyIndex::supports(SUPPORTS_EXACT | SUPPORTS_PREFIX),
+        );
+        engine.add_index(
+            "file",
+            DummyIndex::supports(SUPPORTS_EXACT | SUPPORTS_PREFIX | SUPPORTS_PATTERN),
+        );
+        engine
+    }
+
+    #[test]
+    fn supports() {
+        let engine = create_parser_engine();
+        let q = Query::Exclude(
+            Box::new(Query::And(vec![
+                Query::Exact("pet".into(), "Dog".into()),
+                Query::Or(vec![
+                    Query::Prefix("name".into(), "Ha".into()),
+                    Query::InRange("zipcode".into(), "1".into(), "2".into()),
+                ]),
+            ])),
+            vec![Query::AtLeast(
+                1,
+                vec![Query::Pattern("file".into(), "*".into())],
+            )],
+        );
+        assert!(engine.supports(&q));
+        assert!(engine.supports(&Query::And(vec![])));
+
+        assert!(!engine.supports(&Query::Prefix("pet".into(), "Do".into())));
+        assert!(!engine.supports(&Query::Exact("unknown".into(), "".into())));
+        assert!(!engine.supports(&Query::Exclude(
+            Box::new(Query::Exact("pet".into(), "Dog".into())),
+            vec![Query::Minimum("name".into(), "A".into())],
+        )));
+    }
+
+    #[test]
+    fn query_parser_empty() {
+        let engine = create_parser_engine();
+        let (q, freetext) = engine.query_from_str("").unwrap();
+        assert_eq!(q, Query::And(vec![]));
+        assert_eq!(freetext, vec![] as Vec<&str>);
+    }
+
+    #[test]
+    fn query_parser_basic() {
+        let engine = create_parser_engine();
+        let (q, freetext) = engine
+            .query_from_str("+zipcode:12345 +pet:Dog -name:Hans freetext")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::Exclude(
+                Box::new(Query::And(vec![
+                    Query::Exact("zipcode".into(), "12345".into()),
+                    Query::Exact("pet".into(), "Dog".into())
+                ])),
+                vec![Query::Prefix("name".into(), "Hans".into())]
+            )
+        );
+        assert_eq!(freetext, vec!["freetext"]);
+    }
+
+    #[test]
+    fn query_parser_modificators() {
+        let engine = create_parser_engine();
+        let (q, freetext) = engine
+            .query_from_str(
+                "abc +zipcode:>12345 +zipcode:<99999 +zipcode:50000-60000 +name:=Hans def",
+            )
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Minimum("zipcode".into(), "12345".into()),
+                Query::Maximum("zipcode".into(), "99999".into()),
+                Query::InRange("zipcode".into(), "50000".into(), "60000".into()),
+                Query::Exact("name".into(), "Hans".into()),
+            ])
+        );
+        assert_eq!(freetext, vec!["abc", "def"]);
+    }
+
+    #[test]
+    fn query_parser_alternatives() {
+        let engine = create_parser_engine();
+        let (q, freetext) = engine
+            .query_from_str(
+                "start +pet:Cat,Dog +name:Alex,=Hans middle +zipcode:=10000,<500,50000-60000 end",
+            )
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Or(vec![
+                    Query::Exact("pet".into(), "Cat".into()),
+                    Query::Exact("pet".into(), "Dog".into()),
+                ]),
+                Query::Or(vec![
+                    Query::Prefix("name".into(), "Alex".into()),
+                    Query::Exact("name".into(), "Hans".into()),
+                ]),
+                Query::Or(vec![
+                    Query::Exact("zipcode".into(), "10000".into()),
+                    Query::Maximum("zipcode".into(), "500".into()),
+                    Query::InRange("zipcode".into(), "50000".into(), "60000".into()),
+                ]),
+            ])
+        );
+        assert_eq!(freetext, vec!["start", "middle", "end"]);
+    }
+
+    #[test]
+    fn search_at_least() {
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("a", DummyIndex::new(vec![1, 2, 3]));
+        engine.add_index("b", DummyIndex::new(vec![2, 3, 4]));
+        engine.add_index("c", DummyIndex::new(vec![3, 4, 5]));
+        let subqueries = vec![
+            Query::Exact("a".into(), "DUMMY".into()),
+            Query::Exact("b".into(), "DUMMY".into()),
+            Query::Exact("c".into(), "DUMMY".into()),
+        ];
+        let result = engine.search(&Query::AtLeast(1, subqueries.clone()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 2, 3, 4, 5])));
+        let result = engine.search(&Query::AtLeast(2, subqueries.clone()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2, 3, 4])));
+        let result = engine.search(&Query::AtLeast(3, subqueries.clone()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![3])));
+        let result = engine.search(&Query::AtLeast(4, subqueries));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+    }
+
+    #[test]
+    fn query_parser_at_least() {
+        let engine = create_parser_engine();
+        let (q, freetext) = engine
+            .query_from_str("+pet:2#Cat,Dog,Bird -name:1#Hans,Hanna +pet:0#Cat,Dog +pet:x#Cat,Dog")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::Exclude(
+                Box::new(Query::And(vec![
+                    Query::AtLeast(
+                        2,
+                        vec![
+                            Query::Exact("pet".into(), "Cat".into()),
+                            Query::Exact("pet".into(), "Dog".into()),
+                            Query::Exact("pet".into(), "Bird".into()),
+                        ]
+                    ),
+                    Query::Or(vec![
+                        Query::Exact("pet".into(), "0#Cat".into()),
+                        Query::Exact("pet".into(), "Dog".into()),
+                    ]),
+                    Query::Or(vec![
+                        Query::Exact("pet".into(), "x#Cat".into()),
+                        Query::Exact("pet".into(), "Dog".into()),
+                    ]),
+                ])),
+                vec![Query::AtLeast(
+                    1,
+                    vec![
+                        Query::Prefix("name".into(), "Hans".into()),
+                        Query::Prefix("name".into(), "Hanna".into()),
+                    ]
+                )]
+            )
+        );
+        assert_eq!(freetext, vec![] as Vec<&str>);
+
+        let (q, _) = engine.query_from_str("+pet:2#Cat,Cat,Dog").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::AtLeast(
+                2,
+                vec![
+                    Query::Exact("pet".into(), "Cat".into()),
+                    Query::Exact("pet".into(), "Dog".into()),
+                ]
+            )])
+        );
+
+        // Without values after the `#` there is no count modifier.
+        let (q, _) = engine.query_from_str("+name:1#").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Prefix("name".into(), "1#".into())])
+        );
+
+        // A single value or a count above the number of values is kept as written.
+        let (q, _) = engine.query_from_str("+pet:42#fix").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Exact("pet".into(), "42#fix".into())])
+        );
+        let (q, _) = engine.query_from_str("+pet:3#Cat,Dog").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Or(vec![
+                Query::Exact("pet".into(), "3#Cat".into()),
+                Query::Exact("pet".into(), "Dog".into()),
+            ])])
+        );
+    }
+
+    #[test]
+    fn query_parser_pattern() {
+        let engine = create_parser_engine();
+        let (q, _) = engine
+            .query_from_str("+file:report*.pdf,report +name:Han*s")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Or(vec![
+                    Query::Pattern("file".into(), "report*.pdf".into()),
+                    Query::Prefix("file".into(), "report".into()),
+                ]),
+                Query::Prefix("name".into(), "Han*s".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn query_parser_suffix() {
+        let mut engine = create_parser_engine();
         engine.add_index(
-            "zipcode",
-            DummyIndex::supports(
-                SUPPORTS_EXACT | SUPPORTS_MINIMUM | SUPPORTS_MAXIMUM | SUPPORTS_INRANGE,
-            ),
+            "mail",
+            DummyIndex::supports(SUPPORTS_EXACT | SUPPORTS_SUFFIX | SUPPORTS_PATTERN),
         );
-        engine.add_index("pet", DummyIndex::supports(SUPPORTS_EXACT));
-        engine.add_index(
-            "name",
-            DummyIndex::supports(SUPPORTS_EXACT | SUPPORTS_PREFIX),
+        let (q, _) = engine
+            .query_from_str("+mail:*.com,*@*.org,*ex* +file:*.pdf")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Or(vec![
+                    Query::Suffix("mail".into(), ".com".into()),
+                    Query::Pattern("mail".into(), "*@*.org".into()),
+                    Query::Pattern("mail".into(), "*ex*".into()),
+                ]),
+                Query::Pattern("file".into(), "*.pdf".into()),
+            ])
         );
-        engine
     }
 
     #[test]
-    fn query_parser_empty() {
-        let engine = create_parser_engine();
-        let (q, freetext) = engine.query_from_str("").unwrap();
-        assert_eq!(q, Query::And(vec![]));
-        assert_eq!(freetext, vec![] as Vec<&str>);
+    fn query_parser_fuzzy() {
+        let mut engine = create_parser_engine();
+        engine.add_index(
+            "city",
+            DummyIndex::supports(SUPPORTS_EXACT | SUPPORTS_FUZZY | SUPPORTS_PATTERN),
+        );
+        let (q, _) = engine
+            .query_from_str("+city:berlin~2,a~b~1,*x~0,bern~,bern~300 +name:berlin~1")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Or(vec![
+                    Query::Fuzzy("city".into(), "berlin".into(), 2),
+                    Query::Fuzzy("city".into(), "a~b".into(), 1),
+                    Query::Fuzzy("city".into(), "*x".into(), 0),
+                    Query::Exact("city".into(), "bern~".into()),
+                    Query::Exact("city".into(), "bern~300".into()),
+                ]),
+                Query::Prefix("name".into(), "berlin~1".into()),
+            ])
+        );
     }
 
     #[test]
-    fn query_parser_basic() {
-        let engine = create_parser_engine();
-        let (q, freetext) = engine
-            .query_from_str("+zipcode:12345 +pet:Dog -name:Hans freetext")
+    fn query_parser_bracket_ranges() {
+        let mut engine = create_parser_engine();
+        engine.add_index(
+            "age",
+            DummyIndex::supports(SUPPORTS_EXACT | SUPPORTS_INRANGE | SUPPORTS_INRANGE_BOUNDS),
+        );
+        let (q, _) = engine
+            .query_from_str("+age:[18,65),(18,65],[18,65],(18,65) +age:[-5,5]")
             .unwrap();
         assert_eq!(
             q,
-            Query::Exclude(
-                Box::new(Query::And(vec![
-                    Query::Exact("zipcode".into(), "12345".into()),
-                    Query::Exact("pet".into(), "Dog".into())
-                ])),
-                vec![Query::Prefix("name".into(), "Hans".into())]
-            )
+            Query::And(vec![
+                Query::Or(vec![
+                    Query::InRangeBounds(
+                        "age".into(),
+                        Bound::Included("18".into()),
+                        Bound::Excluded("65".into())
+                    ),
+                    Query::InRangeBounds(
+                        "age".into(),
+                        Bound::Excluded("18".into()),
+                        Bound::Included("65".into())
+                    ),
+                    Query::InRangeBounds(
+                        "age".into(),
+                        Bound::Included("18".into()),
+                        Bound::Included("65".into())
+                    ),
+                    Query::InRangeBounds(
+                        "age".into(),
+                        Bound::Excluded("18".into()),
+                        Bound::Excluded("65".into())
+                    ),
+                ]),
+                Query::InRangeBounds(
+                    "age".into(),
+                    Bound::Included("-5".into()),
+                    Bound::Included("5".into())
+                ),
+            ])
         );
-        assert_eq!(freetext, vec!["freetext"]);
-    }
 
-    #[test]
-    fn query_parser_modificators() {
-        let engine = create_parser_engine();
-        let (q, freetext) = engine
-            .query_from_str(
-                "abc +zipcode:>12345 +zipcode:<99999 +zipcode:50000-60000 +name:=Hans def",
-            )
+        // An empty side is unbounded.
+        let (q, _) = engine.query_from_str("+age:[18,),(,65],[,]").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Or(vec![
+                Query::InRangeBounds("age".into(), Bound::Included("18".into()), Bound::Unbounded),
+                Query::InRangeBounds("age".into(), Bound::Unbounded, Bound::Included("65".into())),
+                Query::InRangeBounds("age".into(), Bound::Unbounded, Bound::Unbounded),
+            ])])
+        );
+
+        // Without bound support only fully inclusive ranges are recognized.
+        let (q, _, warnings) = engine
+            .query_from_str_with_warnings("+zipcode:[1,5],[1,5),[1,]")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Or(vec![
+                Query::InRange("zipcode".into(), "1".into(), "5".into()),
+                Query::Exact("zipcode".into(), "[1,5)".into()),
+                Query::Exact("zipcode".into(), "[1,]".into()),
+            ])])
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                ParseWarning {
+                    token: "[1,5)",
+                    reason: ParseWarningReason::UnsupportedOperator('['),
+                },
+                ParseWarning {
+                    token: "[1,]",
+                    reason: ParseWarningReason::UnsupportedOperator('['),
+                },
+            ]
+        );
+
+        // Without range support the comma in brackets separates values.
+        let (q, _, warnings) = engine
+            .query_from_str_with_warnings("+pet:(1,5) +pet:2#[a,b],c")
             .unwrap();
         assert_eq!(
             q,
             Query::And(vec![
-                Query::Minimum("zipcode".into(), "12345".into()),
-                Query::Maximum("zipcode".into(), "99999".into()),
-                Query::InRange("zipcode".into(), "50000".into(), "60000".into()),
-                Query::Exact("name".into(), "Hans".into()),
+                Query::Or(vec![
+                    Query::Exact("pet".into(), "(1".into()),
+                    Query::Exact("pet".into(), "5)".into()),
+                ]),
+                Query::AtLeast(
+                    2,
+                    vec![
+                        Query::Exact("pet".into(), "[a".into()),
+                        Query::Exact("pet".into(), "b]".into()),
+                        Query::Exact("pet".into(), "c".into()),
+                    ]
+                ),
             ])
         );
-        assert_eq!(freetext, vec!["abc", "def"]);
+        assert_eq!(warnings, vec![]);
     }
 
     #[test]
-    fn query_parser_alternatives() {
+    fn query_parser_warnings() {
         let engine = create_parser_engine();
-        let (q, freetext) = engine
-            .query_from_str(
-                "start +pet:Cat,Dog +name:Alex,=Hans middle +zipcode:=10000,<500,50000-60000 end",
-            )
+        let (q, freetext, warnings) = engine
+            .query_from_str_with_warnings("+pet: +zipcode:>10,<5 +pet:>3,*x foo +name:=Hans,Pe*")
             .unwrap();
         assert_eq!(
             q,
             Query::And(vec![
                 Query::Or(vec![
-                    Query::Exact("pet".into(), "Cat".into()),
-                    Query::Exact("pet".into(), "Dog".into()),
+                    Query::Minimum("zipcode".into(), "10".into()),
+                    Query::Maximum("zipcode".into(), "5".into()),
+                ]),
+                Query::Or(vec![
+                    Query::Exact("pet".into(), ">3".into()),
+                    Query::Exact("pet".into(), "*x".into()),
                 ]),
                 Query::Or(vec![
-                    Query::Prefix("name".into(), "Alex".into()),
                     Query::Exact("name".into(), "Hans".into()),
+                    Query::Prefix("name".into(), "Pe*".into()),
+                ]),
+            ])
+        );
+        assert_eq!(freetext, vec!["foo"]);
+        assert_eq!(
+            warnings,
+            vec![
+                ParseWarning {
+                    token: "pet",
+                    reason: ParseWarningReason::EmptyValueList,
+                },
+                ParseWarning {
+                    token: ">3",
+                    reason: ParseWarningReason::UnsupportedOperator('>'),
+                },
+                ParseWarning {
+                    token: "*x",
+                    reason: ParseWarningReason::UnsupportedOperator('*'),
+                },
+                ParseWarning {
+                    token: "Pe*",
+                    reason: ParseWarningReason::UnsupportedOperator('*'),
+                },
+            ]
+        );
+        assert_eq!(
+            engine.query_from_str_with_warnings("+unknown:foo"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+    }
+
+    #[test]
+    fn search_str_freetext() {
+        let mut city = SearchIndexHashMap::<_, String>::new();
+        let mut description = SearchIndexText::new();
+        let mut role = SearchIndexNGram::new();
+        for (id, (c, d, r)) in [
+            ("Berlin", "Red bike, barely used", "superadmin"),
+            ("Berlin", "Blue bike", "moderator"),
+            ("Hamburg", "Red car", "admin"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            city.insert(id, c.into());
+            description.insert(id, d.into());
+            role.insert(id, r.into());
+        }
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("city", city);
+        engine.add_index("description", description);
+        engine.add_index("role", role);
+
+        assert_eq!(
+            engine.search_str("red", "description"),
+            Ok(HashSet::from_iter(vec![0, 2]))
+        );
+        assert_eq!(
+            engine.search_str("+city:Berlin BIKE red", "description"),
+            Ok(HashSet::from_iter(vec![0]))
+        );
+        assert_eq!(
+            engine.search_str("-city:Hamburg bike", "description"),
+            Ok(HashSet::from_iter(vec![0, 1]))
+        );
+        assert_eq!(
+            engine.search_str("+city:Berlin admin", "role"),
+            Ok(HashSet::from_iter(vec![0]))
+        );
+        assert_eq!(
+            engine.search_str("red", "comment"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+        assert_eq!(
+            engine.search_str("+city:Berlin", "comment"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+    }
+
+    #[test]
+    fn query_parser_default_attribute() {
+        let mut name = SearchIndexPrefixTree::new();
+        name.insert(0, "alice".into());
+        name.insert(1, "alicia".into());
+        name.insert(2, "bob".into());
+        let mut city = SearchIndexHashMap::<_, String>::new();
+        city.insert(0, "Hamburg".into());
+        city.insert(1, "Berlin".into());
+        city.insert(2, "Berlin".into());
+
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("name", name);
+        engine.add_index("city", city);
+        assert_eq!(
+            engine.query_from_str_default("alice"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+        assert_eq!(
+            engine.set_default_attribute("unknown"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+        engine.set_default_attribute("name").unwrap();
+
+        let q = engine.query_from_str_default("ali +city:Berlin").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Exact("city".into(), "Berlin".into()),
+                Query::Prefix("name".into(), "ali".into()),
+            ])
+        );
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1])));
+
+        let q = engine.query_from_str_default("alice -city:Berlin").unwrap();
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+
+        engine.set_default_attribute("city").unwrap();
+        let q = engine.query_from_str_default("Ber").unwrap();
+        assert_eq!(engine.search(&q), Ok(HashSet::new()));
+    }
+
+    #[test]
+    fn query_parser_exact_if_present() {
+        let mut name = SearchIndexPrefixTree::new();
+        name.insert(0, "alex".into());
+        name.insert(1, "alexander".into());
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("name", name);
+        engine.add_index("age", SearchIndexBTreeRange::<_, u8>::new());
+
+        let (q, _) = engine.query_from_str("+name:alex").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Prefix("name".into(), "alex".into())])
+        );
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+
+        engine.set_parser_options(QueryParserOptions {
+            exact_if_present: true,
+            ..Default::default()
+        });
+        let (q, notes) = engine.query_from_str_explained("+name:alex").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Exact("name".into(), "alex".into())])
+        );
+        assert_eq!(notes[0].reason, ParseReason::ExactPresent);
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+
+        let (q, notes) = engine.query_from_str_explained("+name:alexa").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Prefix("name".into(), "alexa".into())])
+        );
+        assert_eq!(notes[0].reason, ParseReason::PrefixFallback);
+
+        // Indices without Prefix support are not consulted.
+        let (q, _) = engine.query_from_str("+age:27").unwrap();
+        assert_eq!(q, Query::And(vec![Query::Exact("age".into(), "27".into())]));
+
+        // The value is looked up like it would be searched.
+        engine.set_normalizer(|value| value.to_lowercase());
+        let (q, _) = engine.query_from_str("+name:ALEX").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![Query::Exact("name".into(), "ALEX".into())])
+        );
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+    }
+
+    #[test]
+    fn query_parser_multi_default() {
+        let mut name = SearchIndexPrefixTree::new();
+        name.insert(0, "alice".into());
+        name.insert(1, "bob".into());
+        name.insert(2, "carol".into());
+        let mut city = SearchIndexHashMap::<_, String>::new();
+        city.insert(0, "Hamburg".into());
+        city.insert(1, "alice".into());
+        city.insert(2, "Berlin".into());
+
+        let mut engine = SearchEngine::<usize>::new();
+        engine.add_index("name", name);
+        engine.add_index("city", city);
+
+        let q = engine
+            .query_from_str_multi_default("alice", &["name", "city"])
+            .unwrap();
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+
+        let q = engine
+            .query_from_str_multi_default("alice -name:bob", &["name", "city"])
+            .unwrap();
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+
+        // Every token must match one of the defaults.
+        let q = engine
+            .query_from_str_multi_default("car Berlin", &["name", "city"])
+            .unwrap();
+        assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2])));
+        let q = engine
+            .query_from_str_multi_default("car Hamburg", &["name", "city"])
+            .unwrap();
+        assert_eq!(engine.search(&q), Ok(HashSet::new()));
+
+        assert_eq!(
+            engine.query_from_str_multi_default("alice", &["name", "unknown"]),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+        assert_eq!(
+            engine.query_from_str_multi_default("alice", &[]),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+    }
+
+    #[test]
+    fn query_parser_contradictory() {
+        let mut engine = create_parser_engine();
+        let query_str = "+pet:Dog -pet:Dog";
+        assert!(engine.query_from_str(query_str).is_ok());
+
+        engine.set_parser_options(QueryParserOptions {
+            strict: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            engine.query_from_str(query_str),
+            Err(SearchEngineError::ContradictoryQuery {
+                attribute: "pet".into(),
+                value: "Dog".into()
+            })
+        );
+        assert_eq!(
+            engine.query_from_str("+zipcode:=5 -pet:Cat -zipcode:<3,5"),
+            Err(SearchEngineError::ContradictoryQuery {
+                attribute: "zipcode".into(),
+                value: "=5".into()
+            })
+        );
+        assert!(engine.query_from_str("+pet:Cat,Dog -pet:Dog").is_ok());
+        assert!(engine.query_from_str("+pet:Dog -name:Dog").is_ok());
+    }
+
+    #[test]
+    fn query_parser_boolean_keywords() {
+        let mut engine = create_parser_engine();
+        let default_query = engine
+            .query_from_str("+pet:Dog +name:Hans -zipcode:5")
+            .unwrap();
+        engine.set_parser_options(QueryParserOptions {
+            boolean_keywords: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            engine.query_from_str("name:Alice OR name:Bob"),
+            Ok((
+                Query::And(vec![Query::Or(vec![
+                    Query::And(vec![Query::Prefix("name".into(), "Alice".into())]),
+                    Query::And(vec![Query::Prefix("name".into(), "Bob".into())]),
+                ])]),
+                vec![]
+            ))
+        );
+        assert_eq!(
+            engine.query_from_str("pet:Dog AND NOT name:Hans free OR zipcode:>5"),
+            Ok((
+                Query::And(vec![Query::Or(vec![
+                    Query::Exclude(
+                        Box::new(Query::And(vec![Query::Exact("pet".into(), "Dog".into())])),
+                        vec![Query::Prefix("name".into(), "Hans".into())]
+                    ),
+                    Query::And(vec![Query::Minimum("zipcode".into(), "5".into())]),
+                ])]),
+                vec!["free"]
+            ))
+        );
+
+        // Without OR the query is the same as without keywords.
+        assert_eq!(
+            engine.query_from_str("pet:Dog name:Hans NOT zipcode:5"),
+            Ok(default_query.clone())
+        );
+        assert_eq!(
+            engine.query_from_str("+pet:Dog AND +name:Hans -zipcode:5"),
+            Ok(default_query)
+        );
+        assert_eq!(
+            engine.query_from_str("NOT -pet:Dog"),
+            Ok((
+                Query::And(vec![Query::Exact("pet".into(), "Dog".into())]),
+                vec![]
+            ))
+        );
+
+        // Keywords that can't be applied are freetext.
+        assert_eq!(
+            engine.query_from_str("OR pet:Dog AND OR name:x NOT"),
+            Ok((
+                Query::And(vec![
+                    Query::Exact("pet".into(), "Dog".into()),
+                    Query::Prefix("name".into(), "x".into()),
+                ]),
+                vec!["OR", "OR", "NOT"]
+            ))
+        );
+        assert_eq!(
+            engine.query_from_str("pet:Dog or name:x"),
+            Ok((
+                Query::And(vec![
+                    Query::Exact("pet".into(), "Dog".into()),
+                    Query::Prefix("name".into(), "x".into()),
                 ]),
+                vec!["or"]
+            ))
+        );
+        assert_eq!(
+            engine.query_from_str("unknown:x"),
+            Err(SearchEngineError::UnknownAttribute)
+        );
+
+        // The strict mode checks every group separately.
+        engine.set_parser_options(QueryParserOptions {
+            strict: true,
+            boolean_keywords: true,
+            ..Default::default()
+        });
+        assert!(engine.query_from_str("pet:Dog OR NOT pet:Dog").is_ok());
+        assert_eq!(
+            engine.query_from_str("name:x OR pet:Dog NOT pet:Dog"),
+            Err(SearchEngineError::ContradictoryQuery {
+                attribute: "pet".into(),
+                value: "Dog".into()
+            })
+        );
+    }
+
+    /// Index with a custom value syntax, `^1.2` for all 1.2.x versions
+    /// and `=latest` for the latest version.
+    #[derive(Clone)]
+    struct VersionIndex;
+
+    impl SearchIndex<usize> for VersionIndex {
+        fn search(&self, _query: &Query) -> Result<HashSet<usize>> {
+            Ok(HashSet::new())
+        }
+
+        fn supported_queries(&self) -> SupportedQueries {
+            SUPPORTS_EXACT | SUPPORTS_PREFIX | SUPPORTS_MINIMUM
+        }
+
+        fn clone_box(&self) -> Box<dyn SearchIndex<usize>> {
+            Box::new(self.clone())
+        }
+
+        fn parse_value(&self, attribute: &str, value: &str) -> Option<Query> {
+            if let Some(version) = value.strip_prefix('^') {
+                return Some(Query::Prefix(attribute.into(), format!("{version}.")));
+            }
+            (value == "=latest").then(|| Query::Exact(attribute.into(), "2.0.1".into()))
+        }
+    }
+
+    #[test]
+    fn query_parser_index_syntax() {
+        let mut engine = create_parser_engine();
+        engine.add_index("version", VersionIndex);
+
+        let (q, notes) = engine
+            .query_from_str_explained("+version:^1.2,=latest,>1.0,=2.0 +name:^1.2")
+            .unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
                 Query::Or(vec![
-                    Query::Exact("zipcode".into(), "10000".into()),
-                    Query::Maximum("zipcode".into(), "500".into()),
-                    Query::InRange("zipcode".into(), "50000".into(), "60000".into()),
+                    Query::Prefix("version".into(), "1.2.".into()),
+                    Query::Exact("version".into(), "2.0.1".into()),
+                    Query::Minimum("version".into(), "1.0".into()),
+                    Query::Exact("version".into(), "2.0".into()),
                 ]),
+                Query::Prefix("name".into(), "^1.2".into()),
             ])
         );
-        assert_eq!(freetext, vec!["start", "middle", "end"]);
+        let reasons: Vec<_> = notes.iter().map(|note| note.reason).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ParseReason::IndexSyntax,
+                ParseReason::IndexSyntax,
+                ParseReason::Operator('>'),
+                ParseReason::Operator('='),
+                ParseReason::PrefixFallback,
+            ]
+        );
+    }
+
+    #[test]
+    fn query_parser_explained() {
+        let engine = create_parser_engine();
+        let (q, notes) = engine
+            .query_from_str_explained("+zipcode:>1,<9,=5,3-4,7 -name:Hans,=Alex +pet:Dog,<2 free")
+            .unwrap();
+        assert_eq!(
+            q,
+            engine
+                .query_from_str("+zipcode:>1,<9,=5,3-4,7 -name:Hans,=Alex +pet:Dog,<2")
+                .unwrap()
+                .0
+        );
+        assert_eq!(
+            notes,
+            vec![
+                ParseNote {
+                    token: ">1",
+                    query: Query::Minimum("zipcode".into(), "1".into()),
+                    reason: ParseReason::Operator('>'),
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "<9",
+                    query: Query::Maximum("zipcode".into(), "9".into()),
+                    reason: ParseReason::Operator('<'),
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "=5",
+                    query: Query::Exact("zipcode".into(), "5".into()),
+                    reason: ParseReason::Operator('='),
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "3-4",
+                    query: Query::InRange("zipcode".into(), "3".into(), "4".into()),
+                    reason: ParseReason::Operator('-'),
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "7",
+                    query: Query::Exact("zipcode".into(), "7".into()),
+                    reason: ParseReason::ExactFallback,
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "Hans",
+                    query: Query::Prefix("name".into(), "Hans".into()),
+                    reason: ParseReason::PrefixFallback,
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "=Alex",
+                    query: Query::Exact("name".into(), "Alex".into()),
+                    reason: ParseReason::Operator('='),
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "Dog",
+                    query: Query::Exact("pet".into(), "Dog".into()),
+                    reason: ParseReason::ExactFallback,
+                    value_type: "unknown",
+                },
+                ParseNote {
+                    token: "<2",
+                    query: Query::Exact("pet".into(), "<2".into()),
+                    reason: ParseReason::ExactFallback,
+                    value_type: "unknown",
+                },
+            ]
+        );
     }
 }
@@ -0,0 +1,70 @@
+use crate::engine::SearchEngine;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An EngineSnapshot is a read-only view of a [SearchEngine] at one point in time.
+/// It is created by [SearchEngine::snapshot_handle].
+///
+/// The snapshot dereferences to a [SearchEngine], so it can be searched
+/// and can parse query strings like the engine it was taken from. Inserting
+/// into the engine or removing rows from it doesn't change the results of
+/// the snapshot, so a reader can run multiple queries on a consistent state
+/// while the engine is updated in between.
+///
+/// # Memory cost
+/// Creating or cloning a snapshot doesn't copy any index, the snapshot and the
+/// engine share them. The engine copies an index with
+/// [clone_box](crate::index::SearchIndex::clone_box) the first time it changes
+/// it while a snapshot still shares it. Until the snapshot is dropped, the index
+/// takes up to twice its memory. [insert](SearchEngine::insert) only copies the
+/// index of the attribute, but [remove_row](SearchEngine::remove_row) copies
+/// every shared index. Adding, replacing or removing indices doesn't copy anything.
+///
+/// The snapshot is [Send] and [Sync] if the primary ids are, so it can be
+/// moved to another thread that searches it while the engine is updated.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+/// use std::collections::HashSet;
+///
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.add_index("status", SearchIndexHashMap::<_, String>::new());
+/// engine.insert("status", 0, "online").unwrap();
+///
+/// let snapshot = engine.snapshot_handle();
+/// engine.insert("status", 1, "online").unwrap();
+///
+/// let q = Query::Exact("status".into(), "online".into());
+/// assert_eq!(snapshot.search(&q), Ok(HashSet::from_iter(vec![0])));
+/// assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+/// ```
+pub struct EngineSnapshot<P> {
+    engine: Arc<SearchEngine<P>>,
+}
+
+impl<P> EngineSnapshot<P> {
+    /// Creates a new `EngineSnapshot` from an engine that shares its indices.
+    pub(crate) fn new(engine: SearchEngine<P>) -> Self {
+        Self {
+            engine: Arc::new(engine),
+        }
+    }
+}
+
+// The engine is shared, so P doesn't need to be Clone.
+impl<P> Clone for EngineSnapshot<P> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: Arc::clone(&self.engine),
+        }
+    }
+}
+
+impl<P> Deref for EngineSnapshot<P> {
+    type Target = SearchEngine<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.engine
+    }
+}
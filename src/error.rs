@@ -1,11 +1,15 @@
-use std::{fmt, result};
+use std::{fmt, io, result};
 
 /// Common Result type for the attribute search engine.
 pub type Result<T> = result::Result<T, SearchEngineError>;
 
 /// Enum of all possible error types that the attribute search engine
 /// can throw by itself.
+///
+/// New kinds of errors can be added in minor releases, so a `match` on
+/// an error needs a wildcard arm.
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum SearchEngineError {
     /// Will be thrown if an unknown attribute is requested,
     /// for example when inserting or by a [Query](crate::query::Query).
@@ -13,11 +17,90 @@ pub enum SearchEngineError {
 
     /// A [Query](crate::query::Query) value cannot be processed by a
     /// specific search index because the string can't be converted to the expected type.
-    MismatchedQueryType,
+    MismatchedQueryType {
+        /// Name of the attribute of the value. It is set by the
+        /// [SearchEngine](crate::engine::SearchEngine) and empty if an index
+        /// was used directly.
+        attribute: String,
+        /// Name of the type that the value was expected to have, like
+        /// [value_type_name](crate::index::SearchIndex::value_type_name).
+        expected: &'static str,
+    },
 
     /// A [Query](crate::query::Query) cannot be processed because it is
     /// not supported.
     UnsupportedQuery,
+
+    /// An index doesn't support an operation, for example
+    /// inserting values by [SearchEngine::insert](crate::engine::SearchEngine::insert).
+    UnsupportedOperation,
+
+    /// A query string includes and excludes the same attribute value.
+    /// This is only reported if the strict mode of the
+    /// [QueryParserOptions](crate::QueryParserOptions) is enabled.
+    ContradictoryQuery {
+        /// Name of the attribute that is included and excluded.
+        attribute: String,
+        /// Value that is included and excluded.
+        value: String,
+    },
+
+    /// A new distinct value can't be inserted into an index because the index
+    /// already has the maximum number of distinct values it accepts.
+    CardinalityLimitExceeded {
+        /// Maximum number of distinct values of the index.
+        limit: usize,
+    },
+
+    /// Reading the input failed, for example in
+    /// [SearchEngine::ingest_lines](crate::engine::SearchEngine::ingest_lines).
+    Io {
+        /// Kind of the underlying I/O error.
+        kind: io::ErrorKind,
+        /// Message of the underlying I/O error.
+        message: String,
+    },
+
+    /// No query was saved under a name in a
+    /// [QueryCatalog](crate::query_catalog::QueryCatalog).
+    UnknownSavedQuery {
+        /// Name of the requested query.
+        name: String,
+    },
+
+    /// A saved query of a [QueryCatalog](crate::query_catalog::QueryCatalog)
+    /// uses a parameter that was not given a value.
+    MissingQueryParameter {
+        /// Name of the parameter without braces.
+        parameter: String,
+    },
+
+    /// A parameter of a saved query of a [QueryCatalog](crate::query_catalog::QueryCatalog)
+    /// was given a value that can't be inserted into the query string, because
+    /// it is empty or would change the structure of the query.
+    InvalidQueryParameter {
+        /// Name of the parameter without braces.
+        parameter: String,
+        /// The rejected value.
+        value: String,
+    },
+
+    /// A [Prefix](crate::query::Query::Prefix) query with an empty prefix was
+    /// rejected, because the index is configured to reject them, like with
+    /// [SearchIndexPrefixTree::set_reject_empty_prefix](crate::index::SearchIndexPrefixTree::set_reject_empty_prefix).
+    EmptyPrefix,
+
+    /// An index that has to be built before it can be searched was searched
+    /// before it was built, like a [SearchIndexSortedVec](crate::index::SearchIndexSortedVec)
+    /// without a call to [build](crate::index::SearchIndexSortedVec::build).
+    IndexNotBuilt,
+
+    /// Serializing a result failed, for example in
+    /// `SearchEngine::search_to_json`.
+    Serialization {
+        /// Message of the underlying serialization error.
+        message: String,
+    },
 }
 
 impl std::error::Error for SearchEngineError {}
@@ -26,8 +109,79 @@ impl fmt::Display for SearchEngineError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             SearchEngineError::UnknownAttribute => write!(f, "Unknown attribute error"),
-            SearchEngineError::MismatchedQueryType => write!(f, "Mismatched query type"),
+            SearchEngineError::MismatchedQueryType {
+                attribute,
+                expected,
+            } if attribute.is_empty() => {
+                write!(f, "Mismatched query type: expected a value of type {expected}")
+            }
+            SearchEngineError::MismatchedQueryType {
+                attribute,
+                expected,
+            } => write!(
+                f,
+                "Mismatched query type: attribute '{attribute}' expects a value of type {expected}"
+            ),
             SearchEngineError::UnsupportedQuery => write!(f, "Unsupported query"),
+            SearchEngineError::UnsupportedOperation => write!(f, "Unsupported operation"),
+            SearchEngineError::ContradictoryQuery { attribute, value } => write!(
+                f,
+                "Contradictory query: '{value}' is included and excluded for attribute '{attribute}'"
+            ),
+            SearchEngineError::CardinalityLimitExceeded { limit } => {
+                write!(f, "Cardinality limit of {limit} distinct values exceeded")
+            }
+            SearchEngineError::Io { message, .. } => write!(f, "I/O error: {message}"),
+            SearchEngineError::UnknownSavedQuery { name } => {
+                write!(f, "Unknown saved query '{name}'")
+            }
+            SearchEngineError::MissingQueryParameter { parameter } => {
+                write!(f, "Missing value for query parameter '{parameter}'")
+            }
+            SearchEngineError::InvalidQueryParameter { parameter, value } => {
+                write!(f, "Invalid value '{value}' for query parameter '{parameter}'")
+            }
+            SearchEngineError::EmptyPrefix => write!(f, "Empty prefix rejected"),
+            SearchEngineError::IndexNotBuilt => write!(f, "Index was searched before it was built"),
+            SearchEngineError::Serialization { message } => {
+                write!(f, "Serialization error: {message}")
+            }
+        }
+    }
+}
+
+impl SearchEngineError {
+    /// Creates a [MismatchedQueryType](SearchEngineError::MismatchedQueryType)
+    /// error for a value that should have the type `T`. The attribute is left
+    /// empty, see [with_attribute](Self::with_attribute).
+    pub(crate) fn mismatched_type<T: ?Sized>() -> Self {
+        SearchEngineError::MismatchedQueryType {
+            attribute: String::new(),
+            expected: std::any::type_name::<T>(),
+        }
+    }
+
+    /// Sets the attribute of a [MismatchedQueryType](SearchEngineError::MismatchedQueryType)
+    /// error that doesn't have one yet. Other errors are returned unchanged.
+    pub(crate) fn with_attribute(self, attribute: &str) -> Self {
+        match self {
+            SearchEngineError::MismatchedQueryType {
+                attribute: unset,
+                expected,
+            } if unset.is_empty() => SearchEngineError::MismatchedQueryType {
+                attribute: attribute.to_owned(),
+                expected,
+            },
+            error => error,
+        }
+    }
+}
+
+impl From<io::Error> for SearchEngineError {
+    fn from(error: io::Error) -> Self {
+        SearchEngineError::Io {
+            kind: error.kind(),
+            message: error.to_string(),
         }
     }
 }
@@ -0,0 +1,129 @@
+use crate::engine::SearchEngine;
+use crate::error::*;
+use crate::query::Query;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A FederatedEngine combines several [SearchEngines](SearchEngine), for example
+/// one for each data partition, so they can be searched like a single engine.
+///
+/// A query is sent to all member engines and their results are merged.
+/// An attribute only has to exist in one member engine. Members without
+/// an index for an attribute are not skipped completely, instead the leaf
+/// queries of this attribute match no rows in them. So an Exclude of a missing
+/// attribute removes nothing and an And with it matches nothing in that member.
+/// Only if no member has an index for an attribute,
+/// [UnknownAttribute](SearchEngineError::UnknownAttribute) is returned.
+///
+/// The row ids / primary ids of the members should be disjoint. If the same id
+/// exists in multiple members, it is returned if it matches in any of them.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{FederatedEngine, SearchEngine, SearchIndexHashMap, Query};
+/// use std::collections::HashSet;
+///
+/// let mut index_2023 = SearchIndexHashMap::<_, String>::new();
+/// index_2023.insert(0, "open".into());
+/// let mut engine_2023 = SearchEngine::<usize>::new();
+/// engine_2023.add_index("status", index_2023);
+///
+/// let mut index_2024 = SearchIndexHashMap::<_, String>::new();
+/// index_2024.insert(1, "open".into());
+/// index_2024.insert(2, "closed".into());
+/// let mut engine_2024 = SearchEngine::<usize>::new();
+/// engine_2024.add_index("status", index_2024);
+///
+/// let mut federated = FederatedEngine::new();
+/// federated.add_engine(engine_2023);
+/// federated.add_engine(engine_2024);
+///
+/// let q = Query::Exact("status".into(), "open".into());
+/// assert_eq!(federated.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+/// ```
+pub struct FederatedEngine<P> {
+    engines: Vec<SearchEngine<P>>,
+}
+
+impl<P: Eq + Hash + Clone> Default for FederatedEngine<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> FederatedEngine<P> {
+    /// Creates a new `FederatedEngine` without any member engines.
+    pub fn new() -> Self {
+        Self { engines: vec![] }
+    }
+
+    /// Add a member engine.
+    pub fn add_engine(&mut self, engine: SearchEngine<P>) {
+        self.engines.push(engine);
+    }
+
+    /// Returns all member engines.
+    pub fn engines(&self) -> &[SearchEngine<P>] {
+        &self.engines
+    }
+
+    /// Run a query on all member engines and return the union of their results.
+    ///
+    /// Returns [UnknownAttribute](SearchEngineError::UnknownAttribute) if no member
+    /// has an index for one of the attributes. Other errors of the members are
+    /// returned as they are.
+    pub fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        if !self.has_all_attributes(query) {
+            return Err(SearchEngineError::UnknownAttribute);
+        }
+        let mut result_set = HashSet::new();
+        for engine in self.engines.iter() {
+            result_set.extend(engine.search(&restrict_to_engine(query, engine))?);
+        }
+        Ok(result_set)
+    }
+
+    /// This internal function checks that every attribute of the
+    /// query has an index in at least one member engine.
+    fn has_all_attributes(&self, query: &Query) -> bool {
+        match query {
+            Query::Or(vec) | Query::And(vec) | Query::AtLeast(_, vec) => {
+                vec.iter().all(|pred| self.has_all_attributes(pred))
+            }
+            Query::Exclude(base, exclude) => {
+                self.has_all_attributes(base)
+                    && exclude.iter().all(|pred| self.has_all_attributes(pred))
+            }
+            Query::All => true,
+            Query::Not(inner) => self.has_all_attributes(inner),
+            leaf => leaf
+                .attribute()
+                .is_some_and(|attr| self.engines.iter().any(|engine| engine.has_index(attr))),
+        }
+    }
+}
+
+/// Replaces all leaf queries with attributes that the engine doesn't
+/// know with an empty [Or](Query::Or), which matches no rows.
+fn restrict_to_engine<P: Eq + Hash + Clone>(query: &Query, engine: &SearchEngine<P>) -> Query {
+    let restrict_all = |vec: &[Query]| -> Vec<Query> {
+        vec.iter()
+            .map(|pred| restrict_to_engine(pred, engine))
+            .collect()
+    };
+    match query {
+        Query::Or(vec) => Query::Or(restrict_all(vec)),
+        Query::And(vec) => Query::And(restrict_all(vec)),
+        Query::AtLeast(min_matches, vec) => Query::AtLeast(*min_matches, restrict_all(vec)),
+        Query::Exclude(base, exclude) => Query::Exclude(
+            Box::new(restrict_to_engine(base, engine)),
+            restrict_all(exclude),
+        ),
+        Query::All => Query::All,
+        Query::Not(inner) => Query::Not(Box::new(restrict_to_engine(inner, engine))),
+        leaf => match leaf.attribute() {
+            Some(attr) if engine.has_index(attr) => leaf.clone(),
+            _ => Query::Or(vec![]),
+        },
+    }
+}
@@ -0,0 +1,330 @@
+use super::{
+    check_supported_query, parse_bounds, parse_out_bounds, string_to_payload_type, SearchIndex,
+};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_INRANGE,
+    SUPPORTS_INRANGE_BOUNDS, SUPPORTS_MAXIMUM, SUPPORTS_MINIMUM, SUPPORTS_OUTRANGE,
+    SUPPORTS_OUTRANGE_BOUNDS,
+};
+use std::{
+    any::TypeId,
+    collections::{BTreeSet, HashMap, HashSet},
+    hash::Hash,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+/// SearchIndexAdaptive is a index backed by a HashMap that can match
+/// Exact, InRange, OutRange, Minimum and Maximum queries.
+///
+/// Exact queries are answered by the HashMap alone. When the first range query
+/// arrives, an ordered set of all distinct values is built and cached to answer
+/// range queries like a [SearchIndexBTreeRange](super::SearchIndexBTreeRange).
+/// Inserting a new distinct value invalidates the ordered set, so it is built
+/// again on the next range query.
+///
+/// This is useful for attributes where it is not known in advance if range
+/// queries will be used. As long as there are none, the index only uses the
+/// memory of a [SearchIndexHashMap](super::SearchIndexHashMap). Once the ordered
+/// set exists, every distinct value is stored twice.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexAdaptive};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_age = SearchIndexAdaptive::<usize, i32>::new();
+/// index_age.insert(0, 17);
+/// index_age.insert(1, 42);
+/// index_age.insert(2, 31);
+///
+/// let result = index_age.search(&Query::Exact("<unused>".into(), "42".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+///
+/// let result = index_age.search(&Query::InRange("<unused>".into(), "20".into(), "40".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexAdaptive<P, V> {
+    index: HashMap<V, HashSet<P>>,
+    ordered: OnceLock<BTreeSet<V>>,
+}
+
+impl<P, V> Default for SearchIndexAdaptive<P, V>
+where
+    P: Eq + Hash + Clone + 'static,
+    V: Eq + Hash + Ord + Clone + FromStr + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, V> SearchIndexAdaptive<P, V>
+where
+    P: Eq + Hash + Clone + 'static,
+    V: Eq + Hash + Ord + Clone + FromStr + 'static,
+{
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT
+        | SUPPORTS_INRANGE
+        | SUPPORTS_INRANGE_BOUNDS
+        | SUPPORTS_MINIMUM
+        | SUPPORTS_MAXIMUM
+        | SUPPORTS_OUTRANGE
+        | SUPPORTS_OUTRANGE_BOUNDS;
+
+    /// Creates a new `SearchIndexAdaptive`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexAdaptive;
+    ///
+    /// let index = SearchIndexAdaptive::<usize, i32>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: HashMap::new(),
+            ordered: OnceLock::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexAdaptive;
+    ///
+    /// let mut index = SearchIndexAdaptive::<usize, i32>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, 42);
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, 69);
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, 32);
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: V) {
+        if !self.index.contains_key(&attribute_value) {
+            self.ordered.take();
+        }
+        self.index
+            .entry(attribute_value)
+            .or_default()
+            .insert(primary_id);
+    }
+
+    /// Returns true if the ordered set for range queries is currently built.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexAdaptive, Query};
+    ///
+    /// let mut index = SearchIndexAdaptive::<usize, i32>::new();
+    /// index.insert(0, 42);
+    /// assert!(!index.has_ordered_values());
+    ///
+    /// index.search(&Query::Minimum("<unused>".into(), "10".into())).unwrap();
+    /// assert!(index.has_ordered_values());
+    /// ```
+    pub fn has_ordered_values(&self) -> bool {
+        self.ordered.get().is_some()
+    }
+
+    /// This internal function adds all rows of a range to an
+    /// existing HashSet, building the ordered set if necessary.
+    fn extend_with_range(&self, result_set: &mut HashSet<P>, range: impl RangeBounds<V>) {
+        let ordered = self
+            .ordered
+            .get_or_init(|| self.index.keys().cloned().collect());
+        for value in ordered.range(range) {
+            result_set.extend(self.index[value].iter().cloned());
+        }
+    }
+
+    /// This internal function helps with searching for all kinds of
+    /// ranges and merging the result to a HashSet.
+    fn search_range(&self, range: impl RangeBounds<V>) -> HashSet<P> {
+        let mut result_set = HashSet::<P>::new();
+        self.extend_with_range(&mut result_set, range);
+        result_set
+    }
+}
+
+impl<P, V> SearchIndex<P> for SearchIndexAdaptive<P, V>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Eq + Hash + Ord + Clone + FromStr + ToString + Send + Sync + 'static,
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: V = string_to_payload_type(value_str)?;
+                Ok(self.index.get(&value).cloned().unwrap_or_default())
+            }
+            Query::InRange(_, min_str, max_str) => {
+                let min: V = string_to_payload_type(min_str)?;
+                let max: V = string_to_payload_type(max_str)?;
+                if min > max {
+                    return Ok(HashSet::new());
+                }
+                Ok(self.search_range(min..=max))
+            }
+            Query::InRangeBounds(_, start_str, end_str) => {
+                match parse_bounds::<V>(start_str, end_str)? {
+                    Some(range) => Ok(self.search_range(range)),
+                    None => Ok(HashSet::new()),
+                }
+            }
+            Query::Minimum(_, min_str) => {
+                let min: V = string_to_payload_type(min_str)?;
+                Ok(self.search_range(min..))
+            }
+            Query::Maximum(_, max_str) => {
+                let max: V = string_to_payload_type(max_str)?;
+                Ok(self.search_range(..=max))
+            }
+            Query::OutRange(_, start_str, end_str) => {
+                let start: V = string_to_payload_type(start_str)?;
+                let end: V = string_to_payload_type(end_str)?;
+                if start > end {
+                    return Ok(HashSet::new());
+                }
+                let mut result_set = self.search_range(..start);
+                self.extend_with_range(&mut result_set, (Bound::Excluded(end), Bound::Unbounded));
+                Ok(result_set)
+            }
+            Query::OutRangeBounds(_, start_str, end_str) => {
+                let [below, above] = parse_out_bounds::<V>(start_str, end_str)?;
+                let mut result_set = self.search_range(below);
+                self.extend_with_range(&mut result_set, above);
+                Ok(result_set)
+            }
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<V>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        TypeId::of::<V>() == TypeId::of::<String>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| value.to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?);
+        Ok(())
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<V>(value).map(|_| ())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = false;
+        let distinct_values = self.index.len();
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        if self.index.len() != distinct_values {
+            self.ordered.take();
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index_exact_then_range() {
+        let mut index = SearchIndexAdaptive::<usize, i32>::new();
+        index.insert(0, 17);
+        index.insert(1, 42);
+        index.insert(2, 31);
+        index.insert(3, 26);
+
+        let result = index.search(&Query::Exact("<not used>".into(), "42".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+        assert!(!index.has_ordered_values());
+
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "20".into(),
+            "40".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2, 3])));
+        assert!(index.has_ordered_values());
+
+        let result = index.search(&Query::Exact("<not used>".into(), "17".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+
+        let result = index.search(&Query::OutRange(
+            "<not used>".into(),
+            "20".into(),
+            "40".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+
+        // Inserting an existing value keeps the ordered set, a new value invalidates it.
+        index.insert(4, 42);
+        assert!(index.has_ordered_values());
+        index.insert(5, 35);
+        assert!(!index.has_ordered_values());
+
+        let result = index.search(&Query::Minimum("<not used>".into(), "31".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 2, 4, 5])));
+
+        let result = index.search(&Query::Maximum("<not used>".into(), "26".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 3])));
+
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "40".into(),
+            "20".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+    }
+}
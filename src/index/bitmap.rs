@@ -0,0 +1,228 @@
+use super::{check_supported_query, string_to_payload_type, SearchIndex};
+use crate::{Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT};
+use roaring::RoaringBitmap;
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    str::FromStr,
+};
+
+/// An index that can return the result of a query as a [RoaringBitmap].
+///
+/// [SearchEngine::search_bitmap](crate::SearchEngine::search_bitmap) uses it
+/// through [SearchIndex::as_bitmap_index] to combine the results of these
+/// indices without converting them to HashSets.
+///
+/// This trait is only available with the `roaring` feature.
+pub trait BitmapSearchIndex {
+    /// Search the index with a query and return the matching row ids as a bitmap.
+    ///
+    /// Returns the same rows and errors as [SearchIndex::search].
+    fn search_bitmap(&self, query: &Query) -> Result<RoaringBitmap>;
+}
+
+/// SearchIndexBitmap is a index backed by a HashMap of roaring bitmaps
+/// that can match Exact queries on rows with `u32` ids.
+///
+/// Every value stores its rows in a compressed [RoaringBitmap] instead of a
+/// HashSet. This needs much less memory for dense ids like `0..N` and lets
+/// [SearchEngine::search_bitmap](crate::SearchEngine::search_bitmap) answer
+/// Or, And and Exclude queries with bitmap operations. [search](SearchIndex::search)
+/// still returns a HashSet, so the index can be used with every method of
+/// the [SearchEngine](crate::SearchEngine).
+///
+/// This index is only available with the `roaring` feature.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{BitmapSearchIndex, SearchIndexBitmap};
+/// use attribute_search_engine::Query;
+///
+/// let mut index_pet = SearchIndexBitmap::<String>::new();
+/// index_pet.insert(0, "Cat".into());
+/// index_pet.insert(1, "Dog".into());
+/// index_pet.insert(2, "Cat".into());
+///
+/// let result = index_pet.search_bitmap(&Query::Exact("<unused>".into(), "Cat".into()));
+/// assert_eq!(result.map(|bitmap| bitmap.iter().collect()), Ok(vec![0, 2]));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexBitmap<V> {
+    index: HashMap<V, RoaringBitmap>,
+}
+
+impl<V> Default for SearchIndexBitmap<V>
+where
+    V: Eq + Hash + FromStr + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> SearchIndexBitmap<V>
+where
+    V: Eq + Hash + FromStr + 'static,
+{
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT;
+
+    /// Creates a new `SearchIndexBitmap`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBitmap;
+    ///
+    /// let index = SearchIndexBitmap::<String>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: HashMap::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBitmap;
+    ///
+    /// let mut index = SearchIndexBitmap::<String>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, "Hello".into());
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, "World".into());
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "Rust".into());
+    /// ```
+    pub fn insert(&mut self, primary_id: u32, attribute_value: V) {
+        self.index
+            .entry(attribute_value)
+            .or_default()
+            .insert(primary_id);
+    }
+}
+
+impl<V> BitmapSearchIndex for SearchIndexBitmap<V>
+where
+    V: Eq + Hash + FromStr + 'static,
+{
+    fn search_bitmap(&self, query: &Query) -> Result<RoaringBitmap> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: V = string_to_payload_type(value_str)?;
+                Ok(self.index.get(&value).cloned().unwrap_or_default())
+            }
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+}
+
+impl<V> SearchIndex<u32> for SearchIndexBitmap<V>
+where
+    V: Eq + Hash + Clone + FromStr + ToString + Send + Sync + 'static,
+{
+    fn search(&self, query: &Query) -> Result<HashSet<u32>> {
+        Ok(self.search_bitmap(query)?.iter().collect())
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<u32>> {
+        Box::new(self.clone())
+    }
+
+    fn as_bitmap_index(&self) -> Option<&dyn BitmapSearchIndex> {
+        Some(self)
+    }
+
+    fn count_query(&self, query: &Query) -> Result<usize> {
+        Ok(self.search_bitmap(query)?.len() as usize)
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<V>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        TypeId::of::<V>() == TypeId::of::<String>()
+    }
+
+    fn all_ids(&self) -> HashSet<u32> {
+        self.index.values().flatten().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(u32, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.to_string();
+                primary_ids.iter().map(move |id| (id, value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &u32) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(*primary_id))
+            .map(|(value, _)| value.to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: u32, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?);
+        Ok(())
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<V>(value).map(|_| ())
+    }
+
+    fn contains_value(&self, value: &str) -> bool {
+        string_to_payload_type(value).is_ok_and(|value: V| self.index.contains_key(&value))
+    }
+
+    fn remove_all(&mut self, primary_id: &u32) -> bool {
+        let mut removed = false;
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(*primary_id);
+            !primary_ids.is_empty()
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index_bitmap_exact() {
+        let mut index = SearchIndexBitmap::<i32>::new();
+        for id in 0..1000 {
+            index.insert(id, (id % 3) as i32);
+        }
+
+        let result = index.search_bitmap(&Query::Exact("<not used>".into(), "1".into()));
+        assert_eq!(result, Ok((0..1000).filter(|id| id % 3 == 1).collect()));
+        let result = index.search(&Query::Exact("<not used>".into(), "3".into()));
+        assert_eq!(result, Ok(HashSet::new()));
+        let result = index.search(&Query::Exact("<not used>".into(), "x".into()));
+        assert_eq!(result, Err(SearchEngineError::mismatched_type::<i32>()));
+        assert_eq!(
+            index.count_query(&Query::Exact("<not used>".into(), "0".into())),
+            Ok(334)
+        );
+        assert_eq!(index.values_of(&4), vec!["1".to_string()]);
+    }
+}
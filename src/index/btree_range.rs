@@ -1,9 +1,14 @@
-use super::{string_to_payload_type, SearchIndex};
+use super::{
+    check_supported_query, parse_bounds, parse_out_bounds, search_null, string_to_payload_type,
+    CompiledSearch, SearchIndex,
+};
 use crate::{
     Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_INRANGE,
-    SUPPORTS_MAXIMUM, SUPPORTS_MINIMUM, SUPPORTS_OUTRANGE,
+    SUPPORTS_INRANGE_BOUNDS, SUPPORTS_MAXIMUM, SUPPORTS_MINIMUM, SUPPORTS_NULL, SUPPORTS_OUTRANGE,
+    SUPPORTS_OUTRANGE_BOUNDS,
 };
 use std::{
+    any::TypeId,
     collections::{BTreeMap, HashSet},
     hash::Hash,
     ops::{Bound, RangeBounds},
@@ -11,7 +16,12 @@ use std::{
 };
 
 /// SearchIndexBTreeRange is a index backed by a BTreeMap that can match
-/// Exact, InRange, OutRange, Minimum and Maximum queries.
+/// Exact, InRange, OutRange, Minimum, Maximum, IsNull and IsNotNull queries.
+///
+/// To be used as a [SearchIndex](crate::SearchIndex), the value type must
+/// implement [ToString] in addition to [FromStr], because
+/// the index returns its values as strings, for example for
+/// [SearchEngine::export_rows](crate::SearchEngine::export_rows).
 ///
 /// # Example
 /// ```
@@ -31,8 +41,10 @@ use std::{
 /// let result = index_age.search(&Query::InRange("<unused>".into(), "20".into(), "40".into()));
 /// assert_eq!(result, Ok(HashSet::from_iter(vec![2, 3])));
 /// ```
+#[derive(Clone)]
 pub struct SearchIndexBTreeRange<P, V> {
     index: BTreeMap<V, HashSet<P>>,
+    null_ids: HashSet<P>,
 }
 
 impl<P, V> Default for SearchIndexBTreeRange<P, V>
@@ -50,6 +62,16 @@ where
     P: Eq + Hash + Clone + 'static,
     V: Ord + FromStr + 'static,
 {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT
+        | SUPPORTS_INRANGE
+        | SUPPORTS_INRANGE_BOUNDS
+        | SUPPORTS_MINIMUM
+        | SUPPORTS_MAXIMUM
+        | SUPPORTS_OUTRANGE
+        | SUPPORTS_OUTRANGE_BOUNDS
+        | SUPPORTS_NULL;
+
     /// Creates a new `SearchIndexBTreeRange`.
     ///
     /// # Example
@@ -61,6 +83,7 @@ where
     pub fn new() -> Self {
         Self {
             index: BTreeMap::new(),
+            null_ids: HashSet::new(),
         }
     }
 
@@ -86,23 +109,336 @@ where
             .insert(primary_id);
     }
 
+    /// Insert a new entry in the index and reserve space for `expected_set_size`
+    /// rows if this is the first entry with this value.
+    ///
+    /// This helps with skewed data, where a single value like `root` is shared by
+    /// thousands of rows, because the set of rows for the value doesn't have to grow
+    /// step by step. Insert all later rows of the value with [insert](Self::insert).
+    /// For values with only a few rows the hint wastes memory, so prefer `insert`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBTreeRange;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, String>::new();
+    /// index.insert_with_hint(0, "root".into(), 10000);
+    /// for id in 1..10000 {
+    ///     index.insert(id, "root".into());
+    /// }
+    /// ```
+    pub fn insert_with_hint(
+        &mut self,
+        primary_id: P,
+        attribute_value: V,
+        expected_set_size: usize,
+    ) {
+        self.index
+            .entry(attribute_value)
+            .or_insert_with(|| HashSet::with_capacity(expected_set_size))
+            .insert(primary_id);
+    }
+
+    /// Record that a row / primary id has no value for this attribute.
+    ///
+    /// The row will be matched by [IsNull](Query::IsNull) queries until a value
+    /// is inserted for it. Rows that are neither inserted nor recorded as null
+    /// don't match IsNull queries.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexBTreeRange, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+    /// index.insert(0, 42);
+    /// index.insert_null(1);
+    ///
+    /// let result = index.search(&Query::IsNull("<unused>".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn insert_null(&mut self, primary_id: P) {
+        self.null_ids.insert(primary_id);
+    }
+
+    /// Remove a value from a row / primary id.
+    ///
+    /// Returns true if the row had the value. A value that is no longer
+    /// used by any row is dropped from the index, so range queries don't
+    /// have to skip it.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexBTreeRange, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+    /// index.insert(0, 42);
+    /// index.insert(1, 42);
+    ///
+    /// assert!(index.remove(&0, &42));
+    /// assert!(!index.remove(&0, &42));
+    ///
+    /// let result = index.search(&Query::Exact("<unused>".into(), "42".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn remove(&mut self, primary_id: &P, attribute_value: &V) -> bool {
+        let Some(primary_ids) = self.index.get_mut(attribute_value) else {
+            return false;
+        };
+        let removed = primary_ids.remove(primary_id);
+        if primary_ids.is_empty() {
+            self.index.remove(attribute_value);
+        }
+        removed
+    }
+
+    /// Remove all values of a row / primary id and its [null record](Self::insert_null).
+    ///
+    /// This is meant for deleted rows whose values are not known anymore and
+    /// checks every distinct value of the index. Values that are no longer
+    /// used by any row are dropped, like in [remove](Self::remove).
+    /// Returns true if the row had any value or was recorded as null.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexBTreeRange};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+    /// index.insert(0, 42);
+    /// index.insert(0, 69);
+    /// index.insert(1, 42);
+    ///
+    /// assert!(index.remove_all(&0));
+    /// assert_eq!(index.all_ids(), HashSet::from_iter(vec![1]));
+    /// ```
+    pub fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = self.null_ids.remove(primary_id);
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        removed
+    }
+
+    /// Returns all distinct values that are used by at least one row,
+    /// sorted in ascending order.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBTreeRange;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, u8>::new();
+    /// index.insert(0, 42);
+    /// index.insert(1, 17);
+    /// index.insert(2, 42);
+    ///
+    /// assert_eq!(index.distinct_values().collect::<Vec<_>>(), vec![&17, &42]);
+    /// ```
+    pub fn distinct_values(&self) -> impl Iterator<Item = &V> {
+        self.index.keys()
+    }
+
+    /// Find the `n` largest values that are at least `threshold`.
+    ///
+    /// The values are returned together with their rows / primary ids, starting
+    /// with the largest value. Fewer than `n` values are returned if not enough
+    /// values reach the threshold. If the threshold can't be parsed,
+    /// [MismatchedQueryType](crate::error::SearchEngineError::MismatchedQueryType)
+    /// is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBTreeRange;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, u32>::new();
+    /// index.insert(0, 10);
+    /// index.insert(1, 20);
+    /// index.insert(2, 30);
+    ///
+    /// let top = index.top_n_above(5, "20").expect("valid threshold");
+    /// assert_eq!(
+    ///     top,
+    ///     vec![(30, HashSet::from_iter(vec![2])), (20, HashSet::from_iter(vec![1]))]
+    /// );
+    /// ```
+    pub fn top_n_above(&self, n: usize, threshold: &str) -> Result<Vec<(V, HashSet<P>)>>
+    where
+        V: Clone,
+    {
+        let threshold: V = string_to_payload_type(threshold)?;
+        Ok(self
+            .index
+            .range(threshold..)
+            .rev()
+            .take(n)
+            .map(|(value, primary_ids)| (value.clone(), primary_ids.clone()))
+            .collect())
+    }
+
+    /// This internal function converts a range query to the list of
+    /// ranges of values that it matches.
+    fn query_ranges(&self, query: &Query) -> Result<Vec<(Bound<V>, Bound<V>)>> {
+        // The values are parsed separately for each bound, so V doesn't need to be Clone.
+        let ranges = match query {
+            Query::Exact(_, value_str) => vec![(
+                Bound::Included(string_to_payload_type(value_str)?),
+                Bound::Included(string_to_payload_type(value_str)?),
+            )],
+            Query::InRange(_, min_str, max_str) => {
+                let min: V = string_to_payload_type(min_str)?;
+                let max: V = string_to_payload_type(max_str)?;
+                if min > max {
+                    vec![]
+                } else {
+                    vec![(Bound::Included(min), Bound::Included(max))]
+                }
+            }
+            Query::InRangeBounds(_, start_str, end_str) => {
+                parse_bounds(start_str, end_str)?.into_iter().collect()
+            }
+            Query::Minimum(_, min_str) => vec![(
+                Bound::Included(string_to_payload_type(min_str)?),
+                Bound::Unbounded,
+            )],
+            Query::Maximum(_, max_str) => vec![(
+                Bound::Unbounded,
+                Bound::Included(string_to_payload_type(max_str)?),
+            )],
+            Query::OutRange(_, start_str, end_str) => {
+                let start: V = string_to_payload_type(start_str)?;
+                let end: V = string_to_payload_type(end_str)?;
+                if start > end {
+                    vec![]
+                } else {
+                    vec![
+                        (Bound::Unbounded, Bound::Excluded(start)),
+                        (Bound::Excluded(end), Bound::Unbounded),
+                    ]
+                }
+            }
+            Query::OutRangeBounds(_, start_str, end_str) => {
+                parse_out_bounds(start_str, end_str)?.into()
+            }
+            _ => return Err(SearchEngineError::UnsupportedQuery),
+        };
+        Ok(ranges)
+    }
+
     /// This internal function helps with searching for all kinds of
     /// ranges and merging the result to a HashSet.
     fn search_range(&self, range: impl RangeBounds<V>) -> HashSet<P> {
         let mut result_set = HashSet::<P>::new();
+        self.extend_with_range(&mut result_set, range);
+        result_set
+    }
+
+    /// This internal function adds all rows of a range to an
+    /// existing HashSet without any intermediate sets.
+    fn extend_with_range(&self, result_set: &mut HashSet<P>, range: impl RangeBounds<V>) {
         for (_, primary_set) in self.index.range(range) {
-            result_set = result_set.union(primary_set).cloned().collect();
+            result_set.extend(primary_set.iter().cloned());
         }
-        result_set
     }
 }
 
-impl<P, V> SearchIndex<P> for SearchIndexBTreeRange<P, V>
+/// Values whose distance to each other can be measured, which is used by
+/// [SearchIndexBTreeRange::nearest].
+///
+/// The distance must not overflow for any two values of the type. It is
+/// implemented for all primitive integer types with `abs_diff`, whose result
+/// always fits into the unsigned type of the same size.
+pub trait Distance {
+    /// The type of the distance between two values.
+    type Output: Ord;
+
+    /// Returns the distance between two values.
+    fn distance(&self, other: &Self) -> Self::Output;
+}
+
+macro_rules! impl_distance {
+    ($($value:ty => $output:ty),* $(,)?) => {
+        $(
+            impl Distance for $value {
+                type Output = $output;
+
+                fn distance(&self, other: &Self) -> $output {
+                    self.abs_diff(*other)
+                }
+            }
+        )*
+    };
+}
+
+impl_distance!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => usize,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize,
+);
+
+impl<P, V> SearchIndexBTreeRange<P, V>
 where
     P: Eq + Hash + Clone + 'static,
-    V: Ord + FromStr + 'static,
+    V: Ord + FromStr + Clone + Distance + 'static,
+{
+    /// Find the `n` values that are closest to a target value.
+    ///
+    /// The values are returned together with their rows / primary ids, ordered by
+    /// their distance to the target. If two values have the same distance, the
+    /// smaller value comes first. If the target can't be parsed,
+    /// [MismatchedQueryType](crate::error::SearchEngineError::MismatchedQueryType)
+    /// is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBTreeRange;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexBTreeRange::<usize, u32>::new();
+    /// index.insert(0, 10);
+    /// index.insert(1, 20);
+    /// index.insert(2, 30);
+    ///
+    /// let nearest = index.nearest("18", 2).expect("valid target");
+    /// assert_eq!(
+    ///     nearest,
+    ///     vec![(20, HashSet::from_iter(vec![1])), (10, HashSet::from_iter(vec![0]))]
+    /// );
+    /// ```
+    pub fn nearest(&self, target: &str, n: usize) -> Result<Vec<(V, HashSet<P>)>> {
+        let target: V = string_to_payload_type(target)?;
+        let mut below = self.index.range(..target.clone()).rev().peekable();
+        let mut above = self.index.range(target.clone()..).peekable();
+
+        let mut result = Vec::with_capacity(n);
+        while result.len() < n {
+            let take_below = match (below.peek(), above.peek()) {
+                (Some((low, _)), Some((high, _))) => target.distance(low) <= high.distance(&target),
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let next = if take_below {
+                below.next()
+            } else {
+                above.next()
+            };
+            if let Some((value, primary_ids)) = next {
+                result.push((value.clone(), primary_ids.clone()));
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<P, V> SearchIndex<P> for SearchIndexBTreeRange<P, V>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Ord + Clone + FromStr + ToString + Send + Sync + 'static,
 {
     fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
         match query {
             Query::Exact(_, value_str) => {
                 let value: V = string_to_payload_type(value_str)?;
@@ -116,6 +452,12 @@ where
                 }
                 Ok(self.search_range(min..=max))
             }
+            Query::InRangeBounds(_, start_str, end_str) => {
+                match parse_bounds::<V>(start_str, end_str)? {
+                    Some(range) => Ok(self.search_range(range)),
+                    None => Ok(HashSet::new()),
+                }
+            }
             Query::Minimum(_, min_str) => {
                 let min: V = string_to_payload_type(min_str)?;
                 Ok(self.search_range(min..))
@@ -130,18 +472,120 @@ where
                 if start > end {
                     return Ok(HashSet::new());
                 }
-                Ok(self
-                    .search_range(..start)
-                    .union(&self.search_range((Bound::Excluded(end), Bound::Unbounded)))
-                    .cloned()
-                    .collect())
+                let mut result_set = self.search_range(..start);
+                self.extend_with_range(&mut result_set, (Bound::Excluded(end), Bound::Unbounded));
+                Ok(result_set)
+            }
+            Query::OutRangeBounds(_, start_str, end_str) => {
+                let [below, above] = parse_out_bounds::<V>(start_str, end_str)?;
+                let mut result_set = self.search_range(below);
+                self.extend_with_range(&mut result_set, above);
+                Ok(result_set)
+            }
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                Ok(search_null(query, &self.null_ids, self.all_ids()))
             }
             _ => Err(SearchEngineError::UnsupportedQuery),
         }
     }
 
     fn supported_queries(&self) -> SupportedQueries {
-        SUPPORTS_EXACT | SUPPORTS_INRANGE | SUPPORTS_MINIMUM | SUPPORTS_MAXIMUM | SUPPORTS_OUTRANGE
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn count_query(&self, query: &Query) -> Result<usize> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: V = string_to_payload_type(value_str)?;
+                Ok(self.index.get(&value).map_or(0, HashSet::len))
+            }
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                self.search(query).map(|result_set| result_set.len())
+            }
+            _ => {
+                // A row can have multiple values in the ranges, so the bucket
+                // sizes can't be summed. The ids are counted by reference instead.
+                let mut primary_ids = HashSet::<&P>::new();
+                for range in self.query_ranges(query)? {
+                    for (_, primary_set) in self.index.range(range) {
+                        primary_ids.extend(primary_set);
+                    }
+                }
+                Ok(primary_ids.len())
+            }
+        }
+    }
+
+    fn compile<'a>(&'a self, query: &Query) -> Result<CompiledSearch<'a, P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        if let Query::IsNull(_) | Query::IsNotNull(_) = query {
+            let query = query.clone();
+            return Ok(Box::new(move || {
+                Ok(search_null(&query, &self.null_ids, self.all_ids()))
+            }));
+        }
+        let ranges = self.query_ranges(query)?;
+        Ok(Box::new(move || {
+            let mut result_set = HashSet::new();
+            for (start, end) in ranges.iter() {
+                self.extend_with_range(&mut result_set, (start.as_ref(), end.as_ref()));
+            }
+            Ok(result_set)
+        }))
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<V>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        TypeId::of::<V>() == TypeId::of::<String>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| value.to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?);
+        Ok(())
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<V>(value).map(|_| ())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        SearchIndexBTreeRange::remove_all(self, primary_id)
     }
 }
 
@@ -172,6 +616,78 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![])));
     }
 
+    #[test]
+    fn search_index_nearest() {
+        let mut index = SearchIndexBTreeRange::<usize, u8>::new();
+        index.insert(0, 27);
+        index.insert(1, 27);
+        index.insert(2, 23);
+        index.insert(3, 25);
+        index.insert(4, 34);
+        index.insert(5, 51);
+
+        let result = index.nearest("28", 2);
+        assert_eq!(
+            result,
+            Ok(vec![
+                (27, HashSet::from_iter(vec![0, 1])),
+                (25, HashSet::from_iter(vec![3])),
+            ])
+        );
+
+        let result = index.nearest("29", 3).unwrap();
+        assert_eq!(
+            result.iter().map(|(v, _)| *v).collect::<Vec<_>>(),
+            vec![27, 25, 34]
+        );
+
+        let result = index.nearest("24", 2).unwrap();
+        assert_eq!(
+            result.iter().map(|(v, _)| *v).collect::<Vec<_>>(),
+            vec![23, 25]
+        );
+
+        let result = index.nearest("0", 10).unwrap();
+        assert_eq!(
+            result.iter().map(|(v, _)| *v).collect::<Vec<_>>(),
+            vec![23, 25, 27, 34, 51]
+        );
+
+        assert_eq!(index.nearest("28", 0), Ok(vec![]));
+        assert_eq!(
+            index.nearest("foo", 2),
+            Err(SearchEngineError::mismatched_type::<u8>())
+        );
+    }
+
+    #[test]
+    fn search_index_nearest_at_type_edges() {
+        let mut index = SearchIndexBTreeRange::<usize, i8>::new();
+        index.insert(0, -100);
+        index.insert(1, 100);
+        index.insert(2, i8::MIN);
+        index.insert(3, i8::MAX);
+
+        let values = |target: &str, n: usize| {
+            let result = index.nearest(target, n).unwrap();
+            result.into_iter().map(|(v, _)| v).collect::<Vec<_>>()
+        };
+        assert_eq!(values("100", 2), vec![100, 127]);
+        assert_eq!(values("-100", 4), vec![-100, -128, 100, 127]);
+        assert_eq!(values("127", 4), vec![127, 100, -100, -128]);
+        assert_eq!(values("-128", 4), vec![-128, -100, 100, 127]);
+        assert_eq!(values("0", 2), vec![-100, 100]);
+
+        let mut index = SearchIndexBTreeRange::<usize, u8>::new();
+        index.insert(0, u8::MIN);
+        index.insert(1, u8::MAX);
+        let result = index.nearest("128", 2).unwrap();
+        assert_eq!(
+            result.iter().map(|(v, _)| *v).collect::<Vec<_>>(),
+            vec![255, 0]
+        );
+    }
+
     #[test]
     fn search_index_exact_number() {
         let mut index = SearchIndexBTreeRange::<usize, i32>::new();
@@ -248,6 +764,175 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![])));
     }
 
+    #[test]
+    fn insert_with_hint() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        index.insert_with_hint(0, 1, 100);
+        index.insert_with_hint(1, 1, 100);
+        index.insert(2, 1);
+        index.insert_with_hint(3, 2, 0);
+
+        let result = index.search(&Query::Exact("<not used>".into(), "1".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2])));
+        let result = index.search(&Query::Exact("<not used>".into(), "2".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![3])));
+    }
+
+    #[test]
+    fn search_index_inrange_bounds() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        for i in 0..6 {
+            index.insert(i, i as i32 * 10);
+        }
+
+        let bounds = |start: Bound<&str>, end: Bound<&str>| {
+            Query::InRangeBounds(
+                "<not used>".into(),
+                start.map(str::to_owned),
+                end.map(str::to_owned),
+            )
+        };
+        let cases = [
+            (
+                bounds(Bound::Included("10"), Bound::Excluded("40")),
+                vec![1, 2, 3],
+            ),
+            (
+                bounds(Bound::Excluded("10"), Bound::Included("40")),
+                vec![2, 3, 4],
+            ),
+            (
+                bounds(Bound::Excluded("10"), Bound::Excluded("40")),
+                vec![2, 3],
+            ),
+            (
+                bounds(Bound::Included("10"), Bound::Included("10")),
+                vec![1],
+            ),
+            (bounds(Bound::Included("10"), Bound::Excluded("10")), vec![]),
+            (bounds(Bound::Excluded("10"), Bound::Excluded("10")), vec![]),
+            (bounds(Bound::Excluded("40"), Bound::Included("10")), vec![]),
+            (bounds(Bound::Unbounded, Bound::Excluded("20")), vec![0, 1]),
+        ];
+        for (query, expected) in cases {
+            let expected = HashSet::from_iter(expected);
+            assert_eq!(index.search(&query), Ok(expected.clone()), "{query:?}");
+            assert_eq!(index.compile(&query).unwrap()(), Ok(expected), "{query:?}");
+        }
+    }
+
+    #[test]
+    fn search_index_count_query() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        index.insert(0, 10);
+        index.insert(1, 20);
+        index.insert(1, 25);
+        index.insert(2, 30);
+        index.insert(3, 30);
+        index.insert_null(4);
+
+        let queries = [
+            Query::Exact("<not used>".into(), "30".into()),
+            Query::Exact("<not used>".into(), "11".into()),
+            Query::InRange("<not used>".into(), "15".into(), "35".into()),
+            Query::InRange("<not used>".into(), "35".into(), "15".into()),
+            Query::InRangeBounds(
+                "<not used>".into(),
+                Bound::Excluded("10".into()),
+                Bound::Excluded("30".into()),
+            ),
+            Query::Minimum("<not used>".into(), "20".into()),
+            Query::Maximum("<not used>".into(), "25".into()),
+            Query::OutRange("<not used>".into(), "12".into(), "22".into()),
+            Query::IsNull("<not used>".into()),
+            Query::IsNotNull("<not used>".into()),
+        ];
+        for query in queries.iter() {
+            let expected = index.search(query).map(|result_set| result_set.len());
+            assert_eq!(index.count_query(query), expected, "{query:?}");
+        }
+        assert_eq!(index.count_query(&queries[2]), Ok(3));
+        assert_eq!(
+            index.count_query(&Query::Exact("<not used>".into(), "x".into())),
+            Err(SearchEngineError::mismatched_type::<i32>())
+        );
+        assert_eq!(
+            index.count_query(&Query::Prefix("<not used>".into(), "1".into())),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+    }
+
+    #[test]
+    fn search_index_remove() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        index.insert(0, 10);
+        index.insert(1, 20);
+        index.insert(1, 25);
+        index.insert(2, 30);
+        index.insert(3, 30);
+        index.insert_null(4);
+
+        let minimum = Query::Minimum("<not used>".into(), "20".into());
+        let maximum = Query::Maximum("<not used>".into(), "25".into());
+        let in_range = Query::InRange("<not used>".into(), "15".into(), "35".into());
+
+        assert!(index.remove(&1, &20));
+        assert!(!index.remove(&1, &20));
+        assert!(!index.remove(&0, &30));
+        assert!(!index.index.contains_key(&20));
+        assert_eq!(
+            index.search(&minimum),
+            Ok(HashSet::from_iter(vec![1, 2, 3]))
+        );
+        assert_eq!(index.search(&maximum), Ok(HashSet::from_iter(vec![0, 1])));
+
+        assert!(index.remove_all(&1));
+        assert!(!index.remove_all(&1));
+        assert_eq!(index.search(&minimum), Ok(HashSet::from_iter(vec![2, 3])));
+        assert_eq!(index.search(&maximum), Ok(HashSet::from_iter(vec![0])));
+        assert_eq!(index.search(&in_range), Ok(HashSet::from_iter(vec![2, 3])));
+
+        assert!(index.remove(&2, &30));
+        assert_eq!(index.search(&in_range), Ok(HashSet::from_iter(vec![3])));
+        assert_eq!(
+            index.compile(&in_range).unwrap()(),
+            Ok(HashSet::from_iter(vec![3]))
+        );
+        assert!(index.remove(&3, &30));
+        assert_eq!(index.search(&in_range), Ok(HashSet::new()));
+        assert_eq!(index.index.keys().collect::<Vec<_>>(), vec![&10]);
+
+        assert!(index.remove_all(&4));
+        assert_eq!(
+            index.search(&Query::IsNull("<not used>".into())),
+            Ok(HashSet::new())
+        );
+    }
+
+    #[test]
+    fn search_index_inrange_exclusive() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        for i in 0..6 {
+            index.insert(i, i as i32 * 10);
+        }
+
+        let exclusive = |min: &str, max: &str| {
+            let query = Query::in_range_exclusive("<not used>", min, max);
+            let result = index.search(&query).unwrap();
+            assert_eq!(
+                index.compile(&query).unwrap()(),
+                Ok(result.clone()),
+                "{query:?}"
+            );
+            result
+        };
+        assert_eq!(exclusive("10", "40"), HashSet::from_iter(vec![2, 3]));
+        assert_eq!(exclusive("9", "41"), HashSet::from_iter(vec![1, 2, 3, 4]));
+        assert_eq!(exclusive("10", "20"), HashSet::new());
+        assert_eq!(exclusive("20", "20"), HashSet::new());
+        assert_eq!(exclusive("40", "10"), HashSet::new());
+    }
+
     #[test]
     fn search_index_outrange_number() {
         let mut index = SearchIndexBTreeRange::<usize, i32>::new();
@@ -301,6 +986,55 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2, 3, 4, 5])));
     }
 
+    #[test]
+    fn search_index_outrange_bounds_number() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        index.insert(0, 00);
+        index.insert(1, 10);
+        index.insert(2, 20);
+        index.insert(3, 30);
+        index.insert(4, 40);
+
+        let out_range = |start: Bound<&str>, end: Bound<&str>| {
+            index
+                .search(&Query::OutRangeBounds(
+                    "<not used>".into(),
+                    start.map(str::to_owned),
+                    end.map(str::to_owned),
+                ))
+                .unwrap()
+        };
+        use Bound::*;
+        assert_eq!(
+            out_range(Included("10"), Included("30")),
+            HashSet::from_iter(vec![0, 1, 3, 4])
+        );
+        assert_eq!(
+            out_range(Excluded("10"), Excluded("30")),
+            HashSet::from_iter(vec![0, 4])
+        );
+        assert_eq!(
+            out_range(Included("30"), Included("10")),
+            HashSet::from_iter(vec![0, 1, 2, 3, 4])
+        );
+        assert_eq!(
+            out_range(Unbounded, Excluded("40")),
+            HashSet::from_iter(vec![0, 1, 2, 3, 4])
+        );
+        assert_eq!(
+            out_range(Excluded("0"), Unbounded),
+            HashSet::from_iter(vec![0, 1, 2, 3, 4])
+        );
+        assert_eq!(
+            index.count_query(&Query::OutRangeBounds(
+                "<not used>".into(),
+                Included("10".into()),
+                Excluded("30".into())
+            )),
+            Ok(3)
+        );
+    }
+
     #[test]
     fn search_index_minimum_number() {
         let mut index = SearchIndexBTreeRange::<usize, i32>::new();
@@ -356,6 +1090,54 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![])));
     }
 
+    #[test]
+    fn compile_matches_search() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        for i in 0..6 {
+            index.insert(i, i as i32 * 10);
+        }
+
+        let queries = [
+            Query::Exact("<not used>".into(), "20".into()),
+            Query::Exact("<not used>".into(), "25".into()),
+            Query::InRange("<not used>".into(), "10".into(), "40".into()),
+            Query::InRange("<not used>".into(), "40".into(), "10".into()),
+            Query::OutRange("<not used>".into(), "10".into(), "40".into()),
+            Query::OutRange("<not used>".into(), "40".into(), "10".into()),
+            Query::Minimum("<not used>".into(), "30".into()),
+            Query::Maximum("<not used>".into(), "30".into()),
+        ];
+        for query in queries.iter() {
+            let compiled = index.compile(query).expect("valid query");
+            assert_eq!(compiled(), index.search(query), "{query:?}");
+        }
+
+        assert!(matches!(
+            index.compile(&Query::Minimum("<not used>".into(), "abc".into())),
+            Err(SearchEngineError::MismatchedQueryType { .. })
+        ));
+        assert!(matches!(
+            index.compile(&Query::Prefix("<not used>".into(), "1".into())),
+            Err(SearchEngineError::UnsupportedQuery)
+        ));
+    }
+
+    #[test]
+    fn search_index_entries() {
+        let mut index = SearchIndexBTreeRange::<usize, i32>::new();
+        index.insert(0, 10);
+        index.insert(0, 20);
+        index.insert(1, 10);
+
+        assert_eq!(index.all_ids(), HashSet::from_iter(vec![0, 1]));
+        let mut entries = index.entries();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![(0, "10".into()), (0, "20".into()), (1, "10".into())]
+        );
+    }
+
     #[test]
     fn search_index_unsupported_queries() {
         let mut index = SearchIndexBTreeRange::<usize, i32>::new();
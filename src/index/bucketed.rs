@@ -0,0 +1,242 @@
+use super::{check_supported_query, string_to_payload_type, SearchIndex};
+use crate::{Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// SearchIndexBucketed is a index backed by two HashMaps that can match
+/// Exact queries on numeric values or on buckets of values.
+///
+/// Every value is assigned to the bucket `value mod bucket_size`, where the
+/// bucket size is configured when the index is created. The remainder is
+/// always positive, so `-3` is in bucket `7` for a bucket size of `10`.
+/// An Exact query matches the value itself. If the query value starts with
+/// a `%`, it matches all rows in the bucket with this remainder instead, so
+/// `%0` finds all multiples of the bucket size. This is useful for sharding
+/// or sampling rows.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexBucketed};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_age = SearchIndexBucketed::<usize>::new(10);
+/// index_age.insert(0, 20);
+/// index_age.insert(1, 27);
+/// index_age.insert(2, 40);
+///
+/// let result = index_age.search(&Query::Exact("<unused>".into(), "27".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+///
+/// let result = index_age.search(&Query::Exact("<unused>".into(), "%0".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexBucketed<P> {
+    index: HashMap<i64, HashSet<P>>,
+    buckets: HashMap<u64, HashSet<P>>,
+    bucket_size: u64,
+}
+
+impl<P> SearchIndexBucketed<P>
+where
+    P: Eq + Hash + Clone + 'static,
+{
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT;
+
+    /// Creates a new `SearchIndexBucketed` with the given bucket size.
+    ///
+    /// # Panics
+    /// Panics if `bucket_size` is zero.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBucketed;
+    ///
+    /// let index = SearchIndexBucketed::<usize>::new(10);
+    /// ```
+    pub fn new(bucket_size: u64) -> Self {
+        assert!(bucket_size != 0, "bucket size must be non-zero");
+        Self {
+            index: HashMap::new(),
+            buckets: HashMap::new(),
+            bucket_size,
+        }
+    }
+
+    /// Returns the bucket size of this index.
+    pub fn bucket_size(&self) -> u64 {
+        self.bucket_size
+    }
+
+    /// Insert a new entry in the index and its bucket.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBucketed;
+    ///
+    /// let mut index = SearchIndexBucketed::<usize>::new(10);
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, 42);
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, 69);
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, 32);
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: i64) {
+        self.buckets
+            .entry(self.bucket_of(attribute_value))
+            .or_default()
+            .insert(primary_id.clone());
+        self.index
+            .entry(attribute_value)
+            .or_default()
+            .insert(primary_id);
+    }
+
+    /// Find all rows with a value in the bucket `remainder`.
+    ///
+    /// The remainder is taken modulo the bucket size, so for a bucket
+    /// size of `10` the remainders `3` and `13` return the same rows.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBucketed;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexBucketed::<usize>::new(3);
+    /// index.insert(0, 1);
+    /// index.insert(1, 4);
+    /// index.insert(2, 6);
+    ///
+    /// assert_eq!(index.search_bucket(1), HashSet::from_iter(vec![0, 1]));
+    /// ```
+    pub fn search_bucket(&self, remainder: u64) -> HashSet<P> {
+        self.buckets
+            .get(&(remainder % self.bucket_size))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// This internal function returns the bucket of a value.
+    fn bucket_of(&self, value: i64) -> u64 {
+        i128::from(value).rem_euclid(i128::from(self.bucket_size)) as u64
+    }
+}
+
+impl<P> SearchIndex<P> for SearchIndexBucketed<P>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => match value_str.strip_prefix('%') {
+                Some(remainder_str) => {
+                    Ok(self.search_bucket(string_to_payload_type(remainder_str)?))
+                }
+                None => {
+                    let value: i64 = string_to_payload_type(value_str)?;
+                    Ok(self.index.get(&value).cloned().unwrap_or_default())
+                }
+            },
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<i64>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| value.to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?);
+        Ok(())
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<i64>(value).map(|_| ())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = false;
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        self.buckets.retain(|_, primary_ids| {
+            primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index_bucket_ages() {
+        let mut index = SearchIndexBucketed::<usize>::new(10);
+        let ages = [20, 27, 30, 41, 50, 33, 0, -10, -3];
+        for (id, age) in ages.into_iter().enumerate() {
+            index.insert(id, age);
+        }
+
+        let result = index.search(&Query::Exact("<not used>".into(), "%0".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2, 4, 6, 7])));
+        assert_eq!(
+            index.search_bucket(10),
+            HashSet::from_iter(vec![0, 2, 4, 6, 7])
+        );
+        assert_eq!(index.search_bucket(7), HashSet::from_iter(vec![1, 8]));
+        assert_eq!(index.search_bucket(5), HashSet::new());
+
+        let result = index.search(&Query::Exact("<not used>".into(), "30".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+        let result = index.search(&Query::Exact("<not used>".into(), "%x".into()));
+        assert_eq!(result, Err(SearchEngineError::mismatched_type::<u64>()));
+        let result = index.search(&Query::Exact("<not used>".into(), "%-1".into()));
+        assert_eq!(result, Err(SearchEngineError::mismatched_type::<u64>()));
+    }
+}
@@ -0,0 +1,506 @@
+use super::{check_supported_query, SearchIndex};
+use crate::{Query, Result, SupportedQueries, SUPPORTS_EXACT};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::Hash,
+    ops::Bound,
+};
+
+/// SearchIndexCaseInsensitive is a index backed by a BTreeMap that can match
+/// Exact queries while ignoring the case of the values.
+///
+/// Inserted values and query values are compared after applying [fold_case] to them.
+/// This is Unicode-aware lowercasing with [str::to_lowercase] plus a few exceptions,
+/// so `Straße` also matches `STRASSE`, which have different lowercase forms.
+/// The folding doesn't depend on the locale, so the Turkish `İ` only matches itself.
+///
+/// The original casing of all inserted values is preserved, so they can still
+/// be displayed, for example with [distinct_values](Self::distinct_values).
+/// The folded values are sorted, so [suggest](Self::suggest) only visits the
+/// values that start with the prefix.
+///
+/// For Prefix queries that ignore the case, use a
+/// [SearchIndexCaseInsensitivePrefixTree](crate::SearchIndexCaseInsensitivePrefixTree),
+/// which folds the values the same way and also keeps their original casing.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexCaseInsensitive};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_city = SearchIndexCaseInsensitive::<usize>::new();
+/// index_city.insert(0, "Berlin".into());
+/// index_city.insert(1, "New York".into());
+/// index_city.insert(2, "BERLIN".into());
+///
+/// let result = index_city.search(&Query::Exact("<unused>".into(), "berlin".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexCaseInsensitive<P> {
+    index: BTreeMap<String, CaseInsensitiveEntry<P>>,
+}
+
+/// All rows and original values that share the same normalized value.
+#[derive(Clone)]
+struct CaseInsensitiveEntry<P> {
+    /// All distinct original values with the rows that have them.
+    originals: HashMap<String, HashSet<P>>,
+
+    /// All rows that have one of the original values.
+    primary_ids: HashSet<P>,
+}
+
+impl<P: Eq + Hash + Clone> Default for SearchIndexCaseInsensitive<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> SearchIndexCaseInsensitive<P> {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT;
+
+    /// Creates a new `SearchIndexCaseInsensitive`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitive;
+    ///
+    /// let index = SearchIndexCaseInsensitive::<usize>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: BTreeMap::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitive;
+    ///
+    /// let mut index = SearchIndexCaseInsensitive::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, "Hello".into());
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, "World".into());
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "hello".into());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: String) {
+        self.insert_cow(primary_id, Cow::Owned(attribute_value));
+    }
+
+    /// Insert a new entry in the index from a borrowed or owned value.
+    ///
+    /// The folded value is always allocated, because it is needed to look
+    /// up the entry. The original value is only stored if this exact
+    /// casing is new to the index, which calls [Cow::into_owned]: this
+    /// allocates for a [Cow::Borrowed] value and moves the `String` out of
+    /// a [Cow::Owned] value without copying it.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitive;
+    /// use std::borrow::Cow;
+    ///
+    /// let mut index = SearchIndexCaseInsensitive::<usize>::new();
+    /// let line = "Berlin,BERLIN,Berlin";
+    ///
+    /// // The original "Berlin" and "BERLIN" are allocated once each.
+    /// for (id, city) in line.split(',').enumerate() {
+    ///     index.insert_cow(id, Cow::Borrowed(city));
+    /// }
+    /// ```
+    pub fn insert_cow(&mut self, primary_id: P, attribute_value: Cow<str>) {
+        let entry = self
+            .index
+            .entry(fold_case(&attribute_value))
+            .or_insert_with(|| CaseInsensitiveEntry {
+                originals: HashMap::new(),
+                primary_ids: HashSet::new(),
+            });
+        entry.primary_ids.insert(primary_id.clone());
+        match entry.originals.get_mut(attribute_value.as_ref()) {
+            Some(primary_ids) => {
+                primary_ids.insert(primary_id);
+            }
+            None => {
+                entry
+                    .originals
+                    .insert(attribute_value.into_owned(), HashSet::from([primary_id]));
+            }
+        }
+    }
+
+    /// Returns all distinct values in their original casing.
+    ///
+    /// Values that only differ in their casing are all returned. The order
+    /// of the values is unspecified.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitive;
+    ///
+    /// let mut index = SearchIndexCaseInsensitive::<usize>::new();
+    /// index.insert(0, "Alice".into());
+    /// index.insert(1, "alice".into());
+    /// index.insert(2, "Alice".into());
+    ///
+    /// let mut values = index.distinct_values();
+    /// values.sort();
+    /// assert_eq!(values, vec!["Alice", "alice"]);
+    /// ```
+    pub fn distinct_values(&self) -> Vec<&str> {
+        self.index
+            .values()
+            .flat_map(|entry| entry.originals.keys().map(String::as_str))
+            .collect()
+    }
+
+    /// Returns all values in their original casing that start with the prefix,
+    /// ignoring the case. The result is sorted.
+    ///
+    /// Only the folded values that start with the folded prefix are visited,
+    /// they are found with a range lookup.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitive;
+    ///
+    /// let mut index = SearchIndexCaseInsensitive::<usize>::new();
+    /// index.insert(0, "Alice".into());
+    /// index.insert(1, "Alex".into());
+    /// index.insert(2, "Bob".into());
+    ///
+    /// assert_eq!(index.suggest("al"), vec!["Alex", "Alice"]);
+    /// ```
+    pub fn suggest(&self, prefix: &str) -> Vec<&str> {
+        let prefix = fold_case(prefix);
+        let mut suggestions: Vec<&str> = self
+            .index
+            .range::<str, _>((Bound::Included(prefix.as_str()), Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .flat_map(|(_, entry)| entry.originals.keys().map(String::as_str))
+            .collect();
+        suggestions.sort_unstable();
+        suggestions
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P>
+    for SearchIndexCaseInsensitive<P>
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value) => Ok(self
+                .index
+                .get(&fold_case(value))
+                .map(|entry| entry.primary_ids.clone())
+                .unwrap_or_default()),
+            _ => unreachable!("unsupported queries are rejected before"),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<String>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        true
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index
+            .values()
+            .flat_map(|entry| entry.primary_ids.iter().cloned())
+            .collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    /// Returns all pairs of row ids and values, every value in the original
+    /// casing it was inserted with for that row.
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .values()
+            .flat_map(|entry| entry.originals.iter())
+            .flat_map(|(value, primary_ids)| {
+                primary_ids.iter().map(|id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, value.to_owned());
+        Ok(())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = false;
+        self.index.retain(|_, entry| {
+            if entry.primary_ids.remove(primary_id) {
+                removed = true;
+                entry.originals.retain(|_, primary_ids| {
+                    primary_ids.remove(primary_id);
+                    !primary_ids.is_empty()
+                });
+            }
+            !entry.primary_ids.is_empty()
+        });
+        removed
+    }
+}
+
+/// Maps a string to a key so that strings which only differ in their case
+/// are equal afterwards.
+///
+/// This is not a complete Unicode case folding (`CaseFolding.txt`), but
+/// lowercasing plus a list of exceptions. All characters are lowercased with
+/// [char::to_lowercase], and afterwards only these mappings are applied:
+/// - `ß` and `ẞ` fold to `ss`, so `Straße` and `STRASSE` are equal.
+/// - The final sigma `ς` folds to `σ`, so `ΣΊΣΥΦΟΣ` and `Σίσυφος` are equal.
+/// - The latin ligatures `ﬀ`, `ﬁ`, `ﬂ`, `ﬃ`, `ﬄ`, `ﬅ` and `ﬆ` fold to their letters.
+///
+/// Other characters whose case folding differs from their lowercase form,
+/// like the long s `ſ`, are only lowercased and don't match their folded form.
+///
+/// The folding is independent of the locale. Turkic specific mappings are not
+/// applied: `I` folds to `i` (not the dotless `ı`) and `İ` folds to `i̇`
+/// (`i` followed by a combining dot above), so `İ` is not equal to `I` or `i`.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::fold_case;
+///
+/// assert_eq!(fold_case("Straße"), fold_case("STRASSE"));
+/// assert_eq!(fold_case("Hello World"), "hello world");
+/// ```
+pub fn fold_case(value: &str) -> String {
+    let mut folded = String::with_capacity(value.len());
+    for c in value.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ß' => folded.push_str("ss"),
+            'ς' => folded.push('σ'),
+            'ﬀ' => folded.push_str("ff"),
+            'ﬁ' => folded.push_str("fi"),
+            'ﬂ' => folded.push_str("fl"),
+            'ﬃ' => folded.push_str("ffi"),
+            'ﬄ' => folded.push_str("ffl"),
+            'ﬅ' | 'ﬆ' => folded.push_str("st"),
+            c => folded.push(c),
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchEngineError;
+
+    #[test]
+    fn search_index_exact_ignores_case() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "ALICE".into());
+        index.insert(2, "Bob".into());
+
+        let result = index.search(&Query::Exact("<not used>".into(), "alice".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "bOB".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "Eve".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+    }
+
+    #[test]
+    fn fold_case_german_sharp_s() {
+        assert_eq!(fold_case("Straße"), "strasse");
+        assert_eq!(fold_case("STRASSE"), "strasse");
+        assert_eq!(fold_case("STRAẞE"), "strasse");
+    }
+
+    #[test]
+    fn fold_case_turkish_i() {
+        assert_eq!(fold_case("I"), "i");
+        assert_eq!(fold_case("İ"), "i\u{307}");
+        assert_eq!(fold_case("ı"), "ı");
+        assert_ne!(fold_case("İstanbul"), fold_case("Istanbul"));
+        assert_ne!(fold_case("ısparta"), fold_case("Isparta"));
+    }
+
+    /// Pins every difference between [fold_case] and [str::to_lowercase]
+    /// that is covered by the index, and shows that all other values
+    /// are just lowercased.
+    #[test]
+    fn fold_case_compared_to_lowercase() {
+        for (value, lowercase, folded) in [
+            ("Straße", "straße", "strasse"),
+            ("STRAẞE", "straße", "strasse"),
+            ("STRASSE", "strasse", "strasse"),
+            ("ΟΔΟΣ", "οδος", "οδοσ"),
+            ("ﬁle", "ﬁle", "file"),
+            ("İZMİR", "i\u{307}zmi\u{307}r", "i\u{307}zmi\u{307}r"),
+            ("IZMIR", "izmir", "izmir"),
+            ("ızmır", "ızmır", "ızmır"),
+            ("ÄÖÜ", "äöü", "äöü"),
+        ] {
+            assert_eq!(value.to_lowercase(), lowercase, "{value:?}");
+            assert_eq!(fold_case(value), folded, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn search_index_turkish_i() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "İstanbul".into());
+        index.insert(1, "ISTANBUL".into());
+        index.insert(2, "ıstanbul".into());
+
+        let exact = |value: &str| {
+            index
+                .search(&Query::Exact("<not used>".into(), value.into()))
+                .unwrap()
+        };
+        assert_eq!(exact("istanbul"), HashSet::from_iter(vec![1]));
+        assert_eq!(exact("İSTANBUL"), HashSet::from_iter(vec![0]));
+        assert_eq!(exact("i\u{307}stanbul"), HashSet::from_iter(vec![0]));
+        assert_eq!(exact("ISTANBUL"), HashSet::from_iter(vec![1]));
+        assert_eq!(exact("ıSTANBUL"), HashSet::from_iter(vec![2]));
+    }
+
+    #[test]
+    fn fold_case_greek_sigma() {
+        assert_eq!(fold_case("ΣΊΣΥΦΟΣ"), fold_case("Σίσυφος"));
+    }
+
+    #[test]
+    fn search_index_exact_unicode() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "Straße".into());
+        index.insert(1, "STRASSE".into());
+        index.insert(2, "İzmir".into());
+        index.insert(3, "Izmir".into());
+
+        let result = index.search(&Query::Exact("<not used>".into(), "strasse".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "STRAẞE".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "izmir".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![3])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "i̇zmir".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+    }
+
+    #[test]
+    fn distinct_values_keep_original_case() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "Alice".into());
+
+        let result = index.search(&Query::Exact("<not used>".into(), "alice".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+        assert_eq!(index.distinct_values(), vec!["Alice"]);
+        assert_eq!(index.suggest("ALI"), vec!["Alice"]);
+        assert_eq!(index.suggest("Bo"), vec![] as Vec<&str>);
+    }
+
+    #[test]
+    fn suggest_only_returns_matching_prefixes() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        for (id, value) in ["Alice", "ALEX", "Al", "alf", "Bob", "Straße", "Alice"]
+            .into_iter()
+            .enumerate()
+        {
+            index.insert(id, value.into());
+        }
+
+        assert_eq!(index.suggest("AL"), vec!["ALEX", "Al", "Alice", "alf"]);
+        assert_eq!(index.suggest("ali"), vec!["Alice"]);
+        assert_eq!(index.suggest("alice"), vec!["Alice"]);
+        assert_eq!(index.suggest("alicia"), vec![] as Vec<&str>);
+        assert_eq!(index.suggest("STRASS"), vec!["Straße"]);
+        assert_eq!(index.suggest("").len(), 6);
+    }
+
+    #[test]
+    fn remove_all_drops_unused_originals() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "ALICE".into());
+        index.insert(2, "ALICE".into());
+
+        assert!(index.remove_all(&1));
+        let mut values = index.distinct_values();
+        values.sort();
+        assert_eq!(values, vec!["ALICE", "Alice"]);
+
+        assert!(index.remove_all(&2));
+        assert_eq!(index.distinct_values(), vec!["Alice"]);
+        assert_eq!(index.suggest("al"), vec!["Alice"]);
+        let result = index.search(&Query::Exact("<not used>".into(), "alice".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+
+        assert!(index.remove_all(&0));
+        assert!(index.distinct_values().is_empty());
+        assert!(!index.remove_all(&0));
+    }
+
+    #[test]
+    fn entries_keep_casing_of_each_row() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "ALICE".into());
+        index.insert(1, "alice".into());
+        index.insert(2, "Bob".into());
+
+        let mut entries = index.entries();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                (0, "Alice".to_string()),
+                (1, "ALICE".to_string()),
+                (1, "alice".to_string()),
+                (2, "Bob".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn search_index_unsupported_queries() {
+        let mut index = SearchIndexCaseInsensitive::<usize>::new();
+        index.insert(0, "A".into());
+
+        assert_eq!(
+            index.search(&Query::Prefix("<not used>".into(), "a".into())),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+        assert_eq!(
+            index.search(&Query::Or(vec![])),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use super::{
+    check_supported_query, parse_bounds, parse_out_bounds, string_to_payload_type, SearchIndex,
+};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_INRANGE,
+    SUPPORTS_INRANGE_BOUNDS, SUPPORTS_MAXIMUM, SUPPORTS_MINIMUM, SUPPORTS_OUTRANGE,
+    SUPPORTS_OUTRANGE_BOUNDS,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::Hash,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+/// SearchIndexF64Range is a index backed by a BTreeMap that can match
+/// Exact and range queries on `f64` values.
+///
+/// `f64` is not [Ord], so it can't be used with a
+/// [SearchIndexBTreeRange](crate::SearchIndexBTreeRange). This index orders
+/// the values like [f64::total_cmp] instead, with two exceptions:
+/// - `NaN` is never stored. [insert](Self::insert) rejects it and a query with
+///   a `NaN` value returns
+///   [MismatchedQueryType](SearchEngineError::MismatchedQueryType).
+/// - `-0.0` is stored and queried as `0.0`, so both are equal like with `==`.
+///
+/// Infinities are ordinary values, so `inf` is larger than every finite value.
+/// Query values are parsed with [f64::from_str].
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexF64Range};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_temperature = SearchIndexF64Range::<usize>::new();
+/// index_temperature.insert(0, -3.5).unwrap();
+/// index_temperature.insert(1, 21.25).unwrap();
+/// index_temperature.insert(2, 19.0).unwrap();
+///
+/// let result = index_temperature.search(&Query::InRange("<unused>".into(), "0".into(), "20.5".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexF64Range<P> {
+    index: BTreeMap<FloatKey, HashSet<P>>,
+}
+
+/// This internal type orders an `f64` that is not `NaN` by the bits of
+/// its total ordering.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct FloatKey(i64);
+
+impl FloatKey {
+    /// Returns the key of a value, or `None` for `NaN`.
+    fn new(value: f64) -> Option<Self> {
+        if value.is_nan() {
+            return None;
+        }
+        // Adding 0.0 turns -0.0 into 0.0 and keeps all other values.
+        let bits = (value + 0.0).to_bits() as i64;
+        // Negative values have the sign bit set. Flipping their other bits
+        // makes the integers order like the floats, see f64::total_cmp.
+        Some(Self(bits ^ (((bits >> 63) as u64) >> 1) as i64))
+    }
+
+    /// Returns the value of the key.
+    fn value(self) -> f64 {
+        let bits = self.0 ^ (((self.0 >> 63) as u64) >> 1) as i64;
+        f64::from_bits(bits as u64)
+    }
+}
+
+impl FromStr for FloatKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::new(s.parse().map_err(|_| ())?).ok_or(())
+    }
+}
+
+/// Parses a query value into a [FloatKey]. The error names `f64` as the
+/// expected type instead of the internal key type.
+fn parse_key(value: &str) -> Result<FloatKey> {
+    value
+        .parse()
+        .map_err(|_| SearchEngineError::mismatched_type::<f64>())
+}
+
+impl<P> Default for SearchIndexF64Range<P>
+where
+    P: Eq + Hash + Clone + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> SearchIndexF64Range<P>
+where
+    P: Eq + Hash + Clone + 'static,
+{
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT
+        | SUPPORTS_INRANGE
+        | SUPPORTS_INRANGE_BOUNDS
+        | SUPPORTS_MINIMUM
+        | SUPPORTS_MAXIMUM
+        | SUPPORTS_OUTRANGE
+        | SUPPORTS_OUTRANGE_BOUNDS;
+
+    /// Creates a new `SearchIndexF64Range`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexF64Range;
+    ///
+    /// let index = SearchIndexF64Range::<usize>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: BTreeMap::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// Returns [MismatchedQueryType](SearchEngineError::MismatchedQueryType)
+    /// and doesn't change the index if the value is `NaN`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexF64Range;
+    ///
+    /// let mut index = SearchIndexF64Range::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, 4.2).unwrap();
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, 6.9).unwrap();
+    /// // NaN is rejected:
+    /// assert!(index.insert(124, f64::NAN).is_err());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: f64) -> Result<()> {
+        let key =
+            FloatKey::new(attribute_value).ok_or_else(SearchEngineError::mismatched_type::<f64>)?;
+        self.index.entry(key).or_default().insert(primary_id);
+        Ok(())
+    }
+
+    /// This internal function helps with searching for all kinds of
+    /// ranges and merging the result to a HashSet.
+    fn search_range(&self, range: impl RangeBounds<FloatKey>) -> HashSet<P> {
+        self.index
+            .range(range)
+            .flat_map(|(_, primary_ids)| primary_ids.iter().cloned())
+            .collect()
+    }
+}
+
+impl<P> SearchIndex<P> for SearchIndexF64Range<P>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: FloatKey = parse_key(value_str)?;
+                Ok(self.index.get(&value).cloned().unwrap_or_default())
+            }
+            Query::InRange(_, min_str, max_str) => {
+                let min: FloatKey = parse_key(min_str)?;
+                let max: FloatKey = parse_key(max_str)?;
+                if min > max {
+                    return Ok(HashSet::new());
+                }
+                Ok(self.search_range(min..=max))
+            }
+            Query::InRangeBounds(_, start_str, end_str) => {
+                let bounds = parse_bounds::<FloatKey>(start_str, end_str)
+                    .map_err(|_| SearchEngineError::mismatched_type::<f64>())?;
+                match bounds {
+                    Some(range) => Ok(self.search_range(range)),
+                    None => Ok(HashSet::new()),
+                }
+            }
+            Query::Minimum(_, min_str) => {
+                let min: FloatKey = parse_key(min_str)?;
+                Ok(self.search_range(min..))
+            }
+            Query::Maximum(_, max_str) => {
+                let max: FloatKey = parse_key(max_str)?;
+                Ok(self.search_range(..=max))
+            }
+            Query::OutRange(_, start_str, end_str) => {
+                let start: FloatKey = parse_key(start_str)?;
+                let end: FloatKey = parse_key(end_str)?;
+                if start > end {
+                    return Ok(HashSet::new());
+                }
+                let mut result_set = self.search_range(..start);
+                result_set.extend(self.search_range((Bound::Excluded(end), Bound::Unbounded)));
+                Ok(result_set)
+            }
+            Query::OutRangeBounds(_, start_str, end_str) => {
+                let [below, above] = parse_out_bounds::<FloatKey>(start_str, end_str)
+                    .map_err(|_| SearchEngineError::mismatched_type::<f64>())?;
+                let mut result_set = self.search_range(below);
+                result_set.extend(self.search_range(above));
+                Ok(result_set)
+            }
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<f64>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.value().to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| value.value().to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?)
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        parse_key(value).map(|_| ())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = false;
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_index() -> SearchIndexF64Range<usize> {
+        let mut index = SearchIndexF64Range::<usize>::new();
+        let values = [
+            -0.0,
+            0.0,
+            1.5,
+            -1.5,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+        ];
+        for (id, value) in values.into_iter().enumerate() {
+            index.insert(id, value).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn search_index_f64_zero() {
+        let index = create_index();
+        let exact = |value: &str| index.search(&Query::Exact("<not used>".into(), value.into()));
+
+        assert_eq!(exact("0"), Ok(HashSet::from_iter(vec![0, 1])));
+        assert_eq!(exact("-0.0"), Ok(HashSet::from_iter(vec![0, 1])));
+        assert_eq!(index.values_of(&0), vec!["0".to_string()]);
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "-0.0".into(),
+            "-0.0".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+        let result = index.search(&Query::InRangeBounds(
+            "<not used>".into(),
+            Bound::Excluded("-0.0".into()),
+            Bound::Included("1.5".into()),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2, 7])));
+    }
+
+    #[test]
+    fn search_index_f64_infinities() {
+        let index = create_index();
+
+        let result = index.search(&Query::Minimum("<not used>".into(), "inf".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4])));
+        let result = index.search(&Query::Maximum("<not used>".into(), "-inf".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![5])));
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "-inf".into(),
+            "inf".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(0..8)));
+        let result = index.search(&Query::InRangeBounds(
+            "<not used>".into(),
+            Bound::Excluded("-inf".into()),
+            Bound::Excluded("inf".into()),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2, 3, 6, 7])));
+        let result = index.search(&Query::OutRange(
+            "<not used>".into(),
+            "-1.5".into(),
+            "1.5".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4, 5, 6])));
+        let result = index.search(&Query::Maximum("<not used>".into(), "-1".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![3, 5])));
+    }
+
+    #[test]
+    fn search_index_f64_nan() {
+        let mut index = create_index();
+
+        assert_eq!(
+            index.insert(8, f64::NAN),
+            Err(SearchEngineError::mismatched_type::<f64>())
+        );
+        assert_eq!(
+            index.insert_str(8, "NaN"),
+            Err(SearchEngineError::mismatched_type::<f64>())
+        );
+        assert!(index.values_of(&8).is_empty());
+        let result = index.search(&Query::Minimum("<not used>".into(), "NaN".into()));
+        assert_eq!(result, Err(SearchEngineError::mismatched_type::<f64>()));
+        let result = index.search(&Query::Exact("<not used>".into(), "one".into()));
+        assert_eq!(result, Err(SearchEngineError::mismatched_type::<f64>()));
+    }
+}
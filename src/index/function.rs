@@ -0,0 +1,128 @@
+use super::{check_supported_query, SearchIndex};
+use crate::{Query, Result, SupportedQueries};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Type of the closure that is used by [SearchIndexFunction].
+type SearchFunction<P> = dyn Fn(&Query) -> HashSet<P> + Send + Sync;
+
+/// SearchIndexFunction is a lightweight index backed by a closure that
+/// computes the matching rows for a query on demand.
+///
+/// It can be used to plug arbitrary logic into a [SearchEngine](crate::engine::SearchEngine),
+/// for example computed filters or ids fetched from an external service,
+/// without implementing a full [SearchIndex] type. The closure is only
+/// called for queries that are listed in the supported queries bitmask.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexFunction, Query, SUPPORTS_EXACT};
+/// use std::collections::HashSet;
+///
+/// let index_even = SearchIndexFunction::<usize>::new(SUPPORTS_EXACT, |query| match query {
+///     Query::Exact(_, value) if value == "true" => HashSet::from_iter(vec![0, 2, 4]),
+///     Query::Exact(_, value) if value == "false" => HashSet::from_iter(vec![1, 3, 5]),
+///     _ => HashSet::new(),
+/// });
+///
+/// let result = index_even.search(&Query::Exact("<unused>".into(), "true".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2, 4])));
+/// ```
+pub struct SearchIndexFunction<P> {
+    function: Arc<SearchFunction<P>>,
+    supported_queries: SupportedQueries,
+}
+
+impl<P> SearchIndexFunction<P> {
+    /// Creates a new `SearchIndexFunction`.
+    ///
+    /// The bitmask describes which queries are passed to the closure. All other
+    /// queries are rejected with [UnsupportedQuery](crate::error::SearchEngineError::UnsupportedQuery).
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndexFunction, SUPPORTS_EXACT};
+    /// use std::collections::HashSet;
+    ///
+    /// let index = SearchIndexFunction::<usize>::new(SUPPORTS_EXACT, |_| HashSet::new());
+    /// ```
+    pub fn new<F>(supported_queries: SupportedQueries, function: F) -> Self
+    where
+        F: Fn(&Query) -> HashSet<P> + Send + Sync + 'static,
+    {
+        Self {
+            function: Arc::new(function),
+            supported_queries,
+        }
+    }
+}
+
+// The closure is shared, so P doesn't need to be Clone.
+impl<P> Clone for SearchIndexFunction<P> {
+    fn clone(&self) -> Self {
+        Self {
+            function: Arc::clone(&self.function),
+            supported_queries: self.supported_queries,
+        }
+    }
+}
+
+impl<P: Send + Sync + 'static> SearchIndex<P> for SearchIndexFunction<P> {
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, self.supported_queries)?;
+        Ok((self.function)(query))
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        self.supported_queries
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SearchEngineError, SUPPORTS_EXACT, SUPPORTS_MINIMUM};
+
+    #[test]
+    fn search_index_function_exact() {
+        let index = SearchIndexFunction::<usize>::new(SUPPORTS_EXACT, |query| match query {
+            Query::Exact(_, value) => value
+                .parse::<usize>()
+                .map(|divisor| (1..=12).filter(|x| x % divisor == 0).collect())
+                .unwrap_or_default(),
+            _ => HashSet::new(),
+        });
+
+        let result = index.search(&Query::Exact("<not used>".into(), "4".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4, 8, 12])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "5".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![5, 10])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "abc".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+    }
+
+    #[test]
+    fn search_index_unsupported_queries() {
+        let index =
+            SearchIndexFunction::<usize>::new(SUPPORTS_MINIMUM, |_| HashSet::from_iter(vec![0]));
+
+        assert_eq!(
+            index.search(&Query::Minimum("<not used>".into(), "0".into())),
+            Ok(HashSet::from_iter(vec![0]))
+        );
+        assert_eq!(
+            index.search(&Query::Exact("<not used>".into(), "0".into())),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+        assert_eq!(
+            index.search(&Query::Or(vec![])),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+    }
+}
@@ -1,13 +1,28 @@
-use super::{string_to_payload_type, SearchIndex};
-use crate::{Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT};
+use super::{
+    check_supported_query, search_null, string_to_payload_type, CompiledSearch, SearchIndex,
+};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_LENGTH,
+    SUPPORTS_NULL,
+};
 use std::{
+    any::TypeId,
+    borrow::Cow,
     collections::{HashMap, HashSet},
     hash::Hash,
     str::FromStr,
 };
 
 /// SearchIndexHashMap is a index backed by a HashMap that can match
-/// Exact queries.
+/// Exact, LengthInRange, IsNull and IsNotNull queries.
+///
+/// LengthInRange queries use the string representation of the values and
+/// have to check every distinct value in the index.
+///
+/// To be used as a [SearchIndex](crate::SearchIndex), the value type must
+/// implement [ToString] in addition to [FromStr], because
+/// the index returns its values as strings, for example for
+/// [SearchEngine::export_rows](crate::SearchEngine::export_rows).
 ///
 /// # Example
 /// ```
@@ -23,8 +38,11 @@ use std::{
 /// let result = index_city.search(&Query::Exact("<unused>".into(), "New York".into()));
 /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
 /// ```
+#[derive(Clone)]
 pub struct SearchIndexHashMap<P, V> {
     index: HashMap<V, HashSet<P>>,
+    null_ids: HashSet<P>,
+    max_distinct_values: Option<usize>,
 }
 
 impl<P, V> Default for SearchIndexHashMap<P, V>
@@ -42,6 +60,10 @@ where
     P: Eq + Hash + Clone + 'static,
     V: Eq + Hash + FromStr + 'static,
 {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries =
+        SUPPORTS_EXACT | SUPPORTS_LENGTH | SUPPORTS_NULL;
+
     /// Creates a new `SearchIndexHashMap`.
     ///
     /// # Example
@@ -53,9 +75,48 @@ where
     pub fn new() -> Self {
         Self {
             index: HashMap::new(),
+            null_ids: HashSet::new(),
+            max_distinct_values: None,
         }
     }
 
+    /// Creates a new `SearchIndexHashMap` that accepts at most
+    /// `max_distinct_values` distinct values.
+    ///
+    /// This is a safety rail against attributes with an unexpectedly high
+    /// cardinality, for example when a unique id is accidentally indexed
+    /// as a facet. The limit is checked by [try_insert](Self::try_insert)
+    /// and by [SearchEngine::insert](crate::SearchEngine::insert), which
+    /// reject new distinct values beyond the limit with
+    /// [CardinalityLimitExceeded](SearchEngineError::CardinalityLimitExceeded).
+    /// Values that already exist can still be inserted for more rows.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngineError, SearchIndexHashMap};
+    ///
+    /// let mut index = SearchIndexHashMap::<usize, String>::new_with_max_distinct_values(2);
+    /// assert_eq!(index.try_insert(0, "A".into()), Ok(()));
+    /// assert_eq!(index.try_insert(1, "B".into()), Ok(()));
+    /// assert_eq!(index.try_insert(2, "A".into()), Ok(()));
+    /// assert_eq!(
+    ///     index.try_insert(3, "C".into()),
+    ///     Err(SearchEngineError::CardinalityLimitExceeded { limit: 2 })
+    /// );
+    /// ```
+    pub fn new_with_max_distinct_values(max_distinct_values: usize) -> Self {
+        Self {
+            max_distinct_values: Some(max_distinct_values),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the maximum number of distinct values of this index
+    /// or `None` if it has no limit.
+    pub fn max_distinct_values(&self) -> Option<usize> {
+        self.max_distinct_values
+    }
+
     /// Insert a new entry in the index.
     ///
     /// # Example
@@ -71,16 +132,205 @@ where
     /// // Add as much entries as you want for as many rows you want:
     /// index.insert(124, "C".into());
     /// ```
+    ///
+    /// This doesn't check the [maximum number of distinct values](Self::new_with_max_distinct_values),
+    /// use [try_insert](Self::try_insert) for that.
     pub fn insert(&mut self, primary_id: P, attribute_value: V) {
         self.index
             .entry(attribute_value)
             .or_default()
             .insert(primary_id);
     }
+
+    /// Insert a new entry in the index if the value already exists or the
+    /// index has less than the [maximum number of distinct values](Self::new_with_max_distinct_values).
+    ///
+    /// Returns [CardinalityLimitExceeded](SearchEngineError::CardinalityLimitExceeded)
+    /// and leaves the index unchanged if the value is new and the limit is reached.
+    pub fn try_insert(&mut self, primary_id: P, attribute_value: V) -> Result<()> {
+        if let Some(limit) = self.max_distinct_values {
+            if self.index.len() >= limit && !self.index.contains_key(&attribute_value) {
+                return Err(SearchEngineError::CardinalityLimitExceeded { limit });
+            }
+        }
+        self.insert(primary_id, attribute_value);
+        Ok(())
+    }
+
+    /// Record that a row / primary id has no value for this attribute.
+    ///
+    /// The row will be matched by [IsNull](Query::IsNull) queries until a value
+    /// is inserted for it. Rows that are neither inserted nor recorded as null
+    /// don't match IsNull queries.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<usize, String>::new();
+    /// index.insert(0, "A".into());
+    /// index.insert_null(1);
+    ///
+    /// let result = index.search(&Query::IsNull("<unused>".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn insert_null(&mut self, primary_id: P) {
+        self.null_ids.insert(primary_id);
+    }
+
+    /// Remove a value from a row / primary id.
+    ///
+    /// Returns true if the row had the value. A value that is no longer
+    /// used by any row is dropped from the index, so it doesn't count towards
+    /// the [maximum number of distinct values](Self::new_with_max_distinct_values)
+    /// anymore.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexHashMap, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<usize, String>::new();
+    /// index.insert(0, "A".into());
+    /// index.insert(1, "A".into());
+    ///
+    /// assert!(index.remove(&0, &"A".into()));
+    /// assert!(!index.remove(&0, &"A".into()));
+    ///
+    /// let result = index.search(&Query::Exact("<unused>".into(), "A".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn remove(&mut self, primary_id: &P, attribute_value: &V) -> bool {
+        let Some(primary_ids) = self.index.get_mut(attribute_value) else {
+            return false;
+        };
+        let removed = primary_ids.remove(primary_id);
+        if primary_ids.is_empty() {
+            self.index.remove(attribute_value);
+        }
+        removed
+    }
+
+    /// Remove all values of a row / primary id and its [null record](Self::insert_null).
+    ///
+    /// This checks every distinct value of the index. Values that are no
+    /// longer used by any row are dropped, like in [remove](Self::remove).
+    /// Returns true if the row had any value or was recorded as null.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexHashMap};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexHashMap::<usize, String>::new();
+    /// index.insert(0, "A".into());
+    /// index.insert(0, "B".into());
+    /// index.insert(1, "A".into());
+    ///
+    /// assert!(index.remove_all(&0));
+    /// assert_eq!(index.all_ids(), HashSet::from_iter(vec![1]));
+    /// ```
+    pub fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = self.null_ids.remove(primary_id);
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        removed
+    }
+
+    /// Returns all distinct values that are used by at least one row.
+    ///
+    /// The order is unspecified. This is meant for faceted filters, like a
+    /// list of all cities to choose from.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexHashMap;
+    ///
+    /// let mut index = SearchIndexHashMap::<usize, String>::new();
+    /// index.insert(0, "Berlin".into());
+    /// index.insert(1, "Bonn".into());
+    /// index.insert(2, "Berlin".into());
+    ///
+    /// let mut cities: Vec<&String> = index.distinct_values().collect();
+    /// cities.sort();
+    /// assert_eq!(cities, vec!["Berlin", "Bonn"]);
+    /// ```
+    pub fn distinct_values(&self) -> impl Iterator<Item = &V> {
+        self.index.keys()
+    }
+}
+
+impl<P> SearchIndexHashMap<P, String>
+where
+    P: Eq + Hash + Clone + 'static,
+{
+    /// Insert a new entry in the index from a borrowed or owned value.
+    ///
+    /// The index is first looked up with the borrowed `&str`. If the value
+    /// already exists, the row is added to it and no `String` is allocated.
+    /// Only a value that is new to the index is stored, which calls
+    /// [Cow::into_owned]: this allocates for a [Cow::Borrowed] value and
+    /// moves the `String` out of a [Cow::Owned] value without copying it.
+    ///
+    /// Like [insert](Self::insert), this doesn't check the maximum number
+    /// of distinct values.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexHashMap;
+    /// use std::borrow::Cow;
+    ///
+    /// let mut index = SearchIndexHashMap::<usize, String>::new();
+    /// let line = "Berlin,Berlin,Madrid";
+    ///
+    /// // Only the first "Berlin" and "Madrid" are allocated.
+    /// for (id, city) in line.split(',').enumerate() {
+    ///     index.insert_cow(id, Cow::Borrowed(city));
+    /// }
+    /// ```
+    pub fn insert_cow(&mut self, primary_id: P, attribute_value: Cow<str>) {
+        match self.index.get_mut(attribute_value.as_ref()) {
+            Some(primary_ids) => {
+                primary_ids.insert(primary_id);
+            }
+            None => {
+                self.index
+                    .entry(attribute_value.into_owned())
+                    .or_default()
+                    .insert(primary_id);
+            }
+        }
+    }
 }
 
-impl<P: Clone, V: Eq + Hash + FromStr> SearchIndex<P> for SearchIndexHashMap<P, V> {
+impl<P, V> SearchIndexHashMap<P, V>
+where
+    P: Eq + Hash + Clone + 'static,
+    V: Eq + Hash + FromStr + ToString + 'static,
+{
+    /// This internal function finds all rows with a value whose string
+    /// representation has a length in the range. It checks every distinct value.
+    fn search_length(&self, min: usize, max: usize) -> HashSet<P> {
+        let mut result_set = HashSet::new();
+        for (value, primary_ids) in self.index.iter() {
+            if (min..=max).contains(&value.to_string().chars().count()) {
+                result_set.extend(primary_ids.iter().cloned());
+            }
+        }
+        result_set
+    }
+}
+
+impl<P, V> SearchIndex<P> for SearchIndexHashMap<P, V>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Eq + Hash + Clone + FromStr + ToString + Send + Sync + 'static,
+{
     fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
         match query {
             Query::Exact(_, value_str) => {
                 let value: V = string_to_payload_type(value_str)?;
@@ -90,12 +340,104 @@ impl<P: Clone, V: Eq + Hash + FromStr> SearchIndex<P> for SearchIndexHashMap<P,
                     .cloned()
                     .unwrap_or(HashSet::<P>::new()))
             }
+            Query::LengthInRange(_, min, max) => Ok(self.search_length(*min, *max)),
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                Ok(search_null(query, &self.null_ids, self.all_ids()))
+            }
             _ => Err(SearchEngineError::UnsupportedQuery),
         }
     }
 
     fn supported_queries(&self) -> SupportedQueries {
-        SUPPORTS_EXACT
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn count_query(&self, query: &Query) -> Result<usize> {
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: V = string_to_payload_type(value_str)?;
+                Ok(self.index.get(&value).map_or(0, HashSet::len))
+            }
+            _ => self.search(query).map(|result_set| result_set.len()),
+        }
+    }
+
+    fn compile<'a>(&'a self, query: &Query) -> Result<CompiledSearch<'a, P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: V = string_to_payload_type(value_str)?;
+                Ok(Box::new(move || {
+                    Ok(self.index.get(&value).cloned().unwrap_or_default())
+                }))
+            }
+            &Query::LengthInRange(_, min, max) => {
+                Ok(Box::new(move || Ok(self.search_length(min, max))))
+            }
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                let query = query.clone();
+                Ok(Box::new(move || {
+                    Ok(search_null(&query, &self.null_ids, self.all_ids()))
+                }))
+            }
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<V>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        TypeId::of::<V>() == TypeId::of::<String>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| value.to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.try_insert(primary_id, string_to_payload_type(value)?)
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<V>(value).map(|_| ())
+    }
+
+    fn contains_value(&self, value: &str) -> bool {
+        string_to_payload_type(value).is_ok_and(|value: V| self.index.contains_key(&value))
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        SearchIndexHashMap::remove_all(self, primary_id)
     }
 }
 
@@ -103,6 +445,63 @@ impl<P: Clone, V: Eq + Hash + FromStr> SearchIndex<P> for SearchIndexHashMap<P,
 mod tests {
     use super::*;
 
+    #[test]
+    fn search_index_null() {
+        let mut index = SearchIndexHashMap::<usize, String>::new();
+        index.insert(0, "A".into());
+        index.insert_null(1);
+        index.insert_null(2);
+        index.insert(2, "B".into());
+
+        let result = index.search(&Query::IsNull("<not used>".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+
+        let result = index.search(&Query::IsNotNull("<not used>".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+
+        // Row 3 was never added, so it is neither null nor not null.
+        let compiled = index.compile(&Query::IsNull("<not used>".into())).unwrap();
+        assert!(!compiled().unwrap().contains(&3));
+        let compiled = index
+            .compile(&Query::IsNotNull("<not used>".into()))
+            .unwrap();
+        assert!(!compiled().unwrap().contains(&3));
+    }
+
+    #[test]
+    fn search_index_length() {
+        let mut index = SearchIndexHashMap::<usize, String>::new();
+        index.insert(0, "12345".into());
+        index.insert(1, "1234".into());
+        index.insert(2, "123456".into());
+        index.insert(3, "54321".into());
+        index.insert(3, "123".into());
+        index.insert(4, "Ünïcödé".into());
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 5, 5));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 3])));
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 6, usize::MAX));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2, 4])));
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 7, 7));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4])));
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 5, 4));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+
+        let compiled = index
+            .compile(&Query::LengthInRange("<not used>".into(), 0, 4))
+            .unwrap();
+        assert_eq!(compiled(), Ok(HashSet::from_iter(vec![1, 3])));
+
+        let mut index = SearchIndexHashMap::<usize, u32>::new();
+        index.insert(0, 12345);
+        index.insert(1, 123);
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 0, 4));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    }
+
     #[test]
     fn search_index_exact_string() {
         let mut index = SearchIndexHashMap::<usize, String>::new();
@@ -126,6 +525,82 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![])));
     }
 
+    #[test]
+    fn search_index_insert_cow() {
+        let mut index = SearchIndexHashMap::<usize, String>::new();
+        let line = String::from("A,B,A");
+        for (id, value) in line.split(',').enumerate() {
+            index.insert_cow(id, Cow::Borrowed(value));
+        }
+        index.insert_cow(3, Cow::Owned("B".into()));
+        index.insert_cow(4, Cow::Owned("C".into()));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "A".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "B".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 3])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "C".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4])));
+    }
+
+    #[test]
+    fn search_index_max_distinct_values() {
+        let mut index = SearchIndexHashMap::<usize, String>::new_with_max_distinct_values(3);
+        for (id, value) in ["A", "B", "A", "C"].into_iter().enumerate() {
+            assert_eq!(index.try_insert(id, value.into()), Ok(()));
+        }
+
+        assert_eq!(
+            index.try_insert(4, "D".into()),
+            Err(SearchEngineError::CardinalityLimitExceeded { limit: 3 })
+        );
+        assert_eq!(
+            index.insert_str(5, "E"),
+            Err(SearchEngineError::CardinalityLimitExceeded { limit: 3 })
+        );
+        assert_eq!(index.try_insert(6, "B".into()), Ok(()));
+        assert_eq!(index.insert_str(7, "C"), Ok(()));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "D".into()));
+        assert_eq!(result, Ok(HashSet::new()));
+        let result = index.search(&Query::Exact("<not used>".into(), "B".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 6])));
+        assert_eq!(index.all_ids(), HashSet::from_iter(vec![0, 1, 2, 3, 6, 7]));
+    }
+
+    #[test]
+    fn search_index_remove() {
+        let mut index = SearchIndexHashMap::<usize, String>::new_with_max_distinct_values(2);
+        index.insert(0, "A".into());
+        index.insert(0, "B".into());
+        index.insert(1, "A".into());
+        index.insert_null(2);
+
+        assert!(index.remove(&0, &"B".into()));
+        assert!(!index.remove(&0, &"B".into()));
+        assert!(!index.remove(&1, &"C".into()));
+        assert_eq!(index.index.len(), 1);
+        // The dropped value makes room for a new distinct value.
+        assert_eq!(index.try_insert(3, "C".into()), Ok(()));
+
+        assert!(index.remove_all(&0));
+        assert!(index.remove_all(&2));
+        assert!(!index.remove_all(&4));
+        let result = index.search(&Query::Exact("<not used>".into(), "A".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+        assert_eq!(
+            index.search(&Query::IsNull("<not used>".into())),
+            Ok(HashSet::new())
+        );
+
+        assert!(index.remove_all(&1));
+        assert!(index.remove_all(&3));
+        assert!(index.index.is_empty());
+        assert!(index.all_ids().is_empty());
+    }
+
     #[test]
     fn search_index_exact_number() {
         let mut index = SearchIndexHashMap::<usize, i32>::new();
@@ -149,6 +624,22 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![])));
     }
 
+    #[test]
+    fn search_index_entries() {
+        let mut index = SearchIndexHashMap::<usize, i32>::new();
+        index.insert(0, 10);
+        index.insert(0, 20);
+        index.insert(1, 10);
+
+        assert_eq!(index.all_ids(), HashSet::from_iter(vec![0, 1]));
+        let mut entries = index.entries();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![(0, "10".into()), (0, "20".into()), (1, "10".into())]
+        );
+    }
+
     #[test]
     fn search_index_unsupported_queries() {
         let mut index = SearchIndexHashMap::<usize, i32>::new();
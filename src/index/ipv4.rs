@@ -0,0 +1,326 @@
+use super::{check_supported_query, string_to_payload_type, SearchIndex};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_INRANGE,
+    SUPPORTS_MAXIMUM, SUPPORTS_MINIMUM, SUPPORTS_PREFIX,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::Hash,
+    net::Ipv4Addr,
+    ops::RangeBounds,
+};
+
+/// SearchIndexIpv4 is a index backed by a BTreeMap that stores IPv4
+/// addresses by their numeric value.
+///
+/// Because addresses are compared as numbers and not as strings,
+/// `192.168.9.1` is smaller than `192.168.10.1`. Besides Exact and range
+/// queries, the index supports Prefix queries in the CIDR notation, so
+/// `10.0.0.0/8` matches all addresses from `10.0.0.0` to `10.255.255.255`.
+/// A Prefix query without a prefix length matches the address exactly.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexIpv4};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_ip = SearchIndexIpv4::<usize>::new();
+/// index_ip.insert(0, "192.168.9.1".parse().unwrap());
+/// index_ip.insert(1, "192.168.10.1".parse().unwrap());
+/// index_ip.insert(2, "10.0.0.1".parse().unwrap());
+///
+/// let result = index_ip.search(&Query::Prefix("<unused>".into(), "192.168.8.0/23".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+///
+/// let result = index_ip.search(&Query::Minimum("<unused>".into(), "192.168.9.0".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexIpv4<P> {
+    index: BTreeMap<u32, HashSet<P>>,
+}
+
+impl<P> Default for SearchIndexIpv4<P>
+where
+    P: Eq + Hash + Clone + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> SearchIndexIpv4<P>
+where
+    P: Eq + Hash + Clone + 'static,
+{
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries =
+        SUPPORTS_EXACT | SUPPORTS_PREFIX | SUPPORTS_INRANGE | SUPPORTS_MINIMUM | SUPPORTS_MAXIMUM;
+
+    /// Creates a new `SearchIndexIpv4`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexIpv4;
+    ///
+    /// let index = SearchIndexIpv4::<usize>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: BTreeMap::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexIpv4;
+    /// use std::net::Ipv4Addr;
+    ///
+    /// let mut index = SearchIndexIpv4::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an address:
+    /// index.insert(123, Ipv4Addr::new(10, 0, 0, 1));
+    /// // The same row / primary id can have multiple addresses assigned:
+    /// index.insert(123, Ipv4Addr::new(10, 0, 0, 2));
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, Ipv4Addr::new(192, 168, 0, 1));
+    /// ```
+    pub fn insert(&mut self, primary_id: P, address: Ipv4Addr) {
+        self.index
+            .entry(u32::from(address))
+            .or_default()
+            .insert(primary_id);
+    }
+
+    /// Find all rows with an address in a CIDR network like `10.0.0.0/8`.
+    ///
+    /// The host bits of the network address are ignored, so `10.1.2.3/8`
+    /// matches the same addresses as `10.0.0.0/8`. Returns
+    /// [MismatchedQueryType](SearchEngineError::MismatchedQueryType) if the
+    /// address or the prefix length can't be parsed or the prefix length
+    /// is larger than 32.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexIpv4;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexIpv4::<usize>::new();
+    /// index.insert(0, "10.0.0.1".parse().unwrap());
+    /// index.insert(1, "11.0.0.1".parse().unwrap());
+    ///
+    /// assert_eq!(index.search_cidr("10.0.0.0/8"), Ok(HashSet::from_iter(vec![0])));
+    /// ```
+    pub fn search_cidr(&self, cidr: &str) -> Result<HashSet<P>> {
+        let (address_str, prefix_len) = match cidr.split_once('/') {
+            Some((address_str, prefix_len_str)) => {
+                (address_str, string_to_payload_type::<u32>(prefix_len_str)?)
+            }
+            None => (cidr, 32),
+        };
+        if prefix_len > 32 {
+            return Err(SearchEngineError::mismatched_type::<u32>());
+        }
+        let address = u32::from(string_to_payload_type::<Ipv4Addr>(address_str)?);
+        let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+        let first = address & mask;
+        Ok(self.search_range(first..=first | !mask))
+    }
+
+    /// This internal function returns all rows with an address in a range.
+    fn search_range(&self, range: impl RangeBounds<u32>) -> HashSet<P> {
+        self.index
+            .range(range)
+            .flat_map(|(_, primary_ids)| primary_ids.iter().cloned())
+            .collect()
+    }
+}
+
+/// This internal function parses an address to its numeric value.
+fn parse_address(value: &str) -> Result<u32> {
+    string_to_payload_type::<Ipv4Addr>(value).map(u32::from)
+}
+
+impl<P> SearchIndex<P> for SearchIndexIpv4<P>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value_str) => {
+                let value = parse_address(value_str)?;
+                Ok(self.index.get(&value).cloned().unwrap_or_default())
+            }
+            Query::Prefix(_, cidr) => self.search_cidr(cidr),
+            Query::InRange(_, min_str, max_str) => {
+                let min = parse_address(min_str)?;
+                let max = parse_address(max_str)?;
+                if min > max {
+                    return Ok(HashSet::new());
+                }
+                Ok(self.search_range(min..=max))
+            }
+            Query::Minimum(_, min_str) => Ok(self.search_range(parse_address(min_str)?..)),
+            Query::Maximum(_, max_str) => Ok(self.search_range(..=parse_address(max_str)?)),
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<Ipv4Addr>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = Ipv4Addr::from(*value).to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.index
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| Ipv4Addr::from(*value).to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?);
+        Ok(())
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<Ipv4Addr>(value).map(|_| ())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = false;
+        self.index.retain(|_, primary_ids| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_index() -> SearchIndexIpv4<usize> {
+        let mut index = SearchIndexIpv4::<usize>::new();
+        let addresses = [
+            "192.168.9.1",
+            "192.168.10.1",
+            "192.168.10.20",
+            "10.0.0.1",
+            "10.255.255.255",
+            "11.0.0.0",
+            "0.0.0.0",
+        ];
+        for (id, address) in addresses.into_iter().enumerate() {
+            index.insert_str(id, address).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn search_index_ipv4_ranges() {
+        let index = create_index();
+
+        let result = index.search(&Query::Exact("<not used>".into(), "192.168.10.1".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "192.168.9.0".into(),
+            "192.168.10.10".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+        let result = index.search(&Query::Minimum("<not used>".into(), "192.168.10.0".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 2])));
+        let result = index.search(&Query::Maximum(
+            "<not used>".into(),
+            "10.255.255.255".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![3, 4, 6])));
+        let result = index.search(&Query::Exact("<not used>".into(), "192.168.10".into()));
+        assert_eq!(
+            result,
+            Err(SearchEngineError::mismatched_type::<Ipv4Addr>())
+        );
+    }
+
+    #[test]
+    fn search_index_ipv4_cidr() {
+        let index = create_index();
+        let prefix = |cidr: &str| index.search(&Query::Prefix("<not used>".into(), cidr.into()));
+
+        assert_eq!(prefix("10.0.0.0/8"), Ok(HashSet::from_iter(vec![3, 4])));
+        assert_eq!(prefix("10.1.2.3/8"), Ok(HashSet::from_iter(vec![3, 4])));
+        assert_eq!(
+            prefix("192.168.10.0/24"),
+            Ok(HashSet::from_iter(vec![1, 2]))
+        );
+        assert_eq!(
+            prefix("192.168.8.0/22"),
+            Ok(HashSet::from_iter(vec![0, 1, 2]))
+        );
+        assert_eq!(prefix("192.168.10.1/32"), Ok(HashSet::from_iter(vec![1])));
+        assert_eq!(prefix("192.168.10.20"), Ok(HashSet::from_iter(vec![2])));
+        assert_eq!(prefix("0.0.0.0/0"), Ok(HashSet::from_iter(0..7)));
+        assert_eq!(
+            prefix("10.0.0.0/33"),
+            Err(SearchEngineError::mismatched_type::<u32>())
+        );
+        assert_eq!(
+            prefix("10.0.0/8"),
+            Err(SearchEngineError::mismatched_type::<Ipv4Addr>())
+        );
+        assert_eq!(
+            prefix("10.0.0.0/x"),
+            Err(SearchEngineError::mismatched_type::<u32>())
+        );
+    }
+
+    #[test]
+    fn search_index_ipv4_remove_all() {
+        let mut index = create_index();
+        index.insert_str(1, "10.0.0.1").unwrap();
+        assert!(index.remove_all(&1));
+        assert!(!index.remove_all(&1));
+        assert_eq!(index.values_of(&3), vec!["10.0.0.1".to_string()]);
+        assert_eq!(
+            index.search(&Query::Prefix(
+                "<not used>".into(),
+                "192.168.10.0/24".into()
+            )),
+            Ok(HashSet::from_iter(vec![2]))
+        );
+    }
+}
@@ -1,17 +1,49 @@
 use crate::{Query, Result, SearchEngineError, SupportedQueries};
-use std::{collections::HashSet, str::FromStr};
+use std::{collections::HashSet, hash::Hash, ops::Bound, str::FromStr};
 
+mod adaptive;
+#[cfg(feature = "roaring")]
+mod bitmap;
 mod btree_range;
+mod bucketed;
+mod case_insensitive;
+mod f64_range;
+mod function;
 mod hashmap;
+mod ipv4;
+mod ngram;
 mod prefix;
+#[cfg(feature = "regex")]
+mod regex_scan;
+mod sorted_vec;
+mod text;
 
+pub use adaptive::*;
+#[cfg(feature = "roaring")]
+pub use bitmap::*;
 pub use btree_range::*;
+pub use bucketed::*;
+pub use case_insensitive::*;
+pub use f64_range::*;
+pub use function::*;
 pub use hashmap::*;
+pub use ipv4::*;
+pub use ngram::*;
 pub use prefix::*;
+#[cfg(feature = "regex")]
+pub use regex_scan::*;
+pub use sorted_vec::*;
+pub use text::*;
+
+/// A compiled search function that is returned by [SearchIndex::compile].
+pub type CompiledSearch<'a, P> = Box<dyn Fn() -> Result<HashSet<P>> + 'a>;
 
 /// This trait describes the minimum features an Index must support to be
 /// usable as a SearchIndex, for example in a [SearchEngine](crate::engine::SearchEngine).
-pub trait SearchIndex<P> {
+///
+/// Indices must be [Send] and [Sync], so an engine and its
+/// [snapshots](crate::engine_snapshot::EngineSnapshot) can be shared between threads.
+pub trait SearchIndex<P>: Send + Sync {
     /// Perform a search on an index.
     ///
     /// This function returns an HashSet of all matching results.
@@ -31,17 +63,292 @@ pub trait SearchIndex<P> {
     /// signals which operators (=,>,<,-) in the query parser are supported
     /// by an index.
     fn supported_queries(&self) -> SupportedQueries;
+
+    /// Returns the delimiter of [InRange](Query::InRange) values in query strings,
+    /// like `..` in `a..b`, or `None` to let the query parser choose it.
+    ///
+    /// This function is used by
+    /// [SearchEngine::query_from_str](crate::engine::SearchEngine::query_from_str)
+    /// for indices with values that commonly contain a `-`. Without a delimiter
+    /// of the index, the parser uses
+    /// [string_range_delimiter](crate::QueryParserOptions::string_range_delimiter)
+    /// for indices with String values and `-` otherwise. The default
+    /// implementation returns `None`.
+    fn range_delimiter(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns true if the values of this index are `String`s.
+    ///
+    /// The query parser uses
+    /// [string_range_delimiter](crate::QueryParserOptions::string_range_delimiter)
+    /// for these indices. The default implementation returns false.
+    fn has_string_values(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the index rejects [Prefix](Query::Prefix) queries
+    /// with an empty prefix.
+    ///
+    /// [SearchEngine::query_from_str](crate::engine::SearchEngine::query_from_str)
+    /// uses it to reject a selector without values, like `+name:`, with an
+    /// [EmptyPrefix](SearchEngineError::EmptyPrefix) error while parsing.
+    /// The default implementation returns `false`.
+    fn rejects_empty_prefix(&self) -> bool {
+        false
+    }
+
+    /// Returns the index as a [BitmapSearchIndex] if it can return its
+    /// results as roaring bitmaps.
+    ///
+    /// [SearchEngine::search_bitmap](crate::engine::SearchEngine::search_bitmap)
+    /// uses it to skip the conversion of the results to HashSets. Requires the
+    /// `roaring` feature. The default implementation returns `None`.
+    #[cfg(feature = "roaring")]
+    fn as_bitmap_index(&self) -> Option<&dyn BitmapSearchIndex> {
+        None
+    }
+
+    /// Returns a copy of the index behind a new box.
+    ///
+    /// This function lets a [SearchEngine](crate::engine::SearchEngine) copy an
+    /// index that is shared with an
+    /// [EngineSnapshot](crate::engine_snapshot::EngineSnapshot) before changing
+    /// it. Indices usually implement it as `Box::new(self.clone())`.
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>>;
+
+    /// Parse a single value of a selector in a query string into a [Query].
+    ///
+    /// This lets an index define its own value syntax, like `/24` for networks.
+    /// If it returns `Some`, the query is used by
+    /// [SearchEngine::query_from_str](crate::engine::SearchEngine::query_from_str)
+    /// instead of the generic operator symbols, so the index parse always wins.
+    /// If it returns `None`, the value is parsed with the generic operator symbols.
+    /// The default implementation returns `None` for all values.
+    fn parse_value(&self, attribute: &str, value: &str) -> Option<Query> {
+        let _ = (attribute, value);
+        None
+    }
+
+    /// Compile a query into a reusable search function.
+    ///
+    /// The returned function performs the same search as [search](Self::search),
+    /// but the [Query] is validated and its values are parsed only once. It returns
+    /// the same errors as [search](Self::search) if that fails. The function
+    /// itself returns a [Result] for indices that can only fail while searching.
+    ///
+    /// The default implementation validates the query by running it once and
+    /// calls [search](Self::search) every time the function is invoked, returning
+    /// its errors.
+    fn compile<'a>(&'a self, query: &Query) -> Result<CompiledSearch<'a, P>> {
+        self.search(query)?;
+        let query = query.clone();
+        Ok(Box::new(move || self.search(&query)))
+    }
+
+    /// Returns the name of the type of the values stored in this index.
+    ///
+    /// The name is only meant for diagnostics, for example to tell a user which
+    /// type the values of a query must have. The default implementation returns `"unknown"`.
+    fn value_type_name(&self) -> &'static str {
+        "unknown"
+    }
+
+    /// Returns all row ids / primary ids that have at least one value in this index.
+    ///
+    /// The default implementation returns an empty set. Indices that can't
+    /// enumerate their rows, like [SearchIndexFunction], keep this default.
+    fn all_ids(&self) -> HashSet<P> {
+        HashSet::new()
+    }
+
+    /// Returns all pairs of row ids / primary ids and values stored in this index.
+    /// The values are converted to strings and the order is unspecified.
+    ///
+    /// This function is used by a [SearchEngine](crate::engine::SearchEngine) to
+    /// look up the values of rows. The default implementation returns an empty vector.
+    /// Indices that implement it should also override [supports_entries](Self::supports_entries).
+    fn entries(&self) -> Vec<(P, String)> {
+        vec![]
+    }
+
+    /// Returns true if [entries](Self::entries) returns all values of this index.
+    ///
+    /// An index that returns false can't be rebuilt from its values, for example
+    /// by [SearchEngine::reindex_attribute](crate::engine::SearchEngine::reindex_attribute).
+    /// The default implementation returns false.
+    fn supports_entries(&self) -> bool {
+        false
+    }
+
+    /// Returns all values of a row id / primary id, converted to strings.
+    /// The order is unspecified.
+    ///
+    /// This function is used by a [SearchEngine](crate::engine::SearchEngine) to
+    /// look up the values of single rows. The default implementation filters
+    /// [entries](Self::entries), indices should override it with a faster lookup.
+    fn values_of(&self, primary_id: &P) -> Vec<String>
+    where
+        P: PartialEq,
+    {
+        self.entries()
+            .into_iter()
+            .filter(|(id, _)| id == primary_id)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Returns true if at least one row has exactly this value, given as a string.
+    ///
+    /// This function is used by the query parser option
+    /// [exact_if_present](crate::QueryParserOptions::exact_if_present). The
+    /// default implementation runs an [Exact](Query::Exact) query and returns
+    /// false if it fails, indices should override it with a lookup that
+    /// doesn't copy the matching rows.
+    fn contains_value(&self, value: &str) -> bool {
+        self.search(&Query::Exact(String::new(), value.to_owned()))
+            .is_ok_and(|result_set| !result_set.is_empty())
+    }
+
+    /// Returns the number of rows / primary ids that match a query.
+    ///
+    /// The result is the length of the result of [search](Self::search). This
+    /// function is used by [SearchEngine::count](crate::engine::SearchEngine::count).
+    /// The default implementation runs the search, indices should override it
+    /// for queries they can count without copying the matching rows.
+    fn count_query(&self, query: &Query) -> Result<usize> {
+        self.search(query).map(|result_set| result_set.len())
+    }
+
+    /// Insert a new entry in the index with a value that is given as a string.
+    ///
+    /// The value is parsed like the values of a [Query]. If that fails,
+    /// [MismatchedQueryType](crate::error::SearchEngineError::MismatchedQueryType)
+    /// is returned. This function is used by [SearchEngine::insert](crate::engine::SearchEngine::insert).
+    /// The default implementation returns
+    /// [UnsupportedOperation](crate::error::SearchEngineError::UnsupportedOperation).
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        let _ = (primary_id, value);
+        Err(SearchEngineError::UnsupportedOperation)
+    }
+
+    /// Checks that a value given as a string can be parsed into the value type
+    /// of the index, without changing the index or running a search.
+    ///
+    /// Returns the same
+    /// [MismatchedQueryType](crate::error::SearchEngineError::MismatchedQueryType)
+    /// error as [insert_str](Self::insert_str) for values that can't be parsed.
+    /// This function is used by
+    /// [SearchEngine::validate_data](crate::engine::SearchEngine::validate_data).
+    /// The default implementation accepts all values, which is correct for
+    /// indices with String values.
+    fn validate_value(&self, value: &str) -> Result<()> {
+        let _ = value;
+        Ok(())
+    }
+
+    /// Removes all values of a row id / primary id from this index, including
+    /// a record that the row has no value.
+    ///
+    /// Returns true if the index had anything stored for the row. This function
+    /// is used by [SearchEngine::remove_row](crate::engine::SearchEngine::remove_row).
+    /// The default implementation returns false, which is only correct for
+    /// indices that don't store rows, like [SearchIndexFunction].
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let _ = primary_id;
+        false
+    }
+}
+
+impl<P> Clone for Box<dyn SearchIndex<P>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Checks if a [Query] is part of the supported queries of an index.
+///
+/// This is an internal function that indices call before processing a query.
+/// It returns [UnsupportedQuery](crate::error::SearchEngineError::UnsupportedQuery)
+/// for all queries that are not listed, so the result of `search` can't drift
+/// apart from the result of `supported_queries`.
+fn check_supported_query(query: &Query, supported: SupportedQueries) -> Result<()> {
+    if query.supported_flag() & supported == 0 {
+        return Err(SearchEngineError::UnsupportedQuery);
+    }
+    Ok(())
+}
+
+/// Answers [IsNull](Query::IsNull) and [IsNotNull](Query::IsNotNull) queries.
+///
+/// This is an internal function. `null_ids` are the rows that were recorded
+/// without a value and `all_ids` are the rows with at least one value. A row
+/// that has a value is never null, even if it was recorded as null before.
+fn search_null<P: Eq + Hash + Clone>(
+    query: &Query,
+    null_ids: &HashSet<P>,
+    all_ids: HashSet<P>,
+) -> HashSet<P> {
+    match query {
+        Query::IsNotNull(_) => all_ids,
+        _ => null_ids
+            .iter()
+            .filter(|id| !all_ids.contains(id))
+            .cloned()
+            .collect(),
+    }
 }
 
 /// Tries to parse a string into a payload value.
 ///
 /// This is an internal function. If it fails it returns
 /// [MismatchedQueryType](crate::error::SearchEngineError::MismatchedQueryType)
-/// to signal failure.
+/// with the name of `T` and without an attribute to signal failure.
 fn string_to_payload_type<T: FromStr>(value: &str) -> Result<T> {
     value
         .parse()
-        .map_err(|_| SearchEngineError::MismatchedQueryType)
+        .map_err(|_| SearchEngineError::mismatched_type::<T>())
+}
+
+/// Parses the bounds of an [InRangeBounds](Query::InRangeBounds) query.
+///
+/// This is an internal function. It returns `None` if the range is empty,
+/// so indices don't pass invalid ranges to a BTreeMap, which would panic.
+fn parse_bounds<V: FromStr + Ord>(
+    start: &Bound<String>,
+    end: &Bound<String>,
+) -> Result<Option<(Bound<V>, Bound<V>)>> {
+    let (start, end) = (parse_bound(start)?, parse_bound(end)?);
+    let is_empty = match (&start, &end) {
+        (Bound::Included(min), Bound::Included(max)) => min > max,
+        (
+            Bound::Included(min) | Bound::Excluded(min),
+            Bound::Included(max) | Bound::Excluded(max),
+        ) => min >= max,
+        _ => false,
+    };
+    Ok((!is_empty).then_some((start, end)))
+}
+
+/// Parses the bounds of an [OutRangeBounds](Query::OutRangeBounds) query
+/// into the range below the first bound and the range above the second bound.
+///
+/// This is an internal function. The ranges may overlap, so rows can be in both.
+fn parse_out_bounds<V: FromStr>(
+    start: &Bound<String>,
+    end: &Bound<String>,
+) -> Result<[(Bound<V>, Bound<V>); 2]> {
+    let (start, end) = (parse_bound(start)?, parse_bound(end)?);
+    Ok([(Bound::Unbounded, start), (end, Bound::Unbounded)])
+}
+
+/// Parses a single bound of a range query.
+fn parse_bound<V: FromStr>(bound: &Bound<String>) -> Result<Bound<V>> {
+    Ok(match bound {
+        Bound::Included(value) => Bound::Included(string_to_payload_type(value)?),
+        Bound::Excluded(value) => Bound::Excluded(string_to_payload_type(value)?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
 }
 
 #[cfg(test)]
@@ -55,7 +362,178 @@ mod tests {
         assert_eq!(string_to_payload_type("true"), Ok(true));
         assert_eq!(
             string_to_payload_type::<usize>("Hello"),
-            Err(SearchEngineError::MismatchedQueryType)
+            Err(SearchEngineError::mismatched_type::<usize>())
+        );
+    }
+
+    /// Asserts that an index returns an UnsupportedQuery error exactly for
+    /// the leaf queries that are not part of its supported queries.
+    fn assert_supported_queries_consistent(index: &impl SearchIndex<usize>) {
+        let leaf_queries = [
+            Query::Exact("<not used>".into(), "0".into()),
+            Query::Prefix("<not used>".into(), "0".into()),
+            Query::Suffix("<not used>".into(), "0".into()),
+            Query::Contains("<not used>".into(), "0".into()),
+            Query::AllTokens("<not used>".into(), "0".into()),
+            Query::AnyTokens("<not used>".into(), "0".into()),
+            Query::Regex("<not used>".into(), "0".into()),
+            Query::Fuzzy("<not used>".into(), "0".into(), 1),
+            Query::Pattern("<not used>".into(), "0*".into()),
+            Query::InRange("<not used>".into(), "0".into(), "1".into()),
+            Query::InRangeBounds(
+                "<not used>".into(),
+                Bound::Included("0".into()),
+                Bound::Excluded("1".into()),
+            ),
+            Query::OutRange("<not used>".into(), "0".into(), "1".into()),
+            Query::OutRangeBounds(
+                "<not used>".into(),
+                Bound::Included("0".into()),
+                Bound::Excluded("1".into()),
+            ),
+            Query::Minimum("<not used>".into(), "0".into()),
+            Query::Maximum("<not used>".into(), "0".into()),
+            Query::LengthInRange("<not used>".into(), 0, 1),
+            Query::IsNull("<not used>".into()),
+            Query::IsNotNull("<not used>".into()),
+        ];
+        for query in leaf_queries.iter() {
+            let is_supported = query.supported_flag() & index.supported_queries() != 0;
+            let result = index.search(query);
+            assert_eq!(
+                result != Err(SearchEngineError::UnsupportedQuery),
+                is_supported,
+                "{query:?} returned {result:?}"
+            );
+        }
+        assert_eq!(
+            index.search(&Query::Not(Box::new(leaf_queries[0].clone()))),
+            Err(SearchEngineError::UnsupportedQuery)
         );
     }
+
+    /// Asserts that removing a row with [remove_all](SearchIndex::remove_all)
+    /// removes it from all results and keeps the other rows.
+    fn assert_remove_all_consistent(mut index: impl SearchIndex<usize>) {
+        for (id, value) in [(0, "10"), (0, "20"), (1, "10"), (2, "30")] {
+            index.insert_str(id, value).unwrap();
+        }
+        assert!(index.supports_entries());
+        assert_eq!(index.entries().len(), 4);
+        assert!(index.remove_all(&0));
+        assert!(!index.remove_all(&0));
+        assert!(!index.remove_all(&3));
+
+        assert_eq!(index.all_ids(), HashSet::from_iter(vec![1, 2]));
+        assert!(index.entries().iter().all(|(id, _)| *id != 0));
+        assert!(index.values_of(&0).is_empty());
+        let exact = |value: &str| index.search(&Query::Exact("<not used>".into(), value.into()));
+        assert_eq!(exact("10"), Ok(HashSet::from_iter(vec![1])));
+        assert_eq!(exact("20"), Ok(HashSet::new()));
+
+        assert!(index.remove_all(&1));
+        assert!(index.remove_all(&2));
+        assert!(index.all_ids().is_empty());
+        assert!(index.entries().is_empty());
+    }
+
+    #[test]
+    fn remove_all_consistent() {
+        assert_remove_all_consistent(SearchIndexHashMap::<usize, i32>::new());
+        assert_remove_all_consistent(SearchIndexPrefixTree::<usize>::new());
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.enable_suffix_search();
+        assert_remove_all_consistent(index);
+        assert_remove_all_consistent(SearchIndexBTreeRange::<usize, i32>::new());
+        assert_remove_all_consistent(SearchIndexCaseInsensitive::<usize>::new());
+        assert_remove_all_consistent(SearchIndexCaseInsensitivePrefixTree::<usize>::new());
+        assert_remove_all_consistent(SearchIndexAdaptive::<usize, i32>::new());
+        assert_remove_all_consistent(SearchIndexBucketed::<usize>::new(10));
+        assert_remove_all_consistent(SearchIndexF64Range::<usize>::new());
+        assert_remove_all_consistent(SearchIndexNGram::<usize>::new_with_n(1));
+        assert_remove_all_consistent(SearchIndexBytePrefixTree::<usize>::new());
+        assert_remove_all_consistent(SearchIndexText::<usize>::new());
+        let mut index = SearchIndexSortedVec::<usize, i32>::new();
+        index.build();
+        assert_remove_all_consistent(index);
+        #[cfg(feature = "regex")]
+        assert_remove_all_consistent(SearchIndexRegexScan::<usize>::new());
+    }
+
+    #[test]
+    fn value_type_names() {
+        let index = SearchIndexBTreeRange::<usize, u8>::new();
+        assert!(index.value_type_name().contains("u8"));
+        let index = SearchIndexHashMap::<usize, i32>::new();
+        assert!(index.value_type_name().contains("i32"));
+        let index = SearchIndexPrefixTree::<usize>::new();
+        assert!(index.value_type_name().contains("String"));
+        let index = SearchIndexIpv4::<usize>::new();
+        assert!(index.value_type_name().contains("Ipv4Addr"));
+        let index = SearchIndexF64Range::<usize>::new();
+        assert_eq!(index.value_type_name(), "f64");
+        let index = SearchIndexBytePrefixTree::<usize>::new();
+        assert!(index.value_type_name().contains("Vec<u8>"));
+        let index = SearchIndexSortedVec::<usize, u16>::new();
+        assert!(index.value_type_name().contains("u16"));
+        let index = SearchIndexFunction::<usize>::new(0, |_| HashSet::new());
+        assert_eq!(index.value_type_name(), "unknown");
+        assert!(!index.has_string_values());
+
+        assert!(SearchIndexBTreeRange::<usize, String>::new().has_string_values());
+        assert!(!SearchIndexBTreeRange::<usize, u8>::new().has_string_values());
+        assert!(SearchIndexPrefixTree::<usize>::new().has_string_values());
+    }
+
+    #[test]
+    fn validate_value_matches_insert_str() {
+        let indices: Vec<Box<dyn SearchIndex<usize>>> = vec![
+            Box::new(SearchIndexHashMap::<usize, i32>::new()),
+            Box::new(SearchIndexBTreeRange::<usize, u8>::new()),
+            Box::new(SearchIndexSortedVec::<usize, u16>::new()),
+            Box::new(SearchIndexAdaptive::<usize, i64>::new()),
+            Box::new(SearchIndexF64Range::<usize>::new()),
+            Box::new(SearchIndexIpv4::<usize>::new()),
+            Box::new(SearchIndexBucketed::<usize>::new(10)),
+            Box::new(SearchIndexPrefixTree::<usize>::new()),
+        ];
+        for index in indices {
+            for value in ["27", "-1", "300", "1.5", "NaN", "10.0.0.1", "abc", ""] {
+                let is_valid = index.validate_value(value).is_ok();
+                let mut copy = index.clone();
+                assert_eq!(
+                    copy.insert_str(0, value).is_ok(),
+                    is_valid,
+                    "{} {value:?}",
+                    index.value_type_name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn supported_queries_consistent() {
+        assert_supported_queries_consistent(&SearchIndexHashMap::<usize, i32>::new());
+        assert_supported_queries_consistent(&SearchIndexPrefixTree::<usize>::new());
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.enable_suffix_search();
+        assert_supported_queries_consistent(&index);
+        assert_supported_queries_consistent(&SearchIndexBTreeRange::<usize, i32>::new());
+        assert_supported_queries_consistent(&SearchIndexCaseInsensitive::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexCaseInsensitivePrefixTree::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexAdaptive::<usize, i32>::new());
+        assert_supported_queries_consistent(&SearchIndexBucketed::<usize>::new(10));
+        assert_supported_queries_consistent(&SearchIndexIpv4::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexF64Range::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexSortedVec::<usize, i32>::new());
+        assert_supported_queries_consistent(&SearchIndexBytePrefixTree::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexText::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexNGram::<usize>::new());
+        #[cfg(feature = "regex")]
+        assert_supported_queries_consistent(&SearchIndexRegexScan::<usize>::new());
+        assert_supported_queries_consistent(&SearchIndexFunction::<usize>::new(
+            crate::SUPPORTS_PREFIX | crate::SUPPORTS_MAXIMUM,
+            |_| HashSet::new(),
+        ));
+    }
 }
@@ -0,0 +1,323 @@
+use super::{check_supported_query, SearchIndex};
+use crate::{Query, Result, SupportedQueries, SUPPORTS_CONTAINS, SUPPORTS_EXACT};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// SearchIndexNGram is a index that splits every value into character n-grams
+/// to find rows whose value contains a substring.
+///
+/// Each n-gram of an inserted value is mapped to the primary IDs of the rows
+/// that have the value. A [Contains](Query::Contains) query intersects the rows
+/// of all n-grams of the needle and then checks the stored values of the
+/// remaining candidates, so rows that only share the n-grams but don't contain
+/// the needle are not returned. Needles shorter than `n` have no n-grams and
+/// are matched with a linear scan over all stored values.
+///
+/// This index trades memory for speed: besides the values themselves it stores
+/// the primary IDs once for every n-gram of a value. A value with `k` characters
+/// has up to `k - n + 1` n-grams, so long values need a lot more memory than in
+/// a [SearchIndexHashMap](crate::SearchIndexHashMap). A larger `n` produces
+/// fewer matching candidates but makes more needles fall back to the linear scan.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexNGram};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_role = SearchIndexNGram::<usize>::new();
+/// index_role.insert(0, "superadmin".into());
+/// index_role.insert(1, "admins".into());
+/// index_role.insert(2, "moderator".into());
+///
+/// let result = index_role.search(&Query::Contains("<unused>".into(), "admin".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexNGram<P> {
+    n: usize,
+    index: HashMap<String, HashSet<P>>,
+    ngrams: HashMap<String, HashSet<P>>,
+    values: HashMap<P, HashSet<String>>,
+}
+
+impl<P: Eq + Hash + Clone> Default for SearchIndexNGram<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> SearchIndexNGram<P> {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT | SUPPORTS_CONTAINS;
+
+    /// Creates a new `SearchIndexNGram` that uses trigrams.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexNGram;
+    ///
+    /// let index = SearchIndexNGram::<usize>::new();
+    /// assert_eq!(index.n(), 3);
+    /// ```
+    pub fn new() -> Self {
+        Self::new_with_n(3)
+    }
+
+    /// Creates a new `SearchIndexNGram` that splits values into n-grams
+    /// of `n` characters.
+    ///
+    /// # Panics
+    /// Panics if `n` is zero.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexNGram;
+    ///
+    /// let index = SearchIndexNGram::<usize>::new_with_n(2);
+    /// assert_eq!(index.n(), 2);
+    /// ```
+    pub fn new_with_n(n: usize) -> Self {
+        assert!(n > 0, "the n-gram length must not be zero");
+        Self {
+            n,
+            index: HashMap::new(),
+            ngrams: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of characters of each n-gram.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexNGram;
+    ///
+    /// let mut index = SearchIndexNGram::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, "Hello".into());
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, "World".into());
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "Hello".into());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: String) {
+        for ngram in ngrams(&attribute_value, self.n) {
+            self.ngrams
+                .entry(ngram.to_owned())
+                .or_default()
+                .insert(primary_id.clone());
+        }
+        self.values
+            .entry(primary_id.clone())
+            .or_default()
+            .insert(attribute_value.clone());
+        self.index
+            .entry(attribute_value)
+            .or_default()
+            .insert(primary_id);
+    }
+
+    /// Returns all rows that have a value which contains the needle.
+    fn search_contains(&self, needle: &str) -> HashSet<P> {
+        let needle_ngrams: HashSet<&str> = ngrams(needle, self.n).collect();
+        if needle_ngrams.is_empty() {
+            return self
+                .index
+                .iter()
+                .filter(|(value, _)| value.contains(needle))
+                .flat_map(|(_, primary_ids)| primary_ids.iter().cloned())
+                .collect();
+        }
+
+        let mut candidate_sets = Vec::with_capacity(needle_ngrams.len());
+        for ngram in needle_ngrams {
+            match self.ngrams.get(ngram) {
+                Some(primary_ids) => candidate_sets.push(primary_ids),
+                None => return HashSet::new(),
+            }
+        }
+        candidate_sets.sort_unstable_by_key(|primary_ids| primary_ids.len());
+        let (smallest, others) = candidate_sets
+            .split_first()
+            .expect("a needle with n-grams has at least one candidate set");
+
+        smallest
+            .iter()
+            .filter(|id| others.iter().all(|primary_ids| primary_ids.contains(id)))
+            .filter(|id| {
+                self.values
+                    .get(id)
+                    .is_some_and(|values| values.iter().any(|value| value.contains(needle)))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P> for SearchIndexNGram<P> {
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value) => Ok(self.index.get(value).cloned().unwrap_or_default()),
+            Query::Contains(_, needle) => Ok(self.search_contains(needle)),
+            _ => unreachable!("unsupported queries are rejected before"),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<String>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        true
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.values.keys().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.values
+            .iter()
+            .flat_map(|(id, values)| values.iter().map(|value| (id.clone(), value.clone())))
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.values
+            .get(primary_id)
+            .map(|values| values.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, value.to_owned());
+        Ok(())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let Some(values) = self.values.remove(primary_id) else {
+            return false;
+        };
+        for value in values {
+            for ngram in ngrams(&value, self.n) {
+                remove_from_set(&mut self.ngrams, ngram, primary_id);
+            }
+            remove_from_set(&mut self.index, &value, primary_id);
+        }
+        true
+    }
+}
+
+/// Removes a row from the set of a key and drops the key if its set is empty.
+fn remove_from_set<P: Eq + Hash>(map: &mut HashMap<String, HashSet<P>>, key: &str, primary_id: &P) {
+    if let Some(primary_ids) = map.get_mut(key) {
+        primary_ids.remove(primary_id);
+        if primary_ids.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+/// Returns all n-grams of a value with `n` characters each.
+/// Values with less than `n` characters have no n-grams.
+fn ngrams(value: &str, n: usize) -> impl Iterator<Item = &str> {
+    let boundaries: Vec<usize> = value
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .chain(std::iter::once(value.len()))
+        .collect();
+    let count = boundaries.len().saturating_sub(n);
+    (0..count).map(move |i| &value[boundaries[i]..boundaries[i + n]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ngrams_by_characters() {
+        assert_eq!(
+            ngrams("admin", 3).collect::<Vec<_>>(),
+            ["adm", "dmi", "min"]
+        );
+        assert_eq!(ngrams("äöü", 2).collect::<Vec<_>>(), ["äö", "öü"]);
+        assert_eq!(ngrams("ab", 3).count(), 0);
+        assert_eq!(ngrams("", 1).count(), 0);
+    }
+
+    #[test]
+    fn search_index_contains() {
+        let mut index = SearchIndexNGram::<usize>::new();
+        index.insert(0, "superadmin".into());
+        index.insert(1, "admins".into());
+        index.insert(2, "moderator".into());
+        index.insert(3, "adm-in".into());
+        index.insert(4, "nimda".into());
+        index.insert(4, "Admin".into());
+
+        let contains = |needle: &str| {
+            index
+                .search(&Query::Contains("<not used>".into(), needle.into()))
+                .unwrap()
+        };
+        assert_eq!(contains("admin"), HashSet::from_iter(vec![0, 1]));
+        assert_eq!(contains("erat"), HashSet::from_iter(vec![2]));
+        assert_eq!(contains("xyz"), HashSet::new());
+        assert_eq!(contains("adminadmin"), HashSet::new());
+
+        // Needles shorter than n are matched by a linear scan.
+        assert_eq!(contains("in"), HashSet::from_iter(vec![0, 1, 3, 4]));
+        assert_eq!(contains("-"), HashSet::from_iter(vec![3]));
+        assert_eq!(contains(""), HashSet::from_iter(vec![0, 1, 2, 3, 4]));
+
+        assert_eq!(
+            index.search(&Query::Exact("<not used>".into(), "admins".into())),
+            Ok(HashSet::from_iter(vec![1]))
+        );
+    }
+
+    #[test]
+    fn search_index_contains_n() {
+        let mut index = SearchIndexNGram::<usize>::new_with_n(1);
+        index.insert(0, "abc".into());
+        index.insert(1, "cba".into());
+        let result = index.search(&Query::Contains("<not used>".into(), "bc".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+
+        let mut index = SearchIndexNGram::<usize>::new_with_n(5);
+        index.insert(0, "abc".into());
+        index.insert(1, "abcdef".into());
+        let result = index.search(&Query::Contains("<not used>".into(), "bcdef".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+        let result = index.search(&Query::Contains("<not used>".into(), "bc".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_with_n_zero() {
+        SearchIndexNGram::<usize>::new_with_n(0);
+    }
+}
@@ -0,0 +1,237 @@
+use super::tree::ByteStringPrefixTree;
+use crate::index::{check_supported_query, search_null, SearchIndex};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_NULL,
+    SUPPORTS_PREFIX,
+};
+use std::{collections::HashSet, hash::Hash};
+
+/// SearchIndexBytePrefixTree is a index backed by a prefix tree of bytes that
+/// can match Exact, Prefix, IsNull and IsNotNull queries on byte string values.
+///
+/// Unlike [SearchIndexPrefixTree](crate::SearchIndexPrefixTree), the values
+/// don't have to be valid UTF-8, so it can store raw identifiers or binary
+/// keys. All queries are byte-exact: a query value matches by its UTF-8 bytes
+/// and a prefix may end in the middle of a multi-byte char, so the prefix
+/// `"\u{e9}"` (`0xC3 0xA9`) matches `"\u{e9}t\u{e9}"`, but the bytes `0xC3`
+/// alone match `"\u{e9}t\u{e9}"` and `"\u{e3}o"` too. No normalization or case
+/// folding is applied.
+///
+/// Values that are not valid UTF-8 can't be written in a [Query], use
+/// [search_exact_bytes](Self::search_exact_bytes) and
+/// [search_prefix_bytes](Self::search_prefix_bytes) for them. Invalid UTF-8
+/// sequences are replaced by `U+FFFD` in the strings returned by
+/// [entries](SearchIndex::entries) and [values_of](SearchIndex::values_of).
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexBytePrefixTree};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_key = SearchIndexBytePrefixTree::<usize>::new();
+/// index_key.insert(0, b"user:1");
+/// index_key.insert(1, b"user:2".to_vec());
+/// index_key.insert(2, [0xFF, 0x00, 0x01]);
+///
+/// let result = index_key.search(&Query::Prefix("<unused>".into(), "user:".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+///
+/// let result = index_key.search_prefix_bytes(&[0xFF]);
+/// assert_eq!(result, HashSet::from_iter(vec![2]));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexBytePrefixTree<P> {
+    index: ByteStringPrefixTree<P>,
+    null_ids: HashSet<P>,
+}
+
+impl<P: Eq + Hash + Clone> Default for SearchIndexBytePrefixTree<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> SearchIndexBytePrefixTree<P> {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries =
+        SUPPORTS_EXACT | SUPPORTS_PREFIX | SUPPORTS_NULL;
+
+    /// Creates a new `SearchIndexBytePrefixTree`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBytePrefixTree;
+    ///
+    /// let index = SearchIndexBytePrefixTree::<usize>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: ByteStringPrefixTree::new(),
+            null_ids: HashSet::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// The value can be anything that can be viewed as bytes, like a
+    /// `&[u8]`, a `Vec<u8>` or a byte string literal.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexBytePrefixTree;
+    ///
+    /// let mut index = SearchIndexBytePrefixTree::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, b"Hello");
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, vec![0xDE, 0xAD]);
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "Rust".as_bytes());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: impl AsRef<[u8]>) {
+        self.index.insert(attribute_value.as_ref(), primary_id);
+    }
+
+    /// Remove a value from a row / primary id.
+    ///
+    /// Returns true if the row had the value.
+    pub fn remove(&mut self, primary_id: &P, attribute_value: impl AsRef<[u8]>) -> bool {
+        self.index.remove(attribute_value.as_ref(), primary_id)
+    }
+
+    /// Record that a row / primary id has no value for this attribute.
+    ///
+    /// The row will be matched by [IsNull](Query::IsNull) queries until a value
+    /// is inserted for it.
+    pub fn insert_null(&mut self, primary_id: P) {
+        self.null_ids.insert(primary_id);
+    }
+
+    /// Find all rows with a value that is exactly equal to `value`.
+    pub fn search_exact_bytes(&self, value: &[u8]) -> HashSet<P> {
+        self.index.get_ref(value).cloned().unwrap_or_default()
+    }
+
+    /// Find all rows with a value that starts with the bytes of `prefix`.
+    pub fn search_prefix_bytes(&self, prefix: &[u8]) -> HashSet<P> {
+        self.index.get_prefix(prefix).unwrap_or_default()
+    }
+
+    /// Count the values that start with the bytes of `prefix`.
+    ///
+    /// Every row is counted once for every matching value it has.
+    pub fn count_prefix(&self, prefix: &[u8]) -> usize {
+        self.index.count_prefix(prefix)
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P> for SearchIndexBytePrefixTree<P> {
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value) => Ok(self.search_exact_bytes(value.as_bytes())),
+            Query::Prefix(_, value) => Ok(self.search_prefix_bytes(value.as_bytes())),
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                Ok(search_null(query, &self.null_ids, self.all_ids()))
+            }
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<Vec<u8>>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .entries()
+            .into_iter()
+            .flat_map(|(key, primary_ids)| {
+                let key = String::from_utf8_lossy(&key).into_owned();
+                primary_ids.iter().map(move |id| (id.clone(), key.clone()))
+            })
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, value);
+        Ok(())
+    }
+
+    fn contains_value(&self, value: &str) -> bool {
+        self.index
+            .get_ref(value.as_bytes())
+            .is_some_and(|primary_ids| !primary_ids.is_empty())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let removed_null = self.null_ids.remove(primary_id);
+        self.index.remove_all(primary_id) || removed_null
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index_byte_prefix_multi_byte() {
+        let mut index = SearchIndexBytePrefixTree::<usize>::new();
+        // "é" is 0xC3 0xA9, "ã" is 0xC3 0xA3 and "€" is 0xE2 0x82 0xAC.
+        index.insert(0, "été");
+        index.insert(1, "ão");
+        index.insert(2, "é");
+        index.insert(3, "€uro");
+        index.insert(4, [0xE2, 0x82]);
+        index.insert(5, [0xFF, 0xFE]);
+
+        let prefix = |value: &str| index.search(&Query::Prefix("<not used>".into(), value.into()));
+        assert_eq!(prefix("é"), Ok(HashSet::from_iter(vec![0, 2])));
+        assert_eq!(prefix("ét"), Ok(HashSet::from_iter(vec![0])));
+        assert_eq!(prefix("€"), Ok(HashSet::from_iter(vec![3])));
+        assert_eq!(prefix("e"), Ok(HashSet::new()));
+        assert_eq!(prefix(""), Ok(HashSet::from_iter(0..6)));
+
+        // Prefixes can end in the middle of a char.
+        assert_eq!(
+            index.search_prefix_bytes(&[0xC3]),
+            HashSet::from_iter(vec![0, 1, 2])
+        );
+        assert_eq!(
+            index.search_prefix_bytes(&[0xE2, 0x82]),
+            HashSet::from_iter(vec![3, 4])
+        );
+        assert_eq!(
+            index.search_exact_bytes(&[0xE2, 0x82]),
+            HashSet::from_iter(vec![4])
+        );
+        assert_eq!(index.count_prefix(&[0xC3]), 3);
+
+        let exact = |value: &str| index.search(&Query::Exact("<not used>".into(), value.into()));
+        assert_eq!(exact("é"), Ok(HashSet::from_iter(vec![2])));
+        // Queries are not normalized, so a decomposed "é" doesn't match.
+        assert_eq!(exact("e\u{301}"), Ok(HashSet::new()));
+
+        assert_eq!(index.values_of(&5), vec!["\u{FFFD}\u{FFFD}".to_string()]);
+        assert!(index.remove(&5, [0xFF, 0xFE]));
+        assert!(index.search_exact_bytes(&[0xFF, 0xFE]).is_empty());
+    }
+}
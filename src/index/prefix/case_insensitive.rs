@@ -0,0 +1,266 @@
+use super::tree::HashSetPrefixTree;
+use crate::index::{check_supported_query, fold_case, SearchIndex};
+use crate::{Query, Result, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_PREFIX};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// SearchIndexCaseInsensitivePrefixTree is a index backed by a prefix tree that
+/// can match Exact and Prefix queries while ignoring the case of the values.
+///
+/// The prefix tree stores the values after applying [fold_case] to them, and
+/// query values are folded the same way, so `Ali` matches `ALICE` and `stras`
+/// matches `Straße`. Unlike a [SearchIndexPrefixTree](crate::SearchIndexPrefixTree)
+/// with a normalizer, the original casing of all inserted values is preserved
+/// next to the tree, so they can still be displayed, for example with
+/// [distinct_values](Self::distinct_values).
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexCaseInsensitivePrefixTree};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_name = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+/// index_name.insert(0, "Alice".into());
+/// index_name.insert(1, "ALEX".into());
+/// index_name.insert(2, "Bob".into());
+///
+/// let result = index_name.search(&Query::Prefix("<unused>".into(), "al".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+///
+/// let result = index_name.search(&Query::Exact("<unused>".into(), "alice".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexCaseInsensitivePrefixTree<P> {
+    /// Rows by their folded values.
+    index: HashSetPrefixTree<P>,
+
+    /// All distinct original values with the rows that have them,
+    /// grouped by their folded value.
+    originals: HashMap<String, HashMap<String, HashSet<P>>>,
+}
+
+impl<P: Eq + Hash + Clone> Default for SearchIndexCaseInsensitivePrefixTree<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> SearchIndexCaseInsensitivePrefixTree<P> {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT | SUPPORTS_PREFIX;
+
+    /// Creates a new `SearchIndexCaseInsensitivePrefixTree`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitivePrefixTree;
+    ///
+    /// let index = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: HashSetPrefixTree::new(),
+            originals: HashMap::new(),
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitivePrefixTree;
+    ///
+    /// let mut index = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, "Hello".into());
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, "World".into());
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "hello".into());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: String) {
+        self.insert_cow(primary_id, Cow::Owned(attribute_value));
+    }
+
+    /// Insert a new entry in the index from a borrowed or owned value.
+    ///
+    /// Like in [SearchIndexCaseInsensitive::insert_cow](crate::SearchIndexCaseInsensitive::insert_cow),
+    /// the original value is only allocated if this exact casing is new to the index.
+    pub fn insert_cow(&mut self, primary_id: P, attribute_value: Cow<str>) {
+        let folded = fold_case(&attribute_value);
+        self.index.insert(&folded, primary_id.clone());
+        let originals = self.originals.entry(folded).or_default();
+        match originals.get_mut(attribute_value.as_ref()) {
+            Some(primary_ids) => {
+                primary_ids.insert(primary_id);
+            }
+            None => {
+                originals.insert(attribute_value.into_owned(), HashSet::from([primary_id]));
+            }
+        }
+    }
+
+    /// Returns all distinct values in their original casing.
+    ///
+    /// Values that only differ in their casing are all returned. The order
+    /// of the values is unspecified.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexCaseInsensitivePrefixTree;
+    ///
+    /// let mut index = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+    /// index.insert(0, "Alice".into());
+    /// index.insert(1, "alice".into());
+    /// index.insert(2, "Alice".into());
+    ///
+    /// let mut values = index.distinct_values();
+    /// values.sort();
+    /// assert_eq!(values, vec!["Alice", "alice"]);
+    /// ```
+    pub fn distinct_values(&self) -> Vec<&str> {
+        self.originals
+            .values()
+            .flat_map(|originals| originals.keys().map(String::as_str))
+            .collect()
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P>
+    for SearchIndexCaseInsensitivePrefixTree<P>
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value) => Ok(self.index.get(&fold_case(value)).unwrap_or_default()),
+            Query::Prefix(_, value) => {
+                Ok(self.index.get_prefix(&fold_case(value)).unwrap_or_default())
+            }
+            _ => unreachable!("unsupported queries are rejected before"),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<String>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        true
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    /// Returns all pairs of row ids and values, every value in the original
+    /// casing it was inserted with for that row.
+    fn entries(&self) -> Vec<(P, String)> {
+        self.originals
+            .values()
+            .flat_map(|originals| originals.iter())
+            .flat_map(|(value, primary_ids)| {
+                primary_ids.iter().map(|id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert_cow(primary_id, Cow::Borrowed(value));
+        Ok(())
+    }
+
+    fn contains_value(&self, value: &str) -> bool {
+        self.originals.contains_key(&fold_case(value))
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let removed = self.index.remove_all(primary_id);
+        if removed {
+            self.originals.retain(|_, originals| {
+                originals.retain(|_, primary_ids| {
+                    primary_ids.remove(primary_id);
+                    !primary_ids.is_empty()
+                });
+                !originals.is_empty()
+            });
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distinct_values_keep_original_case() {
+        let mut index = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+        index.insert(0, "Alice".into());
+
+        let result = index.search(&Query::Exact("<not used>".into(), "alice".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+        let result = index.search(&Query::Prefix("<not used>".into(), "ali".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+        assert_eq!(index.distinct_values(), vec!["Alice"]);
+    }
+
+    #[test]
+    fn search_keeps_original_case() {
+        let mut index = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "ALEX".into());
+        index.insert(2, "Straße".into());
+
+        let exact = |value: &str| index.search(&Query::Exact("<not used>".into(), value.into()));
+        assert_eq!(exact("alice"), Ok(HashSet::from_iter(vec![0])));
+        assert_eq!(exact("STRASSE"), Ok(HashSet::from_iter(vec![2])));
+        assert_eq!(exact("ali"), Ok(HashSet::new()));
+
+        let prefix = |value: &str| index.search(&Query::Prefix("<not used>".into(), value.into()));
+        assert_eq!(prefix("AL"), Ok(HashSet::from_iter(vec![0, 1])));
+        assert_eq!(prefix("alice"), Ok(HashSet::from_iter(vec![0])));
+        assert_eq!(prefix("stras"), Ok(HashSet::from_iter(vec![2])));
+        assert_eq!(prefix(""), Ok(HashSet::from_iter(vec![0, 1, 2])));
+
+        let mut values = index.distinct_values();
+        values.sort();
+        assert_eq!(values, vec!["ALEX", "Alice", "Straße"]);
+        assert_eq!(index.values_of(&0), vec!["Alice".to_string()]);
+        assert!(index.contains_value("alice"));
+    }
+
+    #[test]
+    fn remove_all_drops_unused_originals() {
+        let mut index = SearchIndexCaseInsensitivePrefixTree::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "alice".into());
+
+        assert!(index.remove_all(&1));
+        assert_eq!(index.distinct_values(), vec!["Alice"]);
+        let result = index.search(&Query::Prefix("<not used>".into(), "a".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+
+        assert!(index.remove_all(&0));
+        assert!(index.distinct_values().is_empty());
+        assert!(!index.contains_value("alice"));
+        assert!(!index.remove_all(&0));
+    }
+}
@@ -1,12 +1,38 @@
+mod bytes;
+mod case_insensitive;
 mod tree;
 
-use super::SearchIndex;
-use crate::{Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_PREFIX};
-use std::{collections::HashSet, hash::Hash};
-use tree::HashSetPrefixTree;
+pub use bytes::SearchIndexBytePrefixTree;
+pub use case_insensitive::SearchIndexCaseInsensitivePrefixTree;
+
+use super::{check_supported_query, search_null, SearchIndex};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_FUZZY,
+    SUPPORTS_LENGTH, SUPPORTS_NULL, SUPPORTS_PATTERN, SUPPORTS_PREFIX, SUPPORTS_SUFFIX,
+};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    hash::Hash,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+use tree::{invalid_data, read_set, read_u32, write_set, write_u32, HashSetPrefixTree};
+
+/// Magic bytes at the start of a [SearchIndexPrefixTree::dump].
+const DUMP_MAGIC: &[u8; 4] = b"ASPT";
+
+/// Version of the binary layout written by [SearchIndexPrefixTree::dump].
+const DUMP_VERSION: u32 = 2;
 
 /// SearchIndexPrefixTree is a index backed by a prefix tree that can match
-/// Exact and Prefix queries. It can only store String attribute values.
+/// Exact, Prefix, Pattern, LengthInRange, IsNull and IsNotNull queries.
+/// It can only store String attribute values.
+///
+/// LengthInRange queries have to visit all nodes of the tree up to the maximum length.
+///
+/// Suffix queries are only supported after [enable_suffix_search](Self::enable_suffix_search)
+/// was called, because they need a second tree with all values reversed.
 ///
 /// # Example
 /// ```
@@ -26,8 +52,13 @@ use tree::HashSetPrefixTree;
 /// let result = index_firstname.search(&Query::Prefix("<unused>".into(), "Alex".into()));
 /// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
 /// ```
+#[derive(Clone)]
 pub struct SearchIndexPrefixTree<P> {
     index: HashSetPrefixTree<P>,
+    reversed: Option<HashSetPrefixTree<P>>,
+    null_ids: HashSet<P>,
+    delimiter: Option<char>,
+    reject_empty_prefix: bool,
 }
 
 impl<P: Eq + Hash + Clone> Default for SearchIndexPrefixTree<P> {
@@ -37,6 +68,14 @@ impl<P: Eq + Hash + Clone> Default for SearchIndexPrefixTree<P> {
 }
 
 impl<P: Eq + Hash + Clone> SearchIndexPrefixTree<P> {
+    /// All queries that are supported by this index without suffix search.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT
+        | SUPPORTS_PREFIX
+        | SUPPORTS_PATTERN
+        | SUPPORTS_FUZZY
+        | SUPPORTS_LENGTH
+        | SUPPORTS_NULL;
+
     /// Creates a new `SearchIndexPrefixTree`.
     ///
     /// # Example
@@ -48,6 +87,104 @@ impl<P: Eq + Hash + Clone> SearchIndexPrefixTree<P> {
     pub fn new() -> Self {
         Self {
             index: HashSetPrefixTree::new(),
+            reversed: None,
+            null_ids: HashSet::new(),
+            delimiter: None,
+            reject_empty_prefix: false,
+        }
+    }
+
+    /// Enable [Suffix](Query::Suffix) queries on this index.
+    ///
+    /// A second tree stores every value reversed, so a suffix query becomes a
+    /// prefix query on the reversed tree. This roughly doubles the memory used
+    /// by the values and the time to insert them. All values that are already
+    /// in the index are added to the reversed tree. Suffix queries don't respect
+    /// the delimiter. Calling this function again has no effect.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexPrefixTree, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.enable_suffix_search();
+    /// index.insert(0, "alice@example.com".into());
+    /// index.insert(1, "bob@example.org".into());
+    ///
+    /// let result = index.search(&Query::Suffix("<unused>".into(), ".com".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+    /// ```
+    pub fn enable_suffix_search(&mut self) {
+        if self.reversed.is_some() {
+            return;
+        }
+        let mut reversed = HashSetPrefixTree::new();
+        for (key, primary_ids) in self.index.entries() {
+            let key = reverse(&key);
+            for primary_id in primary_ids {
+                reversed.insert(&key, primary_id.clone());
+            }
+        }
+        self.reversed = Some(reversed);
+    }
+
+    /// Returns true if [Suffix](Query::Suffix) queries are enabled.
+    pub fn has_suffix_search(&self) -> bool {
+        self.reversed.is_some()
+    }
+
+    /// Set if a [Prefix](Query::Prefix) query with an empty prefix is rejected.
+    ///
+    /// An empty prefix matches every row with a value, which is rarely intended
+    /// and expensive on large indices. If enabled, such a query returns an
+    /// [EmptyPrefix](SearchEngineError::EmptyPrefix) error instead, and
+    /// [SearchEngine::query_from_str](crate::SearchEngine::query_from_str)
+    /// rejects a selector like `+name:` with the same error. It is disabled
+    /// by default. The setting is not written by [dump](Self::dump).
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngineError, SearchIndex, SearchIndexPrefixTree, Query};
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "Alex".into());
+    /// index.set_reject_empty_prefix(true);
+    ///
+    /// let result = index.search(&Query::Prefix("<unused>".into(), "".into()));
+    /// assert_eq!(result, Err(SearchEngineError::EmptyPrefix));
+    /// ```
+    pub fn set_reject_empty_prefix(&mut self, reject: bool) {
+        self.reject_empty_prefix = reject;
+    }
+
+    /// Creates a new `SearchIndexPrefixTree` for hierarchical values
+    /// whose segments are separated by a delimiter.
+    ///
+    /// Prefix queries on this index only match on full segments: the prefix
+    /// must either be the whole value or be followed by the delimiter.
+    /// A prefix that is empty or ends with the delimiter matches like in
+    /// a normal prefix tree. Exact and Pattern queries are not affected.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexPrefixTree, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new_with_delimiter('.');
+    /// index.insert(0, "finances.read".into());
+    /// index.insert(1, "finance".into());
+    ///
+    /// let result = index.search(&Query::Prefix("<unused>".into(), "finances".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+    ///
+    /// let result = index.search(&Query::Prefix("<unused>".into(), "finance".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn new_with_delimiter(delimiter: char) -> Self {
+        Self {
+            delimiter: Some(delimiter),
+            ..Self::new()
         }
     }
 
@@ -67,24 +204,455 @@ impl<P: Eq + Hash + Clone> SearchIndexPrefixTree<P> {
     /// index.insert(124, "Rust".into());
     /// ```
     pub fn insert(&mut self, primary_id: P, attribute_value: String) {
+        self.insert_cow(primary_id, Cow::Owned(attribute_value));
+    }
+
+    /// Insert a new entry in the index from a borrowed or owned value.
+    ///
+    /// The tree stores the characters of the value in its nodes and never
+    /// keeps the value itself, so this never allocates a `String`, not even
+    /// for a new value. Only the nodes for a new value are allocated, like
+    /// with [insert](Self::insert). If suffix search is enabled, the reversed
+    /// value is allocated for the reversed tree.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexPrefixTree;
+    /// use std::borrow::Cow;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// let line = "Berlin,Bern,Madrid";
+    ///
+    /// for (id, city) in line.split(',').enumerate() {
+    ///     index.insert_cow(id, Cow::Borrowed(city));
+    /// }
+    /// ```
+    pub fn insert_cow(&mut self, primary_id: P, attribute_value: Cow<str>) {
+        if let Some(reversed) = self.reversed.as_mut() {
+            reversed.insert(&reverse(&attribute_value), primary_id.clone());
+        }
         self.index.insert(&attribute_value, primary_id);
     }
+
+    /// Remove a value from a row / primary id.
+    ///
+    /// Returns true if the row had the value. A value that is no longer used
+    /// by any row doesn't match any query anymore. Its nodes stay in the tree
+    /// and are reused when the value or a value that starts with it is
+    /// inserted again, so the tree never shrinks.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexPrefixTree, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "finances.read".into());
+    /// index.insert(1, "finances.write".into());
+    ///
+    /// assert!(index.remove(&1, "finances.write"));
+    /// assert!(!index.remove(&1, "finances.write"));
+    ///
+    /// let result = index.search(&Query::Prefix("<unused>".into(), "finances.".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+    /// ```
+    pub fn remove(&mut self, primary_id: &P, attribute_value: &str) -> bool {
+        if let Some(reversed) = self.reversed.as_mut() {
+            reversed.remove(&reverse(attribute_value), primary_id);
+        }
+        self.index.remove(attribute_value, primary_id)
+    }
+
+    /// Record that a row / primary id has no value for this attribute.
+    ///
+    /// The row will be matched by [IsNull](Query::IsNull) queries until a value
+    /// is inserted for it. Rows that are neither inserted nor recorded as null
+    /// don't match IsNull queries.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexPrefixTree, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "Hello".into());
+    /// index.insert_null(1);
+    ///
+    /// let result = index.search(&Query::IsNull("<unused>".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![1])));
+    /// ```
+    pub fn insert_null(&mut self, primary_id: P) {
+        self.null_ids.insert(primary_id);
+    }
+
+    /// Count the entries of all values that start with the prefix.
+    ///
+    /// A row / primary id is counted once for every matching value it was inserted
+    /// with, so the result can be greater than the number of rows returned by a
+    /// Prefix query. The counts are maintained on insert, so this is a cheap way to
+    /// estimate the selectivity of a Prefix query without running it.
+    /// If the index has a delimiter, only values that match on a segment
+    /// boundary are counted, like in Prefix queries.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexPrefixTree;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "Alex".into());
+    /// index.insert(0, "Alexander".into());
+    /// index.insert(1, "Andrea".into());
+    ///
+    /// assert_eq!(index.count_prefix("Alex"), 2);
+    /// assert_eq!(index.count_prefix("A"), 3);
+    /// ```
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        match self.segment_prefix(prefix) {
+            Some(segment_prefix) => {
+                self.index.get_ref(prefix).map_or(0, HashSet::len)
+                    + self.index.count_prefix(&segment_prefix)
+            }
+            None => self.index.count_prefix(prefix),
+        }
+    }
+
+    /// Run a prefix search that drops trailing chars of the prefix until it
+    /// matches at least one value.
+    ///
+    /// The prefix is shortened one char at a time, but never below `min_len`
+    /// chars. Returns the prefix that was used in the end together with its
+    /// rows, which are empty if even the shortest allowed prefix doesn't match.
+    /// This can be used for autocompletion that still shows suggestions
+    /// after a typo. Each step only checks the [count](Self::count_prefix) of
+    /// the prefix, so only the final prefix is searched.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexPrefixTree;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "Alex".into());
+    /// index.insert(1, "Alexander".into());
+    /// index.insert(2, "Andrea".into());
+    ///
+    /// let (prefix, result) = index.search_prefix_relaxed("Alexy", 2);
+    /// assert_eq!(prefix, "Alex");
+    /// assert_eq!(result, HashSet::from_iter(vec![0, 1]));
+    /// ```
+    pub fn search_prefix_relaxed(&self, prefix: &str, min_len: usize) -> (String, HashSet<P>) {
+        let mut prefix = prefix;
+        let mut len = prefix.chars().count();
+        while len > min_len && self.count_prefix(prefix) == 0 {
+            let (last_char_start, _) = prefix.char_indices().next_back().expect("not empty");
+            prefix = &prefix[..last_char_start];
+            len -= 1;
+        }
+        (prefix.to_owned(), self.search_prefix(prefix))
+    }
+
+    /// Find the longest stored value that is a prefix of the input and
+    /// return it together with its rows.
+    ///
+    /// This is the opposite of a [Prefix](Query::Prefix) query, which finds all
+    /// values that start with the input. It can be used for longest-prefix
+    /// matching, like in routing tables. If the index has a delimiter, only
+    /// values that end at a segment boundary of the input match, so `a.b`
+    /// matches `a.b.c` but not `a.bc`. The walk runs in O(input length).
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexPrefixTree;
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "192.168.".into());
+    /// index.insert(1, "192.168.10.".into());
+    ///
+    /// assert_eq!(
+    ///     index.longest_prefix_of("192.168.10.5"),
+    ///     Some(("192.168.10.".into(), HashSet::from_iter(vec![1])))
+    /// );
+    /// assert_eq!(index.longest_prefix_of("10.0.0.1"), None);
+    /// ```
+    pub fn longest_prefix_of(&self, input: &str) -> Option<(String, HashSet<P>)> {
+        let is_boundary = |len: usize| match self.delimiter {
+            Some(delimiter) => {
+                len == input.len()
+                    || input[len..].starts_with(delimiter)
+                    || input[..len].ends_with(delimiter)
+            }
+            None => true,
+        };
+        self.index
+            .longest_prefix_of(input, is_boundary)
+            .map(|(len, set)| (input[..len].to_owned(), set.clone()))
+    }
+
+    /// Find all rows with a value that starts with the prefix,
+    /// respecting the segments if the index has a delimiter.
+    fn search_prefix(&self, prefix: &str) -> HashSet<P> {
+        match self.segment_prefix(prefix) {
+            Some(segment_prefix) => {
+                let mut result_set = self.index.get(prefix).unwrap_or_default();
+                if let Some(set) = self.index.get_prefix(&segment_prefix) {
+                    result_set.extend(set);
+                }
+                result_set
+            }
+            None => self.index.get_prefix(prefix).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the prefix followed by the delimiter if a prefix query must
+    /// be restricted to segment boundaries, otherwise `None`.
+    fn segment_prefix(&self, prefix: &str) -> Option<String> {
+        let delimiter = self.delimiter?;
+        if prefix.is_empty() || prefix.ends_with(delimiter) {
+            return None;
+        }
+        let mut segment_prefix = prefix.to_owned();
+        segment_prefix.push(delimiter);
+        Some(segment_prefix)
+    }
+
+    /// Merge all entries of another index into this index.
+    ///
+    /// This can be used to combine multiple indices that were filled in parallel.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexPrefixTree;
+    ///
+    /// let mut index_a = SearchIndexPrefixTree::<usize>::new();
+    /// index_a.insert(0, "Hello".into());
+    ///
+    /// let mut index_b = SearchIndexPrefixTree::<usize>::new();
+    /// index_b.insert(1, "World".into());
+    ///
+    /// index_a.merge(index_b);
+    /// ```
+    pub fn merge(&mut self, other: SearchIndexPrefixTree<P>) {
+        if let Some(reversed) = self.reversed.as_mut() {
+            for (key, primary_ids) in other.index.entries() {
+                let key = reverse(&key);
+                for primary_id in primary_ids {
+                    reversed.insert(&key, primary_id.clone());
+                }
+            }
+        }
+        self.index.merge(other.index);
+        self.null_ids.extend(other.null_ids);
+    }
+
+    /// Returns all distinct values that are used by at least one row,
+    /// sorted in ascending order.
+    ///
+    /// The tree only stores the characters of the values, so every value is
+    /// reconstructed by walking the whole tree.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexPrefixTree;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "Bonn".into());
+    /// index.insert(1, "Berlin".into());
+    /// index.insert(2, "Bonn".into());
+    /// index.remove(&1, "Berlin");
+    ///
+    /// assert_eq!(index.distinct_keys(), vec!["Bonn"]);
+    /// ```
+    pub fn distinct_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .index
+            .entries()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
 }
 
-impl<P: Eq + Hash + Clone> SearchIndex<P> for SearchIndexPrefixTree<P> {
+impl<P: Eq + Hash + Clone + ToString + FromStr> SearchIndexPrefixTree<P> {
+    /// Write the index in a compact binary layout.
+    ///
+    /// The nodes and values of the tree are written directly, so no keys have
+    /// to be reconstructed and [load](Self::load) doesn't have to insert them
+    /// again. Row ids / primary ids are stored as strings, using [ToString] and [FromStr].
+    ///
+    /// All numbers are little endian. Version 2 of the layout is:
+    /// - the magic bytes `ASPT` and the version as u32,
+    /// - the delimiter as u32, `u32::MAX` if there is none,
+    /// - the number of nodes as u64, followed by every node: the id of its value
+    ///   or `u64::MAX` as u64, its subtree count as u64, the number of children as
+    ///   u64 and the char (u32) and node id (u64) of every child,
+    /// - the number of values as u64, followed by every value set,
+    /// - the set of rows that were recorded as null,
+    /// - a u32 that is `1` if suffix search is enabled and `0` otherwise.
+    ///   The reversed tree is not stored, but built again by [load](Self::load).
+    ///
+    /// A set is stored as its length (u64), followed by the length (u64) and
+    /// UTF-8 bytes of every id. Version 1 is the same layout without the suffix
+    /// search flag. Later versions may extend the layout, but [load](Self::load)
+    /// rejects versions it doesn't know.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexPrefixTree, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut index = SearchIndexPrefixTree::<usize>::new();
+    /// index.insert(0, "Hello".into());
+    /// index.insert(1, "Help".into());
+    ///
+    /// let mut buf = vec![];
+    /// index.dump(&mut buf).expect("no io error");
+    /// let loaded = SearchIndexPrefixTree::<usize>::load(buf.as_slice()).expect("valid dump");
+    ///
+    /// let result = loaded.search(&Query::Prefix("<unused>".into(), "Hel".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+    /// ```
+    pub fn dump<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(DUMP_MAGIC)?;
+        write_u32(&mut w, DUMP_VERSION)?;
+        write_u32(&mut w, self.delimiter.map_or(u32::MAX, u32::from))?;
+        self.index.dump(&mut w)?;
+        write_set(&mut w, &self.null_ids)?;
+        write_u32(&mut w, u32::from(self.reversed.is_some()))
+    }
+
+    /// Read an index that was written by [dump](Self::dump).
+    ///
+    /// Returns an error of the kind [InvalidData](io::ErrorKind::InvalidData)
+    /// if the input is not a valid dump or has an unknown version.
+    pub fn load<R: Read>(mut r: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        let version = read_u32(&mut r)?;
+        if &magic != DUMP_MAGIC || !(1..=DUMP_VERSION).contains(&version) {
+            return Err(invalid_data());
+        }
+        let delimiter = match read_u32(&mut r)? {
+            u32::MAX => None,
+            c => Some(char::from_u32(c).ok_or_else(invalid_data)?),
+        };
+        let index = HashSetPrefixTree::load(&mut r)?;
+        let null_ids = read_set(&mut r)?;
+        let suffix_search = match version {
+            1 => false,
+            _ => match read_u32(&mut r)? {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid_data()),
+            },
+        };
+        let mut index = Self {
+            index,
+            reversed: None,
+            null_ids,
+            delimiter,
+            reject_empty_prefix: false,
+        };
+        if suffix_search {
+            index.enable_suffix_search();
+        }
+        Ok(index)
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P> for SearchIndexPrefixTree<P> {
     fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, self.supported_queries())?;
         match query {
             Query::Exact(_, value) => Ok(self.index.get(value).unwrap_or_default()),
-            Query::Prefix(_, value) => Ok(self.index.get_prefix(value).unwrap_or_default()),
+            Query::Prefix(_, value) if value.is_empty() && self.reject_empty_prefix => {
+                Err(SearchEngineError::EmptyPrefix)
+            }
+            Query::Prefix(_, value) => Ok(self.search_prefix(value)),
+            Query::Suffix(_, value) => match self.reversed.as_ref() {
+                Some(reversed) => Ok(reversed.get_prefix(&reverse(value)).unwrap_or_default()),
+                None => Err(SearchEngineError::UnsupportedQuery),
+            },
+            Query::Pattern(_, value) => Ok(self.index.get_pattern(value).unwrap_or_default()),
+            Query::Fuzzy(_, value, max_distance) => {
+                Ok(self.index.get_fuzzy(value, usize::from(*max_distance)))
+            }
+            Query::LengthInRange(_, min, max) => Ok(self.index.get_length_range(*min, *max)),
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                Ok(search_null(query, &self.null_ids, self.all_ids()))
+            }
             _ => Err(SearchEngineError::UnsupportedQuery),
         }
     }
 
     fn supported_queries(&self) -> SupportedQueries {
-        SUPPORTS_EXACT | SUPPORTS_PREFIX
+        match self.reversed {
+            Some(_) => Self::SUPPORTED_QUERIES | SUPPORTS_SUFFIX,
+            None => Self::SUPPORTED_QUERIES,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn rejects_empty_prefix(&self) -> bool {
+        self.reject_empty_prefix
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<String>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        true
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.index.values().flatten().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.index
+            .entries()
+            .into_iter()
+            .flat_map(|(key, primary_ids)| {
+                primary_ids.iter().map(move |id| (id.clone(), key.clone()))
+            })
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, value.to_owned());
+        Ok(())
+    }
+
+    fn contains_value(&self, value: &str) -> bool {
+        self.index
+            .get_ref(value)
+            .is_some_and(|primary_ids| !primary_ids.is_empty())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        if let Some(reversed) = self.reversed.as_mut() {
+            reversed.remove_all(primary_id);
+        }
+        let removed_null = self.null_ids.remove(primary_id);
+        self.index.remove_all(primary_id) || removed_null
     }
 }
 
+/// Returns a string with its chars in reverse order.
+fn reverse(value: &str) -> String {
+    value.chars().rev().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,6 +680,41 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![])));
     }
 
+    #[test]
+    fn search_index_remove() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.enable_suffix_search();
+        index.insert(0, "dashboard.show".into());
+        index.insert(0, "finances.read".into());
+        index.insert(1, "finances.read".into());
+        index.insert(1, "finances.write".into());
+
+        assert!(index.remove(&1, "finances.read"));
+        assert!(!index.remove(&1, "finances.read"));
+        assert!(!index.remove(&1, "finances"));
+        assert!(!index.remove(&2, "finances.write"));
+
+        let exact = Query::Exact("<not used>".into(), "finances.read".into());
+        let prefix = Query::Prefix("<not used>".into(), "finances.".into());
+        let suffix = Query::Suffix("<not used>".into(), ".read".into());
+        assert_eq!(index.search(&exact), Ok(HashSet::from_iter(vec![0])));
+        assert_eq!(index.search(&prefix), Ok(HashSet::from_iter(vec![0, 1])));
+        assert_eq!(index.search(&suffix), Ok(HashSet::from_iter(vec![0])));
+
+        assert!(index.remove(&1, "finances.write"));
+        assert!(index.remove(&0, "finances.read"));
+        assert_eq!(index.search(&exact), Ok(HashSet::new()));
+        assert_eq!(index.search(&prefix), Ok(HashSet::new()));
+        assert_eq!(index.search(&suffix), Ok(HashSet::new()));
+        assert_eq!(index.count_prefix("finances."), 0);
+        assert_eq!(index.all_ids(), HashSet::from_iter(vec![0]));
+        assert_eq!(index.entries(), vec![(0, "dashboard.show".into())]);
+
+        index.insert(2, "finances.read".into());
+        assert_eq!(index.search(&prefix), Ok(HashSet::from_iter(vec![2])));
+        assert_eq!(index.search(&suffix), Ok(HashSet::from_iter(vec![2])));
+    }
+
     #[test]
     fn search_index_prefix_string() {
         let mut index = SearchIndexPrefixTree::<usize>::new();
@@ -141,6 +744,365 @@ mod tests {
         assert_eq!(result, Ok(HashSet::from_iter(vec![5])));
     }
 
+    #[test]
+    fn search_index_pattern_string() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "report.pdf".into());
+        index.insert(1, "report-2024.pdf".into());
+        index.insert(2, "report-2024.docx".into());
+        index.insert(3, "summary-report.pdf".into());
+        index.insert(4, "report-2024-draft.pdf".into());
+        index.insert(5, "reports".into());
+
+        let result = index.search(&Query::Pattern("<not used>".into(), "report*.pdf".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 4])));
+
+        let result = index.search(&Query::Pattern(
+            "<not used>".into(),
+            "report-*-*.pdf".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4])));
+
+        let result = index.search(&Query::Pattern("<not used>".into(), "*report*".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2, 3, 4, 5])));
+
+        let result = index.search(&Query::Pattern("<not used>".into(), "*.docx".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+
+        let result = index.search(&Query::Pattern("<not used>".into(), "reports".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![5])));
+
+        let result = index.search(&Query::Pattern("<not used>".into(), "x*.pdf".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+    }
+
+    #[test]
+    fn search_index_fuzzy() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "berlin".into());
+        index.insert(1, "bern".into());
+        index.insert(2, "bremen".into());
+        index.insert(3, "berlinale".into());
+        index.insert(4, "börlin".into());
+
+        let fuzzy = |value: &str, max_distance: u8| {
+            index
+                .search(&Query::Fuzzy(
+                    "<not used>".into(),
+                    value.into(),
+                    max_distance,
+                ))
+                .unwrap()
+        };
+        assert_eq!(fuzzy("berlin", 0), HashSet::from_iter(vec![0]));
+        assert_eq!(fuzzy("berlin", 1), HashSet::from_iter(vec![0, 4]));
+        assert_eq!(fuzzy("berln", 1), HashSet::from_iter(vec![0, 1]));
+        assert_eq!(fuzzy("brelin", 2), HashSet::from_iter(vec![0, 2, 4]));
+        assert_eq!(fuzzy("berlin", 3), HashSet::from_iter(vec![0, 1, 3, 4]));
+        assert_eq!(fuzzy("", 4), HashSet::from_iter(vec![1]));
+        assert_eq!(fuzzy("xyz", 2), HashSet::new());
+    }
+
+    #[test]
+    fn search_index_delimiter() {
+        let mut index = SearchIndexPrefixTree::<usize>::new_with_delimiter('/');
+        index.insert(0, "a/b/c".into());
+        index.insert(1, "a/bc".into());
+        index.insert(2, "a/b".into());
+        index.insert(3, "ab".into());
+
+        for (prefix, expected) in [
+            ("", vec![0, 1, 2, 3]),
+            ("a", vec![0, 1, 2]),
+            ("a/", vec![0, 1, 2]),
+            ("a/b", vec![0, 2]),
+            ("a/b/c", vec![0]),
+            ("a/b/", vec![0]),
+            ("ab", vec![3]),
+            ("a/b/c/d", vec![]),
+        ] {
+            let result = index.search(&Query::Prefix("<not used>".into(), prefix.into()));
+            assert_eq!(
+                result,
+                Ok(HashSet::from_iter(expected.clone())),
+                "{prefix:?}"
+            );
+            assert_eq!(index.count_prefix(prefix), expected.len(), "{prefix:?}");
+        }
+    }
+
+    #[test]
+    fn longest_prefix_routing_table() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "10.".into());
+        index.insert(1, "192.168.".into());
+        index.insert(2, "192.168.10.".into());
+        index.insert(3, "192.168.10.".into());
+        index.insert(4, "192.168.100.".into());
+
+        for (input, expected) in [
+            ("192.168.10.5", Some(("192.168.10.", vec![2, 3]))),
+            ("192.168.100.7", Some(("192.168.100.", vec![4]))),
+            ("192.168.1.1", Some(("192.168.", vec![1]))),
+            ("192.168.", Some(("192.168.", vec![1]))),
+            ("10.0.0.1", Some(("10.", vec![0]))),
+            ("192.167.0.1", None),
+            ("172.16.0.1", None),
+            ("", None),
+        ] {
+            let expected = expected.map(|(key, ids)| (key.to_owned(), HashSet::from_iter(ids)));
+            assert_eq!(index.longest_prefix_of(input), expected, "{input:?}");
+        }
+
+        // The empty value is a default route for every input.
+        index.insert(5, "".into());
+        assert_eq!(
+            index.longest_prefix_of("172.16.0.1"),
+            Some(("".into(), HashSet::from_iter(vec![5])))
+        );
+
+        let mut index = SearchIndexPrefixTree::<usize>::new_with_delimiter('.');
+        index.insert(0, "192.168".into());
+        index.insert(1, "192.168.1".into());
+        assert_eq!(
+            index.longest_prefix_of("192.168.10.5"),
+            Some(("192.168".into(), HashSet::from_iter(vec![0])))
+        );
+        assert_eq!(
+            index.longest_prefix_of("192.168.1.5"),
+            Some(("192.168.1".into(), HashSet::from_iter(vec![1])))
+        );
+        assert_eq!(index.longest_prefix_of("192.1680"), None);
+    }
+
+    #[test]
+    fn search_index_suffix() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "alice@example.com".into());
+        index.insert(1, "bob@example.org".into());
+        let suffix = Query::Suffix("<not used>".into(), "example.com".into());
+        assert_eq!(
+            index.search(&suffix),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+        assert_eq!(index.supported_queries() & SUPPORTS_SUFFIX, 0);
+
+        index.enable_suffix_search();
+        assert!(index.has_suffix_search());
+        assert_ne!(index.supported_queries() & SUPPORTS_SUFFIX, 0);
+        index.insert(2, "carol@mail.example.com".into());
+        index.insert(3, "report.pdf".into());
+        index.insert(3, "ÄÖÜ".into());
+
+        let mut other = SearchIndexPrefixTree::<usize>::new();
+        other.insert(4, "dave@example.com".into());
+        index.merge(other);
+
+        for (suffix, expected) in [
+            ("example.com", vec![0, 2, 4]),
+            ("@example.com", vec![0, 4]),
+            (".org", vec![1]),
+            (".pdf", vec![3]),
+            ("ÖÜ", vec![3]),
+            ("alice@example.com", vec![0]),
+            ("x.alice@example.com", vec![]),
+            ("", vec![0, 1, 2, 3, 4]),
+        ] {
+            let result = index.search(&Query::Suffix("<not used>".into(), suffix.into()));
+            assert_eq!(result, Ok(HashSet::from_iter(expected)), "{suffix:?}");
+        }
+        assert_eq!(
+            index.search(&Query::Suffix("<not used>".into(), "".into())),
+            index.search(&Query::Prefix("<not used>".into(), "".into()))
+        );
+    }
+
+    #[test]
+    fn search_index_dump_load() {
+        let mut index = SearchIndexPrefixTree::<usize>::new_with_delimiter('/');
+        index.enable_suffix_search();
+        for i in 0..2000 {
+            index.insert(i, format!("{}/{}/{}", i % 7, i % 31, i % 101));
+            index.insert(i, format!("ids/{i}"));
+        }
+        index.insert_null(2000);
+
+        let mut buf = vec![];
+        index.dump(&mut buf).unwrap();
+        let loaded = SearchIndexPrefixTree::<usize>::load(buf.as_slice()).unwrap();
+
+        let queries = [
+            Query::Exact("<not used>".into(), "3/5/7".into()),
+            Query::Exact("<not used>".into(), "ids/42".into()),
+            Query::Prefix("<not used>".into(), "3".into()),
+            Query::Prefix("<not used>".into(), "3/1".into()),
+            Query::Prefix("<not used>".into(), "ids/99".into()),
+            Query::Pattern("<not used>".into(), "*/1*/1".into()),
+            Query::Suffix("<not used>".into(), "/42".into()),
+            Query::IsNull("<not used>".into()),
+        ];
+        for q in queries.iter() {
+            assert_eq!(index.search(q), loaded.search(q), "{q:?}");
+        }
+        for prefix in ["", "1", "1/", "ids", "ids/1"] {
+            assert_eq!(index.count_prefix(prefix), loaded.count_prefix(prefix));
+        }
+
+        // Version 1 has no suffix search flag.
+        let mut v1 = buf[..buf.len() - 4].to_vec();
+        v1[4] = 1;
+        let loaded = SearchIndexPrefixTree::<usize>::load(v1.as_slice()).unwrap();
+        assert!(!loaded.has_suffix_search());
+        assert_eq!(index.search(&queries[0]), loaded.search(&queries[0]));
+
+        let mut bad = buf.clone();
+        bad[4] = 3;
+        let err = SearchIndexPrefixTree::<usize>::load(bad.as_slice()).err();
+        assert_eq!(err.map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+        let err = SearchIndexPrefixTree::<usize>::load(&buf[..buf.len() / 2]).err();
+        assert_eq!(err.map(|e| e.kind()), Some(io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn search_index_length() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "Alice".into());
+        index.insert(1, "Al".into());
+        index.insert(2, "Alexander the Great".into());
+        index.insert(3, "Bob".into());
+        index.insert(3, "Robert".into());
+        index.insert(4, "".into());
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 10, usize::MAX));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 2, 5));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 3])));
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 0, 0));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4])));
+
+        let result = index.search(&Query::LengthInRange("<not used>".into(), 7, 18));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![])));
+    }
+
+    #[test]
+    fn search_index_merge() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "A".into());
+        index.insert(1, "AB".into());
+        index.insert(2, "B".into());
+
+        let mut other = SearchIndexPrefixTree::<usize>::new();
+        other.insert(3, "ABC".into());
+        other.insert(4, "AB".into());
+        other.insert(5, "C".into());
+        other.insert(6, "".into());
+
+        index.merge(other);
+
+        let result = index.search(&Query::Prefix("<not used>".into(), "A".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 3, 4])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "AB".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 4])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "C".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![5])));
+
+        let result = index.search(&Query::Exact("<not used>".into(), "".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![6])));
+
+        let result = index.search(&Query::Prefix("<not used>".into(), "".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2, 3, 4, 5, 6])));
+    }
+
+    #[test]
+    fn search_index_entries() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "A".into());
+        index.insert(0, "AB".into());
+        index.insert(1, "A".into());
+        index.insert(2, "".into());
+
+        assert_eq!(index.all_ids(), HashSet::from_iter(vec![0, 1, 2]));
+        let mut entries = index.entries();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                (0, "A".into()),
+                (0, "AB".into()),
+                (1, "A".into()),
+                (2, "".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn search_index_prefix_relaxed() {
+        let mut index = SearchIndexPrefixTree::<usize>::new();
+        index.insert(0, "Berlin".into());
+        index.insert(1, "Bern".into());
+        index.insert(2, "Bergen".into());
+        index.insert(3, "Köln".into());
+
+        assert_eq!(
+            index.search_prefix_relaxed("Berlxyz", 1),
+            ("Berl".into(), HashSet::from_iter(vec![0]))
+        );
+        assert_eq!(
+            index.search_prefix_relaxed("Berx", 1),
+            ("Ber".into(), HashSet::from_iter(vec![0, 1, 2]))
+        );
+        assert_eq!(
+            index.search_prefix_relaxed("Bern", 1),
+            ("Bern".into(), HashSet::from_iter(vec![1]))
+        );
+        assert_eq!(
+            index.search_prefix_relaxed("Köx", 1),
+            ("Kö".into(), HashSet::from_iter(vec![3]))
+        );
+        assert_eq!(
+            index.search_prefix_relaxed("Bx", 2),
+            ("Bx".into(), HashSet::new())
+        );
+        assert_eq!(
+            index.search_prefix_relaxed("Xyz", 0),
+            ("".into(), HashSet::from_iter(vec![0, 1, 2, 3]))
+        );
+
+        let mut index = SearchIndexPrefixTree::<usize>::new_with_delimiter('.');
+        index.insert(0, "finances.read".into());
+        index.insert(1, "finance.read".into());
+        assert_eq!(
+            index.search_prefix_relaxed("finances.x", 1),
+            ("finances.".into(), HashSet::from_iter(vec![0]))
+        );
+    }
+
+    #[test]
+    fn search_index_reject_empty_prefix() {
+        let mut index_firstname = SearchIndexPrefixTree::<usize>::new();
+        index_firstname.insert(0, "Alex".into());
+        index_firstname.insert(1, "Alexander".into());
+        index_firstname.insert(2, "".into());
+        let empty_prefix = Query::Prefix("<not used>".into(), "".into());
+
+        assert!(!index_firstname.rejects_empty_prefix());
+        let result = index_firstname.search(&empty_prefix);
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2])));
+
+        index_firstname.set_reject_empty_prefix(true);
+        assert!(index_firstname.rejects_empty_prefix());
+        let result = index_firstname.search(&empty_prefix);
+        assert_eq!(result, Err(SearchEngineError::EmptyPrefix));
+        let result = index_firstname.search(&Query::Prefix("<not used>".into(), "A".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1])));
+        let result = index_firstname.search(&Query::Exact("<not used>".into(), "".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+    }
+
     #[test]
     fn search_index_unsupported_queries() {
         let mut index = SearchIndexPrefixTree::<usize>::new();
@@ -1,25 +1,84 @@
+use std::borrow::Borrow;
 use std::clone::Clone;
 use std::cmp::Ord;
 use std::collections::{HashSet, VecDeque};
 use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
 
-/// Prefix tree object that is specialized in storing HashSets and accessing them by strings.
-pub struct HashSetPrefixTree<P> {
+/// A single step in the key of a [PrefixTree], like a char of a string
+/// or a byte of a byte string.
+pub trait KeyUnit: Ord + Copy {
+    /// Type of a borrowed key, like `str` for chars.
+    type Key: ?Sized;
+
+    /// Type of a key that is reconstructed while walking the tree.
+    type OwnedKey: Default + Clone + Borrow<Self::Key>;
+
+    /// Returns the steps of a key in order.
+    fn units(key: &Self::Key) -> impl Iterator<Item = Self> + '_;
+
+    /// Appends a step to a reconstructed key.
+    fn push(key: &mut Self::OwnedKey, unit: Self);
+}
+
+impl KeyUnit for char {
+    type Key = str;
+    type OwnedKey = String;
+
+    fn units(key: &str) -> impl Iterator<Item = char> + '_ {
+        key.chars()
+    }
+
+    fn push(key: &mut String, unit: char) {
+        key.push(unit);
+    }
+}
+
+/// Every node is a single byte, so keys don't have to be valid UTF-8 and
+/// no chars have to be decoded while walking the tree.
+impl KeyUnit for u8 {
+    type Key = [u8];
+    type OwnedKey = Vec<u8>;
+
+    fn units(key: &[u8]) -> impl Iterator<Item = u8> + '_ {
+        key.iter().copied()
+    }
+
+    fn push(key: &mut Vec<u8>, unit: u8) {
+        key.push(unit);
+    }
+}
+
+/// Prefix tree that accesses HashSets by strings.
+pub type HashSetPrefixTree<P> = PrefixTree<char, P>;
+
+/// Prefix tree that accesses HashSets by byte strings.
+pub type ByteStringPrefixTree<P> = PrefixTree<u8, P>;
+
+/// Prefix tree object that is specialized in storing HashSets and accessing
+/// them by keys that are made of [KeyUnit]s.
+#[derive(Clone)]
+pub struct PrefixTree<K, P> {
     /// Root of the prefix tree
-    nodes: Vec<TreeNode>,
+    nodes: Vec<TreeNode<K>>,
 
     /// Stores all values in the tree
     values: Vec<HashSet<P>>,
+
+    /// Ids of values that are no longer used by a node and can be reused
+    free_values: Vec<usize>,
 }
 
-impl<P: Eq + Hash + Clone> HashSetPrefixTree<P> {
-    /// Creates a new HashSetPrefixTree object
-    pub fn new() -> HashSetPrefixTree<P> {
+impl<K: KeyUnit, P: Eq + Hash + Clone> PrefixTree<K, P> {
+    /// Creates a new PrefixTree object
+    pub fn new() -> PrefixTree<K, P> {
         // Initialize the root node
         let nodes = vec![TreeNode::new(None)];
-        HashSetPrefixTree {
+        PrefixTree {
             nodes,
             values: Vec::<HashSet<P>>::new(),
+            free_values: Vec::new(),
         }
     }
 
@@ -27,44 +86,120 @@ impl<P: Eq + Hash + Clone> HashSetPrefixTree<P> {
     ///
     /// If no entry under this key exists, a new HashSet will be created.
     /// If there is already an entry, the new value will be added to the existing set.
-    pub fn insert(&mut self, key: &str, value: P) {
-        let mut node_id = 0usize;
-
-        for c in key.chars() {
-            if let Some(id) = self.nodes[node_id].find_child(&c) {
-                node_id = id;
-            } else {
-                let new_node_id = self.create_new_node();
-                self.nodes[node_id].insert_child(c, new_node_id);
-                node_id = new_node_id;
-            }
+    pub fn insert(&mut self, key: &K::Key, value: P) {
+        if self.get_ref(key).is_some_and(|set| set.contains(&value)) {
+            return;
         }
+        self.get_or_create_set(key, 1).insert(value);
+    }
 
-        let value_id = match self.nodes[node_id].get() {
-            Some(id) => {
-                self.values[id].insert(value);
-                id
-            }
-            None => {
-                let mut new_set = HashSet::new();
-                new_set.insert(value);
-                self.values.push(new_set);
-                self.values.len() - 1
+    /// Removes a value from the HashSet stored under the key.
+    ///
+    /// Returns true if the value was in the set. If the set becomes empty,
+    /// it is detached from its node and reused by the next new key, so the
+    /// key no longer matches any search. The nodes of the key are not
+    /// reclaimed, because other nodes refer to them by their position in
+    /// the node vector, so the node vector never shrinks.
+    pub fn remove(&mut self, key: &K::Key, value: &P) -> bool {
+        let mut path = vec![0usize];
+        for c in K::units(key) {
+            match self.nodes[path[path.len() - 1]].find_child(&c) {
+                Some(id) => path.push(id),
+                None => return false,
             }
+        }
+        let node_id = path[path.len() - 1];
+        let Some(value_id) = self.nodes[node_id].get() else {
+            return false;
         };
+        if !self.values[value_id].remove(value) {
+            return false;
+        }
 
-        self.nodes[node_id].set(value_id);
+        if self.values[value_id].is_empty() {
+            self.values[value_id] = HashSet::new();
+            self.nodes[node_id].value = None;
+            self.free_values.push(value_id);
+        }
+        for node_id in path {
+            self.nodes[node_id].subtree_count -= 1;
+        }
+        true
+    }
+
+    /// Removes a value from the HashSets of all keys.
+    ///
+    /// All keys of the tree are visited. Returns true if the value was
+    /// stored under any key.
+    pub fn remove_all(&mut self, value: &P) -> bool {
+        let keys: Vec<K::OwnedKey> = self
+            .keys()
+            .into_iter()
+            .filter(|(_, value_id)| self.values[*value_id].contains(value))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys.iter() {
+            self.remove(key.borrow(), value);
+        }
+        !keys.is_empty()
+    }
+
+    /// Merges all entries of another tree into this tree.
+    ///
+    /// The nodes of both trees use different index spaces, so the keys of
+    /// all entries in `other` are reconstructed while walking it and then
+    /// inserted into this tree.
+    pub fn merge(&mut self, mut other: PrefixTree<K, P>) {
+        for (key, value_id) in other.keys() {
+            let other_set = std::mem::take(&mut other.values[value_id]);
+            let added = match self.get_ref(key.borrow()) {
+                Some(set) => other_set.iter().filter(|v| !set.contains(v)).count(),
+                None => other_set.len(),
+            };
+            self.get_or_create_set(key.borrow(), added)
+                .extend(other_set);
+        }
+    }
+
+    /// Returns all keys in the tree together with references to their HashSets.
+    pub fn entries(&self) -> Vec<(K::OwnedKey, &HashSet<P>)> {
+        self.keys()
+            .into_iter()
+            .map(|(key, value_id)| (key, &self.values[value_id]))
+            .collect()
+    }
+
+    /// Returns all HashSets stored in the tree.
+    pub fn values(&self) -> impl Iterator<Item = &HashSet<P>> {
+        self.values.iter()
     }
 
     /// Get a HashSet from the tree by exactly matching the key.
-    pub fn get(&self, key: &str) -> Option<HashSet<P>> {
+    pub fn get(&self, key: &K::Key) -> Option<HashSet<P>> {
+        self.get_ref(key).cloned()
+    }
+
+    /// Count all entries that share the same prefix.
+    ///
+    /// Every value counts once for every key it is stored under, so a value
+    /// that is stored under two keys with the prefix is counted twice.
+    /// The count is maintained on insert, so this function only has to find
+    /// the node of the prefix and runs in O(prefix length).
+    pub fn count_prefix(&self, prefix: &K::Key) -> usize {
+        self.find_node(prefix)
+            .map(|node_id| self.nodes[node_id].subtree_count)
+            .unwrap_or_default()
+    }
+
+    /// Get a reference to a HashSet from the tree by exactly matching the key.
+    pub fn get_ref(&self, key: &K::Key) -> Option<&HashSet<P>> {
         let node_id = self.find_node(key)?;
         let value_id = self.nodes[node_id].get()?;
-        Some(self.values[value_id].clone())
+        Some(&self.values[value_id])
     }
 
     /// Get a HashSet from the tree by finding all entries that share the same prefix.
-    pub fn get_prefix(&self, prefix: &str) -> Option<HashSet<P>> {
+    pub fn get_prefix(&self, prefix: &K::Key) -> Option<HashSet<P>> {
         let mut node_ids = VecDeque::new();
         let mut result_set = HashSet::<P>::new();
 
@@ -82,19 +217,147 @@ impl<P: Eq + Hash + Clone> HashSetPrefixTree<P> {
         Some(result_set)
     }
 
+    /// Get a HashSet from the tree by finding all entries with a key that has
+    /// a Levenshtein distance of at most `max_distance` to `key`.
+    ///
+    /// The tree is walked depth-first and every node gets a row of the edit
+    /// distance matrix between its key and all prefixes of `key`, computed from
+    /// the row of its parent. A subtree is skipped as soon as the smallest
+    /// distance in the row exceeds `max_distance`, because appending more steps
+    /// can never make the distance smaller.
+    pub fn get_fuzzy(&self, key: &K::Key, max_distance: usize) -> HashSet<P> {
+        let key: Vec<K> = K::units(key).collect();
+        let mut result_set = HashSet::<P>::new();
+        let mut node_rows = vec![(0usize, (0..=key.len()).collect::<Vec<_>>())];
+
+        while let Some((node_id, row)) = node_rows.pop() {
+            if row[key.len()] <= max_distance {
+                if let Some(value_id) = self.nodes[node_id].get() {
+                    result_set.extend(self.values[value_id].iter().cloned());
+                }
+            }
+            if row.iter().min().is_some_and(|&min| min > max_distance) {
+                continue;
+            }
+            for &(c, child_id) in self.nodes[node_id].children.iter() {
+                let mut child_row = Vec::with_capacity(row.len());
+                child_row.push(row[0] + 1);
+                for (i, &key_char) in key.iter().enumerate() {
+                    let substitution = row[i] + usize::from(key_char != c);
+                    let insertion = child_row[i] + 1;
+                    let deletion = row[i + 1] + 1;
+                    child_row.push(substitution.min(insertion).min(deletion));
+                }
+                node_rows.push((child_id, child_row));
+            }
+        }
+
+        result_set
+    }
+
+    /// Get a HashSet from the tree by finding all entries with a key length
+    /// in steps, like chars, between `min` and `max`, both inclusive.
+    ///
+    /// The depth of a node is the length of its key, so only nodes up to
+    /// a depth of `max` are visited.
+    pub fn get_length_range(&self, min: usize, max: usize) -> HashSet<P> {
+        let mut result_set = HashSet::<P>::new();
+        let mut node_ids = vec![(0usize, 0usize)];
+
+        while let Some((node_id, depth)) = node_ids.pop() {
+            if depth >= min {
+                if let Some(value_id) = self.nodes[node_id].get() {
+                    result_set.extend(self.values[value_id].iter().cloned());
+                }
+            }
+            if depth < max {
+                node_ids.extend(
+                    self.nodes[node_id]
+                        .children
+                        .iter()
+                        .map(|&(_, child_id)| (child_id, depth + 1)),
+                );
+            }
+        }
+
+        result_set
+    }
+
+    /// Reconstructs the keys of all nodes that have a value by walking
+    /// the tree and returns them together with the ids of their values.
+    fn keys(&self) -> Vec<(K::OwnedKey, usize)> {
+        self.keys_below(0, K::OwnedKey::default())
+    }
+
+    /// Reconstructs the keys of all nodes below a node that have a value.
+    /// The keys start with `key`, which should be the key of the start node.
+    fn keys_below(&self, node_id: usize, key: K::OwnedKey) -> Vec<(K::OwnedKey, usize)> {
+        let mut keys = vec![];
+        let mut node_ids = vec![(node_id, key)];
+
+        while let Some((node_id, key)) = node_ids.pop() {
+            node_ids.extend(self.nodes[node_id].children.iter().map(|&(c, child_id)| {
+                let mut child_key = key.clone();
+                K::push(&mut child_key, c);
+                (child_id, child_key)
+            }));
+
+            if let Some(value_id) = self.nodes[node_id].get() {
+                keys.push((key, value_id));
+            }
+        }
+
+        keys
+    }
+
     /// Find a [TreeNode] in the tree by its key.
-    fn find_node(&self, key: &str) -> Option<usize> {
+    fn find_node(&self, key: &K::Key) -> Option<usize> {
         if self.nodes.is_empty() {
             return None;
         }
 
         let mut node_id = 0usize;
-        for c in key.chars() {
+        for c in K::units(key) {
             node_id = self.nodes[node_id].find_child(&c)?;
         }
         Some(node_id)
     }
 
+    /// Get the HashSet stored under the key. If the key or the set don't
+    /// exist yet, they will be created.
+    ///
+    /// The caller must pass the number of values it will add to the set,
+    /// so the subtree counts of all nodes on the path can be updated.
+    fn get_or_create_set(&mut self, key: &K::Key, added: usize) -> &mut HashSet<P> {
+        let mut node_id = 0usize;
+        self.nodes[node_id].subtree_count += added;
+
+        for c in K::units(key) {
+            if let Some(id) = self.nodes[node_id].find_child(&c) {
+                node_id = id;
+            } else {
+                let new_node_id = self.create_new_node();
+                self.nodes[node_id].insert_child(c, new_node_id);
+                node_id = new_node_id;
+            }
+            self.nodes[node_id].subtree_count += added;
+        }
+
+        let value_id = match self.nodes[node_id].get() {
+            Some(id) => id,
+            None => match self.free_values.pop() {
+                Some(id) => id,
+                None => {
+                    self.values.push(HashSet::new());
+                    self.values.len() - 1
+                }
+            },
+        };
+
+        self.nodes[node_id].set(value_id);
+        &mut self.values[value_id]
+    }
+
     /// Create a new node
     fn create_new_node(&mut self) -> usize {
         self.nodes.push(TreeNode::new(None));
@@ -102,27 +365,267 @@ impl<P: Eq + Hash + Clone> HashSetPrefixTree<P> {
     }
 }
 
-/// A single node in the prefix tree.
-struct TreeNode {
+impl<P: Eq + Hash + Clone> PrefixTree<char, P> {
+    /// Find the longest key in the tree that is a prefix of `input` and
+    /// accepted by `is_boundary`.
+    ///
+    /// The tree is walked along the chars of the input and the deepest node
+    /// with a value is remembered. `is_boundary` gets the length of a key in
+    /// bytes. Returns the byte length of the key and a reference to its HashSet.
+    pub fn longest_prefix_of(
+        &self,
+        input: &str,
+        is_boundary: impl Fn(usize) -> bool,
+    ) -> Option<(usize, &HashSet<P>)> {
+        let mut node_id = 0usize;
+        let mut longest = None;
+        for (idx, c) in input.char_indices().chain([(input.len(), '\0')]) {
+            if let Some(value_id) = self.nodes.get(node_id)?.get() {
+                if is_boundary(idx) {
+                    longest = Some((idx, &self.values[value_id]));
+                }
+            }
+            if idx == input.len() {
+                break;
+            }
+            match self.nodes[node_id].find_child(&c) {
+                Some(id) => node_id = id,
+                None => break,
+            }
+        }
+        longest
+    }
+
+    /// Get a HashSet from the tree by finding all entries that match a pattern.
+    /// Each `*` in the pattern matches any sequence of characters.
+    ///
+    /// Only the subtree of the part before the first `*` is visited, all keys
+    /// in there are matched against the rest of the pattern.
+    pub fn get_pattern(&self, pattern: &str) -> Option<HashSet<P>> {
+        let (prefix, rest) = match pattern.split_once('*') {
+            Some(parts) => parts,
+            None => return self.get(pattern),
+        };
+        let node_id = self.find_node(prefix)?;
+
+        let mut result_set = HashSet::<P>::new();
+        for (key, value_id) in self.keys_below(node_id, String::new()) {
+            if matches_pattern(&key, rest) {
+                result_set.extend(self.values[value_id].iter().cloned());
+            }
+        }
+        Some(result_set)
+    }
+}
+
+impl<P: Eq + Hash + Clone + ToString + FromStr> PrefixTree<char, P> {
+    /// Writes the nodes and values of the tree in a binary layout.
+    ///
+    /// The layout is described in [SearchIndexPrefixTree::dump](super::SearchIndexPrefixTree::dump).
+    pub fn dump<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_u64(w, self.nodes.len() as u64)?;
+        for node in self.nodes.iter() {
+            write_u64(w, node.value.map_or(u64::MAX, |id| id as u64))?;
+            write_u64(w, node.subtree_count as u64)?;
+            write_u64(w, node.children.len() as u64)?;
+            for &(c, child_id) in node.children.iter() {
+                write_u32(w, c as u32)?;
+                write_u64(w, child_id as u64)?;
+            }
+        }
+        write_u64(w, self.values.len() as u64)?;
+        for set in self.values.iter() {
+            write_set(w, set)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree that was written by [dump](Self::dump).
+    ///
+    /// All node and value references are checked, so malformed input
+    /// returns an error of the kind [InvalidData](io::ErrorKind::InvalidData).
+    pub fn load<R: Read>(r: &mut R) -> io::Result<Self> {
+        let node_count = read_len(r)?;
+        let mut nodes = Vec::new();
+        let mut child_ids = HashSet::new();
+        for node_id in 0..node_count {
+            let value = match read_u64(r)? {
+                u64::MAX => None,
+                id => Some(id as usize),
+            };
+            let mut node = TreeNode::new(value);
+            node.subtree_count = read_u64(r)? as usize;
+            for _ in 0..read_len(r)? {
+                let c = char::from_u32(read_u32(r)?).ok_or_else(invalid_data)?;
+                let child_id = read_u64(r)? as usize;
+                // Children are always created after their parent, this
+                // also guarantees that the tree has no cycles.
+                // A node with more than one parent would be counted twice.
+                if child_id <= node_id || child_id >= node_count || !child_ids.insert(child_id) {
+                    return Err(invalid_data());
+                }
+                node.children.push((c, child_id));
+            }
+            if !node.children.windows(2).all(|w| w[0].0 < w[1].0) {
+                return Err(invalid_data());
+            }
+            nodes.push(node);
+        }
+        if nodes.is_empty() {
+            return Err(invalid_data());
+        }
+
+        let value_count = read_len(r)?;
+        let mut values = Vec::new();
+        for _ in 0..value_count {
+            values.push(read_set(r)?);
+        }
+        if nodes
+            .iter()
+            .any(|node| node.value.is_some_and(|id| id >= value_count))
+        {
+            return Err(invalid_data());
+        }
+        // Values that were removed are still written, but no node uses them.
+        let mut used = vec![false; value_count];
+        for node in nodes.iter() {
+            if let Some(value_id) = node.value {
+                if std::mem::replace(&mut used[value_id], true) {
+                    return Err(invalid_data());
+                }
+            }
+        }
+        // The subtree counts are recomputed bottom up, children always
+        // come after their parent. `remove` relies on them being exact.
+        let mut counts = vec![0usize; nodes.len()];
+        for node_id in (0..nodes.len()).rev() {
+            let node = &nodes[node_id];
+            let own = node.value.map_or(0, |value_id| values[value_id].len());
+            counts[node_id] = own
+                + node
+                    .children
+                    .iter()
+                    .map(|&(_, id)| counts[id])
+                    .sum::<usize>();
+            if counts[node_id] != node.subtree_count {
+                return Err(invalid_data());
+            }
+        }
+        let free_values = (0..value_count)
+            .filter(|&value_id| !used[value_id] && values[value_id].is_empty())
+            .collect();
+        Ok(PrefixTree {
+            nodes,
+            values,
+            free_values,
+        })
+    }
+}
+
+/// Writes a HashSet of ids as a length followed by all ids as strings.
+pub(crate) fn write_set<P: ToString, W: Write>(w: &mut W, set: &HashSet<P>) -> io::Result<()> {
+    write_u64(w, set.len() as u64)?;
+    for id in set.iter() {
+        let id = id.to_string();
+        write_u64(w, id.len() as u64)?;
+        w.write_all(id.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Reads a HashSet of ids that was written by [write_set].
+pub(crate) fn read_set<P: Eq + Hash + FromStr, R: Read>(r: &mut R) -> io::Result<HashSet<P>> {
+    let mut set = HashSet::new();
+    for _ in 0..read_len(r)? {
+        let len = read_u64(r)?;
+        let mut buf = Vec::new();
+        r.by_ref().take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let id = String::from_utf8(buf).map_err(|_| invalid_data())?;
+        set.insert(id.parse().map_err(|_| invalid_data())?);
+    }
+    Ok(set)
+}
+
+/// Writes a u32 in little endian.
+pub(crate) fn write_u32<W: Write>(w: &mut W, value: u32) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+/// Writes a u64 in little endian.
+fn write_u64<W: Write>(w: &mut W, value: u64) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+/// Reads a u32 in little endian.
+pub(crate) fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Reads a u64 in little endian.
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Reads a length or count. Nothing is preallocated with it, so a
+/// corrupt length can only lead to an early end of the input.
+fn read_len<R: Read>(r: &mut R) -> io::Result<usize> {
+    usize::try_from(read_u64(r)?).map_err(|_| invalid_data())
+}
+
+/// Returns the error for malformed input.
+pub(crate) fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid prefix tree dump")
+}
+
+/// Checks if a key matches a pattern that starts with an implicit `*`,
+/// where each `*` matches any sequence of characters.
+fn matches_pattern(key: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let last = parts.next_back().unwrap_or_default();
+    let mut rest = key;
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// A single node in the prefix tree. `K` is the type of a single
+/// step in a key, like a char or a byte.
+#[derive(Clone)]
+pub(super) struct TreeNode<K> {
     /// Index of the value in the trees value vector.
     pub value: Option<usize>,
 
     /// Children of this sub-tree.
-    pub children: Vec<(char, usize)>,
+    pub children: Vec<(K, usize)>,
+
+    /// Number of values stored in this node and all of its children.
+    pub subtree_count: usize,
 }
 
-impl TreeNode {
+impl<K: Ord + Copy> TreeNode<K> {
     /// Create a new TreeNode.
-    pub fn new(value: Option<usize>) -> TreeNode {
+    pub fn new(value: Option<usize>) -> TreeNode<K> {
         TreeNode {
             value,
-            children: Vec::<(char, usize)>::new(),
+            children: Vec::<(K, usize)>::new(),
+            subtree_count: 0,
         }
     }
 
     /// Find the index of a child that matches the key.
     /// If no child is found, None is returned.
-    pub fn find_child(&self, key: &char) -> Option<usize> {
+    pub fn find_child(&self, key: &K) -> Option<usize> {
         self.children
             .binary_search_by(|x| x.0.cmp(key))
             .map(|idx| self.children[idx].1)
@@ -130,9 +633,9 @@ impl TreeNode {
     }
 
     /// Insert a new child and sort the children for faster access.
-    pub fn insert_child(&mut self, key: char, child_id: usize) {
+    pub fn insert_child(&mut self, key: K, child_id: usize) {
         self.children.push((key, child_id));
-        self.children.sort_by(|a, b| a.0.cmp(&b.0));
+        self.children.sort_by_key(|a| a.0);
     }
 
     /// Set the value of this node.
@@ -146,3 +649,207 @@ impl TreeNode {
         self.value
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts all values below a prefix by walking the whole subtree.
+    fn count_prefix_walk(tree: &HashSetPrefixTree<usize>, prefix: &str) -> usize {
+        let Some(node_id) = tree.find_node(prefix) else {
+            return 0;
+        };
+        let mut node_ids = vec![node_id];
+        let mut count = 0;
+        while let Some(node_id) = node_ids.pop() {
+            if let Some(value_id) = tree.nodes[node_id].get() {
+                count += tree.values[value_id].len();
+            }
+            node_ids.extend(tree.nodes[node_id].children.iter().map(|x| x.1));
+        }
+        count
+    }
+
+    /// Computes the Levenshtein distance with the full matrix.
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, a_char) in a.chars().enumerate() {
+            let mut next = vec![i + 1];
+            for (j, &b_char) in b.iter().enumerate() {
+                next.push(
+                    (row[j] + usize::from(a_char != b_char))
+                        .min(next[j] + 1)
+                        .min(row[j + 1] + 1),
+                );
+            }
+            row = next;
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn fuzzy_matches_levenshtein() {
+        // All words with up to four of the chars a, b and ä.
+        let mut words = vec![String::new()];
+        for len in 1..=4 {
+            let shorter: Vec<String> = words
+                .iter()
+                .filter(|w| w.chars().count() == len - 1)
+                .cloned()
+                .collect();
+            for word in shorter {
+                for c in ['a', 'b', 'ä'] {
+                    words.push(format!("{word}{c}"));
+                }
+            }
+        }
+        let mut tree = HashSetPrefixTree::<usize>::new();
+        for (id, word) in words.iter().enumerate() {
+            tree.insert(word, id);
+        }
+
+        for key in ["", "a", "ab", "bäb", "abba", "bbbbb"] {
+            for max_distance in 0..=3 {
+                let expected: HashSet<usize> = (0..words.len())
+                    .filter(|&id| levenshtein(&words[id], key) <= max_distance)
+                    .collect();
+                assert_eq!(
+                    tree.get_fuzzy(key, max_distance),
+                    expected,
+                    "{key:?} with distance {max_distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn remove_updates_counts() {
+        let mut tree = HashSetPrefixTree::<usize>::new();
+        tree.insert("A", 0);
+        tree.insert("AB", 0);
+        tree.insert("AB", 1);
+        tree.insert("ABC", 2);
+
+        assert!(tree.remove("AB", &0));
+        assert!(!tree.remove("AB", &0));
+        assert!(!tree.remove("ABCD", &2));
+        assert!(!tree.remove("", &2));
+        assert_eq!(tree.get("AB"), Some(HashSet::from_iter(vec![1])));
+
+        assert!(tree.remove("AB", &1));
+        assert_eq!(tree.get("AB"), None);
+        assert_eq!(tree.get_prefix("AB"), Some(HashSet::from_iter(vec![2])));
+        assert_eq!(tree.keys().len(), 2);
+        for prefix in ["", "A", "AB", "ABC"] {
+            assert_eq!(tree.count_prefix(prefix), count_prefix_walk(&tree, prefix));
+        }
+
+        // The emptied set is reused for the next new key.
+        let value_count = tree.values.len();
+        tree.insert("B", 3);
+        assert_eq!(tree.values.len(), value_count);
+        assert_eq!(tree.get("B"), Some(HashSet::from_iter(vec![3])));
+
+        tree.remove("B", &3);
+        let mut buffer = vec![];
+        tree.dump(&mut buffer).unwrap();
+        let mut loaded = HashSetPrefixTree::<usize>::load(&mut buffer.as_slice()).unwrap();
+        assert_eq!(loaded.free_values, vec![tree.free_values[0]]);
+        loaded.insert("C", 4);
+        assert_eq!(loaded.values.len(), value_count);
+    }
+
+    #[test]
+    fn load_rejects_corrupted_counts() {
+        let mut tree = HashSetPrefixTree::<usize>::new();
+        tree.insert("a", 0);
+        let mut buffer = vec![];
+        tree.dump(&mut buffer).unwrap();
+        assert!(HashSetPrefixTree::<usize>::load(&mut buffer.as_slice()).is_ok());
+
+        // The subtree count of the root follows the node count and its value id.
+        let mut corrupted = buffer.clone();
+        corrupted[16..24].copy_from_slice(&0u64.to_le_bytes());
+        let err = HashSetPrefixTree::<usize>::load(&mut corrupted.as_slice()).err();
+        assert_eq!(err.map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+
+        // Both nodes refer to the same value.
+        let mut corrupted = buffer.clone();
+        corrupted[8..16].copy_from_slice(&0u64.to_le_bytes());
+        let err = HashSetPrefixTree::<usize>::load(&mut corrupted.as_slice()).err();
+        assert_eq!(err.map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn pattern_matching() {
+        assert!(matches_pattern("report.pdf", ".pdf"));
+        assert!(matches_pattern("abc", ""));
+        assert!(matches_pattern("", ""));
+        assert!(matches_pattern("a-b-c", "-*-c"));
+        assert!(matches_pattern("a-b-c", "b*"));
+        assert!(!matches_pattern("a-c", "-*-c"));
+        assert!(!matches_pattern("abc", "b"));
+    }
+
+    #[test]
+    fn count_prefix_matches_walk() {
+        let mut tree = HashSetPrefixTree::<usize>::new();
+        tree.insert("A", 0);
+        tree.insert("A", 0);
+        tree.insert("AB", 0);
+        tree.insert("AB", 1);
+        tree.insert("ABC", 2);
+        tree.insert("B", 3);
+
+        let mut other = HashSetPrefixTree::<usize>::new();
+        other.insert("AB", 1);
+        other.insert("AB", 4);
+        other.insert("AC", 5);
+        other.insert("", 6);
+        tree.merge(other);
+
+        for (prefix, expected) in [
+            ("", 8),
+            ("A", 6),
+            ("AB", 4),
+            ("ABC", 1),
+            ("AC", 1),
+            ("B", 1),
+            ("C", 0),
+        ] {
+            assert_eq!(tree.count_prefix(prefix), expected, "prefix {prefix:?}");
+            assert_eq!(tree.count_prefix(prefix), count_prefix_walk(&tree, prefix));
+        }
+    }
+
+    #[test]
+    fn multi_byte_prefixes() {
+        let mut tree = ByteStringPrefixTree::<usize>::new();
+        // "é" is 0xC3 0xA9 and "ã" is 0xC3 0xA3, so both share their first byte.
+        tree.insert("é".as_bytes(), 0);
+        tree.insert("ã".as_bytes(), 1);
+        tree.insert(&[0xC3], 2);
+        tree.insert(&[0xFF, 0x00], 3);
+        tree.insert(&[0xFF], 3);
+
+        assert_eq!(tree.get_ref(&[0xC3]), Some(&HashSet::from_iter(vec![2])));
+        assert_eq!(
+            tree.get_prefix(&[0xC3]),
+            Some(HashSet::from_iter(vec![0, 1, 2]))
+        );
+        assert_eq!(
+            tree.get_prefix("é".as_bytes()),
+            Some(HashSet::from_iter(vec![0]))
+        );
+        assert_eq!(tree.count_prefix(&[0xFF]), 2);
+        assert_eq!(tree.count_prefix(&[]), 5);
+
+        assert!(tree.remove(&[0xFF], &3));
+        assert!(!tree.remove(&[0xFF], &3));
+        assert_eq!(tree.count_prefix(&[0xFF]), 1);
+        assert!(tree.remove_all(&3));
+        assert_eq!(tree.get_prefix(&[0xFF]), Some(HashSet::new()));
+        assert_eq!(tree.entries().len(), 3);
+    }
+}
@@ -0,0 +1,190 @@
+use super::{check_supported_query, SearchIndex};
+use crate::{Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_REGEX};
+use regex::Regex;
+use std::{collections::HashSet, hash::Hash};
+
+/// SearchIndexRegexScan is a index that matches [Regex queries](Query::Regex)
+/// by scanning all stored values.
+///
+/// The index only keeps the inserted pairs of primary IDs and values. Every
+/// search compiles the pattern and tests it against all stored values, so the
+/// cost of a search grows linearly with the number of inserted values, see
+/// [values](Self::values). Patterns that fail to compile return
+/// [MismatchedQueryType](SearchEngineError::MismatchedQueryType).
+///
+/// This index is only available with the `regex` feature.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexRegexScan};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_sku = SearchIndexRegexScan::<usize>::new();
+/// index_sku.insert(0, "AB-1234".into());
+/// index_sku.insert(1, "AB-12".into());
+/// index_sku.insert(2, "XY-5678".into());
+///
+/// let result = index_sku.search(&Query::Regex("<unused>".into(), r"^[A-Z]{2}-\d{4}$".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexRegexScan<P> {
+    entries: Vec<(P, String)>,
+}
+
+impl<P: Eq + Hash + Clone> Default for SearchIndexRegexScan<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> SearchIndexRegexScan<P> {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT | SUPPORTS_REGEX;
+
+    /// Creates a new `SearchIndexRegexScan`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexRegexScan;
+    ///
+    /// let index = SearchIndexRegexScan::<usize>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexRegexScan;
+    ///
+    /// let mut index = SearchIndexRegexScan::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, "Hello".into());
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, "World".into());
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "Hello".into());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: String) {
+        self.entries.push((primary_id, attribute_value));
+    }
+
+    /// Returns an iterator over all stored pairs of primary IDs and values
+    /// in the order they were inserted.
+    ///
+    /// Every search visits all of these values.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexRegexScan;
+    ///
+    /// let mut index = SearchIndexRegexScan::<usize>::new();
+    /// index.insert(0, "a".into());
+    /// index.insert(1, "b".into());
+    ///
+    /// let values: Vec<_> = index.values().collect();
+    /// assert_eq!(values, vec![(&0, "a"), (&1, "b")]);
+    /// ```
+    pub fn values(&self) -> impl Iterator<Item = (&P, &str)> {
+        self.entries.iter().map(|(id, value)| (id, value.as_str()))
+    }
+
+    /// Returns all rows that have a value for which the predicate is true.
+    fn scan(&self, predicate: impl Fn(&str) -> bool) -> HashSet<P> {
+        self.entries
+            .iter()
+            .filter(|(_, value)| predicate(value))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P> for SearchIndexRegexScan<P> {
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, expected) => Ok(self.scan(|value| value == expected)),
+            Query::Regex(_, pattern) => {
+                let regex = Regex::new(pattern)
+                    .map_err(|_| SearchEngineError::mismatched_type::<Regex>())?;
+                Ok(self.scan(|value| regex.is_match(value)))
+            }
+            _ => unreachable!("unsupported queries are rejected before"),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<String>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        true
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.entries.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.entries.clone()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, value.to_owned());
+        Ok(())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|(id, _)| id != primary_id);
+        self.entries.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index_regex() {
+        let mut index = SearchIndexRegexScan::<usize>::new();
+        index.insert(0, "alice@example.com".into());
+        index.insert(1, "bob@example.org".into());
+        index.insert(2, "carol@test.com".into());
+        index.insert(2, "carol@example.org".into());
+
+        let regex =
+            |pattern: &str| index.search(&Query::Regex("<not used>".into(), pattern.into()));
+        assert_eq!(regex(r"\.com$"), Ok(HashSet::from_iter(vec![0, 2])));
+        assert_eq!(regex("example"), Ok(HashSet::from_iter(vec![0, 1, 2])));
+        assert_eq!(regex("^bob@"), Ok(HashSet::from_iter(vec![1])));
+        assert_eq!(regex("^dave"), Ok(HashSet::new()));
+        assert_eq!(
+            regex("(unclosed"),
+            Err(SearchEngineError::mismatched_type::<Regex>())
+        );
+
+        assert_eq!(
+            index.search(&Query::Exact("<not used>".into(), "bob@example.org".into())),
+            Ok(HashSet::from_iter(vec![1]))
+        );
+        assert_eq!(index.values().count(), 4);
+    }
+}
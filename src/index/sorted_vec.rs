@@ -0,0 +1,402 @@
+use super::{
+    check_supported_query, parse_bounds, parse_out_bounds, search_null, string_to_payload_type,
+    SearchIndex,
+};
+use crate::{
+    Query, Result, SearchEngineError, SupportedQueries, SUPPORTS_EXACT, SUPPORTS_INRANGE,
+    SUPPORTS_INRANGE_BOUNDS, SUPPORTS_MAXIMUM, SUPPORTS_MINIMUM, SUPPORTS_NULL, SUPPORTS_OUTRANGE,
+    SUPPORTS_OUTRANGE_BOUNDS,
+};
+use std::{
+    any::TypeId,
+    collections::HashSet,
+    hash::Hash,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+/// SearchIndexSortedVec is a index backed by a sorted Vec that can match
+/// the same queries as a [SearchIndexBTreeRange](crate::SearchIndexBTreeRange).
+///
+/// It is meant for indices that are built once and queried many times. The
+/// values are stored next to each other, which needs less memory and is more
+/// cache friendly than the nodes of a BTreeMap. Entries are first collected
+/// with [insert](Self::insert) and then sorted once with [build](Self::build).
+/// Queries find their values with a binary search.
+///
+/// Searching an index that was not built returns
+/// [IndexNotBuilt](SearchEngineError::IndexNotBuilt). After it was built, new
+/// entries are inserted at their sorted position, which moves all larger
+/// values, so the index stays searchable but inserts get slower.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexSortedVec};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_age = SearchIndexSortedVec::<usize, u8>::new();
+/// index_age.insert(0, 17);
+/// index_age.insert(1, 42);
+/// index_age.insert(2, 31);
+/// index_age.build();
+///
+/// let result = index_age.search(&Query::InRange("<unused>".into(), "20".into(), "40".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![2])));
+/// ```
+#[derive(Clone)]
+pub struct SearchIndexSortedVec<P, V> {
+    entries: Vec<(V, HashSet<P>)>,
+    null_ids: HashSet<P>,
+    built: bool,
+}
+
+impl<P, V> Default for SearchIndexSortedVec<P, V>
+where
+    P: Eq + Hash + Clone + 'static,
+    V: Ord + FromStr + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, V> SearchIndexSortedVec<P, V>
+where
+    P: Eq + Hash + Clone + 'static,
+    V: Ord + FromStr + 'static,
+{
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries = SUPPORTS_EXACT
+        | SUPPORTS_INRANGE
+        | SUPPORTS_INRANGE_BOUNDS
+        | SUPPORTS_MINIMUM
+        | SUPPORTS_MAXIMUM
+        | SUPPORTS_OUTRANGE
+        | SUPPORTS_OUTRANGE_BOUNDS
+        | SUPPORTS_NULL;
+
+    /// Creates a new `SearchIndexSortedVec` that is not built yet.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexSortedVec;
+    ///
+    /// let index = SearchIndexSortedVec::<usize, i32>::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            null_ids: HashSet::new(),
+            built: false,
+        }
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// Before the index is [built](Self::build), the entry is only appended.
+    /// Afterwards it is inserted at its sorted position.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexSortedVec;
+    ///
+    /// let mut index = SearchIndexSortedVec::<usize, i32>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, 42);
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, 69);
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, 32);
+    /// // Sort the entries before searching:
+    /// index.build();
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: V) {
+        if !self.built {
+            self.entries
+                .push((attribute_value, HashSet::from([primary_id])));
+            return;
+        }
+        match self
+            .entries
+            .binary_search_by(|(value, _)| value.cmp(&attribute_value))
+        {
+            Ok(pos) => {
+                self.entries[pos].1.insert(primary_id);
+            }
+            Err(pos) => self
+                .entries
+                .insert(pos, (attribute_value, HashSet::from([primary_id]))),
+        }
+    }
+
+    /// Record that a row / primary id has no value for this attribute.
+    ///
+    /// The row will be matched by [IsNull](Query::IsNull) queries until a value
+    /// is inserted for it.
+    pub fn insert_null(&mut self, primary_id: P) {
+        self.null_ids.insert(primary_id);
+    }
+
+    /// Sort the inserted entries, so the index can be searched.
+    ///
+    /// Entries with the same value are merged. Building an index that was
+    /// already built has no effect.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngineError, SearchIndex, SearchIndexSortedVec, Query};
+    ///
+    /// let mut index = SearchIndexSortedVec::<usize, i32>::new();
+    /// index.insert(0, 42);
+    ///
+    /// let q = Query::Exact("<unused>".into(), "42".into());
+    /// assert_eq!(index.search(&q), Err(SearchEngineError::IndexNotBuilt));
+    /// index.build();
+    /// assert!(index.search(&q).is_ok());
+    /// ```
+    pub fn build(&mut self) {
+        if self.built {
+            return;
+        }
+        self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut merged: Vec<(V, HashSet<P>)> = Vec::with_capacity(self.entries.len());
+        for (value, primary_ids) in self.entries.drain(..) {
+            match merged.last_mut() {
+                Some((last, last_ids)) if *last == value => last_ids.extend(primary_ids),
+                _ => merged.push((value, primary_ids)),
+            }
+        }
+        merged.shrink_to_fit();
+        self.entries = merged;
+        self.built = true;
+    }
+
+    /// Returns true if the index was [built](Self::build) and can be searched.
+    pub fn is_built(&self) -> bool {
+        self.built
+    }
+
+    /// This internal function helps with searching for all kinds of
+    /// ranges and merging the result to a HashSet.
+    fn search_range(&self, range: impl RangeBounds<V>) -> HashSet<P> {
+        let mut result_set = HashSet::<P>::new();
+        self.extend_with_range(&mut result_set, range);
+        result_set
+    }
+
+    /// This internal function adds all rows of a range to an
+    /// existing HashSet without any intermediate sets.
+    fn extend_with_range(&self, result_set: &mut HashSet<P>, range: impl RangeBounds<V>) {
+        let start = match range.start_bound() {
+            Bound::Included(min) => self.entries.partition_point(|(value, _)| value < min),
+            Bound::Excluded(min) => self.entries.partition_point(|(value, _)| value <= min),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(max) => self.entries.partition_point(|(value, _)| value <= max),
+            Bound::Excluded(max) => self.entries.partition_point(|(value, _)| value < max),
+            Bound::Unbounded => self.entries.len(),
+        };
+        if start < end {
+            for (_, primary_ids) in self.entries[start..end].iter() {
+                result_set.extend(primary_ids.iter().cloned());
+            }
+        }
+    }
+}
+
+impl<P, V> SearchIndex<P> for SearchIndexSortedVec<P, V>
+where
+    P: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Ord + Clone + FromStr + ToString + Send + Sync + 'static,
+{
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        if !self.built {
+            return Err(SearchEngineError::IndexNotBuilt);
+        }
+        match query {
+            Query::Exact(_, value_str) => {
+                let value: V = string_to_payload_type(value_str)?;
+                Ok(self.search_range(&value..=&value))
+            }
+            Query::InRange(_, min_str, max_str) => {
+                let min: V = string_to_payload_type(min_str)?;
+                let max: V = string_to_payload_type(max_str)?;
+                Ok(self.search_range(min..=max))
+            }
+            Query::InRangeBounds(_, start_str, end_str) => {
+                match parse_bounds::<V>(start_str, end_str)? {
+                    Some(range) => Ok(self.search_range(range)),
+                    None => Ok(HashSet::new()),
+                }
+            }
+            Query::Minimum(_, min_str) => {
+                let min: V = string_to_payload_type(min_str)?;
+                Ok(self.search_range(min..))
+            }
+            Query::Maximum(_, max_str) => {
+                let max: V = string_to_payload_type(max_str)?;
+                Ok(self.search_range(..=max))
+            }
+            Query::OutRange(_, start_str, end_str) => {
+                let start: V = string_to_payload_type(start_str)?;
+                let end: V = string_to_payload_type(end_str)?;
+                if start > end {
+                    return Ok(HashSet::new());
+                }
+                let mut result_set = self.search_range(..&start);
+                self.extend_with_range(&mut result_set, (Bound::Excluded(&end), Bound::Unbounded));
+                Ok(result_set)
+            }
+            Query::OutRangeBounds(_, start_str, end_str) => {
+                let [below, above] = parse_out_bounds::<V>(start_str, end_str)?;
+                let mut result_set = self.search_range(below);
+                self.extend_with_range(&mut result_set, above);
+                Ok(result_set)
+            }
+            Query::IsNull(_) | Query::IsNotNull(_) => {
+                Ok(search_null(query, &self.null_ids, self.all_ids()))
+            }
+            _ => Err(SearchEngineError::UnsupportedQuery),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<V>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        TypeId::of::<V>() == TypeId::of::<String>()
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.entries
+            .iter()
+            .flat_map(|(_, primary_ids)| primary_ids.iter().cloned())
+            .collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.entries
+            .iter()
+            .flat_map(|(value, primary_ids)| {
+                let value = value.to_string();
+                primary_ids
+                    .iter()
+                    .map(move |id| (id.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, primary_ids)| primary_ids.contains(primary_id))
+            .map(|(value, _)| value.to_string())
+            .collect()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, string_to_payload_type(value)?);
+        Ok(())
+    }
+
+    fn validate_value(&self, value: &str) -> Result<()> {
+        string_to_payload_type::<V>(value).map(|_| ())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let mut removed = false;
+        self.entries.retain_mut(|(_, primary_ids)| {
+            removed |= primary_ids.remove(primary_id);
+            !primary_ids.is_empty()
+        });
+        removed |= self.null_ids.remove(primary_id);
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index_sorted_vec_ranges() {
+        let mut index = SearchIndexSortedVec::<usize, i32>::new();
+        for (id, value) in [(0, 30), (1, 10), (2, 20), (3, 10), (4, -5), (0, 40)] {
+            index.insert(id, value);
+        }
+        let exact = Query::Exact("<not used>".into(), "10".into());
+        assert_eq!(index.search(&exact), Err(SearchEngineError::IndexNotBuilt));
+        assert_eq!(
+            index.search(&Query::Prefix("<not used>".into(), "1".into())),
+            Err(SearchEngineError::UnsupportedQuery)
+        );
+
+        index.build();
+        assert!(index.is_built());
+        assert_eq!(index.search(&exact), Ok(HashSet::from_iter(vec![1, 3])));
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "0".into(),
+            "30".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2, 3])));
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "30".into(),
+            "0".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::new()));
+        let result = index.search(&Query::InRangeBounds(
+            "<not used>".into(),
+            Bound::Excluded("10".into()),
+            Bound::Excluded("40".into()),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 2])));
+        let result = index.search(&Query::Minimum("<not used>".into(), "35".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+        let result = index.search(&Query::Maximum("<not used>".into(), "9".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![4])));
+        let result = index.search(&Query::OutRange(
+            "<not used>".into(),
+            "10".into(),
+            "30".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0, 4])));
+
+        // Inserts after building keep the index sorted.
+        index.insert(5, 15);
+        index.insert(6, 10);
+        index.insert(7, 50);
+        let result = index.search(&Query::InRange(
+            "<not used>".into(),
+            "10".into(),
+            "15".into(),
+        ));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![1, 3, 5, 6])));
+        assert_eq!(
+            index.values_of(&0),
+            vec!["30".to_string(), "40".to_string()]
+        );
+        assert!(index
+            .entries
+            .windows(2)
+            .all(|window| window[0].0 < window[1].0));
+    }
+}
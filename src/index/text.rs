@@ -0,0 +1,338 @@
+use super::{check_supported_query, SearchIndex};
+use crate::{
+    Query, Result, SupportedQueries, SUPPORTS_ALL_TOKENS, SUPPORTS_ANY_TOKENS, SUPPORTS_EXACT,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
+
+/// Type of the closure that is used by [SearchIndexText] to split texts into tokens.
+type Tokenizer = dyn Fn(&str) -> Vec<String> + Send + Sync;
+
+/// SearchIndexText is an inverted index that splits texts into tokens to find
+/// rows by the words of a free text attribute, like a description or a comment.
+///
+/// Every inserted text is split into tokens, and each token is mapped to the
+/// rows that have it. By default a token is a run of alphanumeric chars,
+/// lowercased, so `"Hello, World!"` has the tokens `hello` and `world`. A
+/// different tokenizer can be passed to [new_with_tokenizer](Self::new_with_tokenizer).
+/// Tokens in the [stopword list](Self::set_stopwords) are dropped.
+///
+/// The values of queries are split with the same tokenizer:
+/// - [Exact](Query::Exact) matches rows with the token of the query value. A
+///   value that is not exactly one token matches no rows.
+/// - [AllTokens](Query::AllTokens) matches rows with all tokens of the query text.
+/// - [AnyTokens](Query::AnyTokens) matches rows with at least one token of the
+///   query text.
+///
+/// A query text without tokens, for example one with only stopwords, matches no rows.
+///
+/// # Example
+/// ```
+/// use attribute_search_engine::{SearchIndex, SearchIndexText};
+/// use std::collections::HashSet;
+/// use attribute_search_engine::Query;
+///
+/// let mut index_description = SearchIndexText::<usize>::new();
+/// index_description.insert(0, "A small red Bike".into());
+/// index_description.insert(1, "Red car, barely used".into());
+/// index_description.insert(2, "Bike lights".into());
+///
+/// let result = index_description.search(&Query::AllTokens("<unused>".into(), "red bike".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+///
+/// let result = index_description.search(&Query::AnyTokens("<unused>".into(), "red bike".into()));
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0, 1, 2])));
+/// ```
+pub struct SearchIndexText<P> {
+    tokenizer: Arc<Tokenizer>,
+    stopwords: HashSet<String>,
+    tokens: HashMap<String, HashSet<P>>,
+    values: HashMap<P, HashSet<String>>,
+}
+
+// The tokenizer is shared between clones.
+impl<P: Clone> Clone for SearchIndexText<P> {
+    fn clone(&self) -> Self {
+        Self {
+            tokenizer: Arc::clone(&self.tokenizer),
+            stopwords: self.stopwords.clone(),
+            tokens: self.tokens.clone(),
+            values: self.values.clone(),
+        }
+    }
+}
+
+impl<P: Eq + Hash + Clone> Default for SearchIndexText<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Eq + Hash + Clone> SearchIndexText<P> {
+    /// All queries that are supported by this index.
+    pub const SUPPORTED_QUERIES: SupportedQueries =
+        SUPPORTS_EXACT | SUPPORTS_ALL_TOKENS | SUPPORTS_ANY_TOKENS;
+
+    /// Creates a new `SearchIndexText` that splits texts into lowercased words.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexText;
+    ///
+    /// let index = SearchIndexText::<usize>::new();
+    /// assert_eq!(index.tokenize("Hello, World!"), vec!["hello", "world"]);
+    /// ```
+    pub fn new() -> Self {
+        Self::new_with_tokenizer(split_words)
+    }
+
+    /// Creates a new `SearchIndexText` that splits texts with a closure.
+    ///
+    /// The closure is used for inserted texts and for the values of queries,
+    /// so it should also normalize the tokens, for example by lowercasing them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchIndex, SearchIndexText, Query};
+    /// use std::collections::HashSet;
+    ///
+    /// // Split tags on commas and ignore surrounding whitespace.
+    /// let mut index = SearchIndexText::<usize>::new_with_tokenizer(|text| {
+    ///     text.split(',').map(|tag| tag.trim().to_owned()).collect()
+    /// });
+    /// index.insert(0, "rust, search engine".into());
+    ///
+    /// let result = index.search(&Query::Exact("<unused>".into(), "search engine".into()));
+    /// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+    /// ```
+    pub fn new_with_tokenizer<F>(tokenizer: F) -> Self
+    where
+        F: Fn(&str) -> Vec<String> + Send + Sync + 'static,
+    {
+        Self {
+            tokenizer: Arc::new(tokenizer),
+            stopwords: HashSet::new(),
+            tokens: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Set the tokens that are dropped from texts and queries.
+    ///
+    /// The stopwords are compared to the tokens after tokenization, so with the
+    /// default tokenizer they must be lowercase. Stopwords that are already in
+    /// the index are removed from it.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexText;
+    ///
+    /// let mut index = SearchIndexText::<usize>::new();
+    /// index.set_stopwords(["a", "the"]);
+    /// assert_eq!(index.tokenize("The Cat and a Dog"), vec!["cat", "and", "dog"]);
+    /// ```
+    pub fn set_stopwords<S: Into<String>>(&mut self, stopwords: impl IntoIterator<Item = S>) {
+        self.stopwords = stopwords.into_iter().map(Into::into).collect();
+        self.tokens
+            .retain(|token, _| !self.stopwords.contains(token));
+    }
+
+    /// Split a text into tokens like the index does for inserted texts and
+    /// queries. Stopwords are already removed.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        (self.tokenizer)(text)
+            .into_iter()
+            .filter(|token| !self.stopwords.contains(token))
+            .collect()
+    }
+
+    /// Insert a new entry in the index.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::SearchIndexText;
+    ///
+    /// let mut index = SearchIndexText::<usize>::new();
+    ///
+    /// // You insert an entry by giving a row / primary id and an attribute value:
+    /// index.insert(123, "Hello World".into());
+    /// // The same row / primary id can have multiple attributes assigned:
+    /// index.insert(123, "Lorem ipsum".into());
+    /// // Add as much entries as you want for as many rows you want:
+    /// index.insert(124, "Hello Rust".into());
+    /// ```
+    pub fn insert(&mut self, primary_id: P, attribute_value: String) {
+        for token in self.tokenize(&attribute_value) {
+            self.tokens
+                .entry(token)
+                .or_default()
+                .insert(primary_id.clone());
+        }
+        self.values
+            .entry(primary_id)
+            .or_default()
+            .insert(attribute_value);
+    }
+
+    /// Returns all rows that have every token of the text.
+    fn search_all_tokens(&self, text: &str) -> HashSet<P> {
+        let tokens: HashSet<String> = self.tokenize(text).into_iter().collect();
+        let mut token_sets = Vec::with_capacity(tokens.len());
+        for token in tokens.iter() {
+            match self.tokens.get(token) {
+                Some(primary_ids) => token_sets.push(primary_ids),
+                None => return HashSet::new(),
+            }
+        }
+        token_sets.sort_unstable_by_key(|primary_ids| primary_ids.len());
+        let Some((smallest, others)) = token_sets.split_first() else {
+            return HashSet::new();
+        };
+        smallest
+            .iter()
+            .filter(|id| others.iter().all(|primary_ids| primary_ids.contains(id)))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns all rows that have at least one token of the text.
+    fn search_any_tokens(&self, text: &str) -> HashSet<P> {
+        self.tokenize(text)
+            .iter()
+            .filter_map(|token| self.tokens.get(token))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+impl<P: Eq + Hash + Clone + Send + Sync + 'static> SearchIndex<P> for SearchIndexText<P> {
+    fn search(&self, query: &Query) -> Result<HashSet<P>> {
+        check_supported_query(query, Self::SUPPORTED_QUERIES)?;
+        match query {
+            Query::Exact(_, value) => match self.tokenize(value).as_slice() {
+                [token] => Ok(self.tokens.get(token).cloned().unwrap_or_default()),
+                _ => Ok(HashSet::new()),
+            },
+            Query::AllTokens(_, text) => Ok(self.search_all_tokens(text)),
+            Query::AnyTokens(_, text) => Ok(self.search_any_tokens(text)),
+            _ => unreachable!("unsupported queries are rejected before"),
+        }
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        Self::SUPPORTED_QUERIES
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<P>> {
+        Box::new(self.clone())
+    }
+
+    fn value_type_name(&self) -> &'static str {
+        std::any::type_name::<String>()
+    }
+
+    fn has_string_values(&self) -> bool {
+        true
+    }
+
+    fn all_ids(&self) -> HashSet<P> {
+        self.values.keys().cloned().collect()
+    }
+
+    fn supports_entries(&self) -> bool {
+        true
+    }
+
+    fn entries(&self) -> Vec<(P, String)> {
+        self.values
+            .iter()
+            .flat_map(|(id, values)| values.iter().map(|value| (id.clone(), value.clone())))
+            .collect()
+    }
+
+    fn values_of(&self, primary_id: &P) -> Vec<String> {
+        self.values
+            .get(primary_id)
+            .map(|values| values.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn insert_str(&mut self, primary_id: P, value: &str) -> Result<()> {
+        self.insert(primary_id, value.to_owned());
+        Ok(())
+    }
+
+    fn remove_all(&mut self, primary_id: &P) -> bool {
+        let Some(values) = self.values.remove(primary_id) else {
+            return false;
+        };
+        for value in values {
+            for token in self.tokenize(&value) {
+                if let Some(primary_ids) = self.tokens.get_mut(&token) {
+                    primary_ids.remove(primary_id);
+                    if primary_ids.is_empty() {
+                        self.tokens.remove(&token);
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Splits a text into lowercased runs of alphanumeric chars.
+fn split_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words_unicode() {
+        assert_eq!(
+            split_words("Grüße aus Köln! Ça va? 東京-2024"),
+            ["grüße", "aus", "köln", "ça", "va", "東京", "2024"]
+        );
+        assert!(split_words(" ,.- ").is_empty());
+    }
+
+    #[test]
+    fn search_index_text_tokens() {
+        let mut index = SearchIndexText::<usize>::new();
+        index.set_stopwords(["the", "a"]);
+        index.insert(0, "The quick brown fox".into());
+        index.insert(1, "A lazy brown dog".into());
+        index.insert(2, "Quick thinking".into());
+        index.insert(2, "the fox den".into());
+
+        let all = |text: &str| index.search(&Query::AllTokens("<not used>".into(), text.into()));
+        assert_eq!(all("brown"), Ok(HashSet::from_iter(vec![0, 1])));
+        assert_eq!(all("QUICK fox"), Ok(HashSet::from_iter(vec![0, 2])));
+        assert_eq!(all("quick dog"), Ok(HashSet::new()));
+        assert_eq!(all("the"), Ok(HashSet::new()));
+        assert_eq!(all(""), Ok(HashSet::new()));
+
+        let any = |text: &str| index.search(&Query::AnyTokens("<not used>".into(), text.into()));
+        assert_eq!(any("dog, den"), Ok(HashSet::from_iter(vec![1, 2])));
+        assert_eq!(any("cat"), Ok(HashSet::new()));
+
+        let exact = |text: &str| index.search(&Query::Exact("<not used>".into(), text.into()));
+        assert_eq!(exact("Fox"), Ok(HashSet::from_iter(vec![0, 2])));
+        assert_eq!(exact("brown fox"), Ok(HashSet::new()));
+        assert_eq!(exact("the"), Ok(HashSet::new()));
+
+        assert!(index.remove_all(&2));
+        let result = index.search(&Query::AllTokens("<not used>".into(), "fox".into()));
+        assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+        assert!(!index.tokens.contains_key("den"));
+    }
+}
@@ -56,6 +56,22 @@
 //! - [SearchIndexHashMap], backed by a HashMap for quick exact queries.
 //! - [SearchIndexPrefixTree], backed by a prefix tree to find rows just by the prefix of an attribute.
 //! - [SearchIndexBTreeRange], backed by a BTreeMap to find rows with an attribute by providing a range.
+//! - [SearchIndexCaseInsensitive], backed by a HashMap for exact queries that ignore the case.
+//! - [SearchIndexCaseInsensitivePrefixTree], backed by a prefix tree for exact and prefix queries that ignore the case.
+//! - [SearchIndexFunction], backed by a closure to compute matching rows on demand.
+//! - [SearchIndexAdaptive], backed by a HashMap that builds an ordered set on the first range query.
+//! - [SearchIndexBucketed], backed by HashMaps to find rows by a number or by its remainder.
+//! - [SearchIndexNGram], backed by character n-grams to find rows by a substring of an attribute.
+//! - [SearchIndexIpv4], backed by a BTreeMap to find rows by IPv4 addresses, ranges and CIDR networks.
+//! - [SearchIndexF64Range], backed by a BTreeMap to find rows with a floating point attribute by providing a range.
+//! - [SearchIndexBytePrefixTree], backed by a prefix tree of bytes to find rows by the prefix of a binary attribute.
+//! - [SearchIndexText], backed by an inverted index of tokens to find rows by the words of a free text.
+//! - [SearchIndexSortedVec], backed by a sorted Vec that is built once for fast range queries.
+//! - `SearchIndexRegexScan`, backed by a list of values that is scanned for regular expressions.
+//!   It requires the `regex` feature.
+//! - `SearchIndexBitmap`, backed by roaring bitmaps for dense `u32` row ids. With
+//!   `SearchEngine::search_bitmap`, Or, And and Exclude queries are computed as bitmap
+//!   operations. It requires the `roaring` feature.
 //!
 //! The [SearchEngine] can also work with custom search indices as long as they implement the
 //! [SearchIndex] trait.
@@ -67,21 +83,45 @@
 //!
 //! The following table shows which Query variant is supported by which index type.
 //!
-//! | [Query]                     | [SearchIndexHashMap] | [SearchIndexPrefixTree] | [SearchIndexBTreeRange] |
-//! |-----------------------------|----------------------|-------------------------|-------------------------|
-//! | [Exact](Query::Exact)       | Yes ✔️               | Yes ✔️                 | Yes ✔️                 |
-//! | [Prefix](Query::Prefix)     | No  ❌               | Yes ✔️                 | No  ❌                 |
-//! | [InRange](Query::InRange)   | No  ❌               | No  ❌                 | Yes ✔️                 |
-//! | [OutRange](Query::OutRange) | No  ❌               | No  ❌                 | Yes ✔️                 |
-//! | [Minimum](Query::Minimum)   | No  ❌               | No  ❌                 | Yes ✔️                 |
-//! | [Maximum](Query::Maximum)   | No  ❌               | No  ❌                 | Yes ✔️                 |
-//! | [Or](Query::Or)             | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
-//! | [And](Query::And)           | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
-//! | [Exclude](Query::Exclude)   | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
-//!
-//! [^searchengine]: Or, And & Exclude are only supported by [SearchEngine] and not
+//! | [Query]                               | [SearchIndexHashMap] | [SearchIndexPrefixTree] | [SearchIndexBTreeRange] |
+//! |---------------------------------------|----------------------|-------------------------|-------------------------|
+//! | [Exact](Query::Exact)                 | Yes ✔️               | Yes ✔️                 | Yes ✔️                 |
+//! | [Prefix](Query::Prefix)               | No  ❌               | Yes ✔️                 | No  ❌                 |
+//! | [Suffix](Query::Suffix)               | No  ❌               | Yes[^suffix] ✔️        | No  ❌                 |
+//! | [Contains](Query::Contains)           | No[^contains] ❌     | No[^contains] ❌       | No[^contains] ❌       |
+//! | [AllTokens](Query::AllTokens)         | No[^tokens] ❌       | No[^tokens] ❌         | No[^tokens] ❌         |
+//! | [AnyTokens](Query::AnyTokens)         | No[^tokens] ❌       | No[^tokens] ❌         | No[^tokens] ❌         |
+//! | [Fuzzy](Query::Fuzzy)                 | No  ❌               | Yes ✔️                 | No  ❌                 |
+//! | [Regex](Query::Regex)                 | No[^regex] ❌        | No[^regex] ❌          | No[^regex] ❌          |
+//! | [Pattern](Query::Pattern)             | No  ❌               | Yes ✔️                 | No  ❌                 |
+//! | [InRange](Query::InRange)             | No  ❌               | No  ❌                 | Yes ✔️                 |
+//! | [InRangeBounds](Query::InRangeBounds) | No  ❌               | No  ❌                 | Yes ✔️                 |
+//! | [OutRange](Query::OutRange)           | No  ❌               | No  ❌                 | Yes ✔️                 |
+//! | [OutRangeBounds](Query::OutRangeBounds) | No  ❌             | No  ❌                 | Yes ✔️                 |
+//! | [Minimum](Query::Minimum)             | No  ❌               | No  ❌                 | Yes ✔️                 |
+//! | [Maximum](Query::Maximum)             | No  ❌               | No  ❌                 | Yes ✔️                 |
+//! | [LengthInRange](Query::LengthInRange) | Yes ✔️               | Yes ✔️                 | No  ❌                 |
+//! | [IsNull](Query::IsNull)               | Yes ✔️               | Yes ✔️                 | Yes ✔️                 |
+//! | [IsNotNull](Query::IsNotNull)         | Yes ✔️               | Yes ✔️                 | Yes ✔️                 |
+//! | [All](Query::All)                     | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
+//! | [Not](Query::Not)                     | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
+//! | [Or](Query::Or)                       | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
+//! | [And](Query::And)                     | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
+//! | [AtLeast](Query::AtLeast)             | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
+//! | [Exclude](Query::Exclude)             | na[^searchengine] 🔷 | na[^searchengine] 🔷   | na[^searchengine] 🔷   |
+//!
+//! [^searchengine]: All, Not, Or, And, AtLeast & Exclude are only supported by [SearchEngine] and not
 //!                  the indices.
 //!
+//! [^suffix]: Only if suffix search is enabled with
+//!            [SearchIndexPrefixTree::enable_suffix_search].
+//!
+//! [^contains]: Contains queries are supported by [SearchIndexNGram].
+//!
+//! [^tokens]: AllTokens and AnyTokens queries are supported by [SearchIndexText].
+//!
+//! [^regex]: Regex queries are supported by `SearchIndexRegexScan` with the `regex` feature.
+//!
 //! ## Query String Syntax
 //!
 //! The SearchEngine provides the function `query_from_str` that can be used to create queries
@@ -104,13 +144,44 @@
 //! [SearchEngine::query_from_str] function.
 //!
 
+#[cfg(feature = "roaring")]
+mod bitmap_search;
+mod change_log;
+mod compiled_query;
 mod engine;
+mod engine_snapshot;
 mod error;
+mod federated_engine;
 mod index;
 mod query;
+mod query_catalog;
 mod query_lexer;
+mod query_optimizer;
+mod query_parser;
+mod query_recorder;
+mod refinable_search;
+mod result_format;
+mod result_snapshot;
+mod searchable;
 
+pub use change_log::{ChangeSet, Checkpoint};
+pub use compiled_query::*;
 pub use engine::*;
+pub use engine_snapshot::*;
 pub use error::*;
+pub use federated_engine::*;
 pub use index::*;
 pub use query::*;
+pub use query_catalog::*;
+pub use query_parser::{
+    ParseNote, ParseReason, ParseWarning, ParseWarningReason, QueryParserOptions,
+};
+pub use query_recorder::*;
+pub use refinable_search::*;
+pub use result_snapshot::*;
+pub use searchable::*;
+
+/// Derive macro for the [Searchable] trait, see there for the
+/// supported field types. Requires the `derive` feature.
+#[cfg(feature = "derive")]
+pub use attribute_search_engine_derive::Searchable;
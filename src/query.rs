@@ -1,3 +1,5 @@
+use std::{borrow::Cow, fmt, ops::Bound};
+
 /// Query is a recursive datatype that describes a
 /// query to a search index or engine.
 ///
@@ -21,7 +23,11 @@
 ///     ],
 /// );
 /// ```
-#[derive(Debug, PartialEq)]
+///
+/// New kinds of queries can be added in minor releases, so a `match` on
+/// a query needs a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Query {
     /// Only matches if the attribute has exactly the value as the query.
     Exact(String, String),
@@ -29,35 +35,113 @@ pub enum Query {
     /// Matches if the attribute starts with the value of the query.
     Prefix(String, String),
 
+    /// Matches if the attribute ends with the value of the query.
+    Suffix(String, String),
+
+    /// Matches if the attribute contains the value of the query anywhere.
+    Contains(String, String),
+
+    /// Matches if the attribute contains every token of the query text.
+    ///
+    /// The index splits the query text into tokens the same way it splits
+    /// its values, for example into lowercased words.
+    AllTokens(String, String),
+
+    /// Matches if the attribute contains at least one token of the query text.
+    ///
+    /// The query text is split into tokens like for [AllTokens](Query::AllTokens).
+    AnyTokens(String, String),
+
+    /// Matches if the attribute has a Levenshtein distance of at most the
+    /// third value to the query value. Every inserted, removed or replaced
+    /// character counts as one.
+    Fuzzy(String, String, u8),
+
+    /// Matches if the attribute matches a regular expression anywhere.
+    /// Use `^` and `$` to match the whole value.
+    Regex(String, String),
+
+    /// Matches if the attribute matches a pattern where each `*` stands
+    /// for any sequence of characters, including an empty one.
+    /// For example `report*.pdf` matches `report.pdf` and `report-2024.pdf`.
+    Pattern(String, String),
+
     /// Matches if the attribute is in the range of the two query values.
     /// First is minimum, seconds is maximum, both inclusive.
     InRange(String, String, String),
 
+    /// Matches if the attribute is in the range of the two query bounds.
+    /// First is the lower bound, second is the upper bound. Each of them can
+    /// be inclusive, exclusive or unbounded.
+    InRangeBounds(String, Bound<String>, Bound<String>),
+
     /// Matches if the attribute is NOT in the range of the two query values.
     /// First is the start of the range, seconds is the end. A value that is
     /// equal to the start or the end is considered in range and will not be
     /// returned.
     OutRange(String, String, String),
 
+    /// Matches if the attribute is below the first query bound or above the
+    /// second query bound. Each of them can be inclusive, exclusive or unbounded,
+    /// where an inclusive bound matches a value that is equal to it and an
+    /// unbounded bound matches all values.
+    ///
+    /// Unlike [OutRange](Query::OutRange), the two parts may overlap, so this
+    /// query returns the same rows as an [Or](Query::Or) of a
+    /// [Maximum](Query::Maximum) and a [Minimum](Query::Minimum) query if both
+    /// bounds are inclusive.
+    OutRangeBounds(String, Bound<String>, Bound<String>),
+
     /// Matches if the attribute is at least as high/big as the query value.
     Minimum(String, String),
 
     /// Matches if the attribute is at most as high/big as the query value.
     Maximum(String, String),
 
+    /// Matches if the length of the attribute value in characters is in the range
+    /// of the two query values. First is minimum, second is maximum, both inclusive.
+    ///
+    /// Indices usually have to check every distinct value to answer this query.
+    LengthInRange(String, usize, usize),
+
+    /// Matches all rows that were recorded without a value for the attribute.
+    /// Rows that were never added to the index don't match.
+    IsNull(String),
+
+    /// Matches all rows that have at least one value for the attribute.
+    IsNotNull(String),
+
+    /// Matches all rows that have a value in at least one index of the engine.
+    ///
+    /// This is the identity of [And](Query::And): an And that contains All
+    /// returns the same rows as without it.
+    All,
+
+    /// Matches all rows of [All](Query::All) that don't match the subquery.
+    ///
+    /// The set of all rows is only known to a
+    /// [SearchEngine](crate::engine::SearchEngine), so indices return
+    /// [UnsupportedQuery](crate::error::SearchEngineError::UnsupportedQuery)
+    /// for this query.
+    Not(Box<Query>),
+
     /// Matches if at least one of the subqueries matches.
     Or(Vec<Query>),
 
     /// Only matches if all subqueries match.
     And(Vec<Query>),
 
+    /// Matches if at least the given number of subqueries match.
+    /// A subquery that is listed twice also counts twice.
+    AtLeast(usize, Vec<Query>),
+
     /// Removed all matches from the first query that appear in at least
     /// on of the matches of the query vector.
     Exclude(Box<Query>, Vec<Query>),
 }
 
 /// Bitmask type for queries that are supported by an index.
-pub type SupportedQueries = u8;
+pub type SupportedQueries = u32;
 
 /// Signals that an index supports [Exact queries](Query::Exact).
 pub const SUPPORTS_EXACT: SupportedQueries = 1 << 0;
@@ -76,3 +160,373 @@ pub const SUPPORTS_MINIMUM: SupportedQueries = 1 << 4;
 
 /// Signals that an index supports [Maximum queries](Query::Maximum).
 pub const SUPPORTS_MAXIMUM: SupportedQueries = 1 << 5;
+
+/// Signals that an index supports [Pattern queries](Query::Pattern).
+pub const SUPPORTS_PATTERN: SupportedQueries = 1 << 6;
+
+/// Signals that an index supports [IsNull](Query::IsNull) and
+/// [IsNotNull](Query::IsNotNull) queries.
+pub const SUPPORTS_NULL: SupportedQueries = 1 << 7;
+
+/// Signals that an index supports [LengthInRange queries](Query::LengthInRange).
+pub const SUPPORTS_LENGTH: SupportedQueries = 1 << 8;
+
+/// Signals that an index supports [InRangeBounds queries](Query::InRangeBounds).
+pub const SUPPORTS_INRANGE_BOUNDS: SupportedQueries = 1 << 9;
+
+/// Signals that an index supports [Suffix queries](Query::Suffix).
+pub const SUPPORTS_SUFFIX: SupportedQueries = 1 << 10;
+
+/// Signals that an index supports [Contains queries](Query::Contains).
+pub const SUPPORTS_CONTAINS: SupportedQueries = 1 << 11;
+
+/// Signals that an index supports [Regex queries](Query::Regex).
+pub const SUPPORTS_REGEX: SupportedQueries = 1 << 12;
+
+/// Signals that an index supports [Fuzzy queries](Query::Fuzzy).
+pub const SUPPORTS_FUZZY: SupportedQueries = 1 << 13;
+
+/// Signals that an index supports [AllTokens queries](Query::AllTokens).
+pub const SUPPORTS_ALL_TOKENS: SupportedQueries = 1 << 14;
+
+/// Signals that an index supports [AnyTokens queries](Query::AnyTokens).
+pub const SUPPORTS_ANY_TOKENS: SupportedQueries = 1 << 15;
+
+/// Signals that an index supports [OutRangeBounds queries](Query::OutRangeBounds).
+pub const SUPPORTS_OUTRANGE_BOUNDS: SupportedQueries = 1 << 16;
+
+impl Query {
+    /// Creates an [Exact](Query::Exact) query.
+    ///
+    /// The attribute and the value can be a `&str`, a `String` or a `Cow<str>`.
+    /// Borrowed strings are allocated once, owned strings are moved into
+    /// the query without copying them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::Query;
+    /// use std::borrow::Cow;
+    ///
+    /// let value: Cow<str> = Cow::Borrowed("Alice");
+    /// assert_eq!(
+    ///     Query::exact("name", value),
+    ///     Query::Exact("name".into(), "Alice".into())
+    /// );
+    /// ```
+    pub fn exact<'a>(attribute: impl Into<Cow<'a, str>>, value: impl Into<Cow<'a, str>>) -> Self {
+        Query::Exact(attribute.into().into_owned(), value.into().into_owned())
+    }
+
+    /// Creates a [Prefix](Query::Prefix) query.
+    ///
+    /// The attribute and the value can be a `&str`, a `String` or a `Cow<str>`,
+    /// with the same allocation behavior as [exact](Query::exact).
+    pub fn prefix<'a>(attribute: impl Into<Cow<'a, str>>, value: impl Into<Cow<'a, str>>) -> Self {
+        Query::Prefix(attribute.into().into_owned(), value.into().into_owned())
+    }
+
+    /// Creates an [InRangeBounds](Query::InRangeBounds) query with two
+    /// exclusive bounds.
+    ///
+    /// This is the exclusive counterpart of [InRange](Query::InRange): values
+    /// equal to `min` or `max` don't match, so `min == max` matches nothing.
+    /// For half-open intervals, build an InRangeBounds query directly.
+    /// Indices signal support with [SUPPORTS_INRANGE_BOUNDS].
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::Query;
+    /// use std::ops::Bound;
+    ///
+    /// assert_eq!(
+    ///     Query::in_range_exclusive("age", "20", "30"),
+    ///     Query::InRangeBounds(
+    ///         "age".into(),
+    ///         Bound::Excluded("20".into()),
+    ///         Bound::Excluded("30".into())
+    ///     )
+    /// );
+    /// ```
+    pub fn in_range_exclusive<'a>(
+        attribute: impl Into<Cow<'a, str>>,
+        min: impl Into<Cow<'a, str>>,
+        max: impl Into<Cow<'a, str>>,
+    ) -> Self {
+        Query::InRangeBounds(
+            attribute.into().into_owned(),
+            Bound::Excluded(min.into().into_owned()),
+            Bound::Excluded(max.into().into_owned()),
+        )
+    }
+
+    /// Returns the query or [All](Query::All) if there is none.
+    ///
+    /// This is meant for optional filters: an absent filter doesn't
+    /// constrain the results of the [And](Query::And) it is part of.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::Query;
+    ///
+    /// let name: Option<&str> = None;
+    /// let q = Query::And(vec![
+    ///     Query::optional(Some(Query::exact("city", "Berlin"))),
+    ///     Query::optional(name.map(|name| Query::exact("name", name))),
+    /// ]);
+    /// assert_eq!(q, Query::And(vec![Query::exact("city", "Berlin"), Query::All]));
+    /// ```
+    pub fn optional(query: Option<Query>) -> Self {
+        query.unwrap_or(Query::All)
+    }
+
+    /// Combines two queries into a single [Exclude](Query::Exclude) query.
+    ///
+    /// The bases of both queries are combined with [And](Query::And) and the
+    /// excluded queries of both are concatenated, so fragments of a query can
+    /// be parsed independently and assembled later. A query that is not an
+    /// Exclude is used as a base without excluded queries. If neither query
+    /// excludes anything, only the And of the bases is returned, like
+    /// [SearchEngine::query_from_str](crate::SearchEngine::query_from_str)
+    /// does for query strings without `-` selectors.
+    ///
+    /// Bases that are an And are flattened into the new And. An And without
+    /// subqueries, like the base of a query string with only `-` selectors,
+    /// therefore doesn't restrict the merged query.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::Query;
+    ///
+    /// let a = Query::Exclude(
+    ///     Box::new(Query::And(vec![Query::exact("city", "Berlin")])),
+    ///     vec![Query::exact("pet", "Cat")],
+    /// );
+    /// let b = Query::Exclude(Box::new(Query::And(vec![])), vec![Query::exact("pet", "Dog")]);
+    /// assert_eq!(
+    ///     a.merge_excludes(b),
+    ///     Query::Exclude(
+    ///         Box::new(Query::And(vec![Query::exact("city", "Berlin")])),
+    ///         vec![Query::exact("pet", "Cat"), Query::exact("pet", "Dog")],
+    ///     )
+    /// );
+    /// ```
+    pub fn merge_excludes(self, other: Query) -> Query {
+        let mut bases = vec![];
+        let mut excludes = vec![];
+        for query in [self, other] {
+            let base = match query {
+                Query::Exclude(base, exclude) => {
+                    excludes.extend(exclude);
+                    *base
+                }
+                query => query,
+            };
+            match base {
+                Query::And(vec) => bases.extend(vec),
+                base => bases.push(base),
+            }
+        }
+        if excludes.is_empty() {
+            Query::And(bases)
+        } else {
+            Query::Exclude(Box::new(Query::And(bases)), excludes)
+        }
+    }
+
+    /// Returns the name of the attribute of a leaf query or `None`
+    /// if the query combines other queries.
+    pub fn attribute(&self) -> Option<&str> {
+        match self {
+            Query::Exact(attr, _)
+            | Query::Prefix(attr, _)
+            | Query::Suffix(attr, _)
+            | Query::Contains(attr, _)
+            | Query::AllTokens(attr, _)
+            | Query::AnyTokens(attr, _)
+            | Query::Regex(attr, _)
+            | Query::Fuzzy(attr, _, _)
+            | Query::Pattern(attr, _)
+            | Query::InRange(attr, _, _)
+            | Query::InRangeBounds(attr, _, _)
+            | Query::OutRange(attr, _, _)
+            | Query::OutRangeBounds(attr, _, _)
+            | Query::Minimum(attr, _)
+            | Query::Maximum(attr, _)
+            | Query::LengthInRange(attr, _, _)
+            | Query::IsNull(attr)
+            | Query::IsNotNull(attr) => Some(attr),
+            Query::All
+            | Query::Not(_)
+            | Query::Or(_)
+            | Query::And(_)
+            | Query::AtLeast(_, _)
+            | Query::Exclude(_, _) => None,
+        }
+    }
+
+    /// Returns a copy of a leaf query with all of its values mapped by a function.
+    /// Queries that combine other queries are returned unchanged.
+    pub(crate) fn map_values(&self, f: impl Fn(&str) -> String) -> Query {
+        match self {
+            Query::Exact(attr, value) => Query::Exact(attr.clone(), f(value)),
+            Query::Prefix(attr, value) => Query::Prefix(attr.clone(), f(value)),
+            Query::Suffix(attr, value) => Query::Suffix(attr.clone(), f(value)),
+            Query::Contains(attr, value) => Query::Contains(attr.clone(), f(value)),
+            Query::AllTokens(attr, value) => Query::AllTokens(attr.clone(), f(value)),
+            Query::AnyTokens(attr, value) => Query::AnyTokens(attr.clone(), f(value)),
+            Query::Regex(attr, value) => Query::Regex(attr.clone(), f(value)),
+            Query::Fuzzy(attr, value, max_distance) => {
+                Query::Fuzzy(attr.clone(), f(value), *max_distance)
+            }
+            Query::Pattern(attr, value) => Query::Pattern(attr.clone(), f(value)),
+            Query::InRange(attr, min, max) => Query::InRange(attr.clone(), f(min), f(max)),
+            Query::InRangeBounds(attr, start, end) => Query::InRangeBounds(
+                attr.clone(),
+                start.as_ref().map(|value| f(value)),
+                end.as_ref().map(|value| f(value)),
+            ),
+            Query::OutRange(attr, start, end) => Query::OutRange(attr.clone(), f(start), f(end)),
+            Query::OutRangeBounds(attr, start, end) => Query::OutRangeBounds(
+                attr.clone(),
+                start.as_ref().map(|value| f(value)),
+                end.as_ref().map(|value| f(value)),
+            ),
+            Query::Minimum(attr, value) => Query::Minimum(attr.clone(), f(value)),
+            Query::Maximum(attr, value) => Query::Maximum(attr.clone(), f(value)),
+            query => query.clone(),
+        }
+    }
+
+    /// Returns the [SupportedQueries] flag that an index must report
+    /// to be able to process this query directly.
+    ///
+    /// Queries that can only be processed by a
+    /// [SearchEngine](crate::engine::SearchEngine) return `0`.
+    pub(crate) fn supported_flag(&self) -> SupportedQueries {
+        match self {
+            Query::Exact(_, _) => SUPPORTS_EXACT,
+            Query::Prefix(_, _) => SUPPORTS_PREFIX,
+            Query::Suffix(_, _) => SUPPORTS_SUFFIX,
+            Query::Contains(_, _) => SUPPORTS_CONTAINS,
+            Query::AllTokens(_, _) => SUPPORTS_ALL_TOKENS,
+            Query::AnyTokens(_, _) => SUPPORTS_ANY_TOKENS,
+            Query::Regex(_, _) => SUPPORTS_REGEX,
+            Query::Fuzzy(_, _, _) => SUPPORTS_FUZZY,
+            Query::Pattern(_, _) => SUPPORTS_PATTERN,
+            Query::InRange(_, _, _) => SUPPORTS_INRANGE,
+            Query::InRangeBounds(_, _, _) => SUPPORTS_INRANGE_BOUNDS,
+            Query::OutRange(_, _, _) => SUPPORTS_OUTRANGE,
+            Query::OutRangeBounds(_, _, _) => SUPPORTS_OUTRANGE_BOUNDS,
+            Query::Minimum(_, _) => SUPPORTS_MINIMUM,
+            Query::Maximum(_, _) => SUPPORTS_MAXIMUM,
+            Query::LengthInRange(_, _, _) => SUPPORTS_LENGTH,
+            Query::IsNull(_) | Query::IsNotNull(_) => SUPPORTS_NULL,
+            Query::All
+            | Query::Not(_)
+            | Query::Or(_)
+            | Query::And(_)
+            | Query::AtLeast(_, _)
+            | Query::Exclude(_, _) => 0,
+        }
+    }
+}
+
+/// Writes a query in the query string syntax of
+/// [SearchEngine::query_from_str](crate::engine::SearchEngine::query_from_str).
+///
+/// Queries that were built by the parser are written in a way that the parser
+/// builds the same query again, if the indices support all operator symbols
+/// that are used. Parts that have no query string syntax, like an Or of
+/// different attributes, an OutRange or an IsNull query, are written in
+/// their [Debug](fmt::Debug) notation instead. An included [All](Query::All)
+/// is left out, because it doesn't constrain the other selectors.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::Query;
+///
+/// let q = Query::Exclude(
+///     Box::new(Query::And(vec![
+///         Query::Or(vec![
+///             Query::Exact("pet".into(), "Cat".into()),
+///             Query::Exact("pet".into(), "Dog".into()),
+///         ]),
+///         Query::Minimum("age".into(), "18".into()),
+///     ])),
+///     vec![Query::Prefix("name".into(), "Ha".into())],
+/// );
+/// assert_eq!(q.to_string(), "+pet:=Cat,=Dog +age:>18 -name:Ha");
+/// ```
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut selectors = vec![];
+        collect_selectors(self, '+', &mut selectors);
+        write!(f, "{}", selectors.join(" "))
+    }
+}
+
+/// Collects the selectors of a query with the sign for includes or excludes.
+/// Nested Ands of includes are flattened, because all selectors must match.
+fn collect_selectors(query: &Query, sign: char, selectors: &mut Vec<String>) {
+    match query {
+        Query::And(vec) if sign == '+' => {
+            for pred in vec {
+                collect_selectors(pred, sign, selectors);
+            }
+        }
+        Query::All if sign == '+' => {}
+        Query::Exclude(base, exclude) if sign == '+' => {
+            collect_selectors(base, '+', selectors);
+            for pred in exclude {
+                selectors.push(selector(pred, '-'));
+            }
+        }
+        query => selectors.push(selector(query, sign)),
+    }
+}
+
+/// Writes a single selector, like `+pet:Cat,Dog`.
+fn selector(query: &Query, sign: char) -> String {
+    let (count, leaves) = match query {
+        Query::Or(vec) => ("".to_owned(), vec.as_slice()),
+        Query::AtLeast(n, vec) => (format!("{n}#"), vec.as_slice()),
+        leaf => ("".to_owned(), std::slice::from_ref(leaf)),
+    };
+    let attribute = leaves.first().and_then(Query::attribute);
+    let values: Option<Vec<String>> = leaves
+        .iter()
+        .filter(|leaf| leaf.attribute() == attribute)
+        .map(selector_value)
+        .collect();
+    match (attribute, values) {
+        (Some(attribute), Some(values)) if values.len() == leaves.len() => {
+            format!("{sign}{attribute}:{count}{}", values.join(","))
+        }
+        _ => format!("{sign}{query:?}"),
+    }
+}
+
+/// Writes the value of a leaf query with its operator symbol
+/// or returns `None` if the query has no operator symbol.
+fn selector_value(query: &Query) -> Option<String> {
+    let bound = |bound: &Bound<String>, included: char, excluded: char| match bound {
+        Bound::Included(value) => Some((included, value.clone())),
+        Bound::Excluded(value) => Some((excluded, value.clone())),
+        Bound::Unbounded => None,
+    };
+    match query {
+        Query::Exact(_, value) => Some(format!("={value}")),
+        Query::Prefix(_, value) | Query::Pattern(_, value) => Some(value.clone()),
+        Query::Suffix(_, value) => Some(format!("*{value}")),
+        Query::Fuzzy(_, value, max_distance) => Some(format!("{value}~{max_distance}")),
+        Query::InRange(_, min, max) => Some(format!("{min}-{max}")),
+        Query::InRangeBounds(_, start, end) => {
+            let (open, min) = bound(start, '[', '(')?;
+            let (close, max) = bound(end, ']', ')')?;
+            Some(format!("{open}{min},{max}{close}"))
+        }
+        Query::Minimum(_, value) => Some(format!(">{value}")),
+        Query::Maximum(_, value) => Some(format!("<{value}")),
+        _ => None,
+    }
+}
@@ -0,0 +1,237 @@
+use crate::engine::SearchEngine;
+use crate::error::*;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// A QueryCatalog maps names to saved query strings, so common searches
+/// can be shared and run with [SearchEngine::search_named].
+///
+/// The query strings are stored as text and parsed against the engine every
+/// time they are run, so a catalog can be used with different engines and
+/// always uses their current indices and parser options. A query string can
+/// contain parameters like `{tenant}`, which are replaced before parsing.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{QueryCatalog, SearchEngine, SearchIndexHashMap};
+/// use std::collections::HashSet;
+///
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.add_index("tenant", SearchIndexHashMap::<_, String>::new());
+/// engine.add_index("role", SearchIndexHashMap::<_, String>::new());
+/// engine.insert("tenant", 0, "acme").unwrap();
+/// engine.insert("role", 0, "admin").unwrap();
+///
+/// let mut catalog = QueryCatalog::new();
+/// catalog.register("admins", "+tenant:{tenant} +role:admin");
+///
+/// let result = engine.search_named(&catalog, "admins", &[("tenant", "acme")]);
+/// assert_eq!(result, Ok(HashSet::from_iter(vec![0])));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryCatalog {
+    queries: HashMap<String, String>,
+}
+
+impl QueryCatalog {
+    /// Creates a new empty `QueryCatalog`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save a query string under a name.
+    ///
+    /// A query that was saved under the same name before is replaced. The
+    /// query string is only checked when it is run.
+    pub fn register(&mut self, name: &str, query_str: &str) {
+        self.queries.insert(name.to_owned(), query_str.to_owned());
+    }
+
+    /// Returns the saved query string of a name, with its parameters
+    /// not replaced, or `None` if no query was saved under the name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.queries.get(name).map(String::as_str)
+    }
+
+    /// Returns the names of all saved queries, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.queries.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Returns the saved query string of a name with all parameters replaced.
+    ///
+    /// Every `{parameter}` in the query string is replaced by the value of the
+    /// parameter with this name. Braces that don't enclose a parameter name of
+    /// letters, digits and `_` are kept.
+    ///
+    /// The values are inserted as they are, so they can't change the structure
+    /// of the query: a value must not be empty, must not contain whitespace or
+    /// commas and must not start with `+`, `-` or an operator symbol like `>`,
+    /// `<`, `=`, `[`, `(` or `*`.
+    ///
+    /// Returns [UnknownSavedQuery](SearchEngineError::UnknownSavedQuery) if no
+    /// query was saved under the name,
+    /// [MissingQueryParameter](SearchEngineError::MissingQueryParameter) if a
+    /// parameter of the query string has no value and
+    /// [InvalidQueryParameter](SearchEngineError::InvalidQueryParameter) if a
+    /// value is rejected.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::QueryCatalog;
+    ///
+    /// let mut catalog = QueryCatalog::new();
+    /// catalog.register("by-city", "+city:{city}");
+    /// assert_eq!(
+    ///     catalog.render("by-city", &[("city", "Berlin")]),
+    ///     Ok("+city:Berlin".into())
+    /// );
+    /// ```
+    pub fn render(&self, name: &str, params: &[(&str, &str)]) -> Result<String> {
+        let query_str = self
+            .get(name)
+            .ok_or_else(|| SearchEngineError::UnknownSavedQuery {
+                name: name.to_owned(),
+            })?;
+
+        let mut rendered = String::with_capacity(query_str.len());
+        let mut rest = query_str;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let parameter = after
+                .find('}')
+                .map(|end| &after[..end])
+                .filter(|parameter| is_parameter_name(parameter));
+            match parameter {
+                Some(parameter) => {
+                    let (_, value) = params
+                        .iter()
+                        .find(|(key, _)| *key == parameter)
+                        .ok_or_else(|| SearchEngineError::MissingQueryParameter {
+                            parameter: parameter.to_owned(),
+                        })?;
+                    if !is_parameter_value(value) {
+                        return Err(SearchEngineError::InvalidQueryParameter {
+                            parameter: parameter.to_owned(),
+                            value: (*value).to_owned(),
+                        });
+                    }
+                    rendered.push_str(value);
+                    rest = &after[parameter.len() + 1..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = after;
+                }
+            }
+        }
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+}
+
+/// Returns true if a name between braces is a parameter.
+fn is_parameter_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Returns true if a value can be inserted for a parameter without
+/// changing the structure of the query, like adding values or selectors.
+fn is_parameter_value(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with(['+', '-', '>', '<', '=', '[', '(', '*'])
+        && !value.contains(|c: char| c == ',' || c.is_whitespace())
+}
+
+impl<P: Eq + Hash + Clone> SearchEngine<P> {
+    /// Run a query that was saved in a [QueryCatalog].
+    ///
+    /// The parameters of the saved query string are replaced with
+    /// [QueryCatalog::render], then it is parsed with
+    /// [query_from_str](Self::query_from_str) and searched. Freetext in the
+    /// saved query string is ignored. Returns the errors of all these steps.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{QueryCatalog, SearchEngine, SearchIndexBTreeRange};
+    /// use std::collections::HashSet;
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("age", SearchIndexBTreeRange::<_, u8>::new());
+    /// engine.insert("age", 0, "17").unwrap();
+    /// engine.insert("age", 1, "42").unwrap();
+    ///
+    /// let mut catalog = QueryCatalog::new();
+    /// catalog.register("adults", "+age:>18");
+    /// assert_eq!(
+    ///     engine.search_named(&catalog, "adults", &[]),
+    ///     Ok(HashSet::from_iter(vec![1]))
+    /// );
+    /// ```
+    pub fn search_named(
+        &self,
+        catalog: &QueryCatalog,
+        name: &str,
+        params: &[(&str, &str)],
+    ) -> Result<HashSet<P>> {
+        let query_str = catalog.render(name, params)?;
+        let (query, _) = self.query_from_str(&query_str)?;
+        self.search(&query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_parameters() {
+        let mut catalog = QueryCatalog::new();
+        catalog.register("q", "+a:{x},{y_2} +b:{x} -c:{} +d:{not a param} +e:{");
+        assert_eq!(
+            catalog.render("q", &[("x", "1"), ("y_2", "2"), ("z", "3")]),
+            Ok("+a:1,2 +b:1 -c:{} +d:{not a param} +e:{".into())
+        );
+        assert_eq!(
+            catalog.render("q", &[("x", "1")]),
+            Err(SearchEngineError::MissingQueryParameter {
+                parameter: "y_2".into()
+            })
+        );
+        assert_eq!(
+            catalog.render("unknown", &[]),
+            Err(SearchEngineError::UnknownSavedQuery {
+                name: "unknown".into()
+            })
+        );
+
+        for value in [
+            "",
+            "Dog,Cat",
+            "acme +role:x",
+            "a\tb",
+            "+x",
+            "-x",
+            ">3",
+            "=x",
+            "[1,2]",
+            "*x",
+        ] {
+            assert_eq!(
+                catalog.render("q", &[("x", value), ("y_2", "2")]),
+                Err(SearchEngineError::InvalidQueryParameter {
+                    parameter: "x".into(),
+                    value: value.into()
+                }),
+                "{value}"
+            );
+        }
+
+        catalog.register("q", "+a:ä{x}😁");
+        assert_eq!(catalog.render("q", &[("x", "ö")]), Ok("+a:äö😁".into()));
+        assert_eq!(catalog.names(), vec!["q"]);
+    }
+}
@@ -20,6 +20,7 @@ pub enum QueryToken<'a> {
 pub struct QueryLexer<'a> {
     query_str: &'a str,
     char_it: Peekable<CharIndices<'a>>,
+    bare_attributes: bool,
 }
 
 impl<'a> QueryLexer<'a> {
@@ -28,6 +29,16 @@ impl<'a> QueryLexer<'a> {
         QueryLexer {
             query_str,
             char_it: query_str.char_indices().peekable(),
+            bare_attributes: false,
+        }
+    }
+
+    /// Creates a new QueryLexer object that also reads attributes
+    /// without a `+` or `-` sign, like `name:Alice`, as inclusive attributes.
+    pub fn new_with_bare_attributes(query_str: &'a str) -> Self {
+        QueryLexer {
+            bare_attributes: true,
+            ..Self::new(query_str)
         }
     }
 
@@ -40,6 +51,9 @@ impl<'a> QueryLexer<'a> {
         if first_char == '+' || first_char == '-' {
             return Some(self.read_attribute());
         }
+        if self.bare_attributes && char::is_alphanumeric(first_char) {
+            return Some(self.read_bare_attribute(start_idx));
+        }
         Some(self.read_freetext(start_idx))
     }
 
@@ -72,7 +86,8 @@ impl<'a> QueryLexer<'a> {
     fn read_attribute(&mut self) -> QueryToken<'a> {
         let (start_idx, first_char) = self.char_it.next().unwrap();
 
-        let (attribute_index, attribute_ok) = self.read_attribute_index(start_idx + 1);
+        let (attribute_index, attribute_ok) =
+            self.read_attribute_index(start_idx + first_char.len_utf8());
         if !attribute_ok || attribute_index.is_empty() {
             return self.read_freetext(start_idx);
         }
@@ -85,19 +100,44 @@ impl<'a> QueryLexer<'a> {
             "if attribute_ok is true, the next char should be a colon"
         );
 
-        let attribute_values = self.read_attribute_values(colon_idx + 1);
+        let attribute_values = self.read_attribute_values(colon_idx + c.len_utf8());
         QueryToken::Attribute(first_char == '+', attribute_index, attribute_values)
     }
 
+    /// Read a full attribute without a sign, which is always inclusive.
+    /// If the input is malformed, a [Freetext Token](QueryToken::Freetext)
+    /// is returned instead.
+    fn read_bare_attribute(&mut self, start_idx: usize) -> QueryToken<'a> {
+        let (attribute_index, attribute_ok) = self.read_attribute_index(start_idx);
+        if !attribute_ok || attribute_index.is_empty() {
+            return self.read_freetext(start_idx);
+        }
+        let (colon_idx, c) = self
+            .char_it
+            .next()
+            .expect("if attribute_ok is true there must be a next char");
+
+        let attribute_values = self.read_attribute_values(colon_idx + c.len_utf8());
+        QueryToken::Attribute(true, attribute_index, attribute_values)
+    }
+
     /// Read the name of an attribute index. Stop if a colon, a
     /// unexpected character or the end of the string is found.
     /// The second value of the result tuple indicates if a colon
     /// was found at the end.
+    ///
+    /// Names consist of alphanumeric characters. A `.` separates the parts of
+    /// a dotted path like `address.city`, so it must follow an alphanumeric
+    /// character and can't end the name.
     fn read_attribute_index(&mut self, start_idx: usize) -> (&'a str, bool) {
+        let mut last_char = None;
         while let Some(&(idx, c)) = self.char_it.peek() {
-            if c == ':' || !char::is_alphanumeric(c) {
-                return (&self.query_str[start_idx..idx], c == ':');
+            let is_path_separator = c == '.' && last_char.is_some_and(char::is_alphanumeric);
+            if c == ':' || !(char::is_alphanumeric(c) || is_path_separator) {
+                let colon_ok = c == ':' && last_char != Some('.');
+                return (&self.query_str[start_idx..idx], colon_ok);
             }
+            last_char = Some(c);
             self.char_it.next();
         }
         // If we are at the end of the query string, this can't be a valid
@@ -106,11 +146,23 @@ impl<'a> QueryLexer<'a> {
     }
 
     /// Read a vector of comma seperated attributes from the query string.
+    ///
+    /// All slice indices are either taken from the char iterator or advanced
+    /// by the UTF-8 length of the delimiter, so they are always char boundaries.
+    /// Commas in a value that starts with `[` or `(` don't separate values
+    /// until the closing `]` or `)`, so ranges like `[18,65)` stay intact.
+    /// The parser splits them again for indices without range support.
     fn read_attribute_values(&mut self, mut value_start_idx: usize) -> Vec<&'a str> {
         let mut values = vec![];
+        let mut in_brackets = false;
 
         while let Some(&(idx, c)) = self.char_it.peek() {
-            if c == ',' || char::is_whitespace(c) {
+            if idx == value_start_idx && (c == '[' || c == '(') {
+                in_brackets = true;
+            } else if c == ']' || c == ')' {
+                in_brackets = false;
+            }
+            if (c == ',' && !in_brackets) || char::is_whitespace(c) {
                 // We only push non-empty values to our result vector.
                 if value_start_idx < idx {
                     values.push(&self.query_str[value_start_idx..idx]);
@@ -121,7 +173,7 @@ impl<'a> QueryLexer<'a> {
                     return values;
                 }
 
-                value_start_idx = idx + 1;
+                value_start_idx = idx + c.len_utf8();
             }
             self.char_it.next();
         }
@@ -161,6 +213,57 @@ mod tests {
         };
     }
 
+    #[test]
+    fn bare_attributes() {
+        let query = "name:Alice OR +pet:Dog NOT -age:3 :x a b: ä:1,2";
+        let result: Vec<QueryToken> = QueryLexer::new_with_bare_attributes(query).collect();
+        assert_eq!(
+            result,
+            vec![
+                Attribute(true, "name", vec!["Alice"]),
+                Freetext("OR"),
+                Attribute(true, "pet", vec!["Dog"]),
+                Freetext("NOT"),
+                Attribute(false, "age", vec!["3"]),
+                Freetext(":x"),
+                Freetext("a"),
+                Attribute(true, "b", vec![]),
+                Attribute(true, "ä", vec!["1", "2"]),
+            ]
+        );
+    }
+
+    /// Inserts multi-byte characters at every position of some queries and
+    /// checks that the lexer never slices inside of a character and that
+    /// the inserted character is part of a token.
+    #[test]
+    fn multi_byte_boundaries() {
+        for query in ["+ab:c,d", "-a:[1,2) x", "+a:b-c:d,,e f"] {
+            let chars: Vec<char> = query.chars().collect();
+            for insert in ['ä', '😁', '☝'] {
+                for pos in 0..=chars.len() {
+                    let mut input: String = chars[..pos].iter().collect();
+                    input.push(insert);
+                    input.extend(&chars[pos..]);
+                    for bare_attributes in [false, true] {
+                        let mut lexer = if bare_attributes {
+                            QueryLexer::new_with_bare_attributes(&input)
+                        } else {
+                            QueryLexer::new(&input)
+                        };
+                        let found = lexer.any(|token| match token {
+                            Attribute(_, index, values) => {
+                                index.contains(insert) || values.iter().any(|v| v.contains(insert))
+                            }
+                            Freetext(text) => text.contains(insert),
+                        });
+                        assert!(found, "{insert:?} is missing in the tokens of {input:?}");
+                    }
+                }
+            }
+        }
+    }
+
     query_lexer_test! {empty "";}
     query_lexer_test! {single_char "A"; Freetext("A")}
     query_lexer_test! {single_umlaut "Ä"; Freetext("Ä")}
@@ -173,7 +276,21 @@ mod tests {
     query_lexer_test! {plus_colon "+:"; Freetext("+:")}
     query_lexer_test! {colon_plus ":+"; Freetext(":+")}
     query_lexer_test! {empty_attribute "+a:"; Attribute(true, "a", vec![])}
+    query_lexer_test! {umlaut_attribute "+ä:b"; Attribute(true, "ä", vec!["b"])}
+    query_lexer_test! {umlaut_value "+a:ä,ö"; Attribute(true, "a", vec!["ä", "ö"])}
+    query_lexer_test! {umlaut_half_attribute "+ä"; Freetext("+ä")}
+    query_lexer_test! {emoji_attribute "+a😁:b"; Freetext("+a😁:b")}
+    query_lexer_test! {emoji_after_colon "-a:😁"; Attribute(false, "a", vec!["😁"])}
+    query_lexer_test! {emoji_after_comma "+a:b,😁,"; Attribute(true, "a", vec!["b", "😁"])}
+    query_lexer_test! {emoji_brackets "+a:[😁,ä)"; Attribute(true, "a", vec!["[😁,ä)"])}
     query_lexer_test! {empty_attribute_space "+a: "; Attribute(true, "a", vec![])}
+    query_lexer_test! {dotted_attribute "+address.city:Berlin"; Attribute(true, "address.city", vec!["Berlin"])}
+    query_lexer_test! {dotted_attribute_values "-a.b.c:1.5,x.y"; Attribute(false, "a.b.c", vec!["1.5", "x.y"])}
+    query_lexer_test! {dotted_umlaut_attribute "+straße.nr:1"; Attribute(true, "straße.nr", vec!["1"])}
+    query_lexer_test! {leading_dot_attribute "+.a:b"; Freetext("+.a:b")}
+    query_lexer_test! {trailing_dot_attribute "+a.:b"; Freetext("+a.:b")}
+    query_lexer_test! {double_dot_attribute "+a..b:c"; Freetext("+a..b:c")}
+    query_lexer_test! {dotted_half_attribute "+a.b"; Freetext("+a.b")}
 
     query_lexer_test! {
         basic "hello  +zipcode:12345  +pet:Dog  -name:Hans  world";
@@ -203,6 +320,12 @@ mod tests {
         Attribute(true, "a6", vec![]),
     }
 
+    query_lexer_test! {
+        brackets "+a:[1,2),(3,4],x,[5,6 +b:y(1,2),z";
+        Attribute(true, "a", vec!["[1,2)", "(3,4]", "x", "[5,6"]),
+        Attribute(true, "b", vec!["y(1", "2)", "z"]),
+    }
+
     query_lexer_test! {
         garbage "\ne376$$bf% sfse-§$\t hello+world ÄÖÜ-+- 😁☝🏼\n\t";
         Freetext("e376$$bf%"),
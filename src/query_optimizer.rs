@@ -0,0 +1,181 @@
+use crate::query::*;
+
+/// Rewrites a query into an equivalent query that is usually faster to search.
+///
+/// The following rewrites are applied bottom-up:
+/// - Nested [And](Query::And) and [Or](Query::Or) queries are flattened
+///   and single subqueries are unwrapped.
+/// - Nested [Exclude](Query::Exclude) queries are merged.
+/// - An Exclude of an And is pushed into the most selective subquery of the And.
+/// - The subqueries of an And are sorted by their estimated selectivity,
+///   so the result set is small early.
+pub(crate) fn optimize(query: &Query) -> Query {
+    match query {
+        Query::And(vec) => {
+            let mut subqueries = vec![];
+            for pred in vec.iter().map(optimize) {
+                match pred {
+                    // An empty And matches nothing, so it can't be flattened.
+                    Query::And(inner) if !inner.is_empty() => subqueries.extend(inner),
+                    pred => subqueries.push(pred),
+                }
+            }
+            // All is the identity of And and can be dropped if anything else is left.
+            if subqueries.iter().any(|pred| !matches!(pred, Query::All)) {
+                subqueries.retain(|pred| !matches!(pred, Query::All));
+            }
+            subqueries.sort_by_key(estimated_selectivity);
+            unwrap_single(subqueries, Query::And)
+        }
+        Query::Or(vec) => {
+            let mut subqueries = vec![];
+            for pred in vec.iter().map(optimize) {
+                match pred {
+                    Query::Or(inner) => subqueries.extend(inner),
+                    pred => subqueries.push(pred),
+                }
+            }
+            unwrap_single(subqueries, Query::Or)
+        }
+        Query::AtLeast(min_matches, vec) => {
+            Query::AtLeast(*min_matches, vec.iter().map(optimize).collect())
+        }
+        Query::Exclude(base, exclude) => {
+            push_down_exclude(optimize(base), exclude.iter().map(optimize).collect())
+        }
+        Query::Not(inner) => Query::Not(Box::new(optimize(inner))),
+        leaf => leaf.clone(),
+    }
+}
+
+/// Removes the rows of the exclude queries from an optimized base query.
+///
+/// Nested Excludes are merged and the exclusion is pushed into the first
+/// subquery of an And, which is the most selective one after sorting,
+/// because `(A ∩ B) \ C = (A \ C) ∩ B`.
+fn push_down_exclude(base: Query, mut exclude: Vec<Query>) -> Query {
+    if exclude.is_empty() {
+        return base;
+    }
+    match base {
+        Query::Exclude(inner_base, mut inner_exclude) => {
+            inner_exclude.append(&mut exclude);
+            Query::Exclude(inner_base, inner_exclude)
+        }
+        Query::And(mut subqueries) if !subqueries.is_empty() => {
+            let most_selective = subqueries.remove(0);
+            subqueries.insert(0, push_down_exclude(most_selective, exclude));
+            Query::And(subqueries)
+        }
+        base => Query::Exclude(Box::new(base), exclude),
+    }
+}
+
+/// Returns the only query of a vector or combines all of them.
+fn unwrap_single(mut subqueries: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if subqueries.len() == 1 {
+        subqueries.remove(0)
+    } else {
+        combine(subqueries)
+    }
+}
+
+/// Estimates how many rows a query matches, lower values match fewer rows.
+///
+/// This is only a heuristic based on the kind of the query,
+/// because the indices don't provide statistics.
+fn estimated_selectivity(query: &Query) -> usize {
+    match query {
+        Query::Exact(_, _) | Query::IsNull(_) => 1,
+        Query::InRange(_, _, _) | Query::InRangeBounds(_, _, _) => 2,
+        Query::Prefix(_, _)
+        | Query::Suffix(_, _)
+        | Query::Contains(_, _)
+        | Query::AllTokens(_, _)
+        | Query::AnyTokens(_, _)
+        | Query::Regex(_, _)
+        | Query::Fuzzy(_, _, _)
+        | Query::Pattern(_, _)
+        | Query::LengthInRange(_, _, _) => 3,
+        Query::Minimum(_, _) | Query::Maximum(_, _) => 4,
+        Query::OutRange(_, _, _)
+        | Query::OutRangeBounds(_, _, _)
+        | Query::IsNotNull(_)
+        | Query::All
+        | Query::Not(_) => 5,
+        Query::And(vec) => vec.iter().map(estimated_selectivity).min().unwrap_or(0),
+        Query::Or(vec) | Query::AtLeast(_, vec) => {
+            vec.iter().map(estimated_selectivity).sum::<usize>()
+        }
+        Query::Exclude(base, _) => estimated_selectivity(base),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exact(attr: &str) -> Query {
+        Query::Exact(attr.into(), "".into())
+    }
+
+    #[test]
+    fn flatten_and_sort() {
+        let q = Query::And(vec![
+            Query::Minimum("a".into(), "".into()),
+            Query::And(vec![
+                exact("b"),
+                Query::Or(vec![Query::Or(vec![exact("c")])]),
+            ]),
+            Query::And(vec![]),
+        ]);
+        assert_eq!(
+            optimize(&q),
+            Query::And(vec![
+                Query::And(vec![]),
+                exact("b"),
+                exact("c"),
+                Query::Minimum("a".into(), "".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn drop_all_from_and() {
+        let q = Query::And(vec![Query::All, exact("a"), Query::And(vec![Query::All])]);
+        assert_eq!(optimize(&q), exact("a"));
+        assert_eq!(
+            optimize(&Query::And(vec![Query::All, Query::All])),
+            Query::And(vec![Query::All, Query::All])
+        );
+        assert_eq!(
+            optimize(&Query::Or(vec![Query::All, exact("a")])),
+            Query::Or(vec![Query::All, exact("a")])
+        );
+    }
+
+    #[test]
+    fn push_down_exclude() {
+        let q = Query::Exclude(
+            Box::new(Query::Exclude(
+                Box::new(Query::And(vec![
+                    Query::Minimum("a".into(), "".into()),
+                    exact("b"),
+                ])),
+                vec![exact("c")],
+            )),
+            vec![exact("d")],
+        );
+        assert_eq!(
+            optimize(&q),
+            Query::And(vec![
+                Query::Exclude(Box::new(exact("b")), vec![exact("c"), exact("d")]),
+                Query::Minimum("a".into(), "".into()),
+            ])
+        );
+        assert_eq!(
+            optimize(&Query::Exclude(Box::new(exact("a")), vec![])),
+            exact("a")
+        );
+    }
+}
@@ -0,0 +1,546 @@
+use crate::error::*;
+use crate::query::*;
+use std::ops::Bound;
+
+/// Options that change how [SearchEngine::query_from_str](crate::engine::SearchEngine::query_from_str)
+/// builds queries.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{QueryParserOptions, SearchEngine};
+///
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.set_parser_options(QueryParserOptions {
+///     strict: true,
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryParserOptions {
+    /// If enabled, the parser rejects query strings that include and
+    /// exclude the same value of an attribute, like `+pet:Dog -pet:Dog`,
+    /// with a [ContradictoryQuery](SearchEngineError::ContradictoryQuery) error.
+    /// Otherwise such queries are built as usual and simply match nothing.
+    pub strict: bool,
+
+    /// Attributes where repeated `+` selectors are combined with Or instead of And,
+    /// so `+pet:Cat +pet:Dog` means the same as `+pet:Cat,Dog`.
+    ///
+    /// This is meant for multi-valued attributes, where users expect repeated
+    /// selectors to match "any of" the values. For single-valued attributes
+    /// the default And makes repeated exact selectors contradictory, because
+    /// a row can't have both values. Values of repeated selectors on these
+    /// attributes are not checked by [strict](Self::strict).
+    pub or_attributes: Vec<String>,
+
+    /// If enabled, the uppercase keywords `AND`, `OR` and `NOT` combine the
+    /// selectors of a query string, like `name:Alice OR name:Bob`.
+    ///
+    /// Selectors can be written without a sign in this mode. `NOT` excludes
+    /// the following selector, `AND` is the default between two selectors and
+    /// `OR` matches the rows of either side. `NOT` binds stronger than `AND`,
+    /// which binds stronger than `OR`. The `+` and `-` signs still work, a `-`
+    /// selector is the same as a `NOT` selector. Keywords that are not
+    /// followed by a selector are returned as freetext.
+    ///
+    /// Repeated selectors are not merged by [or_attributes](Self::or_attributes)
+    /// in this mode and [strict](Self::strict) checks every `AND` group separately.
+    pub boolean_keywords: bool,
+
+    /// If enabled, a selector with exactly the values `<x` and `>y`, like
+    /// `+age:<25,>40`, is built as a single [OutRangeBounds](Query::OutRangeBounds)
+    /// query with inclusive bounds instead of an Or of a Maximum and a Minimum
+    /// query, if the index supports OutRangeBounds queries. Indices can answer
+    /// it with one pass over their values instead of two searches whose
+    /// results are merged.
+    ///
+    /// The merged query returns the same rows as the Or, so `x` and `y`
+    /// themselves are still matched.
+    pub merge_out_range: bool,
+
+    /// If enabled, a value without an operator symbol is searched with an
+    /// [Exact](Query::Exact) query instead of a [Prefix](Query::Prefix) query
+    /// if the index supports both and a row has exactly this value, see
+    /// [SearchIndex::contains_value](crate::SearchIndex::contains_value).
+    /// Values that are not stored verbatim are still searched as a prefix.
+    ///
+    /// This fits fully specified values like `+name:Alex`, which otherwise
+    /// also match `Alexander`. The index is consulted while parsing, so the
+    /// same query string can result in different queries after inserts.
+    pub exact_if_present: bool,
+
+    /// Range delimiter for indices with String values, like
+    /// `SearchIndexBTreeRange<_, String>`. If set, a value like
+    /// `2024-01-01..2024-12-31` with the delimiter `..` is built as an
+    /// [InRange](Query::InRange) query and a `-` in a value is kept as it is.
+    /// Otherwise these indices use `-` like all other indices.
+    ///
+    /// An index can choose its own delimiter with
+    /// [SearchIndex::range_delimiter](crate::SearchIndex::range_delimiter),
+    /// which takes precedence over this option.
+    pub string_range_delimiter: Option<String>,
+}
+
+/// Describes how the query string parser processed a single value of
+/// an attribute selector.
+///
+/// Notes are returned by
+/// [SearchEngine::query_from_str_explained](crate::engine::SearchEngine::query_from_str_explained).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseNote<'a> {
+    /// The value as it was found in the query string.
+    pub token: &'a str,
+
+    /// The query that was built from the value.
+    pub query: Query,
+
+    /// The reason why this query was chosen.
+    pub reason: ParseReason,
+
+    /// Name of the type that the index of the attribute expects the value to
+    /// have, see [SearchIndex::value_type_name](crate::SearchIndex::value_type_name).
+    pub value_type: &'static str,
+}
+
+/// The reason why the query string parser chose a specific [Query] for a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseReason {
+    /// An operator symbol was found that is supported by the index.
+    Operator(char),
+
+    /// No supported operator symbol was found and the index supports Prefix queries.
+    PrefixFallback,
+
+    /// No supported operator symbol was found and the index doesn't support Prefix queries.
+    ExactFallback,
+
+    /// No supported operator symbol was found, the index supports Prefix queries,
+    /// but a row has exactly this value and
+    /// [exact_if_present](QueryParserOptions::exact_if_present) is enabled.
+    ExactPresent,
+
+    /// The index parsed the value with its own syntax,
+    /// see [SearchIndex::parse_value](crate::SearchIndex::parse_value).
+    IndexSyntax,
+}
+
+/// A non-fatal issue that the query string parser found while building a query.
+///
+/// Warnings are returned by
+/// [SearchEngine::query_from_str_with_warnings](crate::engine::SearchEngine::query_from_str_with_warnings).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning<'a> {
+    /// The part of the query string that caused the warning. This is the
+    /// attribute name for selectors and the value for single values.
+    pub token: &'a str,
+
+    /// The reason for the warning.
+    pub reason: ParseWarningReason,
+}
+
+/// The reason for a [ParseWarning].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseWarningReason {
+    /// The selector has no values and was dropped from the query, like `+attr:`.
+    EmptyValueList,
+
+    /// The value starts with or contains an operator symbol that is not supported
+    /// by the index, so it was used as a Prefix or Exact value instead.
+    UnsupportedOperator(char),
+}
+
+/// The parts of a query string before they are combined into a single [Query].
+pub(crate) struct ParsedQuery<'a> {
+    /// Queries of all `+` selectors.
+    pub include: Vec<Query>,
+
+    /// Queries of all `-` selectors.
+    pub exclude: Vec<Query>,
+
+    /// All parts of the query string that are not selectors.
+    pub freetexts: Vec<&'a str>,
+}
+
+impl<'a> ParsedQuery<'a> {
+    /// Combines all selectors into a single [Query] and returns it with the freetexts.
+    pub fn into_query(self) -> (Query, Vec<&'a str>) {
+        let base_query = Query::And(self.include);
+        if !self.exclude.is_empty() {
+            (
+                Query::Exclude(base_query.into(), self.exclude),
+                self.freetexts,
+            )
+        } else {
+            (base_query, self.freetexts)
+        }
+    }
+}
+
+/// Boolean keywords of the query string parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keyword {
+    And,
+    Or,
+    Not,
+}
+
+impl Keyword {
+    /// Returns the keyword of a freetext token, if it is one.
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "AND" => Some(Keyword::And),
+            "OR" => Some(Keyword::Or),
+            "NOT" => Some(Keyword::Not),
+            _ => None,
+        }
+    }
+}
+
+/// The selectors of a single `AND` group of a query string with boolean keywords.
+#[derive(Default)]
+struct KeywordGroup<'a> {
+    include: Vec<Query>,
+    exclude: Vec<Query>,
+    single_includes: Vec<(Query, &'a str)>,
+}
+
+/// Combines the selectors of a query string with the boolean keywords
+/// between them, see [boolean_keywords](QueryParserOptions::boolean_keywords).
+///
+/// The selectors are collected in `AND` groups, a new group is started by
+/// every `OR`. Keywords are kept until the next selector to check if they
+/// can be applied to it.
+pub(crate) struct KeywordGroups<'a> {
+    groups: Vec<KeywordGroup<'a>>,
+    pending: Vec<(&'a str, Keyword)>,
+}
+
+impl<'a> KeywordGroups<'a> {
+    /// Creates an empty list of groups.
+    pub fn new() -> Self {
+        Self {
+            groups: vec![KeywordGroup::default()],
+            pending: vec![],
+        }
+    }
+
+    /// Records a freetext token if it is a keyword and returns if it was one.
+    pub fn keyword(&mut self, text: &'a str) -> bool {
+        match Keyword::parse(text) {
+            Some(keyword) => {
+                self.pending.push((text, keyword));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds the query of a selector to the current group, applying the
+    /// keywords since the last selector. `single_value` is the value of a
+    /// selector with only one value, for the strict mode. Returns all
+    /// keywords that couldn't be applied, so they can be used as freetext.
+    pub fn selector(
+        &mut self,
+        query: Query,
+        is_include: bool,
+        single_value: Option<&'a str>,
+    ) -> Vec<&'a str> {
+        let has_selectors = self.current_has_selectors();
+        let mut invalid = vec![];
+        let mut is_include = is_include;
+        let mut start_group = false;
+        for (idx, &(text, keyword)) in self.pending.iter().enumerate() {
+            match keyword {
+                Keyword::Not => is_include = !is_include,
+                // A binary keyword must directly follow a selector.
+                Keyword::And | Keyword::Or if !has_selectors || idx > 0 => invalid.push(text),
+                Keyword::And => {}
+                Keyword::Or => start_group = true,
+            }
+        }
+        self.pending.clear();
+        if start_group {
+            self.groups.push(KeywordGroup::default());
+        }
+
+        let group = self.groups.last_mut().expect("there is always a group");
+        if is_include {
+            if let Some(value) = single_value {
+                group.single_includes.push((query.clone(), value));
+            }
+            group.include.push(query);
+        } else {
+            group.exclude.push(query);
+        }
+        invalid
+    }
+
+    /// Combines all groups with Or and returns the includes and excludes
+    /// of the combined query, together with all keywords that couldn't be
+    /// applied. If `strict` is set, every group is checked for contradictions.
+    ///
+    /// Without an `OR`, the includes and excludes are the same as
+    /// without boolean keywords. A group with only excluded selectors, like
+    /// `NOT name:Hans`, excludes them from [All](Query::All) rows.
+    pub fn finish(mut self, strict: bool) -> Result<(Vec<Query>, Vec<Query>, Vec<&'a str>)> {
+        if strict {
+            for group in self.groups.iter() {
+                check_contradictions(&group.single_includes, &group.exclude)?;
+            }
+        }
+        let leftover = self.pending.iter().map(|&(text, _)| text).collect();
+        if self.groups.len() == 1 {
+            let group = self.groups.remove(0);
+            return Ok((group.include, group.exclude, leftover));
+        }
+        let alternatives = self
+            .groups
+            .into_iter()
+            .map(|group| {
+                let include = match group.include.is_empty() {
+                    true => vec![Query::All],
+                    false => group.include,
+                };
+                ParsedQuery {
+                    include,
+                    exclude: group.exclude,
+                    freetexts: vec![],
+                }
+                .into_query()
+                .0
+            })
+            .collect();
+        Ok((vec![Query::Or(alternatives)], vec![], leftover))
+    }
+
+    /// Returns true if the current group has at least one selector.
+    fn current_has_selectors(&self) -> bool {
+        self.groups
+            .last()
+            .is_some_and(|group| !group.include.is_empty() || !group.exclude.is_empty())
+    }
+}
+
+/// Builds the query for a single value of an attribute selector depending
+/// on the operator symbols and the queries supported by the index.
+pub(crate) fn parse_value(
+    attribute: &str,
+    value: &str,
+    supported: SupportedQueries,
+    range_delimiter: &str,
+) -> (Query, ParseReason) {
+    let attr = attribute.to_owned();
+    if (supported & SUPPORTS_MINIMUM) != 0 && value.starts_with('>') {
+        return (
+            Query::Minimum(attr, value[1..].to_owned()),
+            ParseReason::Operator('>'),
+        );
+    }
+    if (supported & SUPPORTS_MAXIMUM) != 0 && value.starts_with('<') {
+        return (
+            Query::Maximum(attr, value[1..].to_owned()),
+            ParseReason::Operator('<'),
+        );
+    }
+    if (supported & SUPPORTS_EXACT) != 0 && value.starts_with('=') {
+        return (
+            Query::Exact(attr, value[1..].to_owned()),
+            ParseReason::Operator('='),
+        );
+    }
+    if let Some((start, end)) = split_bracket_range(value) {
+        let operator = ParseReason::Operator(value.chars().next().unwrap_or_default());
+        if (supported & SUPPORTS_INRANGE_BOUNDS) != 0 {
+            return (
+                Query::InRangeBounds(attr, start.map(str::to_owned), end.map(str::to_owned)),
+                operator,
+            );
+        }
+        // Indices without bound support can still answer fully inclusive ranges.
+        if let (true, Bound::Included(min), Bound::Included(max)) =
+            ((supported & SUPPORTS_INRANGE) != 0, start, end)
+        {
+            return (
+                Query::InRange(attr, min.to_owned(), max.to_owned()),
+                operator,
+            );
+        }
+    }
+    if let Some((fuzzy_value, max_distance)) = split_fuzzy(value) {
+        if (supported & SUPPORTS_FUZZY) != 0 {
+            return (
+                Query::Fuzzy(attr, fuzzy_value.to_owned(), max_distance),
+                ParseReason::Operator('~'),
+            );
+        }
+    }
+    if let Some(suffix) = value.strip_prefix('*') {
+        if (supported & SUPPORTS_SUFFIX) != 0 && !suffix.contains('*') {
+            return (
+                Query::Suffix(attr, suffix.to_owned()),
+                ParseReason::Operator('*'),
+            );
+        }
+    }
+    if (supported & SUPPORTS_PATTERN) != 0 && value.contains('*') {
+        return (
+            Query::Pattern(attr, value.to_owned()),
+            ParseReason::Operator('*'),
+        );
+    }
+    if (supported & SUPPORTS_INRANGE) != 0
+        && !range_delimiter.is_empty()
+        && value.contains(range_delimiter)
+    {
+        let parts = value.split(range_delimiter).collect::<Vec<_>>();
+        if parts.len() == 2 {
+            let operator = range_delimiter.chars().next().unwrap_or_default();
+            return (
+                Query::InRange(attr, parts[0].to_owned(), parts[1].to_owned()),
+                ParseReason::Operator(operator),
+            );
+        }
+    }
+
+    // Fallback, if nothing is found we use prefix if we can
+    // and exact otherwise.
+    if (supported & SUPPORTS_PREFIX) != 0 {
+        return (
+            Query::Prefix(attr, value.to_owned()),
+            ParseReason::PrefixFallback,
+        );
+    }
+    (
+        Query::Exact(attr, value.to_owned()),
+        ParseReason::ExactFallback,
+    )
+}
+
+/// Returns the operator symbol a value seems to use, ignoring if the index
+/// supports it. The `-` of ranges is not considered, because it is common
+/// in normal values.
+pub(crate) fn implied_operator(value: &str) -> Option<char> {
+    match value.chars().next() {
+        Some(c @ ('>' | '<' | '=')) => Some(c),
+        Some(c @ ('[' | '(')) if split_bracket_range(value).is_some() => Some(c),
+        _ if value.contains('*') => Some('*'),
+        _ if split_fuzzy(value).is_some() => Some('~'),
+        _ => None,
+    }
+}
+
+/// Splits a fuzzy value like `berlin~2` into the value and the maximum
+/// edit distance, which is the number after the last `~`.
+pub(crate) fn split_fuzzy(value: &str) -> Option<(&str, u8)> {
+    let (value, max_distance) = value.rsplit_once('~')?;
+    if max_distance.is_empty() || !max_distance.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((value, max_distance.parse().ok()?))
+}
+
+/// Splits a range in bracket notation like `[18,65)` into its bounds.
+/// A `[` or `]` marks an inclusive bound and a `(` or `)` an exclusive one.
+/// An empty side like in `[18,)` or `(,65]` is unbounded.
+pub(crate) fn split_bracket_range<'a>(value: &'a str) -> Option<(Bound<&'a str>, Bound<&'a str>)> {
+    let inner = value.get(1..value.len().checked_sub(1)?)?;
+    let (min, max) = inner.split_once(',')?;
+    let start = match value.chars().next()? {
+        '[' => Bound::Included(min),
+        '(' => Bound::Excluded(min),
+        _ => return None,
+    };
+    let end = match value.chars().last()? {
+        ']' => Bound::Included(max),
+        ')' => Bound::Excluded(max),
+        _ => return None,
+    };
+    let unbounded_if_empty = |bound: Bound<&'a str>| match bound {
+        Bound::Included("") | Bound::Excluded("") => Bound::Unbounded,
+        bound => bound,
+    };
+    Some((unbounded_if_empty(start), unbounded_if_empty(end)))
+}
+
+/// Splits values the lexer kept together because they start with `[` or `(`
+/// at their commas again. This is used for indices without range support,
+/// where a selector like `+tag:(a,b)` lists the two values `(a` and `b)`.
+pub(crate) fn split_bracket_commas(values: Vec<&str>) -> Vec<&str> {
+    values
+        .into_iter()
+        .flat_map(|value| {
+            let parts: Vec<&str> = if value.starts_with(['[', '(']) {
+                value.split(',').filter(|part| !part.is_empty()).collect()
+            } else {
+                vec![value]
+            };
+            parts
+        })
+        .collect()
+}
+
+/// Merges the queries of a selector into an [OutRangeBounds](Query::OutRangeBounds)
+/// query with inclusive bounds if they are exactly a Maximum and a Minimum.
+/// Returns `None` if the queries have any other shape.
+pub(crate) fn merge_out_range(queries: &[Query]) -> Option<Query> {
+    match queries {
+        [Query::Maximum(attr, start), Query::Minimum(other, end)]
+        | [Query::Minimum(other, end), Query::Maximum(attr, start)]
+            if attr == other =>
+        {
+            Some(Query::OutRangeBounds(
+                attr.clone(),
+                Bound::Included(start.clone()),
+                Bound::Included(end.clone()),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Removes a `N#` count modifier from the first value of a selector
+/// and returns the count, if there is a valid one.
+///
+/// The modifier is only valid if the selector lists more than one value and
+/// `N` is between one and the number of values. Otherwise nothing is removed
+/// and the first value is used as written, like `42#fix` in `+issue:42#fix`.
+pub(crate) fn split_min_matches(values: &mut [&str]) -> Option<usize> {
+    let value_count = values.len();
+    if value_count < 2 {
+        return None;
+    }
+    let first = values.first_mut()?;
+    let (count, rest) = first.split_once('#')?;
+    if rest.is_empty() {
+        return None;
+    }
+    let count = count
+        .parse::<usize>()
+        .ok()
+        .filter(|&n| n > 0 && n <= value_count)?;
+    *first = rest;
+    Some(count)
+}
+
+/// Returns a [ContradictoryQuery](SearchEngineError::ContradictoryQuery) error
+/// if one of the single value includes is also matched by an exclude query.
+pub(crate) fn check_contradictions(
+    single_includes: &[(Query, &str)],
+    exclude: &[Query],
+) -> Result<()> {
+    for exclude_query in exclude {
+        let leaves = match exclude_query {
+            Query::Or(vec) => vec.as_slice(),
+            leaf => std::slice::from_ref(leaf),
+        };
+        for leaf in leaves {
+            if let Some((include_query, value)) = single_includes.iter().find(|(q, _)| q == leaf) {
+                return Err(SearchEngineError::ContradictoryQuery {
+                    attribute: include_query.attribute().unwrap_or_default().to_owned(),
+                    value: (*value).to_owned(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use crate::engine::SearchEngine;
+use crate::query::Query;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Function that receives every recorded query.
+type QuerySink = dyn FnMut(&Query) + Send;
+
+/// A QueryRecorder collects the queries that are run on a [SearchEngine],
+/// so a real workload can be replayed later with [replay].
+///
+/// It is attached with [SearchEngine::set_recorder]. Clones of a recorder
+/// share the same recorded queries, so one clone can be attached to the
+/// engine while another one is used to read the queries, also on another thread.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{replay, SearchEngine, SearchIndexHashMap, QueryRecorder, Query};
+///
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+/// let recorder = QueryRecorder::new();
+/// engine.set_recorder(recorder.clone());
+///
+/// engine.search(&Query::Exact("name".into(), "Alice".into())).expect("valid query");
+/// engine.search(&Query::Exact("name".into(), "Bob".into())).expect("valid query");
+///
+/// let report = replay(&engine, &recorder.take());
+/// assert_eq!(report.durations.len(), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct QueryRecorder {
+    queries: Arc<Mutex<Vec<Query>>>,
+    sink: Option<Arc<Mutex<Box<QuerySink>>>>,
+}
+
+impl QueryRecorder {
+    /// Creates a new `QueryRecorder` that keeps all queries in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new `QueryRecorder` that passes every query to a function
+    /// instead of keeping it in memory, for example to write it to a file.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::QueryRecorder;
+    /// use std::io::Write;
+    ///
+    /// let mut log = std::io::sink();
+    /// let recorder = QueryRecorder::with_sink(move |query| {
+    ///     writeln!(log, "{query:?}").expect("writable log");
+    /// });
+    /// ```
+    pub fn with_sink(sink: impl FnMut(&Query) + Send + 'static) -> Self {
+        Self {
+            queries: Arc::default(),
+            sink: Some(Arc::new(Mutex::new(Box::new(sink)))),
+        }
+    }
+
+    /// Record a query.
+    pub fn record(&self, query: &Query) {
+        match self.sink.as_ref() {
+            Some(sink) => (lock(sink))(query),
+            None => lock(&self.queries).push(query.clone()),
+        }
+    }
+
+    /// Returns a copy of all queries that were recorded in memory.
+    pub fn queries(&self) -> Vec<Query> {
+        lock(&self.queries).clone()
+    }
+
+    /// Returns all queries that were recorded in memory and clears them.
+    pub fn take(&self) -> Vec<Query> {
+        std::mem::take(&mut lock(&self.queries))
+    }
+}
+
+/// Locks a mutex of a recorder. A sink that panicked doesn't leave the
+/// recorded queries in an invalid state, so a poisoned mutex is used anyway.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The result of [replay], with the duration of every query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    /// The duration of each query, in the order of the queries.
+    pub durations: Vec<Duration>,
+
+    /// The number of queries that returned an error.
+    pub errors: usize,
+}
+
+impl ReplayReport {
+    /// Returns the sum of all durations.
+    pub fn total(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+
+    /// Returns the duration that `percent` percent of the queries didn't exceed,
+    /// for example `50.0` for the median or `99.0` for the slowest percent.
+    /// Returns `None` if no queries were replayed.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let mut sorted = self.durations.clone();
+        sorted.sort_unstable();
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted.get(rank.max(1) - 1).copied()
+    }
+}
+
+/// Run all queries on an engine and measure the duration of each one.
+///
+/// The results of the queries are discarded. Queries that return an error
+/// are counted in [errors](ReplayReport::errors) and their duration is
+/// recorded as well. If a recorder is attached to the engine, the replayed
+/// queries are recorded again.
+pub fn replay<P: Eq + Hash + Clone>(engine: &SearchEngine<P>, queries: &[Query]) -> ReplayReport {
+    let mut durations = Vec::with_capacity(queries.len());
+    let mut errors = 0;
+    for query in queries {
+        let start = Instant::now();
+        let result = engine.search(query);
+        durations.push(start.elapsed());
+        if result.is_err() {
+            errors += 1;
+        }
+    }
+    ReplayReport { durations, errors }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile() {
+        let report = ReplayReport {
+            durations: (1..=10).rev().map(Duration::from_millis).collect(),
+            errors: 0,
+        };
+        assert_eq!(report.total(), Duration::from_millis(55));
+        assert_eq!(report.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(report.percentile(50.0), Some(Duration::from_millis(5)));
+        assert_eq!(report.percentile(95.0), Some(Duration::from_millis(10)));
+        assert_eq!(report.percentile(100.0), Some(Duration::from_millis(10)));
+
+        let empty = ReplayReport {
+            durations: vec![],
+            errors: 0,
+        };
+        assert_eq!(empty.percentile(50.0), None);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::engine::SearchEngine;
+use crate::error::Result;
+use crate::query::Query;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A RefinableSearch holds the result of a search and allows to
+/// narrow or widen it with additional queries, without running
+/// the earlier queries again. It is created by
+/// [SearchEngine::refinable](crate::engine::SearchEngine::refinable).
+///
+/// This is only valid for monotone refinements: after calling [refine](Self::refine)
+/// with `q` the result is the same as searching for `And(previous, q)` and after
+/// calling [broaden](Self::broaden) it is the same as searching for `Or(previous, q)`.
+/// The result is not updated if the indices of the engine change.
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+/// use std::collections::HashSet;
+///
+/// let mut index = SearchIndexHashMap::<_, String>::new();
+/// index.insert(0, "foo".into());
+/// index.insert(1, "foo".into());
+/// index.insert(1, "bar".into());
+/// index.insert(2, "baz".into());
+///
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.add_index("attribute", index);
+///
+/// let mut search = engine
+///     .refinable(&Query::Exact("attribute".into(), "foo".into()))
+///     .expect("valid query");
+/// assert_eq!(search.results(), &HashSet::from_iter(vec![0, 1]));
+///
+/// let result = search.refine(&Query::Exact("attribute".into(), "bar".into()));
+/// assert_eq!(result, Ok(&HashSet::from_iter(vec![1])));
+///
+/// let result = search.broaden(&Query::Exact("attribute".into(), "baz".into()));
+/// assert_eq!(result, Ok(&HashSet::from_iter(vec![1, 2])));
+/// ```
+pub struct RefinableSearch<'a, P> {
+    engine: &'a SearchEngine<P>,
+    results: HashSet<P>,
+}
+
+impl<'a, P: Eq + Hash + Clone> RefinableSearch<'a, P> {
+    /// Creates a new `RefinableSearch` from the results of a previous search.
+    pub(crate) fn new(engine: &'a SearchEngine<P>, results: HashSet<P>) -> Self {
+        Self { engine, results }
+    }
+
+    /// Narrow the current results to the rows that also match the query.
+    ///
+    /// If the search fails, the current results are kept unchanged.
+    /// Once the results are empty, the query isn't executed anymore.
+    pub fn refine(&mut self, additional: &Query) -> Result<&HashSet<P>> {
+        if !self.results.is_empty() {
+            let matches = self.engine.search(additional)?;
+            self.results.retain(|id| matches.contains(id));
+        }
+        Ok(&self.results)
+    }
+
+    /// Widen the current results by adding the rows that match the query.
+    ///
+    /// If the search fails, the current results are kept unchanged.
+    pub fn broaden(&mut self, additional: &Query) -> Result<&HashSet<P>> {
+        let matches = self.engine.search(additional)?;
+        self.results.extend(matches);
+        Ok(&self.results)
+    }
+
+    /// Returns the current results.
+    pub fn results(&self) -> &HashSet<P> {
+        &self.results
+    }
+
+    /// Consumes the search and returns the current results.
+    pub fn into_results(self) -> HashSet<P> {
+        self.results
+    }
+}
@@ -0,0 +1,79 @@
+use crate::engine::SearchEngine;
+use crate::error::*;
+use crate::query::Query;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::Write;
+
+impl<P: Eq + Hash + Clone> SearchEngine<P> {
+    /// Run a query on the search engine and return the row ids as a JSON array.
+    ///
+    /// The order of the ids is unspecified, like the order of the result of
+    /// [search](Self::search). If serializing an id fails,
+    /// [Serialization](SearchEngineError::Serialization) is returned.
+    /// Requires the `serde_json` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "serde_json")]
+    /// # {
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(7, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// assert_eq!(engine.search_to_json(&q), Ok("[7]".into()));
+    /// # }
+    /// ```
+    #[cfg(feature = "serde_json")]
+    pub fn search_to_json(&self, query: &Query) -> Result<String>
+    where
+        P: serde::Serialize,
+    {
+        let result_set = self.search(query)?;
+        serde_json::to_string(&result_set).map_err(|error| SearchEngineError::Serialization {
+            message: error.to_string(),
+        })
+    }
+
+    /// Run a query on the search engine and write the row ids as CSV,
+    /// one id per line.
+    ///
+    /// The order of the ids is unspecified, like the order of the result of
+    /// [search](Self::search). Ids that contain a comma, a quote or a line break
+    /// are quoted. If writing fails, [Io](SearchEngineError::Io) is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+    ///
+    /// let mut index = SearchIndexHashMap::<_, String>::new();
+    /// index.insert(7, "foo".into());
+    ///
+    /// let mut engine = SearchEngine::<usize>::new();
+    /// engine.add_index("attribute", index);
+    ///
+    /// let q = Query::Exact("attribute".into(), "foo".into());
+    /// let mut csv = Vec::new();
+    /// engine.search_to_csv(&q, &mut csv).expect("valid query");
+    /// assert_eq!(csv, b"7\n");
+    /// ```
+    pub fn search_to_csv<W: Write>(&self, query: &Query, mut writer: W) -> Result<()>
+    where
+        P: Display,
+    {
+        for id in self.search(query)? {
+            let id = id.to_string();
+            if id.contains([',', '"', '\n', '\r']) {
+                writeln!(writer, "\"{}\"", id.replace('"', "\"\""))?;
+            } else {
+                writeln!(writer, "{id}")?;
+            }
+        }
+        Ok(())
+    }
+}
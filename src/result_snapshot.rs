@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A ResultSnapshot stores the result of a search at one point in time,
+/// so it can later be compared with a newer result of the same query.
+/// It is created by [SearchEngine::snapshot](crate::engine::SearchEngine::snapshot).
+///
+/// # Example
+/// ```rust
+/// use attribute_search_engine::{SearchEngine, SearchIndexHashMap, Query};
+/// use std::collections::HashSet;
+///
+/// let q = Query::Exact("status".into(), "online".into());
+///
+/// let mut index = SearchIndexHashMap::<_, String>::new();
+/// index.insert(0, "online".into());
+/// index.insert(1, "online".into());
+/// let mut engine = SearchEngine::<usize>::new();
+/// engine.add_index("status", index);
+/// let older = engine.snapshot(&q).expect("valid query");
+///
+/// let mut index = SearchIndexHashMap::<_, String>::new();
+/// index.insert(1, "online".into());
+/// index.insert(2, "online".into());
+/// engine.add_index("status", index);
+/// let newer = engine.snapshot(&q).expect("valid query");
+///
+/// let (added, removed) = older.diff(&newer);
+/// assert_eq!(added, HashSet::from_iter(vec![2]));
+/// assert_eq!(removed, HashSet::from_iter(vec![0]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSnapshot<P: Eq + Hash>(HashSet<P>);
+
+impl<P: Eq + Hash + Clone> ResultSnapshot<P> {
+    /// Creates a new `ResultSnapshot` from a result set.
+    pub fn new(results: HashSet<P>) -> Self {
+        Self(results)
+    }
+
+    /// Compare this snapshot with a newer one.
+    ///
+    /// Returns the rows / primary ids that were added in the newer
+    /// snapshot and the ones that were removed from it.
+    pub fn diff(&self, newer: &ResultSnapshot<P>) -> (HashSet<P>, HashSet<P>) {
+        let added = newer.0.difference(&self.0).cloned().collect();
+        let removed = self.0.difference(&newer.0).cloned().collect();
+        (added, removed)
+    }
+
+    /// Returns the rows / primary ids of this snapshot.
+    pub fn results(&self) -> &HashSet<P> {
+        &self.0
+    }
+
+    /// Consumes the snapshot and returns its rows / primary ids.
+    pub fn into_results(self) -> HashSet<P> {
+        self.0
+    }
+}
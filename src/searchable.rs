@@ -0,0 +1,184 @@
+use crate::{Query, Result, SearchEngine};
+use std::{hash::Hash, marker::PhantomData};
+
+/// A type whose fields can be searched with a [SearchEngine].
+///
+/// This trait is usually implemented with `#[derive(Searchable)]`, which requires
+/// the `derive` feature. The derive macro adds one index per field and a function
+/// per field that returns a typed attribute, like [ExactAttribute] or
+/// [RangeAttribute], to build queries without spelling out attribute names.
+///
+/// # Field Types
+/// The index of a field is chosen by its type. `Option<T>` and `Vec<T>` fields
+/// use the index of `T` and insert no value or every value of the field.
+///
+/// | Field type                                    | Index                   | Attribute           |
+/// |-----------------------------------------------|-------------------------|---------------------|
+/// | `u8` to `u128`, `i8` to `i128`, `usize`, `isize` | [SearchIndexBTreeRange](crate::SearchIndexBTreeRange) | [RangeAttribute] |
+/// | `String` with `#[searchable(prefix)]`         | [SearchIndexPrefixTree](crate::SearchIndexPrefixTree) | [PrefixAttribute] |
+/// | any other type with `#[searchable(range)]`    | [SearchIndexBTreeRange](crate::SearchIndexBTreeRange) | [RangeAttribute] |
+/// | any other type, like `String`, `bool` or enums | [SearchIndexHashMap](crate::SearchIndexHashMap) | [ExactAttribute] |
+///
+/// Fields with `#[searchable(skip)]` are left out. The values of a field must
+/// implement [ToString](std::string::ToString) and [FromStr](std::str::FromStr),
+/// because they are inserted with [SearchEngine::insert]. Floating point fields
+/// are not supported by any index and must be skipped.
+///
+/// The attribute functions have the same names as the fields, so a type can't
+/// have other associated functions with these names.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use attribute_search_engine::{Query, Searchable};
+/// use std::collections::HashSet;
+///
+/// #[derive(Searchable)]
+/// struct Person {
+///     name: String,
+///     age: u8,
+///     #[searchable(prefix)]
+///     address: String,
+///     pets: Vec<String>,
+/// }
+///
+/// let mut engine = Person::search_engine::<usize>();
+/// let alice = Person {
+///     name: "Alice".into(),
+///     age: 27,
+///     address: "Germany/Hamburg".into(),
+///     pets: vec!["Cat".into()],
+/// };
+/// alice.insert_into(&mut engine, 0).unwrap();
+///
+/// let q = Query::And(vec![
+///     Person::age().in_range(20, 40),
+///     Person::address().prefix("Germany/"),
+///     Person::pets().exact("Cat"),
+/// ]);
+/// assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+/// # }
+/// ```
+pub trait Searchable {
+    /// Creates a new [SearchEngine] with an empty index for every field.
+    fn search_engine<P: Eq + Hash + Clone + Send + Sync + 'static>() -> SearchEngine<P>;
+
+    /// Inserts the values of all fields as a row into an engine that
+    /// was created by [search_engine](Self::search_engine).
+    fn insert_into<P: Eq + Hash + Clone + Send + Sync + 'static>(
+        &self,
+        engine: &mut SearchEngine<P>,
+        primary_id: P,
+    ) -> Result<()>;
+}
+
+/// A typed attribute that supports [Exact](Query::Exact) queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExactAttribute<T> {
+    name: &'static str,
+    value_type: PhantomData<fn() -> T>,
+}
+
+impl<T: ToString> ExactAttribute<T> {
+    /// Creates a typed attribute for the index with this name.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value_type: PhantomData,
+        }
+    }
+
+    /// Returns the name of the attribute.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Creates an [Exact](Query::Exact) query for this attribute.
+    pub fn exact(&self, value: impl Into<T>) -> Query {
+        Query::Exact(self.name.to_owned(), value.into().to_string())
+    }
+}
+
+/// A typed string attribute that supports [Exact](Query::Exact)
+/// and [Prefix](Query::Prefix) queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixAttribute {
+    name: &'static str,
+}
+
+impl PrefixAttribute {
+    /// Creates a typed attribute for the index with this name.
+    pub const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+
+    /// Returns the name of the attribute.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Creates an [Exact](Query::Exact) query for this attribute.
+    pub fn exact(&self, value: &str) -> Query {
+        Query::Exact(self.name.to_owned(), value.to_owned())
+    }
+
+    /// Creates a [Prefix](Query::Prefix) query for this attribute.
+    pub fn prefix(&self, prefix: &str) -> Query {
+        Query::Prefix(self.name.to_owned(), prefix.to_owned())
+    }
+}
+
+/// A typed attribute that supports [Exact](Query::Exact) and range queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeAttribute<T> {
+    name: &'static str,
+    value_type: PhantomData<fn() -> T>,
+}
+
+impl<T: Ord + ToString> RangeAttribute<T> {
+    /// Creates a typed attribute for the index with this name.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value_type: PhantomData,
+        }
+    }
+
+    /// Returns the name of the attribute.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Creates an [Exact](Query::Exact) query for this attribute.
+    pub fn exact(&self, value: T) -> Query {
+        Query::Exact(self.name.to_owned(), value.to_string())
+    }
+
+    /// Creates an [InRange](Query::InRange) query for this attribute.
+    /// Both bounds are inclusive.
+    pub fn in_range(&self, min: T, max: T) -> Query {
+        Query::InRange(self.name.to_owned(), min.to_string(), max.to_string())
+    }
+
+    /// Creates an [InRangeBounds](Query::InRangeBounds) query for this
+    /// attribute where both bounds are exclusive.
+    pub fn in_range_exclusive(&self, min: T, max: T) -> Query {
+        Query::in_range_exclusive(self.name, min.to_string(), max.to_string())
+    }
+
+    /// Creates an [OutRange](Query::OutRange) query for this attribute.
+    pub fn out_range(&self, start: T, end: T) -> Query {
+        Query::OutRange(self.name.to_owned(), start.to_string(), end.to_string())
+    }
+
+    /// Creates a [Minimum](Query::Minimum) query for this attribute.
+    pub fn minimum(&self, value: T) -> Query {
+        Query::Minimum(self.name.to_owned(), value.to_string())
+    }
+
+    /// Creates a [Maximum](Query::Maximum) query for this attribute.
+    pub fn maximum(&self, value: T) -> Query {
+        Query::Maximum(self.name.to_owned(), value.to_string())
+    }
+}
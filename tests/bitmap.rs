@@ -0,0 +1,103 @@
+#![cfg(feature = "roaring")]
+
+use std::collections::HashSet;
+
+use attribute_search_engine::*;
+
+/// Creates an engine with dense ids where `pet` and `city` are bitmap indices
+/// and `age` is a normal range index.
+fn create_bitmap_search_engine() -> SearchEngine<u32> {
+    let mut index_pet = SearchIndexBitmap::<String>::new();
+    let mut index_city = SearchIndexBitmap::<String>::new();
+    let mut index_age = SearchIndexBTreeRange::<u32, u8>::new();
+    let pets = ["Cat", "Dog", "Bird"];
+    let cities = ["Berlin", "Bonn", "Hamburg", "Köln", "München"];
+    for id in 0..5000 {
+        index_pet.insert(id, pets[id as usize % pets.len()].into());
+        index_city.insert(id, cities[id as usize % cities.len()].into());
+        index_age.insert(id, (id % 80) as u8);
+    }
+    // Some rows have a second pet.
+    for id in (0..5000).step_by(7) {
+        index_pet.insert(id, "Fish".into());
+    }
+
+    let mut engine = SearchEngine::<u32>::new();
+    engine.add_index("pet", index_pet);
+    engine.add_index("city", index_city);
+    engine.add_index("age", index_age);
+    engine
+}
+
+#[test]
+fn search_bitmap_matches_search() {
+    let mut engine = create_bitmap_search_engine();
+    let queries = [
+        "+pet:Cat",
+        "+pet:Cat,Dog +city:Berlin",
+        "+pet:Cat -city:Bonn,Köln",
+        "+age:<10 +pet:Fish",
+        "+age:20-30 -pet:Bird",
+        "+pet:Cat,Dog,Fish,Bird,Unknown",
+        "+pet:Unknown +city:Berlin",
+        "-pet:Cat",
+        "+pet:Cat,Fish,Dog,>=2",
+    ];
+    for query_str in queries {
+        let (q, _) = engine.query_from_str(query_str).unwrap();
+        let expected = engine.search(&q).unwrap();
+        let result = engine.search_bitmap(&q).unwrap();
+        assert_eq!(
+            result.iter().collect::<HashSet<_>>(),
+            expected,
+            "{query_str}"
+        );
+    }
+    let not_berlin = Query::Not(Box::new(Query::Exact("city".into(), "Berlin".into())));
+    let result = engine.search_bitmap(&not_berlin).unwrap();
+    assert_eq!(result.len(), 4000);
+    assert_eq!(
+        engine
+            .search_bitmap(&Query::And(vec![Query::All]))
+            .map(|r| r.len()),
+        Ok(5000)
+    );
+
+    engine
+        .set_multi_value_match("pet", MultiValueMatch::Exactly)
+        .unwrap();
+    let cats = Query::Exact("pet".into(), "Cat".into());
+    let result = engine.search_bitmap(&cats).unwrap();
+    assert_eq!(
+        result.iter().collect::<HashSet<_>>(),
+        engine.search(&cats).unwrap()
+    );
+    assert!(!result.contains(0));
+
+    assert_eq!(
+        engine.search_bitmap(&Query::Exact("color".into(), "Red".into())),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    // The empty first subquery doesn't hide the unknown attribute.
+    let q = Query::And(vec![
+        Query::Exact("pet".into(), "Unknown".into()),
+        Query::Exact("color".into(), "Red".into()),
+    ]);
+    assert_eq!(engine.search(&q), Err(SearchEngineError::UnknownAttribute));
+    assert_eq!(
+        engine.search_bitmap(&q),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    let q = Query::Exclude(
+        Box::new(Query::Exact("pet".into(), "Unknown".into())),
+        vec![Query::Exact("color".into(), "Red".into())],
+    );
+    assert_eq!(
+        engine.search_bitmap(&q),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    assert_eq!(
+        engine.search_bitmap(&Query::Prefix("pet".into(), "C".into())),
+        Err(SearchEngineError::UnsupportedQuery)
+    );
+}
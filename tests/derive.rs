@@ -0,0 +1,141 @@
+#![cfg(feature = "derive")]
+
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use attribute_search_engine::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Role {
+    Admin,
+    User,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "Admin" => Ok(Self::Admin),
+            "User" => Ok(Self::User),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Searchable)]
+struct Person {
+    name: String,
+    age: u8,
+    #[searchable(prefix)]
+    address: String,
+    role: Role,
+    nickname: Option<String>,
+    pets: Vec<String>,
+    #[searchable(range)]
+    zipcode: String,
+    #[searchable(skip)]
+    #[allow(dead_code)]
+    height: f32,
+}
+
+fn create_person_search_engine() -> SearchEngine<usize> {
+    let persons = [
+        Person {
+            name: "Alice".into(),
+            age: 27,
+            address: "Germany/Hamburg".into(),
+            role: Role::Admin,
+            nickname: None,
+            pets: vec![],
+            zipcode: "20095".into(),
+            height: 1.7,
+        },
+        Person {
+            name: "Bob".into(),
+            age: 35,
+            address: "Germany/Berlin".into(),
+            role: Role::User,
+            nickname: Some("Bobby".into()),
+            pets: vec!["Cat".into(), "Dog".into()],
+            zipcode: "10115".into(),
+            height: 1.8,
+        },
+        Person {
+            name: "Eve".into(),
+            age: 23,
+            address: "France/Paris".into(),
+            role: Role::User,
+            nickname: None,
+            pets: vec!["Cat".into()],
+            zipcode: "75001".into(),
+            height: 1.6,
+        },
+    ];
+    let mut engine = Person::search_engine();
+    for (id, person) in persons.iter().enumerate() {
+        person.insert_into(&mut engine, id).unwrap();
+    }
+    engine
+}
+
+#[test]
+fn derive_typed_queries() {
+    let engine = create_person_search_engine();
+
+    let search = |q: Query| engine.search(&q).unwrap();
+    assert_eq!(
+        search(Person::name().exact("Bob")),
+        HashSet::from_iter(vec![1])
+    );
+    assert_eq!(
+        search(Person::age().in_range(20, 30)),
+        HashSet::from_iter(vec![0, 2])
+    );
+    assert_eq!(
+        search(Person::age().minimum(30)),
+        HashSet::from_iter(vec![1])
+    );
+    assert_eq!(
+        search(Person::address().prefix("Germany/")),
+        HashSet::from_iter(vec![0, 1])
+    );
+    assert_eq!(
+        search(Person::role().exact(Role::User)),
+        HashSet::from_iter(vec![1, 2])
+    );
+    assert_eq!(
+        search(Person::nickname().exact("Bobby")),
+        HashSet::from_iter(vec![1])
+    );
+    assert_eq!(
+        search(Person::pets().exact("Cat")),
+        HashSet::from_iter(vec![1, 2])
+    );
+    assert_eq!(
+        search(Person::zipcode().maximum("50000".into())),
+        HashSet::from_iter(vec![0, 1])
+    );
+
+    let q = Query::And(vec![Person::pets().exact("Cat"), Person::age().maximum(30)]);
+    assert_eq!(search(q), HashSet::from_iter(vec![2]));
+}
+
+#[test]
+fn derive_attribute_names() {
+    let engine = create_person_search_engine();
+    assert_eq!(Person::name().name(), "name");
+    assert_eq!(Person::address().name(), "address");
+
+    let (q, _) = engine.query_from_str("+age:>30 +address:Germany/").unwrap();
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1])));
+    assert_eq!(
+        engine.search(&Query::Exact("height".into(), "1.8".into())),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
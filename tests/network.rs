@@ -1,8 +1,12 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    str::FromStr,
+};
 
 use attribute_search_engine::*;
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 enum ServerOs {
     Debian,
     Alpine,
@@ -10,6 +14,18 @@ enum ServerOs {
     Win,
 }
 
+impl fmt::Display for ServerOs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Debian => "Debian",
+            Self::Alpine => "Alpine",
+            Self::Router => "Router",
+            Self::Win => "Win",
+        };
+        write!(f, "{name}")
+    }
+}
+
 impl FromStr for ServerOs {
     type Err = ();
 
@@ -31,7 +47,7 @@ fn create_network_search_engine() -> SearchEngine<u8> {
     let mut index_uptime = SearchIndexBTreeRange::<_, u64>::new();
     let mut index_user = SearchIndexHashMap::<_, String>::new();
 
-    #[cfg_attr(rustfmt, rustfmt_skip)]
+    #[rustfmt::skip]
     let systems = vec![
         ( 0,"gateway",        ServerOs::Router, "192.168.0.1",  4323, vec!["root"]),
         ( 1,"firewall-01",    ServerOs::Debian, "192.168.0.11", 1133, vec!["root"]),
@@ -72,9 +88,437 @@ fn create_network_search_engine() -> SearchEngine<u8> {
     engine
 }
 
+#[test]
+fn any_present_extra_user_or_router() {
+    let mut engine = create_network_search_engine();
+    engine.add_index("extra_user", SearchIndexHashMap::<_, String>::new());
+    engine.add_index("firmware", SearchIndexHashMap::<_, String>::new());
+    for (id, row) in engine.export_rows() {
+        for user in row["user"].iter().filter(|user| *user != "root") {
+            engine.insert("extra_user", id, user).unwrap();
+        }
+        if row["os"].iter().any(|os| os == "Router") {
+            engine.insert("firmware", id, "1.0").unwrap();
+        }
+    }
+
+    let result = engine.any_present(&["extra_user", "firmware"]).unwrap();
+    assert_eq!(result, (0..=16).filter(|id| ![1, 2].contains(id)).collect());
+    assert_eq!(
+        engine.any_present(&["extra_user"]),
+        Ok((6..=16).filter(|id| ![12, 13].contains(id)).collect())
+    );
+    assert_eq!(engine.any_present(&[]), Ok(Default::default()));
+    assert_eq!(
+        engine.any_present(&["extra_user", "unknown"]),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn orphans_without_name_or_ip() {
+    let mut engine = create_network_search_engine();
+    engine.insert("user", 17, "root").unwrap();
+    engine.insert("uptime", 17, "10").unwrap();
+    engine.insert("user", 18, "alex").unwrap();
+    engine.insert("ip4", 18, "192.168.40.1").unwrap();
+
+    assert_eq!(
+        engine.orphans(&["name", "ip4"]),
+        Ok(HashSet::from_iter(vec![17]))
+    );
+    assert_eq!(
+        engine.orphans(&["name"]),
+        Ok(HashSet::from_iter(vec![17, 18]))
+    );
+    assert_eq!(engine.orphans(&[]), Ok(HashSet::from_iter(0..=18)));
+    assert_eq!(
+        engine.orphans(&["name", "unknown"]),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn universe_after_insert() {
+    let mut engine = create_network_search_engine();
+    assert_eq!(engine.universe(), &HashSet::from_iter(0..=16));
+
+    let not_root = Query::Not(Box::new(Query::Exact("user".into(), "root".into())));
+    let before = engine.search(&not_root).unwrap();
+    assert!(!before.contains(&17));
+
+    engine.insert("uptime", 17, "10").unwrap();
+    assert_eq!(engine.universe(), &HashSet::from_iter(0..=17));
+    assert_eq!(engine.search(&not_root).unwrap().len(), before.len() + 1);
+
+    engine.add_index("source", SearchIndexPrefixTree::<_>::new());
+    engine.insert("source", 18, "10.0.0.5").unwrap();
+    assert_eq!(engine.universe(), &engine.all_ids());
+    assert!(engine.universe().contains(&18));
+}
+
+#[test]
+fn validate_data_rejects_unparsable_values() {
+    let mut engine = create_network_search_engine();
+    let rows = || {
+        vec![
+            (9, "uptime".to_owned(), "3600".to_owned()),
+            (9, "uptime".to_owned(), "abc".to_owned()),
+            (9, "os".to_owned(), "BeOS".to_owned()),
+            (9, "name".to_owned(), "abc".to_owned()),
+            (9, "unknown".to_owned(), "abc".to_owned()),
+        ]
+    };
+    assert_eq!(
+        engine.validate_data(rows()),
+        Err(vec![
+            (9, "uptime".into(), "abc".into()),
+            (9, "os".into(), "BeOS".into()),
+            (9, "unknown".into(), "abc".into()),
+        ])
+    );
+    assert_eq!(engine.validate_data(rows().into_iter().take(1)), Ok(()));
+
+    // Every rejected triple is also rejected by insert.
+    for (primary_id, attribute, value) in engine.validate_data(rows()).unwrap_err() {
+        assert!(engine.insert(&attribute, primary_id, &value).is_err());
+    }
+    engine.insert("uptime", 9, "3600").unwrap();
+}
+
+#[test]
+fn swap_index() {
+    let mut engine = create_network_search_engine();
+    let old_index = engine.remove_index("name").expect("index exists");
+    assert!(!engine.has_index("name"));
+    assert_eq!(
+        engine.search(&Query::Exact("name".into(), "web-01".into())),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+
+    let mut index_name = SearchIndexPrefixTree::<_>::new();
+    for (id, value) in old_index.entries() {
+        index_name.insert(id, value);
+    }
+    engine.add_index("name", index_name);
+    assert!(engine.has_index("name"));
+    assert_eq!(
+        engine.search(&Query::Prefix("name".into(), "web-".into())),
+        Ok(HashSet::from_iter(vec![6, 7, 8]))
+    );
+
+    // Adding an index with an existing name replaces it.
+    engine.add_index("name", SearchIndexHashMap::<_, String>::new());
+    assert_eq!(
+        engine.search(&Query::Exact("name".into(), "web-01".into())),
+        Ok(HashSet::new())
+    );
+    assert!(engine.remove_index("unknown").is_none());
+}
+
+#[test]
+fn ingest_login_log() {
+    let mut engine = create_network_search_engine();
+    engine.add_index("login", SearchIndexHashMap::<_, String>::new());
+    engine.add_index("source", SearchIndexPrefixTree::<_>::new());
+
+    let log = "\
+2024-03-01T08:12:44 host=9 user=alex from=10.0.0.5
+2024-03-01T08:13:02 host=11 user=peter from=10.0.1.7
+malformed line without fields
+2024-03-01T09:45:10 host=7 user=alex from=172.16.0.3
+
+2024-03-01T10:01:59 host=9 user=hans from=10.0.0.9
+";
+    let parse = |line: &str| {
+        let fields: Vec<(&str, &str)> = line
+            .split_whitespace()
+            .filter_map(|field| field.split_once('='))
+            .collect();
+        let Some(host) = fields.iter().find(|(key, _)| *key == "host") else {
+            return vec![];
+        };
+        let id: u8 = host.1.parse().unwrap();
+        fields
+            .iter()
+            .filter_map(|(key, value)| match *key {
+                "user" => Some((id, "login".to_owned(), value.to_string())),
+                "from" => Some((id, "source".to_owned(), value.to_string())),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(engine.ingest_lines(log.as_bytes(), parse), Ok(8));
+
+    let (q, _) = engine.query_from_str("+login:alex +source:10.0.").unwrap();
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![9])));
+    let (q, _) = engine.query_from_str("+source:10.0.").unwrap();
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![9, 11])));
+
+    let result = engine.ingest_lines(&b"host=1 user=root\n\xff\n"[..], parse);
+    assert!(matches!(
+        result,
+        Err(SearchEngineError::Io {
+            kind: std::io::ErrorKind::InvalidData,
+            ..
+        })
+    ));
+    assert_eq!(
+        engine.search(&Query::Exact("login".into(), "root".into())),
+        Ok(HashSet::from_iter(vec![1]))
+    );
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn search_to_json_array() {
+    let engine = create_network_search_engine();
+    let q = Query::Exact("user".into(), "hans".into());
+    let json = engine.search_to_json(&q).unwrap();
+    let ids: Vec<u8> = serde_json::from_str(&json).unwrap();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(HashSet::from_iter(ids), engine.search(&q).unwrap());
+
+    let empty = Query::Exact("user".into(), "nobody".into());
+    assert_eq!(engine.search_to_json(&empty), Ok("[]".into()));
+    assert_eq!(
+        engine.search_to_json(&Query::Exact("unknown".into(), "x".into())),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn search_to_csv_lines() {
+    let engine = create_network_search_engine();
+    let q = Query::Exact("user".into(), "hans".into());
+    let mut csv = Vec::new();
+    engine.search_to_csv(&q, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let ids: HashSet<u8> = csv.lines().map(|line| line.parse().unwrap()).collect();
+    assert_eq!(csv.lines().count(), 3);
+    assert_eq!(ids, HashSet::from_iter(vec![9, 11, 16]));
+
+    let mut engine = SearchEngine::<String>::new();
+    engine.add_index("tag", SearchIndexHashMap::<_, String>::new());
+    engine.insert("tag", "a,b".into(), "x").unwrap();
+    engine.insert("tag", "say \"hi\"".into(), "y").unwrap();
+    let mut csv = Vec::new();
+    engine
+        .search_to_csv(&Query::Exact("tag".into(), "x".into()), &mut csv)
+        .unwrap();
+    engine
+        .search_to_csv(&Query::Exact("tag".into(), "y".into()), &mut csv)
+        .unwrap();
+    assert_eq!(csv, b"\"a,b\"\n\"say \"\"hi\"\"\"\n");
+}
+
+#[test]
+fn search_into_accumulates() {
+    let engine = create_network_search_engine();
+    let queries = [
+        Query::Exact("user".into(), "hans".into()),
+        Query::Or(vec![
+            Query::Exact("os".into(), "Win".into()),
+            Query::Minimum("uptime".into(), "9000".into()),
+        ]),
+        Query::And(vec![
+            Query::Exact("user".into(), "alex".into()),
+            Query::Or(vec![
+                Query::Exact("os".into(), "Alpine".into()),
+                Query::Exact("os".into(), "Debian".into()),
+            ]),
+        ]),
+        Query::Exclude(
+            Query::Prefix("ip4".into(), "192.168.20.".into()).into(),
+            vec![Query::Exact("os".into(), "Win".into())],
+        ),
+        Query::Not(Query::Exact("user".into(), "root".into()).into()),
+        Query::AtLeast(
+            2,
+            vec![
+                Query::Exact("user".into(), "alex".into()),
+                Query::Exact("user".into(), "peter".into()),
+                Query::Exact("user".into(), "hans".into()),
+            ],
+        ),
+        Query::And(vec![Query::All]),
+    ];
+    for query in queries.iter() {
+        let expected = engine.search(query).unwrap();
+
+        let mut result_set = HashSet::new();
+        engine.search_into(query, &mut result_set).unwrap();
+        assert_eq!(result_set, expected, "{query:?}");
+
+        let mut result_set = HashSet::from_iter(vec![3, 100]);
+        engine.search_into(query, &mut result_set).unwrap();
+        let mut expected = expected;
+        expected.extend([3, 100]);
+        assert_eq!(result_set, expected, "{query:?}");
+    }
+
+    let mut result_set = HashSet::new();
+    assert_eq!(
+        engine.search_into(&Query::Exact("unknown".into(), "x".into()), &mut result_set),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn uptime_top_n_above() {
+    let mut engine = create_network_search_engine();
+    let uptime = engine.remove_index("uptime").expect("uptime index");
+    let mut index = SearchIndexBTreeRange::<u8, u64>::new();
+    for (id, value) in uptime.entries() {
+        index.insert_str(id, &value).unwrap();
+    }
+
+    let values = |top: Vec<(u64, HashSet<u8>)>| -> Vec<u64> {
+        top.into_iter().map(|(value, _)| value).collect()
+    };
+    assert_eq!(
+        values(index.top_n_above(3, "4000").unwrap()),
+        vec![9132, 8543, 5322]
+    );
+    assert_eq!(
+        values(index.top_n_above(10, "4300").unwrap()),
+        vec![9132, 8543, 5322, 4567, 4534, 4323]
+    );
+    assert_eq!(
+        index.top_n_above(1, "9000").unwrap(),
+        vec![(9132, HashSet::from_iter(vec![11]))]
+    );
+    assert_eq!(index.top_n_above(10, "10000").unwrap(), vec![]);
+    assert_eq!(index.top_n_above(0, "0").unwrap(), vec![]);
+    assert_eq!(
+        index.top_n_above(3, "-1"),
+        Err(SearchEngineError::MismatchedQueryType {
+            attribute: "".into(),
+            expected: "u64"
+        })
+    );
+}
+
+#[test]
+fn uptime_histogram() {
+    let engine = create_network_search_engine();
+
+    let all = Query::Minimum("uptime".into(), "0".into());
+    assert_eq!(
+        engine.histogram(&all, "uptime", 1000),
+        Ok(BTreeMap::from([
+            (0, 3),
+            (1000, 3),
+            (2000, 4),
+            (4000, 4),
+            (5000, 1),
+            (8000, 1),
+            (9000, 1),
+        ]))
+    );
+
+    let (routers, _) = engine.query_from_str("+os:Router").unwrap();
+    assert_eq!(
+        engine.histogram(&routers, "uptime", 1000),
+        Ok(BTreeMap::from([(1000, 2), (2000, 1), (4000, 3)]))
+    );
+    assert_eq!(
+        engine.histogram(&routers, "uptime", 5000),
+        Ok(BTreeMap::from([(0, 6)]))
+    );
+
+    assert_eq!(
+        engine.histogram(&routers, "name", 1000),
+        Err(SearchEngineError::MismatchedQueryType {
+            attribute: "name".into(),
+            expected: "f64"
+        })
+    );
+    assert_eq!(
+        engine.histogram(&routers, "unknown", 1000),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    assert_eq!(
+        engine.histogram(&routers, "uptime", 0),
+        Err(SearchEngineError::UnsupportedOperation)
+    );
+}
+
+#[test]
+fn load_histogram_of_floats() {
+    let mut index_load = SearchIndexF64Range::<usize>::new();
+    index_load.insert(0, 0.25).unwrap();
+    index_load.insert(1, 0.5).unwrap();
+    index_load.insert(2, 1.75).unwrap();
+    index_load.insert(3, -0.5).unwrap();
+
+    let mut engine = SearchEngine::<usize>::new();
+    engine.add_index("load", index_load);
+
+    let all = Query::Minimum("load".into(), "-10".into());
+    assert_eq!(
+        engine.histogram(&all, "load", 1),
+        Ok(BTreeMap::from([(-1, 1), (0, 2), (1, 1)]))
+    );
+    assert_eq!(
+        engine.histogram(&all, "load", 10),
+        Ok(BTreeMap::from([(-10, 1), (0, 3)]))
+    );
+}
+
+#[test]
+fn histogram_of_large_u64_values() {
+    let mut index_bytes = SearchIndexBTreeRange::<usize, u64>::new();
+    index_bytes.insert(0, u64::MAX);
+    index_bytes.insert(1, u64::MAX - 1);
+    index_bytes.insert(2, u64::MAX - 20);
+    index_bytes.insert(3, 1 << 63);
+
+    let mut engine = SearchEngine::<usize>::new();
+    engine.add_index("bytes", index_bytes);
+
+    let all = Query::Minimum("bytes".into(), "0".into());
+    let max = i128::from(u64::MAX);
+    assert_eq!(
+        engine.histogram(&all, "bytes", 10),
+        Ok(BTreeMap::from([
+            ((1 << 63) - 8, 1),
+            (max - 25, 1),
+            (max - 5, 2)
+        ]))
+    );
+}
+
+#[test]
+fn common_values_of_selection() {
+    let engine = create_network_search_engine();
+
+    let selected = HashSet::from_iter(vec![1, 2, 9, 10]);
+    assert_eq!(
+        engine.common_values(&selected, "os"),
+        Ok(vec!["Debian".into()])
+    );
+    assert_eq!(
+        engine.common_values(&selected, "user"),
+        Ok(vec!["root".into()])
+    );
+
+    let (q, _) = engine.query_from_str("+ip4:192.168.10.").unwrap();
+    let dmz = engine.search(&q).unwrap();
+    assert_eq!(engine.common_values(&dmz, "os"), Ok(vec![]));
+    assert_eq!(
+        engine.common_values(&HashSet::from_iter(vec![6, 7]), "user"),
+        Ok(vec!["alex".into(), "root".into(), "webmaster".into()])
+    );
+    assert_eq!(engine.common_values(&HashSet::new(), "os"), Ok(vec![]));
+    assert_eq!(
+        engine.common_values(&selected, "unknown"),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
 mod query_string {
     use super::*;
-    use std::collections::HashSet;
 
     macro_rules! query_test {
         ($name:ident $query:literal; $($res:expr),* $(,)?) => {
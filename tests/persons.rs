@@ -1,6 +1,10 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Bound,
+};
 
 use attribute_search_engine::*;
+use proptest::prelude::*;
 
 #[test]
 fn query_btree_range_index() {
@@ -71,6 +75,37 @@ fn query_prefix_tree_index() {
     assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![])));
 }
 
+#[test]
+fn query_prefix_tree_delimiter() {
+    let mut index_permission = SearchIndexPrefixTree::<u8>::new_with_delimiter('.');
+    for (id, row) in create_person_search_engine().export_rows() {
+        for permission in row["permission"].iter() {
+            index_permission.insert(id, permission.clone());
+        }
+    }
+    index_permission.insert(6, "finance".into());
+    let mut engine = SearchEngine::new();
+    engine.add_index("permission", index_permission);
+
+    let q = Query::Prefix("permission".into(), "finances".into());
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1, 4, 5])));
+
+    let q = Query::Prefix("permission".into(), "finance".into());
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![6])));
+
+    let q = Query::Prefix("permission".into(), "finances.re".into());
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![])));
+
+    let q = Query::Prefix("permission".into(), "personel.read".into());
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1, 2])));
+
+    let q = Query::Prefix("permission".into(), "".into());
+    assert_eq!(
+        engine.search(&q),
+        Ok(HashSet::from_iter(vec![0, 1, 2, 3, 4, 5, 6]))
+    );
+}
+
 #[test]
 fn query_advanced() {
     let engine = create_person_search_engine();
@@ -99,6 +134,196 @@ fn query_advanced() {
     );
 }
 
+#[test]
+fn query_parser_multiple_ranges() {
+    let engine = create_person_search_engine();
+
+    let (q, _) = engine
+        .query_from_str("+age:20-26,30-40")
+        .expect("valid query");
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Or(vec![
+            Query::InRange("age".into(), "20".into(), "26".into()),
+            Query::InRange("age".into(), "30".into(), "40".into()),
+        ])])
+    );
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2, 3, 4])));
+
+    let (q, _) = engine
+        .query_from_str("+age:<24,26-30,>50")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1, 2, 5])));
+}
+
+#[test]
+fn query_exclude_matches_difference() {
+    let engine = create_person_search_engine();
+
+    let cases = [
+        (
+            Query::Exact("zipcode".into(), "12345".into()),
+            vec![Query::Exact("name".into(), "Hans".into())],
+        ),
+        (
+            Query::Prefix("permission".into(), "".into()),
+            vec![
+                Query::Exact("pet".into(), "Dog".into()),
+                Query::Minimum("age".into(), "30".into()),
+            ],
+        ),
+        (
+            Query::Exact("name".into(), "Bob".into()),
+            vec![Query::Prefix("permission".into(), "".into())],
+        ),
+        (
+            Query::Exact("pet".into(), "Dog".into()),
+            vec![Query::Exact("city".into(), "Frankfurt".into())],
+        ),
+    ];
+    for (base, exclude) in cases {
+        let mut expected = engine.search(&base).unwrap();
+        for pred in exclude.iter() {
+            let attribute_set = engine.search(pred).unwrap();
+            expected = expected.difference(&attribute_set).cloned().collect();
+        }
+
+        let q = Query::Exclude(base.into(), exclude);
+        assert_eq!(engine.search(&q), Ok(expected.clone()));
+        assert_eq!(engine.compile(&q).unwrap().execute(), Ok(expected));
+    }
+}
+
+#[test]
+fn query_optional_filters() {
+    let engine = create_person_search_engine();
+
+    let filters = |pet: Option<&str>, zipcode: Option<&str>, min_age: Option<u8>| {
+        Query::And(vec![
+            Query::optional(pet.map(|pet| Query::exact("pet", pet))),
+            Query::optional(zipcode.map(|zipcode| Query::exact("zipcode", zipcode))),
+            Query::optional(min_age.map(|age| Query::Minimum("age".into(), age.to_string()))),
+        ])
+    };
+
+    let q = filters(Some("Dog"), None, Some(30));
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![4, 5])));
+    assert_eq!(
+        engine.compile(&q).unwrap().execute(),
+        Ok(HashSet::from_iter(vec![4, 5]))
+    );
+
+    let q = filters(None, Some("12345"), None);
+    assert_eq!(
+        engine.search(&q),
+        engine.search(&Query::exact("zipcode", "12345"))
+    );
+
+    let q = filters(None, None, None);
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(0..=5)));
+    assert_eq!(engine.search(&Query::All), Ok(HashSet::from_iter(0..=5)));
+}
+
+#[test]
+fn query_not() {
+    let engine = create_person_search_engine();
+    assert_eq!(engine.all_ids(), HashSet::from_iter(0..=5));
+
+    let q = Query::Not(Box::new(Query::Exact("pet".into(), "Dog".into())));
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 2])));
+    assert_eq!(
+        engine.compile(&q).unwrap().execute(),
+        Ok(HashSet::from_iter(vec![0, 2]))
+    );
+
+    let q = Query::And(vec![
+        Query::Exact("zipcode".into(), "12345".into()),
+        Query::Not(Box::new(Query::Or(vec![
+            Query::Exact("name".into(), "Hans".into()),
+            Query::Minimum("age".into(), "50".into()),
+        ]))),
+    ]);
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1, 2])));
+
+    let q = Query::Not(Box::new(Query::Not(Box::new(Query::Exact(
+        "city".into(),
+        "Berlin".into(),
+    )))));
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2])));
+    assert_eq!(
+        engine.search(&Query::Not(Box::new(Query::All))),
+        Ok(HashSet::new())
+    );
+    assert_eq!(
+        engine.search(&Query::Not(Box::new(Query::Exact(
+            "unknown".into(),
+            "".into()
+        )))),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+/// Generates random queries over the attributes of the person search engine.
+fn arbitrary_query() -> impl Strategy<Value = Query> {
+    let leaf = prop_oneof![
+        prop::sample::select(vec!["Alice", "Bob", "Hans", "Eve"])
+            .prop_map(|name| Query::Exact("name".into(), name.into())),
+        prop::sample::select(vec!["Cat", "Dog", "Bees"])
+            .prop_map(|pet| Query::Exact("pet".into(), pet.into())),
+        prop::sample::select(vec!["12345", "54321"])
+            .prop_map(|zipcode| Query::Exact("zipcode".into(), zipcode.into())),
+        (20u8..60, 0u8..20).prop_map(|(min, len)| Query::InRange(
+            "age".into(),
+            min.to_string(),
+            (min + len).to_string()
+        )),
+        (20u8..60).prop_map(|min| Query::Minimum("age".into(), min.to_string())),
+        (20u8..60).prop_map(|max| Query::Maximum("age".into(), max.to_string())),
+        prop::sample::select(vec!["", "dashboard", "finances", "personel.read"])
+            .prop_map(|prefix| Query::Prefix("permission".into(), prefix.into())),
+        Just(Query::All),
+        Just(Query::Exact("unknown".into(), "x".into())),
+        Just(Query::Minimum("age".into(), "old".into())),
+    ];
+    leaf.prop_recursive(4, 32, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Query::And),
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Query::Or),
+            (1usize..3, prop::collection::vec(inner.clone(), 0..4))
+                .prop_map(|(n, vec)| Query::AtLeast(n, vec)),
+            (inner.clone(), prop::collection::vec(inner.clone(), 0..3))
+                .prop_map(|(base, exclude)| Query::Exclude(Box::new(base), exclude)),
+            inner.prop_map(|inner| Query::Not(Box::new(inner))),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn optimize_keeps_results(q in arbitrary_query()) {
+        let engine = create_person_search_engine();
+        let optimized = engine.optimize(&q);
+        let expected = engine.search(&q);
+        let result = engine.search(&optimized);
+        // A mistyped value is only noticed if its subquery is searched,
+        // which may be skipped by an And in either form. Unknown attributes
+        // are always reported.
+        let mismatched = |result: &Result<HashSet<u8>>| {
+            matches!(result, Err(SearchEngineError::MismatchedQueryType { .. }))
+        };
+        if mismatched(&expected) || mismatched(&result) {
+            prop_assert!(
+                mismatched(&expected) || expected.is_ok(),
+                "{:?}",
+                optimized
+            );
+            prop_assert!(mismatched(&result) || result.is_ok(), "{:?}", optimized);
+        } else {
+            prop_assert_eq!(result, expected, "{:?}", optimized);
+        }
+    }
+}
+
 #[test]
 fn query_parser() {
     let engine = create_person_search_engine();
@@ -114,6 +339,902 @@ fn query_parser() {
     assert_eq!(ft, vec![] as Vec<&str>);
 }
 
+#[test]
+fn query_parser_at_least() {
+    let engine = create_person_search_engine();
+
+    let (q, _) = engine
+        .query_from_str("+pet:2#Cat,Dog,Bees")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1, 5])));
+
+    let (q, _) = engine
+        .query_from_str("+pet:3#Cat,Dog,Bees")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1])));
+
+    let (q, _) = engine
+        .query_from_str("+pet:1#Cat,Dog,Bees -pet:2#Cat,Dog")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2, 3, 4])));
+
+    let (q, _) = engine
+        .query_from_str("+pet:2#Dog,Dog")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::new()));
+}
+
+#[test]
+fn query_parser_open_bracket_range() {
+    let engine = create_person_search_engine();
+
+    let (q, _) = engine.query_from_str("+age:[27,)").expect("valid query");
+    assert_eq!(
+        q,
+        Query::And(vec![Query::InRangeBounds(
+            "age".into(),
+            Bound::Included("27".into()),
+            Bound::Unbounded
+        )])
+    );
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1, 4, 5])));
+
+    let (q, _) = engine.query_from_str("+age:(,27]").expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1, 2, 3])));
+
+    // The hashmap index doesn't support ranges, so the comma separates values.
+    let (q, _) = engine
+        .query_from_str("+pet:(Dog,Cat)")
+        .expect("valid query");
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Or(vec![
+            Query::Exact("pet".into(), "(Dog".into()),
+            Query::Exact("pet".into(), "Cat)".into()),
+        ])])
+    );
+}
+
+#[test]
+fn remove_row_everywhere() {
+    let mut engine = create_person_search_engine();
+    // Bob has a name, zipcode, city, pets, age and permissions.
+    assert_eq!(engine.remove_row(&1), 6);
+    assert_eq!(engine.remove_row(&1), 0);
+    assert_eq!(engine.all_ids(), HashSet::from_iter(vec![0, 2, 3, 4, 5]));
+    assert_eq!(engine.universe(), &engine.all_ids());
+
+    assert_eq!(
+        engine.search(&Query::Exact("name".into(), "Bob".into())),
+        Ok(HashSet::new())
+    );
+    assert_eq!(
+        engine.search(&Query::Prefix("permission".into(), "finances.".into())),
+        Ok(HashSet::from_iter(vec![0, 4, 5]))
+    );
+    assert_eq!(
+        engine.search(&Query::Exact("age".into(), "27".into())),
+        Ok(HashSet::from_iter(vec![0]))
+    );
+}
+
+#[test]
+fn query_parser_merge_out_range() {
+    let plain_engine = create_person_search_engine();
+    let (or_query, _) = plain_engine.query_from_str("+age:<25,>40").unwrap();
+    assert_eq!(
+        or_query,
+        Query::And(vec![Query::Or(vec![
+            Query::Maximum("age".into(), "25".into()),
+            Query::Minimum("age".into(), "40".into()),
+        ])])
+    );
+
+    let mut engine = create_person_search_engine();
+    engine.set_parser_options(QueryParserOptions {
+        merge_out_range: true,
+        ..Default::default()
+    });
+    let (merged, _) = engine.query_from_str("+age:<25,>40").unwrap();
+    assert_eq!(
+        merged,
+        Query::And(vec![Query::OutRangeBounds(
+            "age".into(),
+            Bound::Included("25".into()),
+            Bound::Included("40".into())
+        )])
+    );
+    // Victor is exactly 25 and is matched by both forms.
+    assert_eq!(
+        engine.search(&merged),
+        Ok(HashSet::from_iter(vec![2, 3, 5]))
+    );
+    assert_eq!(engine.search(&merged), engine.search(&or_query));
+
+    // Reversed, overlapping and invalid values return the same as the Or.
+    for query_str in ["+age:>40,<24", "+age:<40,>24", "+age:<x,>40"] {
+        let (merged, _) = engine.query_from_str(query_str).unwrap();
+        let (or_query, _) = plain_engine.query_from_str(query_str).unwrap();
+        assert_ne!(merged, or_query, "{query_str:?}");
+        assert_eq!(
+            engine.search(&merged),
+            engine.search(&or_query),
+            "{query_str:?}"
+        );
+    }
+
+    // Other shapes and other indices keep the Or.
+    for query_str in ["+age:<24,>40,=30", "+name:<24,>40"] {
+        let (q, _) = engine.query_from_str(query_str).unwrap();
+        let Query::And(subqueries) = q else {
+            panic!("{query_str:?} is not an And");
+        };
+        assert!(matches!(subqueries[0], Query::Or(_)), "{query_str:?}");
+    }
+}
+
+#[test]
+fn query_parser_boolean_keywords_negated_group() {
+    let mut engine = create_person_search_engine();
+    engine.set_parser_options(QueryParserOptions {
+        boolean_keywords: true,
+        ..Default::default()
+    });
+
+    let (q, _) = engine.query_from_str("pet:Dog OR NOT name:Hans").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Or(vec![
+            Query::And(vec![Query::Exact("pet".into(), "Dog".into())]),
+            Query::Exclude(
+                Box::new(Query::And(vec![Query::All])),
+                vec![Query::Exact("name".into(), "Hans".into())]
+            ),
+        ])])
+    );
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(0..=5)));
+
+    let (q, _) = engine.query_from_str("pet:Cat OR NOT name:Hans").unwrap();
+    assert_eq!(
+        engine.search(&q),
+        Ok(HashSet::from_iter(vec![0, 1, 2, 3, 5]))
+    );
+}
+
+#[test]
+fn query_parser_or_attributes() {
+    let mut engine = create_person_search_engine();
+    let query_str = "+pet:Cat +zipcode:12345 +pet:Dog";
+    let (q, _) = engine.query_from_str(query_str).expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1, 5])));
+
+    engine.set_parser_options(QueryParserOptions {
+        or_attributes: vec!["pet".into()],
+        ..Default::default()
+    });
+    let (q, _) = engine.query_from_str(query_str).expect("valid query");
+    assert_eq!(
+        q,
+        Query::And(vec![
+            Query::Or(vec![
+                Query::Exact("pet".into(), "Cat".into()),
+                Query::Exact("pet".into(), "Dog".into()),
+            ]),
+            Query::Exact("zipcode".into(), "12345".into()),
+        ])
+    );
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1, 2, 4, 5])));
+
+    let (q, _) = engine
+        .query_from_str("+pet:Bees +pet:Cat,Dog")
+        .expect("valid query");
+    assert_eq!(
+        engine.search(&q),
+        Ok(HashSet::from_iter(vec![1, 2, 3, 4, 5]))
+    );
+
+    // Count modifiers and excludes are kept as they are.
+    let (q, _) = engine
+        .query_from_str("+pet:2#Cat,Dog +pet:Bees -pet:Dog")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::new()));
+    let (q, _) = engine
+        .query_from_str("+pet:2#Cat,Dog +pet:Cat -pet:Dog")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2])));
+
+    // Other attributes still combine repeated selectors with And.
+    let (q, _) = engine
+        .query_from_str("+name:Hans +name:Bob")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::new()));
+}
+
+#[test]
+fn query_function_index() {
+    let mut engine = create_person_search_engine();
+    engine.add_index(
+        "even",
+        SearchIndexFunction::new(SUPPORTS_EXACT, |query| match query {
+            Query::Exact(_, value) if value == "true" => HashSet::from_iter(vec![0, 2, 4]),
+            Query::Exact(_, value) if value == "false" => HashSet::from_iter(vec![1, 3, 5]),
+            _ => HashSet::new(),
+        }),
+    );
+
+    let q = Query::Exact("even".into(), "true".into());
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 2, 4])));
+
+    let (q, _) = engine
+        .query_from_str("+even:false +pet:Dog")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1, 3, 5])));
+
+    let q = Query::Prefix("even".into(), "t".into());
+    assert_eq!(engine.search(&q), Err(SearchEngineError::UnsupportedQuery));
+}
+
+#[test]
+fn query_multi_value_match() {
+    let mut engine = create_person_search_engine();
+    let dog = Query::Exact("pet".into(), "Dog".into());
+    let cat = Query::Exact("pet".into(), "Cat".into());
+    assert_eq!(
+        engine.search(&dog),
+        Ok(HashSet::from_iter(vec![1, 3, 4, 5]))
+    );
+
+    engine
+        .set_multi_value_match("pet", MultiValueMatch::Exactly)
+        .expect("index exists");
+    assert_eq!(engine.search(&dog), Ok(HashSet::from_iter(vec![3, 4])));
+    assert_eq!(engine.search(&cat), Ok(HashSet::from_iter(vec![2])));
+    assert_eq!(
+        engine.compile(&dog).unwrap().execute(),
+        Ok(HashSet::from_iter(vec![3, 4]))
+    );
+
+    // Only exact queries on the configured attribute are affected.
+    let q = Query::And(vec![
+        dog.clone(),
+        Query::Exact("zipcode".into(), "12345".into()),
+    ]);
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![4])));
+    let (q, _) = engine
+        .query_from_str("+pet:2#Cat,Dog")
+        .expect("valid query");
+    assert_eq!(engine.search(&q), Ok(HashSet::new()));
+
+    assert_eq!(
+        engine.set_multi_value_match("unknown", MultiValueMatch::Exactly),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    engine
+        .set_multi_value_match("pet", MultiValueMatch::Any)
+        .expect("index exists");
+    assert_eq!(
+        engine.search(&dog),
+        Ok(HashSet::from_iter(vec![1, 3, 4, 5]))
+    );
+}
+
+#[test]
+fn search_scored_multi_pet() {
+    let mut engine = create_person_search_engine();
+    let pets = Query::Or(vec![
+        Query::Exact("pet".into(), "Cat".into()),
+        Query::Exact("pet".into(), "Dog".into()),
+    ]);
+    let q = Query::And(vec![
+        pets.clone(),
+        Query::Exact("zipcode".into(), "12345".into()),
+    ]);
+
+    // Rows 1 and 5 have both pets, but by default they count once.
+    assert_eq!(engine.score_mode(), ScoreMode::Dedup);
+    assert_eq!(
+        engine.search_scored(&pets),
+        Ok(vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1)])
+    );
+    assert_eq!(
+        engine.search_scored(&q),
+        Ok(vec![(1, 2), (2, 2), (4, 2), (5, 2)])
+    );
+
+    engine.set_score_mode(ScoreMode::CountEach);
+    assert_eq!(
+        engine.search_scored(&pets),
+        Ok(vec![(1, 2), (5, 2), (2, 1), (3, 1), (4, 1)])
+    );
+    assert_eq!(
+        engine.search_scored(&q),
+        Ok(vec![(1, 3), (5, 3), (2, 2), (4, 2)])
+    );
+
+    // Exclusions only remove rows and the result set doesn't depend on the mode.
+    let (q, _) = engine
+        .query_from_str("+pet:Cat,Dog,Bees -name:Bob")
+        .unwrap();
+    assert_eq!(
+        engine.search_scored(&q),
+        Ok(vec![(5, 2), (2, 1), (3, 1), (4, 1)])
+    );
+    let rows: HashSet<u8> = engine
+        .search_scored(&q)
+        .unwrap()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(engine.search(&q), Ok(rows));
+}
+
+#[test]
+fn search_weighted_name_before_city() {
+    let mut engine = create_person_search_engine();
+    engine.insert("name", 6, "Jordan").unwrap();
+    engine.insert("city", 7, "Jordan").unwrap();
+    engine.insert("name", 8, "Jordan").unwrap();
+    engine.insert("city", 8, "Jordan").unwrap();
+
+    let weights = [("name", 2.0), ("city", 0.5), ("age", 10.0)];
+    assert_eq!(
+        engine.search_weighted("Jordan", &weights),
+        Ok(vec![(8, 2.5), (6, 2.0), (7, 0.5)])
+    );
+    assert_eq!(
+        engine.search_weighted("dashboard.", &[("permission", 1.0), ("name", 1.0)]),
+        Ok((0..=5).map(|id| (id, 1.0)).collect())
+    );
+    assert_eq!(engine.search_weighted("Jordan", &[]), Ok(vec![]));
+    assert_eq!(
+        engine.search_weighted("Jordan", &[("unknown", 1.0)]),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn index_metadata() {
+    let mut engine = create_person_search_engine();
+    assert_eq!(engine.index_metadata("age"), None);
+
+    let meta = IndexMetadata {
+        label: "Age".into(),
+        description: "Age of the person in years".into(),
+        user_facing: true,
+    };
+    engine
+        .set_index_metadata("age", meta.clone())
+        .expect("index exists");
+    assert_eq!(engine.index_metadata("age"), Some(&meta));
+    assert_eq!(engine.index_metadata("name"), None);
+    assert_eq!(
+        engine.set_index_metadata("unknown", meta),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+
+    // The metadata doesn't affect searching.
+    let q = Query::Exact("age".into(), "27".into());
+    assert_eq!(engine.search(&q), create_person_search_engine().search(&q));
+}
+
+#[test]
+fn federated_engines() {
+    // A second partition with the same people, but other row ids and a
+    // nickname attribute that the first partition doesn't have.
+    let mut second = SearchEngine::new();
+    second.add_index("name", SearchIndexHashMap::<_, String>::new());
+    second.add_index("pet", SearchIndexHashMap::<_, String>::new());
+    second.add_index("age", SearchIndexBTreeRange::<_, u8>::new());
+    second.add_index("nickname", SearchIndexHashMap::<_, String>::new());
+    for (id, row) in create_person_search_engine().export_rows() {
+        for attribute in ["name", "pet", "age"] {
+            for value in row.get(attribute).into_iter().flatten() {
+                second.insert(attribute, id + 100, value).unwrap();
+            }
+        }
+    }
+    second.insert("nickname", 101, "Bobby").unwrap();
+
+    let mut federated = FederatedEngine::new();
+    federated.add_engine(create_person_search_engine());
+    federated.add_engine(second);
+    assert_eq!(federated.engines().len(), 2);
+
+    let q = Query::Exact("name".into(), "Bob".into());
+    assert_eq!(federated.search(&q), Ok(HashSet::from_iter(vec![1, 101])));
+
+    let q = Query::And(vec![
+        Query::Exact("pet".into(), "Dog".into()),
+        Query::Minimum("age".into(), "30".into()),
+    ]);
+    assert_eq!(
+        federated.search(&q),
+        Ok(HashSet::from_iter(vec![4, 5, 104, 105]))
+    );
+
+    // Attributes that only exist in some members match nothing in the others.
+    let q = Query::Exact("nickname".into(), "Bobby".into());
+    assert_eq!(federated.search(&q), Ok(HashSet::from_iter(vec![101])));
+    let q = Query::Exclude(
+        Box::new(Query::Exact("name".into(), "Bob".into())),
+        vec![Query::Exact("nickname".into(), "Bobby".into())],
+    );
+    assert_eq!(federated.search(&q), Ok(HashSet::from_iter(vec![1])));
+    let q = Query::And(vec![
+        Query::Exact("zipcode".into(), "12345".into()),
+        Query::Exact("name".into(), "Bob".into()),
+    ]);
+    assert_eq!(federated.search(&q), Ok(HashSet::from_iter(vec![1])));
+
+    let q = Query::Exact("unknown".into(), "".into());
+    assert_eq!(
+        federated.search(&q),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn export_rows() {
+    let engine = create_person_search_engine();
+
+    let rows = engine.export_rows();
+    assert_eq!(
+        rows.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4, 5]
+    );
+    assert_eq!(
+        rows[2],
+        (
+            2,
+            BTreeMap::from([
+                ("age".into(), vec!["23".into()]),
+                ("city".into(), vec!["Berlin".into(), "New York".into()]),
+                ("name".into(), vec!["Eve".into()]),
+                (
+                    "permission".into(),
+                    vec![
+                        "dashboard.show".into(),
+                        "personel.read".into(),
+                        "personel.write".into()
+                    ]
+                ),
+                ("pet".into(), vec!["Cat".into()]),
+                ("zipcode".into(), vec!["12345".into(), "54321".into()]),
+            ])
+        )
+    );
+    assert_eq!(
+        rows[3].1.get("zipcode"),
+        None,
+        "rows without a value don't contain the attribute"
+    );
+}
+
+#[test]
+fn reindex_name_as_prefix_tree() {
+    let mut engine = create_person_search_engine();
+    let q = Query::Prefix("name".into(), "Al".into());
+    assert_eq!(engine.search(&q), Err(SearchEngineError::UnsupportedQuery));
+
+    engine
+        .reindex_attribute::<SearchIndexPrefixTree<_>>("name")
+        .unwrap();
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+    assert_eq!(
+        engine.search(&Query::Exact("name".into(), "Eve".into())),
+        Ok(HashSet::from_iter(vec![2]))
+    );
+    assert_eq!(engine.universe(), &HashSet::from_iter(0..=5));
+
+    // The city "New York" is no valid number, so the old index is kept.
+    assert_eq!(
+        engine.reindex_attribute::<SearchIndexBTreeRange<_, u32>>("city"),
+        Err(SearchEngineError::MismatchedQueryType {
+            attribute: "city".into(),
+            expected: "u32"
+        })
+    );
+    assert_eq!(
+        engine.search(&Query::Exact("city".into(), "Berlin".into())),
+        Ok(HashSet::from_iter(vec![2]))
+    );
+    assert_eq!(
+        engine.reindex_attribute::<SearchIndexPrefixTree<_>>("unknown"),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+
+    engine.add_index(
+        "fn",
+        SearchIndexFunction::new(SUPPORTS_EXACT, |_| HashSet::new()),
+    );
+    assert_eq!(
+        engine.reindex_attribute::<SearchIndexPrefixTree<_>>("fn"),
+        Err(SearchEngineError::UnsupportedOperation)
+    );
+
+    // An empty index still supports entries, so it can be reindexed.
+    engine.add_index("nickname", SearchIndexHashMap::<_, String>::new());
+    assert_eq!(
+        engine.reindex_attribute::<SearchIndexPrefixTree<_>>("nickname"),
+        Ok(())
+    );
+    assert_eq!(
+        engine.search(&Query::Prefix("nickname".into(), "A".into())),
+        Ok(HashSet::new())
+    );
+}
+
+#[test]
+fn search_sorted_by_age() {
+    let engine = create_person_search_engine();
+    let ages = [27u8, 27, 23, 25, 34, 51];
+    let q = Query::Exact("pet".into(), "Dog".into());
+    assert_eq!(
+        engine.search_sorted(&q, |a, b| ages[*b as usize].cmp(&ages[*a as usize])),
+        Ok(vec![5, 4, 1, 3])
+    );
+    assert_eq!(
+        engine.search_sorted(&Query::Exact("pet".into(), "Fish".into()), u8::cmp),
+        Ok(vec![])
+    );
+    assert_eq!(
+        engine.search_sorted(&Query::Exact("unknown".into(), "x".into()), u8::cmp),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn count_matches_search() {
+    let mut engine = create_person_search_engine();
+    engine
+        .set_multi_value_match("pet", MultiValueMatch::Exactly)
+        .unwrap();
+    let queries = [
+        Query::Exact("zipcode".into(), "12345".into()),
+        Query::Exact("pet".into(), "Cat".into()),
+        Query::InRange("age".into(), "25".into(), "34".into()),
+        Query::Prefix("permission".into(), "finances".into()),
+        Query::And(vec![
+            Query::Exact("city".into(), "New York".into()),
+            Query::Minimum("age".into(), "30".into()),
+        ]),
+        Query::And(vec![
+            Query::Not(Query::Exact("pet".into(), "Dog".into()).into()),
+            Query::All,
+            Query::Exact("zipcode".into(), "12345".into()),
+        ]),
+        Query::And(vec![
+            Query::Or(vec![
+                Query::Exact("name".into(), "Eve".into()),
+                Query::Exact("name".into(), "Hans".into()),
+            ]),
+            Query::Exact("pet".into(), "Dog".into()),
+        ]),
+        Query::And(vec![
+            Query::Exact("name".into(), "Nobody".into()),
+            Query::Exact("pet".into(), "Dog".into()),
+        ]),
+        Query::And(vec![Query::Exact("pet".into(), "Dog".into())]),
+        Query::And(vec![Query::All]),
+        Query::And(vec![]),
+        Query::And(vec![
+            Query::Exact("name".into(), "Nobody".into()),
+            Query::Prefix("unknown".into(), "x".into()),
+        ]),
+        Query::And(vec![
+            Query::Prefix("unknown".into(), "x".into()),
+            Query::Exact("name".into(), "Nobody".into()),
+        ]),
+    ];
+    for query in queries.iter() {
+        let expected = engine.search(query).map(|result_set| result_set.len());
+        assert_eq!(engine.count(query), expected, "{query:?}");
+    }
+    assert_eq!(engine.count(&queries[0]), Ok(5));
+    assert_eq!(engine.count(&queries[1]), Ok(1));
+    assert_eq!(
+        engine.count(&Query::Exact("unknown".into(), "x".into())),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    assert_eq!(
+        engine.count(&queries[queries.len() - 1]),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}
+
+#[test]
+fn schema_info_capabilities() {
+    let engine = create_person_search_engine();
+    let schema = engine.schema_info();
+    let names: Vec<&str> = schema.iter().map(|info| info.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["age", "city", "name", "permission", "pet", "zipcode"]
+    );
+
+    let hashmap = SearchIndexHashMap::<u8, String>::SUPPORTED_QUERIES;
+    let btree = SearchIndexBTreeRange::<u8, u8>::SUPPORTED_QUERIES;
+    let prefix = SearchIndexPrefixTree::<u8>::SUPPORTED_QUERIES;
+    for info in schema.iter() {
+        let (expected_queries, expected_type) = match info.name.as_str() {
+            "age" => (btree, "u8"),
+            "permission" => (prefix, "alloc::string::String"),
+            _ => (hashmap, "alloc::string::String"),
+        };
+        assert_eq!(info.supported_queries, expected_queries, "{}", info.name);
+        assert_eq!(info.value_type_name, expected_type, "{}", info.name);
+    }
+    assert_ne!(btree & SUPPORTS_INRANGE, 0);
+    assert_eq!(hashmap & SUPPORTS_INRANGE, 0);
+    assert_ne!(prefix & SUPPORTS_PREFIX, 0);
+}
+
+#[test]
+fn mismatched_query_type_names_attribute() {
+    let engine = create_person_search_engine();
+    let error = || SearchEngineError::MismatchedQueryType {
+        attribute: "age".into(),
+        expected: "u8",
+    };
+
+    let q = Query::Or(vec![
+        Query::Exact("name".into(), "Alice".into()),
+        Query::Minimum("age".into(), "old".into()),
+    ]);
+    assert_eq!(engine.search(&q), Err(error()));
+    assert_eq!(engine.count(&q), Err(error()));
+    assert_eq!(
+        engine.count(&Query::Exact("age".into(), "300".into())),
+        Err(error())
+    );
+    assert_eq!(
+        error().to_string(),
+        "Mismatched query type: attribute 'age' expects a value of type u8"
+    );
+
+    let mut index = SearchIndexBTreeRange::<u8, u8>::new();
+    index.insert(0, 42);
+    let error = index
+        .search(&Query::Exact("<unused>".into(), "x".into()))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Mismatched query type: expected a value of type u8"
+    );
+}
+
+#[test]
+fn snapshot_handle_is_consistent() {
+    let mut engine = create_person_search_engine();
+    let snapshot = engine.snapshot_handle();
+    let cats = Query::Exact("pet".into(), "Cat".into());
+    let young = Query::Maximum("age".into(), "25".into());
+    let expected_cats = HashSet::from_iter(vec![1, 2, 5]);
+
+    engine.insert("pet", 3, "Cat").unwrap();
+    engine.insert("age", 6, "20").unwrap();
+    assert_eq!(
+        engine.search(&cats),
+        Ok(HashSet::from_iter(vec![1, 2, 3, 5]))
+    );
+    assert_eq!(engine.search(&young), Ok(HashSet::from_iter(vec![2, 3, 6])));
+    assert_eq!(snapshot.search(&cats), Ok(expected_cats.clone()));
+    assert_eq!(snapshot.search(&young), Ok(HashSet::from_iter(vec![2, 3])));
+
+    let cloned = snapshot.clone();
+    engine.remove_row(&2);
+    engine.add_index("pet", SearchIndexHashMap::<u8, String>::new());
+    engine.remove_index("age");
+    assert_eq!(engine.search(&cats), Ok(HashSet::new()));
+    assert_eq!(
+        engine.search(&young),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+    assert_eq!(cloned.search(&cats), Ok(expected_cats));
+    assert_eq!(cloned.count(&young), Ok(2));
+    assert_eq!(snapshot.universe(), &HashSet::from_iter(0..=5));
+    let (query, _) = snapshot.query_from_str("+pet:Cat +city:Berlin").unwrap();
+    assert_eq!(snapshot.search(&query), Ok(HashSet::from_iter(vec![2])));
+}
+
+#[test]
+fn snapshot_handle_on_other_thread() {
+    let mut engine = create_person_search_engine();
+    let snapshot = engine.snapshot_handle();
+    let cats = Query::Exact("pet".into(), "Cat".into());
+    let expected_cats = HashSet::from_iter(vec![1, 2, 5]);
+
+    let reader = std::thread::spawn(move || {
+        for _ in 0..100 {
+            assert_eq!(snapshot.search(&cats), Ok(expected_cats.clone()));
+        }
+        snapshot
+    });
+    for row in 6..100 {
+        engine.insert("pet", row, "Cat").unwrap();
+    }
+    let snapshot = reader.join().unwrap();
+
+    let cats = Query::Exact("pet".into(), "Cat".into());
+    assert_eq!(engine.count(&cats), Ok(97));
+    assert_eq!(snapshot.count(&cats), Ok(3));
+}
+
+#[test]
+fn search_named_with_parameters() {
+    let engine = create_person_search_engine();
+    let mut catalog = QueryCatalog::new();
+    catalog.register("pet-owners", "+zipcode:{zip} +pet:{pet}");
+
+    assert_eq!(
+        engine.search_named(&catalog, "pet-owners", &[("zip", "12345"), ("pet", "Dog")]),
+        Ok(HashSet::from_iter(vec![1, 4, 5]))
+    );
+    assert_eq!(
+        engine.search_named(&catalog, "pet-owners", &[("zip", "12345"), ("pet", "Cat")]),
+        Ok(HashSet::from_iter(vec![1, 2, 5]))
+    );
+    assert_eq!(
+        engine.search_named(&catalog, "pet-owners", &[("zip", "12345")]),
+        Err(SearchEngineError::MissingQueryParameter {
+            parameter: "pet".into()
+        })
+    );
+    assert_eq!(
+        engine.search_named(&catalog, "unknown", &[]),
+        Err(SearchEngineError::UnknownSavedQuery {
+            name: "unknown".into()
+        })
+    );
+
+    catalog.register("pet-owners", "+pet:{pet} -city:{city}");
+    assert_eq!(
+        engine.search_named(
+            &catalog,
+            "pet-owners",
+            &[("pet", "Cat"), ("city", "Berlin")]
+        ),
+        Ok(HashSet::from_iter(vec![1, 5]))
+    );
+
+    // Values can't add values or selectors to the query or drop the filter.
+    assert_eq!(
+        engine.search_named(
+            &catalog,
+            "pet-owners",
+            &[("pet", "Cat,Dog"), ("city", "Berlin")]
+        ),
+        Err(SearchEngineError::InvalidQueryParameter {
+            parameter: "pet".into(),
+            value: "Cat,Dog".into()
+        })
+    );
+    assert_eq!(
+        engine.search_named(
+            &catalog,
+            "pet-owners",
+            &[("pet", "Cat"), ("city", "Berlin +pet:Dog")]
+        ),
+        Err(SearchEngineError::InvalidQueryParameter {
+            parameter: "city".into(),
+            value: "Berlin +pet:Dog".into()
+        })
+    );
+    assert_eq!(
+        engine.search_named(&catalog, "pet-owners", &[("pet", ""), ("city", "Berlin")]),
+        Err(SearchEngineError::InvalidQueryParameter {
+            parameter: "pet".into(),
+            value: "".into()
+        })
+    );
+}
+
+#[test]
+fn cloned_engine_is_independent() {
+    let mut engine = create_person_search_engine();
+    let mut cloned = engine.clone();
+    let cats = Query::Exact("pet".into(), "Cat".into());
+    assert_eq!(cloned.search(&cats), engine.search(&cats));
+
+    cloned.insert("pet", 3, "Cat").unwrap();
+    cloned.remove_row(&5);
+    assert_eq!(cloned.search(&cats), Ok(HashSet::from_iter(vec![1, 2, 3])));
+    assert_eq!(engine.search(&cats), Ok(HashSet::from_iter(vec![1, 2, 5])));
+
+    engine.insert("pet", 0, "Cat").unwrap();
+    engine.remove_index("age");
+    assert_eq!(
+        engine.search(&cats),
+        Ok(HashSet::from_iter(vec![0, 1, 2, 5]))
+    );
+    assert_eq!(cloned.search(&cats), Ok(HashSet::from_iter(vec![1, 2, 3])));
+    assert_eq!(
+        cloned.search(&Query::Minimum("age".into(), "34".into())),
+        Ok(HashSet::from_iter(vec![4]))
+    );
+    assert_eq!(cloned.universe(), &HashSet::from_iter(0..=4));
+}
+
+#[test]
+fn merge_excludes_of_parsed_fragments() {
+    let engine = create_person_search_engine();
+    let (location, _) = engine
+        .query_from_str("+zipcode:12345 -city:Berlin")
+        .unwrap();
+    let (pets, _) = engine.query_from_str("+pet:Dog -age:>40").unwrap();
+    let (names, _) = engine.query_from_str("-name:Bob").unwrap();
+    assert_eq!(
+        engine.search(&location),
+        Ok(HashSet::from_iter(vec![0, 1, 4, 5]))
+    );
+    assert_eq!(engine.search(&pets), Ok(HashSet::from_iter(vec![1, 3, 4])));
+
+    let merged = location.merge_excludes(pets);
+    assert_eq!(
+        merged,
+        Query::Exclude(
+            Box::new(Query::And(vec![
+                Query::Exact("zipcode".into(), "12345".into()),
+                Query::Exact("pet".into(), "Dog".into()),
+            ])),
+            vec![
+                Query::Exact("city".into(), "Berlin".into()),
+                Query::Minimum("age".into(), "40".into()),
+            ]
+        )
+    );
+    assert_eq!(engine.search(&merged), Ok(HashSet::from_iter(vec![1, 4])));
+
+    let merged = merged.merge_excludes(names);
+    assert_eq!(engine.search(&merged), Ok(HashSet::from_iter(vec![4])));
+
+    let (cats, _) = engine.query_from_str("+pet:Cat").unwrap();
+    let merged = cats.merge_excludes(Query::Exact("age".into(), "27".into()));
+    assert_eq!(
+        merged,
+        Query::And(vec![
+            Query::Exact("pet".into(), "Cat".into()),
+            Query::Exact("age".into(), "27".into()),
+        ])
+    );
+    assert_eq!(engine.search(&merged), Ok(HashSet::from_iter(vec![1])));
+}
+
+#[test]
+fn changes_since_checkpoint() {
+    let mut engine = create_person_search_engine();
+    // Changes before the first checkpoint are not recorded.
+    engine.insert("name", 6, "Greta").unwrap();
+
+    let first = engine.checkpoint();
+    assert!(engine.changes_since(&first).is_empty());
+    engine.insert("name", 7, "Ingo").unwrap();
+    engine.insert("pet", 0, "Fish").unwrap();
+    assert_eq!(engine.remove_row(&3), 5);
+    // Rows without values and failed inserts are not recorded.
+    assert_eq!(engine.remove_row(&42), 0);
+    assert!(engine.insert("age", 8, "old").is_err());
+
+    let second = engine.checkpoint();
+    assert!(second > first);
+    engine.remove_row(&7);
+    engine.insert("city", 3, "Prag").unwrap();
+
+    let changes = engine.changes_since(&first);
+    assert_eq!(changes.added, HashSet::from_iter(vec![0, 3]));
+    assert_eq!(changes.removed, HashSet::from_iter(vec![7]));
+    let changes = engine.changes_since(&second);
+    assert_eq!(changes.added, HashSet::from_iter(vec![3]));
+    assert_eq!(changes.removed, HashSet::from_iter(vec![7]));
+
+    engine.compact_changes(&second);
+    let changes = engine.changes_since(&first);
+    assert_eq!(changes.added, HashSet::from_iter(vec![3]));
+    assert_eq!(changes.removed, HashSet::from_iter(vec![7]));
+    let third = engine.checkpoint();
+    assert!(engine.changes_since(&third).is_empty());
+}
+
 fn create_person_search_engine() -> SearchEngine<u8> {
     let mut index_name = SearchIndexHashMap::<_, String>::new();
     let mut index_zipcode = SearchIndexHashMap::<_, String>::new();
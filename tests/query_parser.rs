@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+use attribute_search_engine::*;
+use proptest::prelude::*;
+
+/// Creates an engine whose indices accept every query type that has an operator
+/// symbol, so the parser never falls back and every value keeps its operator.
+fn create_fuzz_search_engine() -> SearchEngine<usize> {
+    let supported = SUPPORTS_EXACT
+        | SUPPORTS_PREFIX
+        | SUPPORTS_SUFFIX
+        | SUPPORTS_PATTERN
+        | SUPPORTS_FUZZY
+        | SUPPORTS_INRANGE
+        | SUPPORTS_INRANGE_BOUNDS
+        | SUPPORTS_MINIMUM
+        | SUPPORTS_MAXIMUM;
+    let mut engine = SearchEngine::new();
+    engine.add_index("a", SearchIndexFunction::new(supported, |_| HashSet::new()));
+    engine.add_index("b", SearchIndexFunction::new(supported, |_| HashSet::new()));
+    engine
+}
+
+/// Generates query strings that are built from selectors of the fuzz engine,
+/// operator symbols, separators and other characters, so that many of them
+/// contain valid and almost valid selectors.
+fn query_string() -> impl Strategy<Value = String> {
+    let part = prop_oneof![
+        "[+-][ab]:[a-c0-9=<>*#~,\\[\\]()\\-ä😁]{0,8}",
+        "[ab+\\-,*ä😁]{1,3}",
+        "[ \t\n]{1,2}",
+    ];
+    prop::collection::vec(part, 0..8).prop_map(|parts| parts.concat())
+}
+
+proptest! {
+    #[test]
+    fn never_panics(query_str in "\\PC*", boolean_keywords: bool) {
+        let mut engine = create_fuzz_search_engine();
+        engine.set_parser_options(QueryParserOptions {
+            boolean_keywords,
+            ..Default::default()
+        });
+        if let Ok((_, freetexts)) = engine.query_from_str(&query_str) {
+            for text in freetexts {
+                prop_assert!(!text.is_empty());
+                prop_assert!(!text.contains(char::is_whitespace), "{:?}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn freetext_has_no_whitespace(query_str in query_string()) {
+        let engine = create_fuzz_search_engine();
+        let (_, freetexts) = engine.query_from_str(&query_str).unwrap();
+        for text in freetexts {
+            prop_assert!(!text.is_empty());
+            prop_assert!(!text.contains(char::is_whitespace), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn display_round_trip(query_str in query_string()) {
+        let engine = create_fuzz_search_engine();
+        let (q, _) = engine.query_from_str(&query_str).unwrap();
+        let display = q.to_string();
+        let (reparsed, freetexts) = engine.query_from_str(&display).unwrap();
+        prop_assert_eq!(&reparsed, &q, "{:?} was written as {:?}", query_str, display);
+        prop_assert!(freetexts.is_empty(), "{:?}", freetexts);
+    }
+}
+
+#[test]
+fn display_round_trip_examples() {
+    let engine = create_fuzz_search_engine();
+    for query_str in [
+        "",
+        "+a:x",
+        "+a:=x,>1,<2,1-2,[1,2),(3,4],x*y,*z +b:2#c,=,*",
+        "+a:x~1,*y~2,[1,2]~0,1-2~3 -b:~1",
+        "+a:x+b:y -a:ä -b:😁,=😁",
+        "+a:[1,x,y +b:[1]x,2",
+    ] {
+        let (q, _) = engine.query_from_str(query_str).unwrap();
+        assert_eq!(q.to_string(), query_str.trim());
+        assert_eq!(engine.query_from_str(&q.to_string()).unwrap().0, q);
+    }
+}
+
+#[test]
+fn string_range_delimiter_dates() {
+    let mut index = SearchIndexBTreeRange::<usize, String>::new();
+    index.insert(0, "2023-12-31".into());
+    index.insert(1, "2024-01-01".into());
+    index.insert(2, "2024-06-15".into());
+    index.insert(3, "2025-01-01".into());
+    let mut engine = SearchEngine::new();
+    engine.add_index("date", index);
+    engine.add_index("age", SearchIndexBTreeRange::<usize, u8>::new());
+
+    // Without the option, the hyphens of the dates are taken as range operators.
+    let (q, _) = engine.query_from_str("+date:2024-06").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::InRange(
+            "date".into(),
+            "2024".into(),
+            "06".into()
+        )])
+    );
+    let (q, _) = engine
+        .query_from_str("+date:2024-01-01..2024-12-31")
+        .unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Exact(
+            "date".into(),
+            "2024-01-01..2024-12-31".into()
+        )])
+    );
+
+    engine.set_parser_options(QueryParserOptions {
+        string_range_delimiter: Some("..".into()),
+        ..Default::default()
+    });
+    let (q, _) = engine
+        .query_from_str("+date:2024-01-01..2024-12-31")
+        .unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::InRange(
+            "date".into(),
+            "2024-01-01".into(),
+            "2024-12-31".into()
+        )])
+    );
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![1, 2])));
+    let (q, _) = engine.query_from_str("+date:2024-06-15").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Exact("date".into(), "2024-06-15".into())])
+    );
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![2])));
+
+    // Indices with other value types keep the `-` delimiter.
+    let (q, _) = engine.query_from_str("+age:20-30").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::InRange("age".into(), "20".into(), "30".into())])
+    );
+}
+
+/// Index with numbers that can be negative, so it uses `..` for ranges.
+#[derive(Clone)]
+struct SignedIndex;
+
+impl SearchIndex<usize> for SignedIndex {
+    fn search(&self, _query: &Query) -> attribute_search_engine::Result<HashSet<usize>> {
+        Ok(HashSet::new())
+    }
+
+    fn supported_queries(&self) -> SupportedQueries {
+        SUPPORTS_EXACT | SUPPORTS_INRANGE
+    }
+
+    fn range_delimiter(&self) -> Option<&str> {
+        Some("..")
+    }
+
+    fn clone_box(&self) -> Box<dyn SearchIndex<usize>> {
+        Box::new(self.clone())
+    }
+}
+
+#[test]
+fn index_range_delimiter() {
+    let mut engine = SearchEngine::new();
+    engine.add_index("temperature", SignedIndex);
+    engine.set_parser_options(QueryParserOptions {
+        string_range_delimiter: Some("~".into()),
+        ..Default::default()
+    });
+
+    let (q, _) = engine.query_from_str("+temperature:-5..5").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::InRange(
+            "temperature".into(),
+            "-5".into(),
+            "5".into()
+        )])
+    );
+    let (q, _) = engine.query_from_str("+temperature:-5").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Exact("temperature".into(), "-5".into())])
+    );
+}
+
+#[test]
+fn reject_empty_prefix() {
+    let mut index_firstname = SearchIndexPrefixTree::<usize>::new();
+    index_firstname.insert(0, "Alex".into());
+    index_firstname.insert(1, "Ben".into());
+    let mut engine = SearchEngine::new();
+    engine.add_index("firstname", index_firstname.clone());
+
+    let q = Query::Prefix("firstname".into(), "".into());
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0, 1])));
+    assert!(engine.query_from_str("+firstname:").is_ok());
+
+    index_firstname.set_reject_empty_prefix(true);
+    engine.add_index("firstname", index_firstname);
+    assert_eq!(
+        engine.query_from_str("+firstname:"),
+        Err(SearchEngineError::EmptyPrefix)
+    );
+    assert_eq!(
+        engine.query_from_str("+firstname:, -firstname:Ben"),
+        Err(SearchEngineError::EmptyPrefix)
+    );
+    assert_eq!(engine.search(&q), Err(SearchEngineError::EmptyPrefix));
+    let (q, _) = engine.query_from_str("+firstname:=").unwrap();
+    assert_eq!(
+        q,
+        Query::And(vec![Query::Exact("firstname".into(), "".into())])
+    );
+    let (q, _) = engine.query_from_str("+firstname:Al").unwrap();
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+}
+
+#[test]
+fn dotted_attribute_names() {
+    let mut index_city = SearchIndexHashMap::<usize, String>::new();
+    index_city.insert(0, "Berlin".into());
+    index_city.insert(1, "Bonn".into());
+    let mut index_version = SearchIndexPrefixTree::<usize>::new();
+    index_version.insert(0, "1.2.3".into());
+    index_version.insert(1, "1.3.0".into());
+    let mut engine = SearchEngine::new();
+    engine.add_index("address.city", index_city);
+    engine.add_index("app.version", index_version);
+
+    let (q, freetext) = engine
+        .query_from_str("+address.city:Berlin,Bonn -app.version:1.3 v1.2")
+        .unwrap();
+    assert_eq!(
+        q,
+        Query::Exclude(
+            Box::new(Query::And(vec![Query::Or(vec![
+                Query::Exact("address.city".into(), "Berlin".into()),
+                Query::Exact("address.city".into(), "Bonn".into()),
+            ])])),
+            vec![Query::Prefix("app.version".into(), "1.3".into())],
+        )
+    );
+    assert_eq!(freetext, vec!["v1.2"]);
+    assert_eq!(engine.search(&q), Ok(HashSet::from_iter(vec![0])));
+    assert_eq!(
+        q.to_string(),
+        "+address.city:=Berlin,=Bonn -app.version:1.3"
+    );
+
+    let (_, freetext) = engine.query_from_str("+address.:Berlin").unwrap();
+    assert_eq!(freetext, vec!["+address.:Berlin"]);
+    assert_eq!(
+        engine.query_from_str("+address:Berlin"),
+        Err(SearchEngineError::UnknownAttribute)
+    );
+}